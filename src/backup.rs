@@ -0,0 +1,101 @@
+// 保存前の版を残すローカル履歴
+// 場所: <状態ディレクトリ>/history （Config::state_dir を参照）
+// ファイルごとに1ディレクトリ、中身は `<保存時刻ナノ秒>.bak`。保存のたびに保持ポリシーで刈り込む
+use crate::config::Config;
+use std::{
+    io,
+    path::{Path, PathBuf},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+// 保持ポリシー。0 は無制限
+#[derive(Clone, Copy)]
+pub struct BackupPolicy {
+    pub max_versions: usize,  // ファイルごとの版数
+    pub max_total_bytes: u64, // 履歴全体のサイズ
+    pub max_age: Duration,    // これより古い版は消す
+}
+
+impl Default for BackupPolicy {
+    fn default() -> Self {
+        BackupPolicy {
+            max_versions: 20,
+            max_total_bytes: 100 * 1024 * 1024,
+            max_age: Duration::from_secs(30 * 86400),
+        }
+    }
+}
+
+pub fn history_dir() -> Option<PathBuf> {
+    Some(Config::state_dir()?.join("history"))
+}
+
+// 絶対パスの区切りを `%` に置き換えてディレクトリ名にする
+fn file_dir(root: &Path, path: &Path) -> PathBuf {
+    let abs = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    root.join(abs.to_string_lossy().replace(['/', '\\'], "%"))
+}
+
+// 上書きされる前の内容を履歴に写す。元ファイルが無ければ何もしない
+pub fn backup(root: &Path, path: &Path) -> io::Result<()> {
+    if !path.is_file() { return Ok(()); }
+    let dir = file_dir(root, path);
+    std::fs::create_dir_all(&dir)?;
+    let stamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    std::fs::copy(path, dir.join(format!("{}.bak", stamp)))?;
+    Ok(())
+}
+
+struct Version {
+    path: PathBuf,
+    stamp: u128,
+    size: u64,
+}
+
+fn versions(dir: &Path) -> Vec<Version> {
+    let mut list: Vec<Version> = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .flatten()
+        .filter_map(|e| {
+            let path = e.path();
+            let stamp = path.file_name()?.to_str()?.strip_suffix(".bak")?.parse().ok()?;
+            Some(Version { size: e.metadata().ok()?.len(), path, stamp })
+        })
+        .collect();
+    list.sort_by_key(|v| v.stamp);
+    list
+}
+
+// ポリシーを超えた版を古い順に消す
+pub fn prune(root: &Path, policy: BackupPolicy) {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos();
+    let mut kept = Vec::new();
+    for dir in std::fs::read_dir(root).into_iter().flatten().flatten().map(|e| e.path()) {
+        let mut list = versions(&dir);
+        let excess = if policy.max_versions > 0 { list.len().saturating_sub(policy.max_versions) } else { 0 };
+        for (i, v) in list.drain(..).enumerate() {
+            let expired = !policy.max_age.is_zero() && now.saturating_sub(v.stamp) > policy.max_age.as_nanos();
+            if i < excess || expired {
+                let _ = std::fs::remove_file(&v.path);
+            } else {
+                kept.push(v);
+            }
+        }
+        // 空になったディレクトリは片付ける（中身があれば失敗するだけ）
+        let _ = std::fs::remove_dir(&dir);
+    }
+    if policy.max_total_bytes > 0 {
+        kept.sort_by_key(|v| v.stamp);
+        let mut total: u64 = kept.iter().map(|v| v.size).sum();
+        for v in &kept {
+            if total <= policy.max_total_bytes { break; }
+            if std::fs::remove_file(&v.path).is_ok() {
+                total -= v.size;
+                if let Some(dir) = v.path.parent() {
+                    let _ = std::fs::remove_dir(dir);
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,57 @@
+// バッファの行
+// Vec<String> と同じように使え、書き換えられるように借りるたびに世代を進める。
+// 構文木や検索の件数のように内容から作るものは、世代が同じなら作り直さない
+use std::ops::{Deref, DerefMut};
+
+pub struct Lines {
+    lines: Vec<String>,
+    generation: u64,
+}
+
+impl Lines {
+    pub fn new() -> Self {
+        Lines { lines: vec![String::new()], generation: 0 }
+    }
+
+    // 同じ内容・同じ世代の写し（App の複製用）。Clone にしないのは、写しを戻して世代が巻き戻らないように
+    pub fn duplicate(&self) -> Self {
+        Lines { lines: self.lines.clone(), generation: self.generation }
+    }
+
+    // 書き換えたかもしれない回数（増えていなければ内容は変わっていない）
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+}
+
+impl Deref for Lines {
+    type Target = Vec<String>;
+
+    fn deref(&self) -> &Vec<String> {
+        &self.lines
+    }
+}
+
+impl DerefMut for Lines {
+    fn deref_mut(&mut self) -> &mut Vec<String> {
+        self.generation += 1;
+        &mut self.lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_mutable_borrows_advance_the_generation() {
+        let mut lines = Lines::new();
+        let _ = (lines.len(), lines.join("\n"), &lines[0]);
+        assert_eq!(lines.generation(), 0);
+        lines[0].push('a');
+        lines.push("b".to_string());
+        assert_eq!(lines.generation(), 2);
+        *lines = vec!["c".to_string()];
+        assert_eq!((lines.generation(), lines.duplicate().generation()), (3, 3));
+    }
+}
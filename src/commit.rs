@@ -0,0 +1,197 @@
+// 簡易 git コミット画面 (Alt+c)
+// 変更のあるファイルを一覧し、Space でステージ／解除、a で全部。Enter でコミットメッセージを
+// エディタのバッファ（.git/COMMIT_EDITMSG）で書き、もう一度 Alt+c でコミットして元のファイルへ戻る
+use crate::{git, writer, App, PopupMode};
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct CommitEntry {
+    xy: String, // git status --porcelain の2文字（ステージ側, 作業ツリー側）
+    pub path: String,
+}
+
+impl CommitEntry {
+    fn staged(&self) -> bool {
+        !matches!(&self.xy[..1], " " | "?")
+    }
+    // 作業ツリーにステージしていない変更がある
+    fn unstaged(&self) -> bool {
+        &self.xy[1..] != " "
+    }
+}
+
+#[derive(Clone)]
+pub struct CommitPanel {
+    root: PathBuf,
+    pub entries: Vec<CommitEntry>,
+    message: Option<PathBuf>, // 書いている途中のコミットメッセージのファイル
+}
+
+fn load_entries(root: &Path) -> Option<Vec<CommitEntry>> {
+    let out = git::git(root, &["status", "--porcelain", "-z"])?;
+    let mut fields = out.split('\0');
+    let mut entries = Vec::new();
+    while let Some(field) = fields.next() {
+        let (Some(xy), Some(path)) = (field.get(..2), field.get(3..)) else { continue };
+        // 名前の変更・コピーは次の欄に元の名前が続く
+        if xy.starts_with(['R', 'C']) { fields.next(); }
+        entries.push(CommitEntry { xy: xy.to_string(), path: path.to_string() });
+    }
+    Some(entries)
+}
+
+impl App {
+    // Alt+c: コミットメッセージを編集中ならコミット、そうでなければ変更一覧を開く
+    pub fn git_commit_key(&mut self) {
+        let writing = self.commit_panel.as_ref().and_then(|p| p.message.as_ref());
+        if writing.is_some() && writing == self.current_file.as_ref() {
+            self.finish_commit();
+        } else {
+            self.open_commit_panel();
+        }
+    }
+
+    fn open_commit_panel(&mut self) {
+        let dir = self
+            .current_file
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|d| !d.as_os_str().is_empty())
+            .map_or_else(|| self.file_tree.current_path.clone(), Path::to_path_buf);
+        let Some(root) = git::git(&dir, &["rev-parse", "--show-toplevel"]).map(|r| PathBuf::from(r.trim_end())) else {
+            self.message = Some("Not in a git repository".to_string());
+            return;
+        };
+        let entries = load_entries(&root).unwrap_or_default();
+        if entries.is_empty() {
+            self.message = Some("Nothing to commit, working tree clean".to_string());
+            return;
+        }
+        self.commit_panel = Some(CommitPanel { root, entries, message: None });
+        self.popup = Some(PopupMode::GitCommit);
+        self.popup_selected = 0;
+    }
+
+    pub fn commit_items(&self) -> Vec<String> {
+        let Some(panel) = &self.commit_panel else { return Vec::new() };
+        panel
+            .entries
+            .iter()
+            .map(|e| {
+                let mark = match (e.staged(), e.unstaged()) {
+                    (true, false) => "x",
+                    (true, true) => "~",
+                    _ => " ",
+                };
+                format!("[{}] {} {}", mark, e.xy, e.path)
+            })
+            .collect()
+    }
+
+    pub fn handle_commit_key(&mut self, code: crossterm::event::KeyCode) {
+        use crossterm::event::KeyCode;
+        let Some(panel) = self.commit_panel.as_ref() else {
+            self.popup = None;
+            return;
+        };
+        let root = panel.root.clone();
+        let len = panel.entries.len();
+        match code {
+            KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+            KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(len.saturating_sub(1)),
+            // ステージしていない変更があればステージ、なければ解除
+            KeyCode::Char(' ') => {
+                let Some(entry) = panel.entries.get(self.popup_selected) else { return };
+                let path = entry.path.clone();
+                if entry.unstaged() {
+                    self.run_git_quiet(&root, &["add", "-A", "--", &path]);
+                } else if git::git(&root, &["restore", "--staged", "--", &path]).is_none() {
+                    // HEAD がない（最初のコミット前）ときは restore できない
+                    self.run_git_quiet(&root, &["rm", "--cached", "-q", "--", &path]);
+                }
+                self.reload_commit_panel();
+            }
+            // 全部ステージ済みなら全解除、そうでなければ全部ステージ
+            KeyCode::Char('a') => {
+                if panel.entries.iter().any(CommitEntry::unstaged) {
+                    self.run_git_quiet(&root, &["add", "-A"]);
+                } else if git::git(&root, &["reset", "-q"]).is_none() {
+                    self.run_git_quiet(&root, &["rm", "--cached", "-r", "-q", "."]);
+                }
+                self.reload_commit_panel();
+            }
+            KeyCode::Enter => self.start_commit_message(),
+            KeyCode::Esc => {
+                self.popup = None;
+                self.commit_panel = None;
+            }
+            _ => {}
+        }
+    }
+
+    fn run_git_quiet(&mut self, root: &Path, args: &[&str]) {
+        if let Err(e) = git::run(root, args) {
+            self.notify_err(format!("git {} failed: {}", args[0], e));
+        }
+    }
+
+    fn reload_commit_panel(&mut self) {
+        let Some(panel) = self.commit_panel.as_mut() else { return };
+        panel.entries = load_entries(&panel.root).unwrap_or_default();
+        self.popup_selected = self.popup_selected.min(panel.entries.len().saturating_sub(1));
+        self.file_tree.refresh_git();
+    }
+
+    // ステージした内容の一覧をコメントにしたメッセージのひな形を開く
+    fn start_commit_message(&mut self) {
+        let Some(panel) = self.commit_panel.as_mut() else { return };
+        let staged: Vec<&CommitEntry> = panel.entries.iter().filter(|e| e.staged()).collect();
+        if staged.is_empty() {
+            self.message = Some("Nothing staged; press Space to stage files".to_string());
+            return;
+        }
+        if self.modified {
+            self.message = Some("Unsaved changes; save before writing the commit message".to_string());
+            return;
+        }
+        let Some(rel) = git::git(&panel.root, &["rev-parse", "--git-path", "COMMIT_EDITMSG"]) else { return };
+        let path = panel.root.join(rel.trim_end());
+        let mut text = String::from("\n\n# Write the commit message, then press Alt+c to commit.\n");
+        text.push_str("# Lines starting with '#' are ignored; an empty message aborts the commit.\n#\n# Changes to be committed:\n");
+        for e in staged {
+            text.push_str(&format!("#\t{} {}\n", &e.xy[..1], e.path));
+        }
+        if let Err(e) = writer::write_atomic(&path, text.as_bytes()) {
+            self.notify_err(format!("Cannot write {}: {}", path.display(), e));
+            return;
+        }
+        panel.message = Some(path.clone());
+        self.popup = None;
+        self.record_jump();
+        self.open_path(path);
+        self.message = Some("Write the commit message, then Alt+c to commit".to_string());
+    }
+
+    // メッセージを保存して git commit し、元のファイルへ戻る
+    fn finish_commit(&mut self) {
+        let Some(panel) = self.commit_panel.clone() else { return };
+        let Some(path) = panel.message else { return };
+        let text = self.lines.join("\n") + "\n";
+        if let Err(e) = writer::write_atomic(&path, text.as_bytes()) {
+            self.notify_err(format!("Cannot write {}: {}", path.display(), e));
+            return;
+        }
+        self.modified = false;
+        let path_arg = path.to_string_lossy();
+        match git::run(&panel.root, &["commit", "--cleanup=strip", "-F", &path_arg]) {
+            Ok(out) => {
+                self.commit_panel = None;
+                self.jump_back();
+                self.load_git_base();
+                self.file_tree.refresh_git();
+                self.notify_ok(out.lines().next().unwrap_or("Committed"));
+            }
+            Err(e) => self.notify_err(format!("Commit failed: {}", e)),
+        }
+    }
+}
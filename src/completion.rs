@@ -0,0 +1,278 @@
+// 補完ポップアップ（LSP の textDocument/completion）
+// Ctrl+Space で要求し、カーソルの下に候補を出す。入力を続けると手元で絞り込み、
+// Up/Down（Ctrl+N/Ctrl+P）で選んで Enter/Tab で挿入、Esc で閉じる。
+// `lsp.auto_complete = true` なら単語の入力やサーバー指定の文字（`.` など）で自動的に要求する
+use crate::{
+    fuzzy,
+    json::Json,
+    lsp::{self, Request, TextEdit},
+    App,
+};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+#[derive(Clone)]
+pub struct CompletionItem {
+    pub label: String,
+    pub detail: String,
+    filter: String,
+    sort: String,
+    insert: String,
+    snippet: bool,
+    edit_start: Option<usize>, // textEdit の開始（要求した行のバイト位置）
+    additional: Vec<(TextEdit, isize)>, // 自動 import など前の行への編集と、それで増える行数
+}
+
+#[derive(Clone)]
+pub struct Completion {
+    items: Vec<CompletionItem>,
+    pub visible: Vec<usize>, // 絞り込み後の items の添字（良い順）
+    pub selected: usize,
+    line: usize,
+    start: usize, // 入力中の単語の先頭（バイト位置）
+}
+
+impl Completion {
+    pub fn visible_items(&self) -> impl Iterator<Item = &CompletionItem> {
+        self.visible.iter().map(|&i| &self.items[i])
+    }
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// スニペット（`${1:name}` `$0` など）を展開した文字列と、最初に入力する範囲（$1 の既定値、なければ $0 の位置）
+pub fn expand_snippet(snippet: &str) -> (String, Option<(usize, usize)>) {
+    let mut out = String::new();
+    let mut stops: Vec<(u32, usize, usize)> = Vec::new();
+    expand_into(snippet, &mut out, &mut stops);
+    let first = stops.iter().filter(|s| s.0 > 0).min_by_key(|s| s.0).or_else(|| stops.iter().find(|s| s.0 == 0));
+    (out, first.map(|s| (s.1, s.2)))
+}
+
+fn expand_into(s: &str, out: &mut String, stops: &mut Vec<(u32, usize, usize)>) {
+    let mut chars = s.char_indices().peekable();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\\' => {
+                if let Some((_, next)) = chars.next() { out.push(next); }
+            }
+            '$' => {
+                let rest = &s[i + 1..];
+                if let Some(body) = rest.strip_prefix('{') {
+                    // 対応する } を探す（入れ子を数える）
+                    let mut depth = 1;
+                    let mut end = None;
+                    let mut escaped = false;
+                    for (j, ch) in body.char_indices() {
+                        match ch {
+                            _ if escaped => escaped = false,
+                            '\\' => escaped = true,
+                            '{' => depth += 1,
+                            '}' => {
+                                depth -= 1;
+                                if depth == 0 {
+                                    end = Some(j);
+                                    break;
+                                }
+                            }
+                            _ => {}
+                        }
+                    }
+                    let Some(end) = end else {
+                        out.push('$');
+                        continue;
+                    };
+                    let inner = &body[..end];
+                    let digits: String = inner.chars().take_while(char::is_ascii_digit).collect();
+                    let after = &inner[digits.len()..];
+                    if let Ok(n) = digits.parse::<u32>() {
+                        let start = out.len();
+                        if let Some(default) = after.strip_prefix(':') {
+                            expand_into(default, out, stops);
+                        } else if let Some(choices) = after.strip_prefix('|') {
+                            out.push_str(choices.trim_end_matches('|').split(',').next().unwrap_or(""));
+                        }
+                        stops.push((n, start, out.len()));
+                    } else if let Some((_, default)) = inner.split_once(':') {
+                        // 変数は既定値だけ使う
+                        expand_into(default, out, stops);
+                    }
+                    // ${ の後ろ、} まで読み飛ばす
+                    let skip_to = i + 2 + end + 1;
+                    while chars.peek().is_some_and(|&(k, _)| k < skip_to) { chars.next(); }
+                } else {
+                    let digits: String = rest.chars().take_while(char::is_ascii_digit).collect();
+                    if let Ok(n) = digits.parse::<u32>() {
+                        stops.push((n, out.len(), out.len()));
+                        for _ in 0..digits.len() { chars.next(); }
+                    } else {
+                        // $VAR は空にする
+                        let name_len = rest.chars().take_while(|&c| is_word_char(c)).count();
+                        if name_len == 0 { out.push('$'); }
+                        for _ in 0..name_len { chars.next(); }
+                    }
+                }
+            }
+            c => out.push(c),
+        }
+    }
+}
+
+impl App {
+    // 入力中の単語の先頭
+    fn word_start(&self) -> usize {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        line[..x].char_indices().rev().take_while(|&(_, c)| is_word_char(c)).last().map_or(x, |(i, _)| i)
+    }
+
+    // manual = false は自動補完（サーバーがなければ何も言わない）
+    pub fn request_completion(&mut self, manual: bool) {
+        if !manual && !self.lsp_attached() { return; }
+        let pos = self.lsp_cursor_params();
+        let line = self.cursor_y;
+        let Some(client) = self.lsp_client() else { return };
+        if !manual && client.is_pending(|r| matches!(r, Request::Completion { .. })) { return; }
+        if let Some(params) = client.position_params(pos) {
+            client.request("textDocument/completion", params, Request::Completion { line });
+        }
+    }
+
+    pub fn show_completion(&mut self, line: usize, result: &Json) {
+        if line != self.cursor_y { return; }
+        let list = result.get("items").unwrap_or(result);
+        let items: Vec<CompletionItem> = list
+            .as_array()
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|item| {
+                let label = item.get("label")?.as_str()?.to_string();
+                let text_edit = item.get("textEdit");
+                let insert = text_edit
+                    .and_then(|e| e.get("newText"))
+                    .or_else(|| item.get("insertText"))
+                    .and_then(Json::as_str)
+                    .unwrap_or(&label)
+                    .to_string();
+                // TextEdit は range、InsertReplaceEdit は insert を使う
+                let edit_start = text_edit
+                    .and_then(|e| e.get("range").or_else(|| e.get("insert")))
+                    .and_then(|r| r.get("start"))
+                    .map(|p| lsp::from_lsp_position(&self.lines, p))
+                    .filter(|p| p.0 == line)
+                    .map(|p| p.1);
+                let additional = lsp::parse_text_edits(&self.lines, item.get("additionalTextEdits").unwrap_or(&Json::Null))
+                    .into_iter()
+                    .filter(|e| e.end.0 < line)
+                    .map(|e| {
+                        let added = e.text.matches('\n').count() as isize - (e.end.0 - e.start.0) as isize;
+                        (e, added)
+                    })
+                    .collect();
+                Some(CompletionItem {
+                    detail: item.get("detail").and_then(Json::as_str).unwrap_or("").to_string(),
+                    filter: item.get("filterText").and_then(Json::as_str).unwrap_or(&label).to_string(),
+                    sort: item.get("sortText").and_then(Json::as_str).unwrap_or(&label).to_string(),
+                    snippet: item.get("insertTextFormat").and_then(Json::as_u64) == Some(2),
+                    label,
+                    insert,
+                    edit_start,
+                    additional,
+                })
+            })
+            .collect();
+        if items.is_empty() {
+            self.completion = None;
+            return;
+        }
+        self.completion = Some(Completion { items, visible: Vec::new(), selected: 0, line, start: self.word_start() });
+        self.refilter_completion();
+    }
+
+    // 入力中の単語で候補を絞り込む。候補がなくなったら閉じる
+    fn refilter_completion(&mut self) {
+        let Some(comp) = self.completion.as_mut() else { return };
+        let line = &self.lines[self.cursor_y];
+        if self.cursor_y != comp.line || self.cursor_x < comp.start || self.cursor_x > line.len() {
+            self.completion = None;
+            return;
+        }
+        let prefix = &line[comp.start..self.cursor_x];
+        let mut scored: Vec<(i64, usize)> = comp
+            .items
+            .iter()
+            .enumerate()
+            .filter_map(|(i, item)| Some((fuzzy::score(prefix, &item.filter)?, i)))
+            .collect();
+        let items = &comp.items;
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| items[a.1].sort.cmp(&items[b.1].sort)));
+        comp.visible = scored.into_iter().map(|(_, i)| i).collect();
+        comp.selected = 0;
+        if comp.visible.is_empty() { self.completion = None; }
+    }
+
+    // 文字の入力や Backspace の後に呼ぶ
+    pub fn completion_after_edit(&mut self, typed: Option<char>) {
+        if self.completion.is_some() {
+            if self.cursor_x < self.word_start() || typed.is_some_and(|c| !is_word_char(c)) {
+                self.completion = None;
+            } else {
+                self.refilter_completion();
+                return;
+            }
+        }
+        let Some(c) = typed else { return };
+        if !self.config.lsp_auto_complete { return; }
+        let ft = self.filetype();
+        let trigger = self.lsp.client_for(ft).is_some_and(|client| client.trigger_chars.contains(&c));
+        if is_word_char(c) || trigger { self.request_completion(false); }
+    }
+
+    // 補完ポップアップが開いているときのキー。処理したら true
+    pub fn handle_completion_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        let Some(comp) = self.completion.as_mut() else { return false };
+        let len = comp.visible.len();
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        match code {
+            KeyCode::Up => comp.selected = (comp.selected + len - 1) % len,
+            KeyCode::Char('p') if ctrl => comp.selected = (comp.selected + len - 1) % len,
+            KeyCode::Down => comp.selected = (comp.selected + 1) % len,
+            KeyCode::Char('n') if ctrl => comp.selected = (comp.selected + 1) % len,
+            KeyCode::Enter | KeyCode::Tab => self.accept_completion(),
+            KeyCode::Esc => self.completion = None,
+            // 入力と削除はそのまま通して、後で絞り込み直す
+            KeyCode::Char(_) | KeyCode::Backspace if !ctrl => return false,
+            _ => {
+                self.completion = None;
+                return false;
+            }
+        }
+        true
+    }
+
+    fn accept_completion(&mut self) {
+        let Some(comp) = self.completion.take() else { return };
+        let Some(item) = comp.visible.get(comp.selected).map(|&i| comp.items[i].clone()) else { return };
+        let start = item.edit_start.unwrap_or(comp.start).min(comp.start).min(self.cursor_x);
+        let (text, stop) = if item.snippet { expand_snippet(&item.insert) } else { (item.insert.clone(), None) };
+        let y = self.cursor_y;
+        // プレースホルダーは選択しておき、そのまま打てば置き換わるようにする
+        let (from, to) = stop.unwrap_or((text.len(), text.len()));
+        let sel = lsp::end_of_insert((y, start), &text[..from]);
+        let end = lsp::end_of_insert((y, start), &text[..to]);
+        let mut edits = vec![TextEdit { start: (y, start), end: (y, self.cursor_x), text }];
+        let shift: isize = item.additional.iter().map(|(_, added)| added).sum();
+        edits.extend(item.additional.into_iter().map(|(e, _)| e));
+        self.save_undo();
+        lsp::apply_text_edits(&mut self.lines, edits);
+        self.selection_reset();
+        let shifted = |p: (usize, usize)| ((p.0 as isize + shift).max(0) as usize, p.1);
+        if sel != end {
+            self.sel_start = Some(shifted(sel));
+            self.sel_end = Some(shifted(end));
+        }
+        (self.cursor_y, self.cursor_x) = shifted(end);
+        self.adjust_h_scroll(0);
+    }
+}
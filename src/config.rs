@@ -0,0 +1,345 @@
+// 設定ファイルの読み込み
+// 場所: $XDG_CONFIG_HOME/rwe/config （未設定なら ~/.config/rwe/config）
+// 書式: 1行に `key = value`。行頭か空白の後ろの `#` 以降はコメント（値の頭の `#282e36` は色）、不明なキーは無視する。
+//       `[markdown]` のような見出し以降はそのファイルタイプだけの設定になる。
+use crate::{backup::BackupPolicy, logging};
+use std::{collections::HashMap, path::PathBuf, time::Duration};
+use tui::style::Color;
+
+// キー操作のプリセット
+#[derive(Clone, Copy, PartialEq)]
+pub enum Keymap {
+    Default,
+    Vim,
+    Emacs,
+}
+
+// 配色（`theme.<名前> = #rrggbb` または色名）
+#[derive(Clone)]
+pub struct Theme {
+    pub current_line: Color,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme {
+            current_line: Color::Rgb(40, 46, 54),
+        }
+    }
+}
+
+// ファイルタイプごとの設定（未指定は None で既定値に任せる）
+#[derive(Clone, Default)]
+pub struct FiletypeConfig {
+    pub list_continuation: Option<bool>,
+    pub color_columns: Option<Vec<usize>>,
+    pub text_width: Option<usize>,
+    pub indent_width: Option<usize>,
+    pub indent_tabs: Option<bool>,
+    pub lsp: Option<String>, // 言語サーバーの起動コマンド
+    pub formatter: Option<String>, // 標準入力を整形して標準出力に書くコマンド（rustfmt など）
+}
+
+#[derive(Clone)]
+pub struct Config {
+    pub keymap: Keymap,
+    pub recent_limit: usize, // FileTree の最近更新ビューに出す件数
+    pub soft_wrap: bool,
+    pub scrolloff: usize, // 上下の移動でカーソルの上下に残す行数
+    pub show_whitespace: bool,
+    pub virtual_space: bool, // 行末より右へもカーソルを進める
+    pub mouse: bool, // スクロールバーのクリック・ドラッグとホイールを使う
+    pub show_byte_offset: bool, // ステータスバーに表示桁と並べて行の中のバイト位置も出す
+    pub indent_guides: bool,
+    pub frame_budget_ms: u64, // これより遅いフレームを perf.log に残す
+    pub perf_log: bool,
+    pub log_level: logging::Level, // rwe.log に書く詳しさ
+    pub color_columns: Vec<usize>, // 縦のガイドを引く桁（`color_column = 80, 120`）
+    pub text_width: usize, // 段落を詰め直す幅（表示桁）
+    pub indent_width: Option<usize>, // インデント1段の桁数（既定 4）
+    pub indent_tabs: Option<bool>,   // `indent_style = tabs / spaces`
+    pub highlight_current_line: bool,
+    pub theme: Theme,
+    pub backup: bool, // 保存時にローカル履歴を残す
+    pub backup_policy: BackupPolicy,
+    pub lsp_auto_complete: bool, // 入力中に自動で補完を要求する
+    pub delete_permanently: bool, // FileTree の削除でゴミ箱を使わない
+    pub spell_check: bool,
+    pub large_file_mb: u64, // これより大きいファイルは開く前に聞く（0 で聞かない）
+    pub spell_dictionary: String, // 1行1語の辞書ファイル
+    pub tasks: Vec<(String, String)>, // Alt+t で実行するタスク（`task.<名前> = コマンド`、書いた順）
+    pub keys: Vec<(String, String)>, // キーの付け替え（`key.<Action の名前> = Ctrl+Shift+K`、書いた順）
+    pub filetypes: HashMap<String, FiletypeConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            keymap: Keymap::Default,
+            recent_limit: 50,
+            soft_wrap: false,
+            scrolloff: 0,
+            show_whitespace: false,
+            virtual_space: false,
+            mouse: true,
+            show_byte_offset: false,
+            indent_guides: true,
+            frame_budget_ms: 16,
+            perf_log: true,
+            log_level: logging::Level::default(),
+            color_columns: Vec::new(),
+            text_width: 80,
+            indent_width: None,
+            indent_tabs: None,
+            highlight_current_line: true,
+            theme: Theme::default(),
+            backup: true,
+            backup_policy: BackupPolicy::default(),
+            lsp_auto_complete: false,
+            delete_permanently: false,
+            spell_check: false,
+            large_file_mb: 50,
+            spell_dictionary: "/usr/share/dict/words".to_string(),
+            tasks: Vec::new(),
+            keys: Vec::new(),
+            filetypes: HashMap::new(),
+        }
+    }
+}
+
+impl Config {
+    pub fn path() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CONFIG_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+        };
+        Some(base.join("rwe").join("config"))
+    }
+
+    // 履歴などアプリが書き出す状態の置き場所
+    // $XDG_STATE_HOME/rwe （未設定なら ~/.local/state/rwe）
+    pub fn state_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_STATE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("state"),
+        };
+        Some(base.join("rwe"))
+    }
+
+    // 消えても困らないもの（ログ）の置き場所
+    // $XDG_CACHE_HOME/rwe （未設定なら ~/.cache/rwe）
+    pub fn cache_dir() -> Option<PathBuf> {
+        let base = match std::env::var_os("XDG_CACHE_HOME") {
+            Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+            _ => PathBuf::from(std::env::var_os("HOME")?).join(".cache"),
+        };
+        Some(base.join("rwe"))
+    }
+
+    pub fn load() -> Self {
+        let mut config = Config::default();
+        if let Some(path) = Config::path()
+            && let Ok(content) = std::fs::read_to_string(path)
+        {
+            config.parse(&content);
+        }
+        config
+    }
+
+    fn parse(&mut self, content: &str) {
+        let mut section: Option<String> = None;
+        for line in content.lines() {
+            let line = line.trim();
+            if line.starts_with('#') { continue; }
+            if let Some(name) = strip_comment(line).strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                section = Some(name.trim().to_string());
+            } else if let Some((key, value)) = line.split_once('=') {
+                let value = strip_comment(value.trim());
+                match section {
+                    Some(ref ft) => {
+                        let entry = self.filetypes.entry(ft.clone()).or_default();
+                        entry.apply(key.trim(), value);
+                    }
+                    None => self.apply(key.trim(), value),
+                }
+            }
+        }
+    }
+
+    pub fn filetype(&self, filetype: &str) -> FiletypeConfig {
+        self.filetypes.get(filetype).cloned().unwrap_or_default()
+    }
+
+    fn apply(&mut self, key: &str, value: &str) {
+        match key {
+            "keymap" => {
+                self.keymap = match value {
+                    "vim" => Keymap::Vim,
+                    "emacs" => Keymap::Emacs,
+                    _ => Keymap::Default,
+                };
+            }
+            "recent_limit" => {
+                if let Ok(n) = value.parse() { self.recent_limit = n; }
+            }
+            "soft_wrap" => {
+                if let Some(b) = parse_bool(value) { self.soft_wrap = b; }
+            }
+            "scrolloff" => {
+                if let Ok(n) = value.parse() { self.scrolloff = n; }
+            }
+            "show_whitespace" => {
+                if let Some(b) = parse_bool(value) { self.show_whitespace = b; }
+            }
+            "mouse" => {
+                if let Some(b) = parse_bool(value) { self.mouse = b; }
+            }
+            "virtual_space" => {
+                if let Some(b) = parse_bool(value) { self.virtual_space = b; }
+            }
+            "show_byte_offset" => {
+                if let Some(b) = parse_bool(value) { self.show_byte_offset = b; }
+            }
+            "indent_guides" => {
+                if let Some(b) = parse_bool(value) { self.indent_guides = b; }
+            }
+            "frame_budget_ms" => {
+                if let Ok(n) = value.parse() { self.frame_budget_ms = n; }
+            }
+            "perf_log" => {
+                if let Some(b) = parse_bool(value) { self.perf_log = b; }
+            }
+            "log_level" => {
+                if let Some(level) = logging::Level::parse(value) { self.log_level = level; }
+            }
+            "color_column" => {
+                if let Some(cols) = parse_columns(value) { self.color_columns = cols; }
+            }
+            "text_width" => {
+                if let Ok(n) = value.parse::<usize>() && n > 0 { self.text_width = n; }
+            }
+            "indent_width" => {
+                if let Ok(n) = value.parse::<usize>() && n > 0 { self.indent_width = Some(n); }
+            }
+            "indent_style" => {
+                if let Some(tabs) = parse_indent_style(value) { self.indent_tabs = Some(tabs); }
+            }
+            "highlight_current_line" => {
+                if let Some(b) = parse_bool(value) { self.highlight_current_line = b; }
+            }
+            "theme.current_line" => {
+                if let Some(c) = parse_color(value) { self.theme.current_line = c; }
+            }
+            "backup" => {
+                if let Some(b) = parse_bool(value) { self.backup = b; }
+            }
+            "backup.max_versions" => {
+                if let Ok(n) = value.parse() { self.backup_policy.max_versions = n; }
+            }
+            "backup.max_total_mb" => {
+                if let Ok(n) = value.parse::<u64>() { self.backup_policy.max_total_bytes = n * 1024 * 1024; }
+            }
+            "backup.max_age_days" => {
+                if let Ok(n) = value.parse::<u64>() { self.backup_policy.max_age = Duration::from_secs(n * 86400); }
+            }
+            "lsp.auto_complete" => {
+                if let Some(b) = parse_bool(value) { self.lsp_auto_complete = b; }
+            }
+            "delete_permanently" => {
+                if let Some(b) = parse_bool(value) { self.delete_permanently = b; }
+            }
+            "spell_check" => {
+                if let Some(b) = parse_bool(value) { self.spell_check = b; }
+            }
+            "large_file_mb" => {
+                if let Ok(n) = value.parse() { self.large_file_mb = n; }
+            }
+            "spell.dictionary" if !value.is_empty() => self.spell_dictionary = value.to_string(),
+            _ if key.starts_with("task.") => {
+                let name = key["task.".len()..].trim().to_string();
+                self.tasks.retain(|(n, _)| *n != name);
+                if !name.is_empty() && !value.is_empty() { self.tasks.push((name, value.to_string())); }
+            }
+            _ if key.starts_with("key.") => {
+                let name = key["key.".len()..].trim().to_string();
+                if !name.is_empty() && !value.is_empty() { self.keys.push((name, value.to_string())); }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl FiletypeConfig {
+    fn apply(&mut self, key: &str, value: &str) {
+        match key {
+            "list_continuation" => self.list_continuation = parse_bool(value),
+            "color_column" => self.color_columns = parse_columns(value),
+            "text_width" => self.text_width = value.parse().ok().filter(|&n| n > 0),
+            "indent_width" => self.indent_width = value.parse().ok().filter(|&n| n > 0),
+            "indent_style" => self.indent_tabs = parse_indent_style(value),
+            "lsp" => self.lsp = Some(value.to_string()).filter(|v| !v.is_empty()),
+            "formatter" => self.formatter = Some(value.to_string()).filter(|v| !v.is_empty()),
+            _ => {}
+        }
+    }
+}
+
+// 空白の後ろの `#` から行末までを落とす。頭の `#` は値の一部
+fn strip_comment(text: &str) -> &str {
+    let cut = text
+        .char_indices()
+        .zip(text.chars().skip(1))
+        .find(|&((_, prev), c)| c == '#' && prev.is_whitespace())
+        .map_or(text.len(), |((i, prev), _)| i + prev.len_utf8());
+    text[..cut].trim_end()
+}
+
+fn parse_bool(value: &str) -> Option<bool> {
+    match value {
+        "true" | "yes" | "on" => Some(true),
+        "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+// tabs なら true
+fn parse_indent_style(value: &str) -> Option<bool> {
+    match value {
+        "tabs" | "tab" => Some(true),
+        "spaces" | "space" => Some(false),
+        _ => None,
+    }
+}
+
+// カンマ区切りの桁番号（1始まり）。空なら「ガイドなし」
+fn parse_columns(value: &str) -> Option<Vec<usize>> {
+    value
+        .split(',')
+        .map(str::trim)
+        .filter(|s| !s.is_empty())
+        .map(|s| s.parse::<usize>().ok().filter(|&n| n > 0))
+        .collect()
+}
+
+// "#rrggbb" または基本の色名
+fn parse_color(value: &str) -> Option<Color> {
+    if let Some(hex) = value.strip_prefix('#') {
+        if hex.len() != 6 { return None; }
+        let n = u32::from_str_radix(hex, 16).ok()?;
+        return Some(Color::Rgb((n >> 16) as u8, (n >> 8) as u8, n as u8));
+    }
+    Some(match value.to_ascii_lowercase().as_str() {
+        "black" => Color::Black,
+        "red" => Color::Red,
+        "green" => Color::Green,
+        "yellow" => Color::Yellow,
+        "blue" => Color::Blue,
+        "magenta" => Color::Magenta,
+        "cyan" => Color::Cyan,
+        "gray" | "grey" => Color::Gray,
+        "darkgray" | "darkgrey" => Color::DarkGray,
+        "white" => Color::White,
+        "reset" | "none" => Color::Reset,
+        _ => return None,
+    })
+}
@@ -0,0 +1,213 @@
+// 行単位の差分（LCS）
+// 共通の先頭・末尾を削ってから中間部分だけ DP する。大きすぎる場合は中間を全置換扱い
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum DiffOp {
+    Equal(usize, usize), // (旧の行, 新の行)
+    Delete(usize),       // 旧の行
+    Insert(usize),       // 新の行
+}
+
+const MAX_DP_CELLS: usize = 4_000_000;
+
+pub fn diff_lines(old: &[String], new: &[String]) -> Vec<DiffOp> {
+    let prefix = old.iter().zip(new).take_while(|(a, b)| a == b).count();
+    let suffix = old[prefix..]
+        .iter()
+        .rev()
+        .zip(new[prefix..].iter().rev())
+        .take_while(|(a, b)| a == b)
+        .count();
+    let a = &old[prefix..old.len() - suffix];
+    let b = &new[prefix..new.len() - suffix];
+
+    let mut ops: Vec<DiffOp> = (0..prefix).map(|i| DiffOp::Equal(i, i)).collect();
+    if a.len().saturating_mul(b.len()) > MAX_DP_CELLS {
+        ops.extend((0..a.len()).map(|i| DiffOp::Delete(prefix + i)));
+        ops.extend((0..b.len()).map(|j| DiffOp::Insert(prefix + j)));
+    } else {
+        // lcs[i][j] = a[i..] と b[j..] の LCS 長
+        let (n, m) = (a.len(), b.len());
+        let mut lcs = vec![0u32; (n + 1) * (m + 1)];
+        for i in (0..n).rev() {
+            for j in (0..m).rev() {
+                lcs[i * (m + 1) + j] = if a[i] == b[j] {
+                    lcs[(i + 1) * (m + 1) + j + 1] + 1
+                } else {
+                    lcs[(i + 1) * (m + 1) + j].max(lcs[i * (m + 1) + j + 1])
+                };
+            }
+        }
+        let (mut i, mut j) = (0, 0);
+        while i < n || j < m {
+            if i < n && j < m && a[i] == b[j] {
+                ops.push(DiffOp::Equal(prefix + i, prefix + j));
+                i += 1;
+                j += 1;
+            } else if i < n && (j == m || lcs[(i + 1) * (m + 1) + j] >= lcs[i * (m + 1) + j + 1]) {
+                ops.push(DiffOp::Delete(prefix + i));
+                i += 1;
+            } else {
+                ops.push(DiffOp::Insert(prefix + j));
+                j += 1;
+            }
+        }
+    }
+    let (old_tail, new_tail) = (old.len() - suffix, new.len() - suffix);
+    ops.extend((0..suffix).map(|k| DiffOp::Equal(old_tail + k, new_tail + k)));
+    ops
+}
+
+// 旧の各行が新しい内容のどの行に対応するか。削除された行は直後に残った位置へ寄せる
+pub fn line_map(ops: &[DiffOp], old_len: usize) -> Vec<usize> {
+    let mut map = vec![0; old_len];
+    let mut new_pos = 0;
+    for op in ops {
+        match *op {
+            DiffOp::Equal(i, j) => {
+                map[i] = j;
+                new_pos = j + 1;
+            }
+            DiffOp::Delete(i) => map[i] = new_pos,
+            DiffOp::Insert(j) => new_pos = j + 1,
+        }
+    }
+    map
+}
+
+// 確認用の簡易プレビュー。変更行だけを `-`/`+` 付きで並べ、離れた箇所の間には `@@ 行番号` を挟む
+pub fn preview(ops: &[DiffOp], old: &[String], new: &[String], limit: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut last_equal = true;
+    for op in ops {
+        if out.len() >= limit {
+            out.push("...".to_string());
+            break;
+        }
+        match *op {
+            DiffOp::Equal(..) => last_equal = true,
+            DiffOp::Delete(i) => {
+                if last_equal { out.push(format!("@@ line {}", i + 1)); }
+                out.push(format!("-{}", old[i]));
+                last_equal = false;
+            }
+            DiffOp::Insert(j) => {
+                if last_equal { out.push(format!("@@ line {}", j + 1)); }
+                out.push(format!("+{}", new[j]));
+                last_equal = false;
+            }
+        }
+    }
+    out
+}
+
+// unified diff の本文（`@@ -旧 +新 @@` の見出しと、前後 context 行を添えた ` `/`-`/`+` の行）
+pub fn unified(ops: &[DiffOp], old: &[String], new: &[String], context: usize) -> Vec<String> {
+    // 各操作の直前の (旧の行, 新の行)
+    let mut pos = Vec::with_capacity(ops.len() + 1);
+    let (mut o, mut n) = (0, 0);
+    for op in ops {
+        pos.push((o, n));
+        match op {
+            DiffOp::Equal(..) => (o, n) = (o + 1, n + 1),
+            DiffOp::Delete(_) => o += 1,
+            DiffOp::Insert(_) => n += 1,
+        }
+    }
+    pos.push((o, n));
+    let changed: Vec<usize> = (0..ops.len()).filter(|&k| !matches!(ops[k], DiffOp::Equal(..))).collect();
+    let mut out = Vec::new();
+    let mut k = 0;
+    while k < changed.len() {
+        let start = changed[k].saturating_sub(context);
+        let mut end = changed[k] + 1;
+        // 間の一致が 2*context 行以下なら同じ塊にまとめる
+        while k + 1 < changed.len() && changed[k + 1] <= end + 2 * context {
+            k += 1;
+            end = changed[k] + 1;
+        }
+        k += 1;
+        let end = (end + context).min(ops.len());
+        let ((o0, n0), (o1, n1)) = (pos[start], pos[end]);
+        out.push(format!("@@ -{} +{} @@", hunk_range(o0, o1 - o0), hunk_range(n0, n1 - n0)));
+        for op in &ops[start..end] {
+            out.push(match *op {
+                DiffOp::Equal(i, _) => format!(" {}", old[i]),
+                DiffOp::Delete(i) => format!("-{}", old[i]),
+                DiffOp::Insert(j) => format!("+{}", new[j]),
+            });
+        }
+    }
+    out
+}
+
+// 見出しの `開始,行数`。空の範囲は直前の行番号で表す
+fn hunk_range(start: usize, len: usize) -> String {
+    match len {
+        0 => format!("{},0", start),
+        1 => format!("{}", start + 1),
+        _ => format!("{},{}", start + 1, len),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use DiffOp::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn diffs_a_changed_line() {
+        let (old, new) = (lines(&["a", "b", "c"]), lines(&["a", "x", "c"]));
+        assert_eq!(diff_lines(&old, &new), [Equal(0, 0), Delete(1), Insert(1), Equal(2, 2)]);
+        assert_eq!(diff_lines(&old, &old), [Equal(0, 0), Equal(1, 1), Equal(2, 2)]);
+    }
+
+    #[test]
+    fn diffs_empty_inputs() {
+        let (empty, one) = (lines(&[]), lines(&["a"]));
+        assert_eq!(diff_lines(&empty, &empty), []);
+        assert_eq!(diff_lines(&empty, &one), [Insert(0)]);
+        assert_eq!(diff_lines(&one, &empty), [Delete(0)]);
+    }
+
+    #[test]
+    fn maps_old_lines_to_new_positions() {
+        // 削除された b は直後に残った c の位置へ
+        let (old, new) = (lines(&["a", "b", "c", "d"]), lines(&["a", "c", "d", "e"]));
+        assert_eq!(line_map(&diff_lines(&old, &new), old.len()), [0, 1, 1, 2]);
+        let (old, new) = (lines(&["a", "b"]), lines(&["x", "a", "b"]));
+        assert_eq!(line_map(&diff_lines(&old, &new), old.len()), [1, 2]);
+    }
+
+    #[test]
+    fn previews_changed_lines() {
+        let (old, new) = (lines(&["a", "b", "c"]), lines(&["a", "x", "c"]));
+        let ops = diff_lines(&old, &new);
+        assert_eq!(preview(&ops, &old, &new, 10), ["@@ line 2", "-b", "+x"]);
+        assert_eq!(preview(&ops, &old, &new, 2), ["@@ line 2", "-b", "..."]);
+    }
+
+    #[test]
+    fn unified_splits_distant_changes_into_hunks() {
+        let old = lines(&["1", "2", "3", "4", "5", "6", "7", "8", "9", "10"]);
+        let mut new = old.clone();
+        new[1] = "two".to_string();
+        new[8] = "nine".to_string();
+        assert_eq!(
+            unified(&diff_lines(&old, &new), &old, &new, 1),
+            ["@@ -1,3 +1,3 @@", " 1", "-2", "+two", " 3", "@@ -8,3 +8,3 @@", " 8", "-9", "+nine", " 10"]
+        );
+        // 近い変更は1つの塊にまとめる
+        assert_eq!(unified(&diff_lines(&old, &new), &old, &new, 3)[0], "@@ -1,10 +1,10 @@");
+    }
+
+    #[test]
+    fn unified_headers_for_empty_ranges() {
+        let (empty, one) = (lines(&[]), lines(&["a"]));
+        assert_eq!(unified(&diff_lines(&empty, &one), &empty, &one, 3), ["@@ -0,0 +1 @@", "+a"]);
+        assert_eq!(unified(&diff_lines(&one, &empty), &one, &empty, 3), ["@@ -1 +0,0 @@", "-a"]);
+    }
+}
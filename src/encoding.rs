@@ -0,0 +1,232 @@
+// テキストとして開けないファイルの扱いと文字コード
+// NUL を含むか UTF-8 として読めないファイルを FileTree から開くときは、黙って失敗したり壊したりせず、
+// 16進表示・読み取り専用（読めないバイトは U+FFFD）・別の文字コード（Latin-1、BOM があれば UTF-16）の
+// どれで開くかを聞く。別の文字コードで開いたバッファは同じ文字コードで保存する。
+// 設定の large_file_mb より大きいファイルは読む前に、全部開くか頭だけ読み取り専用で開くかを聞く
+use crate::{
+    format_size, grapheme,
+    loader::{Extent, Loaded},
+    App, Mode, PopupMode,
+};
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+};
+
+#[derive(Clone, Copy, PartialEq, Default)]
+pub enum Encoding {
+    #[default]
+    Utf8,
+    Latin1,  // ISO-8859-1（1 バイトが 1 文字）
+    Utf16Le, // BOM 付き
+    Utf16Be,
+}
+
+impl Encoding {
+    pub fn label(self) -> &'static str {
+        match self {
+            Encoding::Utf8 => "UTF-8",
+            Encoding::Latin1 => "Latin-1",
+            Encoding::Utf16Le => "UTF-16LE",
+            Encoding::Utf16Be => "UTF-16BE",
+        }
+    }
+
+    pub fn decode(self, bytes: &[u8]) -> Result<String, String> {
+        let utf16 = |bytes: &[u8], pair: fn([u8; 2]) -> u16| {
+            let bytes = bytes.get(2..).unwrap_or_default(); // BOM
+            if !bytes.len().is_multiple_of(2) {
+                return Err("odd number of bytes for UTF-16".to_string());
+            }
+            String::from_utf16(&bytes.chunks(2).map(|c| pair([c[0], c[1]])).collect::<Vec<u16>>()).map_err(|e| e.to_string())
+        };
+        match self {
+            Encoding::Utf8 => String::from_utf8(bytes.to_vec()).map_err(|e| format!("invalid UTF-8 at offset {:#x}", e.utf8_error().valid_up_to())),
+            Encoding::Latin1 => Ok(bytes.iter().map(|&b| b as char).collect()),
+            Encoding::Utf16Le => utf16(bytes, u16::from_le_bytes),
+            Encoding::Utf16Be => utf16(bytes, u16::from_be_bytes),
+        }
+    }
+
+    pub fn encode(self, text: &str) -> Result<Vec<u8>, String> {
+        match self {
+            Encoding::Utf8 => Ok(text.as_bytes().to_vec()),
+            Encoding::Latin1 => text
+                .chars()
+                .map(|c| u8::try_from(c).map_err(|_| format!("'{}' cannot be saved as Latin-1", c)))
+                .collect(),
+            Encoding::Utf16Le => Ok("\u{feff}".encode_utf16().chain(text.encode_utf16()).flat_map(u16::to_le_bytes).collect()),
+            Encoding::Utf16Be => Ok("\u{feff}".encode_utf16().chain(text.encode_utf16()).flat_map(u16::to_be_bytes).collect()),
+        }
+    }
+}
+
+// BOM から UTF-16 かどうか
+fn utf16_bom(bytes: &[u8]) -> Option<Encoding> {
+    match bytes.get(..2)? {
+        [0xff, 0xfe] => Some(Encoding::Utf16Le),
+        [0xfe, 0xff] => Some(Encoding::Utf16Be),
+        _ => None,
+    }
+}
+
+// テキストとして開けない理由。開けるなら None
+fn sniff(bytes: &[u8]) -> Option<String> {
+    if utf16_bom(bytes).is_some() {
+        Some("has a UTF-16 byte order mark".to_string())
+    } else if let Some(pos) = bytes.iter().position(|&b| b == 0) {
+        Some(format!("contains NUL bytes (first at offset {:#x})", pos))
+    } else {
+        Encoding::Utf8.decode(bytes).err()
+    }
+}
+
+// 頭から limit バイトまで。途中で切れた最後の行は捨てる
+pub fn read_head(path: &Path, limit: u64) -> std::io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    std::fs::File::open(path)?.take(limit).read_to_end(&mut bytes)?;
+    drop_partial_line(&mut bytes);
+    Ok(bytes)
+}
+
+pub fn drop_partial_line(bytes: &mut Vec<u8>) {
+    if let Some(end) = bytes.iter().rposition(|&b| b == b'\n') {
+        bytes.truncate(end + 1);
+    }
+}
+
+// 確認で選べる開き方
+#[derive(Clone, PartialEq)]
+pub enum OpenAs {
+    Whole, // 大きくても全部読む
+    Head(u64), // 頭のこのバイト数だけ読み取り専用で
+    Hex,
+    ReadOnly,
+    Encoding(Encoding),
+    Cancel,
+}
+
+impl OpenAs {
+    pub fn label(&self) -> String {
+        match self {
+            OpenAs::Whole => "Open anyway (reads the whole file; may take a while)".to_string(),
+            OpenAs::Head(limit) => format!("Open only the first {}, read-only", format_size(*limit)),
+            OpenAs::Hex => "Open in the hex view (F8 switches to text)".to_string(),
+            OpenAs::ReadOnly => "Open read-only as text (invalid bytes shown as \u{fffd})".to_string(),
+            OpenAs::Encoding(Encoding::Latin1) => "Open as Latin-1 (ISO-8859-1), saved back as Latin-1".to_string(),
+            OpenAs::Encoding(e) => format!("Open as {}, saved back as {}", e.label(), e.label()),
+            OpenAs::Cancel => "Cancel".to_string(),
+        }
+    }
+}
+
+impl App {
+    pub fn large_file_bytes(&self) -> u64 {
+        self.config.large_file_mb * 1024 * 1024
+    }
+
+    // FileTree などから開く。読むのはワーカーに任せ、読み終わったら open_loaded でエディタに切り替える
+    pub fn open_path(&mut self, path: PathBuf) {
+        let limit = self.large_file_bytes();
+        self.goto_after_load = None;
+        self.start_load(path, Extent::Checked(limit));
+    }
+
+    // 大きすぎるかテキストとして読めなければ開き方を聞く
+    pub fn open_loaded(&mut self, path: PathBuf, extent: Extent, loaded: Loaded) {
+        let (bytes, size) = match loaded {
+            Loaded::Bytes { bytes, size } => (bytes, size),
+            Loaded::TooLarge(size) => {
+                let limit = self.large_file_bytes();
+                let reason = format!("is {} (over large_file_mb = {})", format_size(size), self.config.large_file_mb);
+                self.popup = Some(PopupMode::OpenChoice { path, reason, choices: vec![OpenAs::Whole, OpenAs::Head(limit), OpenAs::Cancel] });
+                self.popup_selected = 0;
+                return;
+            }
+            Loaded::Failed(e) => {
+                self.notify_err(format!("Cannot open {}: {}", path.display(), e));
+                return;
+            }
+        };
+        if let Extent::Head(_) = extent {
+            self.load_buffer(path, &String::from_utf8_lossy(&bytes), Encoding::Utf8);
+            self.read_only = true;
+            self.partial = Some(size);
+            self.mode = Mode::Editor;
+            self.lsp_open_document();
+            return;
+        }
+        let Some(reason) = sniff(&bytes).map(|r| format!("is not UTF-8 text: {}", r)) else {
+            self.load_buffer(path, &String::from_utf8_lossy(&bytes), Encoding::Utf8);
+            self.mode = Mode::Editor;
+            self.lsp_open_document();
+            return;
+        };
+        let mut choices = vec![OpenAs::Hex, OpenAs::ReadOnly];
+        choices.extend(utf16_bom(&bytes).map(OpenAs::Encoding));
+        choices.extend([OpenAs::Encoding(Encoding::Latin1), OpenAs::Cancel]);
+        self.popup = Some(PopupMode::OpenChoice { path, reason, choices });
+        self.popup_selected = 0;
+    }
+
+    pub fn open_choice_title(&self) -> String {
+        let Some(PopupMode::OpenChoice { ref path, ref reason, .. }) = self.popup else { return String::new() };
+        let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+        format!("{} {} (Enter to pick, Esc to cancel)", name, reason)
+    }
+
+    pub fn open_as(&mut self, path: &Path, choice: &OpenAs) {
+        match choice {
+            OpenAs::Whole => {
+                self.start_load(path.to_path_buf(), Extent::Whole);
+                return;
+            }
+            OpenAs::Head(limit) => {
+                self.start_load(path.to_path_buf(), Extent::Head(*limit));
+                return;
+            }
+            _ => {}
+        }
+        let bytes = match std::fs::read(path) {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.notify_err(format!("Cannot open {}: {}", path.display(), e));
+                return;
+            }
+        };
+        match choice {
+            OpenAs::Hex | OpenAs::ReadOnly => {
+                self.load_buffer(path.to_path_buf(), &String::from_utf8_lossy(&bytes), Encoding::Utf8);
+                self.read_only = true;
+                if *choice == OpenAs::Hex { self.toggle_hex(); }
+            }
+            OpenAs::Encoding(encoding) => match encoding.decode(&bytes) {
+                Ok(text) => self.load_buffer(path.to_path_buf(), &text, *encoding),
+                Err(e) => {
+                    self.notify_err(format!("Cannot read as {}: {}", encoding.label(), e));
+                    return;
+                }
+            },
+            OpenAs::Whole | OpenAs::Head(_) | OpenAs::Cancel => return,
+        }
+        self.mode = Mode::Editor;
+        self.lsp_open_document();
+    }
+
+    // 読み取り専用のバッファは編集を取り消す（開いた後に undo の記録が増えたら元へ戻す）
+    pub fn keep_read_only(&mut self) {
+        if !self.read_only || self.hex.is_some() || self.undo_stack.is_empty() { return; }
+        self.dirty = true;
+        *self.lines = self.undo_stack.swap_remove(0);
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        self.modified = false;
+        self.cursor_y = self.cursor_y.min(self.lines.len() - 1);
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        self.selection_reset();
+        self.message = Some(match self.partial {
+            Some(_) => "Read-only: only the beginning of this large file is loaded".to_string(),
+            None => "Read-only: the file is not UTF-8 text (F8 edits its bytes)".to_string(),
+        });
+    }
+}
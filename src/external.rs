@@ -0,0 +1,286 @@
+// 外部コマンドとの連携
+// - 整形 (Alt+=): ファイルタイプごとの `formatter = rustfmt` などにバッファを標準入力で渡し、
+//   成功したら標準出力で置き換える（1回の undo で戻せる）。設定がなければ言語サーバーで整形する
+// - パイプ (Alt+|): 選択範囲（なければバッファ全体）を入力したコマンドに通し、出力で置き換える
+// - 挿入 (Alt+!): 入力したコマンド（date, uuidgen など）の出力をカーソル位置に挿入する
+// コマンドは sh -c でファイルのあるディレクトリから非同期に実行し、結果は poll_external で受け取る。
+// TIMEOUT を過ぎても終わらなければプロセスグループごと止める
+use crate::{
+    lsp::{self, TextEdit},
+    prompt::Prompt,
+    wake, App, TextPos,
+};
+use std::{
+    io::{self, Write},
+    os::unix::process::CommandExt,
+    path::{Path, PathBuf},
+    process::{Command, Output, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+    time::{Duration, Instant},
+};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+pub struct ShellJob {
+    rx: Receiver<io::Result<Output>>,
+    pid: u32, // プロセスグループの ID（sh が先頭）
+    started: Instant,
+}
+
+impl ShellJob {
+    pub fn spawn(command: &str, input: String, dir: &Path) -> io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .current_dir(dir)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        let pid = child.id();
+        // 入力が大きいとパイプが詰まるので書き込みは別スレッドで
+        if let Some(mut stdin) = child.stdin.take() {
+            thread::spawn(move || {
+                let _ = stdin.write_all(input.as_bytes());
+            });
+        }
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let _ = tx.send(child.wait_with_output());
+            wake::wake();
+        });
+        Ok(ShellJob { rx, pid, started: Instant::now() })
+    }
+
+    fn try_recv(&self) -> Option<io::Result<Output>> {
+        self.rx.try_recv().ok()
+    }
+
+    fn timed_out(&self) -> bool {
+        self.started.elapsed() > TIMEOUT
+    }
+
+    fn time_left(&self) -> Duration {
+        TIMEOUT.saturating_sub(self.started.elapsed())
+    }
+
+    // sh から起動された子プロセスも含めて止める
+    fn kill(&self) {
+        let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", self.pid)]).stderr(Stdio::null()).status();
+    }
+}
+
+// 失敗したときの表示: 標準エラーの最初の2行（エラーと場所。なければ終了コード）
+pub fn failure_text(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let lines: Vec<&str> = stderr.lines().map(str::trim).filter(|l| !l.is_empty()).take(2).collect();
+    if lines.is_empty() {
+        match output.status.code() {
+            Some(code) => format!("exit status {}", code),
+            None => "killed by signal".to_string(),
+        }
+    } else {
+        lines.join(" | ")
+    }
+}
+
+// sh に渡すために ' で囲む
+pub fn shell_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+enum JobKind {
+    Format,                                               // バッファ全体を置き換える
+    Pipe { start: TextPos, end: TextPos, input: String }, // start..end（元の内容は input）を置き換える
+    Insert { start: TextPos, end: TextPos },              // start..end（選択がなければカーソル位置）に挿入する
+}
+
+pub struct ExternalJob {
+    job: ShellJob,
+    kind: JobKind,
+    text: String, // 実行したときのバッファ（結果が返るまでに編集されたら適用しない）
+    name: String, // 表示用のコマンド名
+}
+
+impl App {
+    // コマンドを実行するディレクトリ（ファイルのある場所、なければ FileTree の場所）
+    pub fn command_dir(&self) -> PathBuf {
+        self.current_file
+            .as_deref()
+            .and_then(Path::parent)
+            .filter(|d| !d.as_os_str().is_empty())
+            .map_or_else(|| self.file_tree.current_path.clone(), Path::to_path_buf)
+    }
+
+    // 設定された整形コマンド。`{file}` はファイルのパスに置き換える（prettier の --stdin-filepath など）
+    pub fn external_formatter(&self) -> Option<String> {
+        let command = self.config.filetype(self.filetype()).formatter?;
+        let path = self.current_file.as_ref().map(|p| shell_quote(&p.to_string_lossy())).unwrap_or_default();
+        Some(command.replace("{file}", &path))
+    }
+
+    // command に input を渡して実行を始める
+    fn start_job(&mut self, command: &str, input: String, kind: JobKind) {
+        if self.external_job.is_some() {
+            self.message = Some("Another command is still running".to_string());
+            return;
+        }
+        let name = command.split_whitespace().next().unwrap_or("command").to_string();
+        match ShellJob::spawn(command, input, &self.command_dir()) {
+            Ok(job) => {
+                self.message = Some(format!("Running {}...", name));
+                let text = self.lines.join("\n");
+                self.external_job = Some(ExternalJob { job, kind, text, name });
+            }
+            Err(e) => self.message = Some(format!("Cannot run {}: {}", name, e)),
+        }
+    }
+
+    pub fn run_formatter(&mut self, command: String) {
+        self.start_job(&command, self.lines.join("\n") + "\n", JobKind::Format);
+    }
+
+    pub fn open_pipe_prompt(&mut self) {
+        let prompt = Prompt::text("Pipe selection (whole buffer if none) through shell command, e.g. sort", |app| {
+            let command = std::mem::take(&mut app.popup_input);
+            app.pipe_selection(command);
+        });
+        self.open_prompt(prompt, "");
+    }
+
+    pub fn open_insert_command_prompt(&mut self) {
+        let prompt = Prompt::text("Insert output of shell command at cursor, e.g. date", |app| {
+            let command = std::mem::take(&mut app.popup_input);
+            app.insert_command_output(command);
+        });
+        self.open_prompt(prompt, "");
+    }
+
+    // 選択範囲（なければバッファ全体）を command に通す。入力の最後の行にも改行を付けて渡す
+    pub fn pipe_selection(&mut self, command: String) {
+        if command.trim().is_empty() { return; }
+        let (start, end, input) = match (self.sel_start, self.sel_end) {
+            (Some(a), Some(b)) if a != b => (a.min(b), a.max(b), self.get_selected_text().unwrap_or_default()),
+            _ => {
+                let last = self.lines.len() - 1;
+                ((0, 0), (last, self.lines[last].len()), self.lines.join("\n"))
+            }
+        };
+        let mut stdin = input.clone();
+        if !stdin.ends_with('\n') { stdin.push('\n'); }
+        self.start_job(&command, stdin, JobKind::Pipe { start, end, input });
+    }
+
+    // command の出力をカーソル位置に挿入する（選択があれば置き換える）
+    pub fn insert_command_output(&mut self, command: String) {
+        if command.trim().is_empty() { return; }
+        let (start, end) = match (self.sel_start, self.sel_end) {
+            (Some(a), Some(b)) => (a.min(b), a.max(b)),
+            _ => {
+                let at = (self.cursor_y, self.cursor_x.min(self.lines[self.cursor_y].len()));
+                (at, at)
+            }
+        };
+        self.start_job(&command, String::new(), JobKind::Insert { start, end });
+    }
+
+    // 外部コマンドの結果を受け取る（メインループで毎回呼ぶ）
+    // 実行中のコマンドが時間切れになるまで
+    pub fn external_left(&self) -> Option<Duration> {
+        Some(self.external_job.as_ref()?.job.time_left() + Duration::from_millis(1))
+    }
+
+    pub fn poll_external(&mut self) {
+        let Some(job) = self.external_job.as_ref() else { return };
+        let Some(result) = job.job.try_recv() else {
+            if job.job.timed_out() {
+                job.job.kill();
+                self.dirty = true;
+                self.message = Some(format!("{} timed out after {}s; killed", job.name, TIMEOUT.as_secs()));
+                self.external_job = None;
+            }
+            return;
+        };
+        let Some(ExternalJob { kind, text, name, .. }) = self.external_job.take() else { return };
+        self.dirty = true;
+        let output = match result {
+            Ok(output) if output.status.success() => output,
+            Ok(output) => {
+                self.message = Some(format!("{} failed: {}", name, failure_text(&output)));
+                return;
+            }
+            Err(e) => {
+                self.message = Some(format!("Cannot run {}: {}", name, e));
+                return;
+            }
+        };
+        if self.lines.join("\n") != text {
+            self.message = Some(format!("Buffer changed while {} was running; not applied", name));
+            return;
+        }
+        let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+        match kind {
+            JobKind::Format => self.format_output(&stdout, &text, &name),
+            JobKind::Pipe { start, end, input } => self.pipe_output(stdout, start, end, &input, &name),
+            JobKind::Insert { start, end } => self.insert_output(stdout, start, end, &name),
+        }
+    }
+
+    fn format_output(&mut self, stdout: &str, text: &str, name: &str) {
+        // 出力が空なのは整形結果ではなく設定の誤り（ファイルを直接書き換えるコマンドなど）とみなす
+        if stdout.is_empty() && text.trim() != "" {
+            self.message = Some(format!("{} printed nothing; not applied", name));
+            return;
+        }
+        let mut new_lines: Vec<String> = stdout.lines().map(|s| s.to_string()).collect();
+        if new_lines.is_empty() { new_lines.push(String::new()); }
+        if new_lines == *self.lines {
+            self.message = Some("Already formatted".to_string());
+            return;
+        }
+        self.replace_all_lines(new_lines);
+        self.message = Some(format!("Formatted with {}", name));
+    }
+
+    // 範囲を出力で置き換え、置き換えた部分を選択しておく（続けて別のコマンドに通せる）
+    fn pipe_output(&mut self, mut stdout: String, start: TextPos, end: TextPos, input: &str, name: &str) {
+        // 入力に足した末尾の改行は出力からも除く
+        if !input.ends_with('\n') && stdout.ends_with('\n') {
+            stdout.pop();
+        }
+        if stdout == input {
+            self.message = Some(format!("{}: no changes", name));
+            return;
+        }
+        let new_end = lsp::end_of_insert(start, &stdout);
+        self.save_undo();
+        lsp::apply_text_edits(&mut self.lines, vec![TextEdit { start, end, text: stdout }]);
+        self.selection_reset();
+        if start != new_end {
+            self.sel_start = Some(start);
+            self.sel_end = Some(new_end);
+            self.shift_selection = true;
+        }
+        (self.cursor_y, self.cursor_x) = new_end;
+        self.adjust_h_scroll(0);
+        self.message = Some(format!("Replaced with output of {}", name));
+    }
+
+    // 出力の最後の改行は除いて挿入し、カーソルを挿入した内容の後ろへ
+    fn insert_output(&mut self, mut stdout: String, start: TextPos, end: TextPos, name: &str) {
+        if stdout.ends_with('\n') { stdout.pop(); }
+        if stdout.is_empty() {
+            self.message = Some(format!("{} printed nothing", name));
+            return;
+        }
+        let new_end = lsp::end_of_insert(start, &stdout);
+        self.save_undo();
+        lsp::apply_text_edits(&mut self.lines, vec![TextEdit { start, end, text: stdout }]);
+        self.selection_reset();
+        (self.cursor_y, self.cursor_x) = new_end;
+        self.adjust_h_scroll(0);
+        self.message = Some(format!("Inserted output of {}", name));
+    }
+}
@@ -0,0 +1,123 @@
+// FileTree の複数選択とコピー・移動
+// Space で項目に印を付け（もう一度で外す）、Delete・F5 (コピー)・F6 (移動) を印の付いた項目すべてに
+// まとめて行う。印がなければ選択中の項目が対象（F6 は名前の変更）。操作が終わると印は消える
+use crate::{App, FileTree, PopupMode};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+};
+
+// ディレクトリは中身ごと写す。リンクはリンクとして写す
+fn copy_recursive(from: &Path, to: &Path) -> io::Result<()> {
+    let meta = from.symlink_metadata()?;
+    if meta.file_type().is_symlink() {
+        std::os::unix::fs::symlink(fs::read_link(from)?, to)
+    } else if meta.is_dir() {
+        fs::create_dir(to)?;
+        for entry in fs::read_dir(from)? {
+            let entry = entry?;
+            copy_recursive(&entry.path(), &to.join(entry.file_name()))?;
+        }
+        Ok(())
+    } else {
+        fs::copy(from, to).map(|_| ())
+    }
+}
+
+impl FileTree {
+    // Space: 選択中の項目の印を切り替えて次へ
+    pub fn toggle_mark(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else { return };
+        if !self.marked.remove(&entry.path) {
+            self.marked.insert(entry.path.clone());
+        }
+        self.move_down();
+    }
+
+    // 操作の対象。印を付けたディレクトリの中の項目は、ディレクトリごと扱うので除く
+    pub fn targets(&self) -> Vec<PathBuf> {
+        if self.marked.is_empty() {
+            return self.entries.get(self.selected).map(|e| e.path.clone()).into_iter().collect();
+        }
+        let mut paths: Vec<PathBuf> = self.marked.iter().filter(|p| !self.marked.iter().any(|m| m != *p && p.starts_with(m))).cloned().collect();
+        paths.sort();
+        paths
+    }
+}
+
+impl App {
+    // F5: コピー先を聞く
+    pub fn file_tree_copy_prompt(&mut self) {
+        let paths = self.file_tree.targets();
+        if paths.is_empty() { return; }
+        self.popup = Some(PopupMode::TransferEntries { paths, copy: true });
+        self.popup_input.clear();
+    }
+
+    // F6: 印があれば移動先を聞く。なければ選択中の項目の名前を変える
+    pub fn file_tree_move_prompt(&mut self) {
+        if self.file_tree.marked.is_empty() {
+            self.file_tree_rename_prompt();
+            return;
+        }
+        self.popup = Some(PopupMode::TransferEntries { paths: self.file_tree.targets(), copy: false });
+        self.popup_input.clear();
+    }
+
+    pub fn transfer_title(&self) -> String {
+        let Some(PopupMode::TransferEntries { ref paths, copy }) = self.popup else { return String::new() };
+        let what = match paths.as_slice() {
+            [path] => path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            _ => format!("{} entries", paths.len()),
+        };
+        format!("{} {} to directory (relative to the FileTree path)", if copy { "Copy" } else { "Move" }, what)
+    }
+
+    // dest（FileTree のパスからの相対、なければ作る）へ写す・移す。同じ名前があるものは飛ばす
+    pub fn file_tree_transfer(&mut self, paths: &[PathBuf], dest: &str, copy: bool) {
+        let dir = self.file_tree.current_path.join(dest);
+        if let Err(e) = fs::create_dir_all(&dir) {
+            self.notify_err(format!("Cannot create {}: {}", dir.display(), e));
+            return;
+        }
+        let mut done = 0;
+        let mut error = None;
+        for path in paths {
+            let Some(name) = path.file_name() else { continue };
+            let target = dir.join(name);
+            let result = if target.exists() || target.symlink_metadata().is_ok() {
+                Err(format!("{} already exists", target.display()))
+            } else if path.is_dir() && !path.is_symlink() && dir.starts_with(path) {
+                Err(format!("cannot put {} inside itself", name.to_string_lossy()))
+            } else if copy {
+                copy_recursive(path, &target).map_err(|e| format!("cannot copy {}: {}", name.to_string_lossy(), e))
+            } else {
+                fs::rename(path, &target).map_err(|e| format!("cannot move {}: {}", name.to_string_lossy(), e))
+            };
+            match result {
+                Ok(()) => {
+                    done += 1;
+                    if copy { continue; }
+                    // 開いているファイルと開いているディレクトリは移した先へ
+                    let moved = |p: &Path| p.strip_prefix(path).ok().map(|rest| if rest.as_os_str().is_empty() { target.clone() } else { target.join(rest) });
+                    if let Some(file) = self.current_file.as_deref().and_then(moved) {
+                        self.current_file = Some(file);
+                    }
+                    let expanded: Vec<PathBuf> = self.file_tree.expanded.iter().filter(|p| p.starts_with(path)).cloned().collect();
+                    for p in expanded {
+                        self.file_tree.expanded.remove(&p);
+                        self.file_tree.expanded.extend(moved(&p));
+                    }
+                }
+                Err(e) => error = Some(e),
+            }
+        }
+        let verb = if copy { "Copied" } else { "Moved" };
+        match error {
+            None => self.notify_ok(format!("{} {} entr{} to {}", verb, done, if done == 1 { "y" } else { "ies" }, dir.display())),
+            Some(e) => self.notify_err(format!("{} {} of {} entries; {}", verb, done, paths.len(), e)),
+        }
+        self.file_tree.marked.clear();
+        self.file_tree.reload();
+    }
+}
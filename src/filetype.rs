@@ -0,0 +1,31 @@
+// 拡張子・ファイル名からファイルタイプを判定する
+use std::path::Path;
+
+pub fn detect(path: &Path) -> &'static str {
+    let name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    match name {
+        "Makefile" | "makefile" | "GNUmakefile" => return "make",
+        "Dockerfile" => return "dockerfile",
+        "COMMIT_EDITMSG" | "MERGE_MSG" | "SQUASH_MSG" | "TAG_EDITMSG" => return "gitcommit",
+        _ => {}
+    }
+    let ext = path.extension().and_then(|s| s.to_str()).unwrap_or("").to_ascii_lowercase();
+    match ext.as_str() {
+        "md" | "markdown" => "markdown",
+        "rs" => "rust",
+        "py" => "python",
+        "js" | "mjs" | "cjs" | "jsx" => "javascript",
+        "ts" | "tsx" => "typescript",
+        "json" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "c" | "h" => "c",
+        "cpp" | "cc" | "cxx" | "hpp" => "cpp",
+        "go" => "go",
+        "sh" | "bash" | "zsh" => "shell",
+        "html" | "htm" => "html",
+        "css" => "css",
+        "mk" => "make",
+        _ => "text",
+    }
+}
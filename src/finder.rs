@@ -0,0 +1,121 @@
+// Ctrl+P のファイル検索
+// 開いたときにワークスペース（起動ディレクトリ）配下を除外ルールに従って索引し、入力のたびにあいまい一致で絞り込む。
+// 一致の点数が同じものは選択中の並び順（Ctrl+T で切替、既定はよく開く順）で並べる
+use crate::{
+    frecency::Frecency,
+    fuzzy, ignore,
+    sort::{self, SortMode, Sortable},
+    App, PopupMode,
+};
+use std::{path::PathBuf, time::SystemTime};
+
+const MAX_FILES: usize = 50_000;
+
+#[derive(Clone)]
+pub struct FinderEntry {
+    pub path: PathBuf,
+    pub display: String, // ルートからの相対パス
+    modified: Option<SystemTime>,
+    size: Option<u64>,
+    frecency: u64,
+}
+
+impl Sortable for FinderEntry {
+    fn sort_name(&self) -> String {
+        self.display.clone()
+    }
+    fn sort_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+    fn sort_size(&self) -> Option<u64> {
+        self.size
+    }
+    fn sort_frecency(&self) -> u64 {
+        self.frecency
+    }
+}
+
+#[derive(Clone)]
+pub struct Finder {
+    pub root: PathBuf,
+    pub entries: Vec<FinderEntry>,
+    pub sort: SortMode, // セッション中は覚えておく
+    pub show_ignored: bool, // Ctrl+G: .gitignore で除外したファイルも出す（全文検索も同じ）
+}
+
+impl Finder {
+    pub fn new() -> Self {
+        Finder {
+            root: std::env::current_dir().unwrap_or_default(),
+            entries: Vec::new(),
+            sort: SortMode::Frecency,
+            show_ignored: false,
+        }
+    }
+
+    fn index(&mut self) {
+        let frecency = Frecency::load();
+        self.entries = ignore::walk(&self.root, MAX_FILES, self.show_ignored)
+            .into_iter()
+            .map(|path| {
+                let meta = std::fs::metadata(&path).ok();
+                FinderEntry {
+                    display: path.strip_prefix(&self.root).unwrap_or(&path).to_string_lossy().to_string(),
+                    modified: meta.as_ref().and_then(|m| m.modified().ok()),
+                    size: meta.map(|m| m.len()),
+                    frecency: frecency.score(&path),
+                    path,
+                }
+            })
+            .collect();
+    }
+}
+
+impl App {
+    pub fn open_finder(&mut self) {
+        self.finder.index();
+        self.popup = Some(PopupMode::Finder);
+        self.popup_input.clear();
+        self.popup_selected = 0;
+    }
+
+    // 入力に一致するファイル（良い順）
+    pub fn finder_matches(&self) -> Vec<&FinderEntry> {
+        let query: String = self.popup_input.chars().filter(|c| !c.is_whitespace()).collect();
+        let mut scored: Vec<(i64, &FinderEntry)> = self
+            .finder
+            .entries
+            .iter()
+            .filter_map(|e| Some((fuzzy::score(&query, &e.display)?, e)))
+            .collect();
+        let cmp = sort::comparator(self.finder.sort);
+        scored.sort_by(|a, b| b.0.cmp(&a.0).then_with(|| cmp(a.1, b.1)));
+        scored.into_iter().map(|(_, e)| e).collect()
+    }
+
+    pub fn finder_cycle_sort(&mut self) {
+        self.finder.sort = self.finder.sort.next();
+        self.popup_selected = 0;
+    }
+
+    // Ctrl+G: 除外したファイルを出す・隠す。全文検索は次の Enter で検索し直す
+    pub fn toggle_show_ignored(&mut self) {
+        self.finder.show_ignored = !self.finder.show_ignored;
+        self.grep.query.clear();
+        if self.popup == Some(PopupMode::Finder) {
+            self.finder.index();
+            self.popup_selected = 0;
+        }
+    }
+
+    pub fn finder_accept(&mut self) {
+        let matches = self.finder_matches();
+        let path = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))).map(|e| e.path.clone());
+        self.popup = None;
+        self.popup_input.clear();
+        if let Some(path) = path {
+            self.record_jump();
+            self.open_path(path);
+        }
+    }
+}
@@ -0,0 +1,100 @@
+// ファイルを開いた回数と最後に開いた時刻を覚えておき、よく・最近使うファイルを上位に出す
+// 保存先: <状態ディレクトリ>/frecency 。1行に `回数<TAB>最終時刻(UNIX秒)<TAB>絶対パス`
+use crate::config::Config;
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_ENTRIES: usize = 1000;
+
+#[derive(Clone)]
+struct Visit {
+    count: u64,
+    last: u64,
+}
+
+#[derive(Clone, Default)]
+pub struct Frecency {
+    visits: HashMap<PathBuf, Visit>,
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+fn store_path() -> Option<PathBuf> {
+    Some(Config::state_dir()?.join("frecency"))
+}
+
+fn absolute(path: &Path) -> PathBuf {
+    std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+impl Frecency {
+    pub fn load() -> Self {
+        let mut store = Frecency::default();
+        let Some(content) = store_path().and_then(|p| std::fs::read_to_string(p).ok()) else { return store };
+        for line in content.lines() {
+            let mut fields = line.splitn(3, '\t');
+            let (Some(count), Some(last), Some(path)) = (fields.next(), fields.next(), fields.next()) else { continue };
+            let (Ok(count), Ok(last)) = (count.parse(), last.parse()) else { continue };
+            store.visits.insert(PathBuf::from(path), Visit { count, last });
+        }
+        store
+    }
+
+    fn save(&self) {
+        let Some(path) = store_path() else { return };
+        if let Some(dir) = path.parent() {
+            let _ = std::fs::create_dir_all(dir);
+        }
+        let content: String = self
+            .visits
+            .iter()
+            .map(|(p, v)| format!("{}\t{}\t{}\n", v.count, v.last, p.display()))
+            .collect();
+        let _ = std::fs::write(path, content);
+    }
+
+    // 開いたことを記録してすぐ書き出す。件数が上限を超えたらスコアの低いものから忘れる
+    pub fn record(path: &Path) {
+        let mut store = Frecency::load();
+        let now = now_secs();
+        let visit = store.visits.entry(absolute(path)).or_insert(Visit { count: 0, last: now });
+        visit.count += 1;
+        visit.last = now;
+        if store.visits.len() > MAX_ENTRIES {
+            let mut scored: Vec<(u64, PathBuf)> = store.visits.iter().map(|(p, v)| (score(v, now), p.clone())).collect();
+            scored.sort();
+            for (_, p) in scored.into_iter().take(store.visits.len() - MAX_ENTRIES) {
+                store.visits.remove(&p);
+            }
+        }
+        store.save();
+    }
+
+    pub fn score(&self, path: &Path) -> u64 {
+        self.visits.get(&absolute(path)).map(|v| score(v, now_secs())).unwrap_or(0)
+    }
+
+    // dir 配下で記録のあるファイル
+    pub fn paths_under(&self, dir: &Path) -> Vec<PathBuf> {
+        let dir = absolute(dir);
+        self.visits.keys().filter(|p| p.starts_with(&dir)).cloned().collect()
+    }
+}
+
+// 回数 × 最近ほど大きい重み
+fn score(visit: &Visit, now: u64) -> u64 {
+    let days = now.saturating_sub(visit.last) / 86400;
+    let weight = match days {
+        0..=3 => 100,
+        4..=13 => 70,
+        14..=30 => 50,
+        31..=90 => 30,
+        _ => 10,
+    };
+    visit.count * weight
+}
@@ -0,0 +1,28 @@
+// あいまい一致（クエリの文字が順番どおりに含まれていれば一致）
+// 連続した一致、単語の頭（`/` `_` `-` `.` の直後や大文字）、ファイル名部分での一致ほど高得点
+pub fn score(query: &str, candidate: &str) -> Option<i64> {
+    if query.is_empty() { return Some(0); }
+    let chars: Vec<char> = candidate.chars().collect();
+    let name_start = chars.iter().rposition(|&c| c == '/').map(|p| p + 1).unwrap_or(0);
+    let mut score = 0;
+    let mut pos = 0;
+    let mut prev: Option<usize> = None;
+    for q in query.chars() {
+        let q = q.to_ascii_lowercase();
+        let found = (pos..chars.len()).find(|&i| chars[i].to_ascii_lowercase() == q)?;
+        let boundary = found == 0
+            || matches!(chars[found - 1], '/' | '_' | '-' | '.' | ' ')
+            || (chars[found].is_uppercase() && chars[found - 1].is_lowercase());
+        score += 1;
+        if prev.is_some_and(|p| p + 1 == found) { score += 8; }
+        if boundary { score += 6; }
+        if found >= name_start { score += 4; }
+        if let Some(p) = prev {
+            score -= (found - p - 1).min(10) as i64;
+        }
+        prev = Some(found);
+        pos = found + 1;
+    }
+    // 短い候補を少し優先
+    Some(score * 10 - chars.len() as i64)
+}
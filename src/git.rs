@@ -0,0 +1,194 @@
+// git 連携
+// - ガター: ファイルを開いたときに HEAD の内容を読み、編集のたびにバッファと比べて
+//   追加・変更・削除の印を行番号の右に付ける。Alt+j / Alt+k で次・前の変更箇所へ移動する
+// - FileTree: git status で項目を色分けし、ヘッダーにブランチを出す
+use crate::{
+    diff::{self, DiffOp},
+    App,
+};
+use std::{
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Mark {
+    Added,
+    Modified,
+    Deleted, // この行の下で行が消えている
+}
+
+#[derive(Clone, Default)]
+pub struct GitGutter {
+    base: Option<Vec<String>>, // HEAD の内容（git の管理外なら None）
+    lines: Vec<String>,        // marks を計算したときのバッファの内容
+    pub marks: Vec<Option<Mark>>,
+    hunks: Vec<usize>, // 変更箇所の先頭行
+}
+
+// FileTree の項目の状態。後ろほど優先（ディレクトリは中で一番優先されるものを出す）
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum FileStatus {
+    Ignored,
+    Untracked,
+    Staged,   // 変更はすべてステージ済み
+    Modified, // ステージしていない変更がある
+}
+
+#[derive(Clone)]
+pub struct RepoStatus {
+    pub branch: String,
+    dir: PathBuf,                      // 読み込んだディレクトリ
+    prefix: PathBuf,                   // dir のリポジトリ内での相対パス
+    files: Vec<(PathBuf, FileStatus)>, // リポジトリのルートからの相対パス
+}
+
+// dir で git を実行する。成功なら標準出力、失敗なら標準エラーの最初の行
+pub fn run(dir: &Path, args: &[&str]) -> Result<String, String> {
+    let out = Command::new("git").arg("-C").arg(dir).args(args).stdin(Stdio::null()).output().map_err(|e| e.to_string())?;
+    if out.status.success() {
+        Ok(String::from_utf8_lossy(&out.stdout).into_owned())
+    } else {
+        let err = String::from_utf8_lossy(&out.stderr).into_owned();
+        let stdout = String::from_utf8_lossy(&out.stdout).into_owned();
+        // git commit は「コミットするものがない」などを標準出力に書く
+        Err(err.lines().chain(stdout.lines()).find(|l| !l.trim().is_empty()).unwrap_or("unknown error").trim().to_string())
+    }
+}
+
+pub fn git(dir: &Path, args: &[&str]) -> Option<String> {
+    run(dir, args).ok()
+}
+
+// HEAD にあるファイルの内容。リポジトリの外や未追跡のファイルなら None
+pub fn head_content(path: &Path) -> Option<String> {
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let name = path.file_name()?.to_str()?;
+    git(dir, &["show", &format!("HEAD:./{}", name)])
+}
+
+// `## main...origin/main [ahead 1]` の `## ` より後ろを `main [ahead 1]` にする
+fn branch_label(head: &str) -> String {
+    let head = head.strip_prefix("No commits yet on ").unwrap_or(head);
+    let (name, rest) = head.split_once(' ').unwrap_or((head, ""));
+    let name = name.split("...").next().unwrap_or(name);
+    format!("{} {}", name, rest).trim_end().to_string()
+}
+
+impl RepoStatus {
+    // dir がリポジトリの中なら git status を読む
+    pub fn load(dir: &Path) -> Option<Self> {
+        let root = PathBuf::from(git(dir, &["rev-parse", "--show-toplevel"])?.trim_end());
+        let prefix = std::fs::canonicalize(dir).ok()?.strip_prefix(&root).ok()?.to_path_buf();
+        let out = git(dir, &["status", "--porcelain", "-z", "-b", "--ignored"])?;
+        let mut fields = out.split('\0');
+        let mut branch = String::new();
+        let mut files = Vec::new();
+        while let Some(field) = fields.next() {
+            if let Some(head) = field.strip_prefix("## ") {
+                branch = branch_label(head);
+                continue;
+            }
+            let (Some(xy), Some(path)) = (field.get(..2), field.get(3..)) else { continue };
+            let status = match xy {
+                "??" => FileStatus::Untracked,
+                "!!" => FileStatus::Ignored,
+                _ if xy.ends_with(' ') => FileStatus::Staged,
+                _ => FileStatus::Modified,
+            };
+            // 名前の変更・コピーは次の欄に元の名前が続く
+            if xy.starts_with(['R', 'C']) { fields.next(); }
+            files.push((PathBuf::from(path.trim_end_matches('/')), status));
+        }
+        Some(RepoStatus { branch, dir: dir.to_path_buf(), prefix, files })
+    }
+
+    // 項目の状態。未追跡・無視されたディレクトリの中はそれを引き継ぎ、
+    // ディレクトリは中のファイルの状態をまとめる（無視されたファイルは数えない）
+    pub fn status(&self, path: &Path) -> Option<FileStatus> {
+        let rel = self.prefix.join(path.strip_prefix(&self.dir).ok()?);
+        self.files
+            .iter()
+            .filter(|(file, status)| rel.starts_with(file) || (file.starts_with(&rel) && *status != FileStatus::Ignored))
+            .map(|&(_, status)| status)
+            .max()
+    }
+}
+
+// 差分から行ごとの印と変更箇所の先頭行を求める。
+// 削除と追加が並んだ箇所は、削除した行数までを変更、残りを追加とする
+fn marks(ops: &[DiffOp], len: usize) -> (Vec<Option<Mark>>, Vec<usize>) {
+    let mut marks = vec![None; len];
+    let mut hunks = Vec::new();
+    let mut k = 0;
+    while k < ops.len() {
+        if let DiffOp::Equal(..) = ops[k] {
+            k += 1;
+            continue;
+        }
+        let start = k;
+        while k < ops.len() && !matches!(ops[k], DiffOp::Equal(..)) { k += 1; }
+        let hunk = &ops[start..k];
+        let deleted = hunk.iter().filter(|op| matches!(op, DiffOp::Delete(_))).count();
+        let inserted: Vec<usize> = hunk.iter().filter_map(|op| if let DiffOp::Insert(j) = *op { Some(j) } else { None }).collect();
+        for (n, &j) in inserted.iter().enumerate() {
+            marks[j] = Some(if n < deleted { Mark::Modified } else { Mark::Added });
+        }
+        match inserted.first() {
+            Some(&j) => hunks.push(j),
+            None => {
+                // 消えた位置の直前の行に印を付ける（先頭で消えたときは最初の行）
+                let next = match ops.get(k) {
+                    Some(&DiffOp::Equal(_, j)) => j,
+                    _ => len,
+                };
+                let line = next.saturating_sub(1).min(len - 1);
+                if marks[line].is_none() { marks[line] = Some(Mark::Deleted); }
+                hunks.push(line);
+            }
+        }
+    }
+    (marks, hunks)
+}
+
+impl App {
+    // 開いているファイルの HEAD の内容を読み直す
+    pub fn load_git_base(&mut self) {
+        let base = self.current_file.as_deref().and_then(head_content).map(|s| s.lines().map(str::to_string).collect());
+        self.gutter = GitGutter { base, ..GitGutter::default() };
+    }
+
+    // バッファが変わっていたら印を計算し直す（描画の前に呼ぶ）
+    pub fn sync_git_gutter(&mut self) {
+        let gutter = &mut self.gutter;
+        let Some(base) = &gutter.base else { return };
+        if !gutter.marks.is_empty() && gutter.lines == *self.lines { return; }
+        let ops = diff::diff_lines(base, &self.lines);
+        (gutter.marks, gutter.hunks) = marks(&ops, self.lines.len());
+        gutter.lines = self.lines.to_vec();
+    }
+
+    // 次（前）の変更箇所へ。端まで行ったら反対側から探す
+    pub fn goto_hunk(&mut self, forward: bool) {
+        self.sync_git_gutter();
+        let hunks = &self.gutter.hunks;
+        let y = self.cursor_y;
+        let target = if forward {
+            hunks.iter().find(|&&h| h > y).or(hunks.first())
+        } else {
+            hunks.iter().rev().find(|&&h| h < y).or(hunks.last())
+        };
+        match target.copied() {
+            Some(line) if line != y => {
+                self.record_jump();
+                self.cursor_y = line;
+                self.cursor_x = 0;
+                self.selection_reset();
+                self.adjust_h_scroll(0);
+            }
+            Some(_) => self.message = Some("No other changes".to_string()),
+            None if self.gutter.base.is_none() => self.message = Some("Not tracked by git".to_string()),
+            None => self.message = Some("No changes against HEAD".to_string()),
+        }
+    }
+}
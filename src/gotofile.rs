@@ -0,0 +1,99 @@
+// カーソル位置のパスを開く (Alt+e、Vim は gf)
+// `src/main.rs:120:5` のような行・桁の指定や、Python のトレースバック（`File "x.py", line 12`）の行番号も読む。
+// 相対パスは開いているファイルのディレクトリ、FileTree のパス、git のルートの順に探す
+use crate::{git, App};
+use std::path::{Path, PathBuf};
+
+fn is_path_char(c: char) -> bool {
+    c.is_alphanumeric() || "/._-~+@%:".contains(c)
+}
+
+// x を含むパスらしい塊の範囲。x が塊の外ならその直前の文字から探す
+fn token_at(line: &str, x: usize) -> Option<(usize, usize)> {
+    let x = x.min(line.len());
+    let at = if line[x..].starts_with(is_path_char) {
+        x
+    } else {
+        line[..x].char_indices().next_back().filter(|&(_, c)| is_path_char(c))?.0
+    };
+    let start = line[..at].char_indices().rev().take_while(|&(_, c)| is_path_char(c)).last().map_or(at, |(i, _)| i);
+    let end = line[at..].find(|c| !is_path_char(c)).map_or(line.len(), |i| at + i);
+    Some((start, end))
+}
+
+// `path:line:col` を分ける（末尾の `:` は文の区切りとみなす）
+fn split_location(token: &str) -> (&str, Option<usize>, Option<usize>) {
+    let token = token.trim_end_matches([':', '.']);
+    let mut numbers = Vec::new();
+    let mut path = token;
+    while numbers.len() < 2
+        && let Some((rest, n)) = path.rsplit_once(':')
+        && let Ok(n) = n.parse::<usize>()
+    {
+        numbers.insert(0, n);
+        path = rest;
+    }
+    (path, numbers.first().copied(), numbers.get(1).copied())
+}
+
+// `"x.py", line 12` の行番号
+fn traceback_line(rest: &str) -> Option<usize> {
+    let digits = rest.strip_prefix('"')?.strip_prefix(", line ")?;
+    let end = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+    digits[..end].parse().ok()
+}
+
+impl App {
+    // 相対パスを探す場所
+    fn path_bases(&self) -> Vec<PathBuf> {
+        let mut bases = Vec::new();
+        if let Some(dir) = self.current_file.as_deref().and_then(Path::parent) {
+            bases.push(if dir.as_os_str().is_empty() { PathBuf::from(".") } else { dir.to_path_buf() });
+        }
+        bases.push(self.file_tree.current_path.clone());
+        if let Some(root) = git::git(&self.command_dir(), &["rev-parse", "--show-toplevel"]) {
+            bases.push(PathBuf::from(root.trim_end()));
+        }
+        bases
+    }
+
+    fn resolve_path(&self, name: &str) -> Option<PathBuf> {
+        let name = match name.strip_prefix("~/") {
+            Some(rest) => PathBuf::from(std::env::var_os("HOME")?).join(rest),
+            None => PathBuf::from(name),
+        };
+        if name.is_absolute() {
+            return name.is_file().then_some(name);
+        }
+        // `./` は components で落とす
+        self.path_bases().into_iter().map(|base| base.join(&name).components().collect::<PathBuf>()).find(|p| p.is_file())
+    }
+
+    pub fn goto_file(&mut self) {
+        let line = &self.lines[self.cursor_y];
+        let Some((start, end)) = token_at(line, self.cursor_x) else {
+            self.message = Some("No file path at cursor".to_string());
+            return;
+        };
+        let (name, row, col) = split_location(&line[start..end]);
+        let row = row.or_else(|| traceback_line(&line[end..]));
+        if name.is_empty() {
+            self.message = Some("No file path at cursor".to_string());
+            return;
+        }
+        let Some(path) = self.resolve_path(name) else {
+            self.message = Some(format!("File not found: {}", name));
+            return;
+        };
+        let same = self.current_file.as_ref().is_some_and(|cur| {
+            std::fs::canonicalize(cur).ok() == std::fs::canonicalize(&path).ok()
+        });
+        if !same && self.modified {
+            self.message = Some("Unsaved changes; save before opening another file".to_string());
+            return;
+        }
+        self.record_jump();
+        let (row, col) = (row.unwrap_or(1).saturating_sub(1), col.unwrap_or(1).saturating_sub(1));
+        self.goto_location(&path, row, col);
+    }
+}
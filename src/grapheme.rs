@@ -0,0 +1,55 @@
+// 行の中の位置と書記素クラスタ
+// カーソルや選択の x は行のバイト位置で、いつも書記素クラスタ（結合文字・絵文字の合字なども含めた見た目の1文字）の境目に置く。
+// 左右の移動と Backspace はクラスタ単位で進み、描画・折り返し・上下の移動では表示幅やクラスタの番号に直す。
+// 行が変わった後（undo や読み直し）に残った x は floor で境目に寄せる
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::UnicodeWidthStr;
+
+// x 以下で一番近い境目。行末を越えれば行末
+pub fn floor(line: &str, x: usize) -> usize {
+    if x >= line.len() { return line.len(); }
+    line.grapheme_indices(true).map(|(i, _)| i).take_while(|&i| i <= x).last().unwrap_or(0)
+}
+
+// x の1つ前の境目（行頭なら 0）
+pub fn prev(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true).map(|(i, _)| i).take_while(|&i| i < x).last().unwrap_or(0)
+}
+
+// x の1つ後の境目（行末なら行末）
+pub fn next(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true).map(|(i, g)| i + g.len()).find(|&end| end > x).unwrap_or(line.len())
+}
+
+// x より前のクラスタの数
+pub fn index(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true).take_while(|&(i, _)| i < x).count()
+}
+
+// n 番目のクラスタの頭（足りなければ行末）
+pub fn byte(line: &str, n: usize) -> usize {
+    line.grapheme_indices(true).nth(n).map_or(line.len(), |(i, _)| i)
+}
+
+// 行頭から x までの表示幅
+pub fn width(line: &str, x: usize) -> usize {
+    line.grapheme_indices(true).take_while(|&(i, _)| i < x).map(|(_, g)| g.width()).sum()
+}
+
+// 行頭から x までの表示桁。タブは次の tab_width の倍数まで進める（ステータスバーの桁を他のツールと合わせる）
+pub fn column(line: &str, x: usize, tab_width: usize) -> usize {
+    let tab_width = tab_width.max(1);
+    line.grapheme_indices(true)
+        .take_while(|&(i, _)| i < x)
+        .fold(0, |col, (_, g)| if g == "\t" { (col / tab_width + 1) * tab_width } else { col + g.width() })
+}
+
+// 表示幅 col の位置にあるクラスタの頭（全角の右半分なら左の頭）
+pub fn at_width(line: &str, col: usize) -> usize {
+    let mut w = 0;
+    for (i, g) in line.grapheme_indices(true) {
+        w += g.width();
+        if w > col { return i; }
+    }
+    line.len()
+}
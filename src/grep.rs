@@ -0,0 +1,264 @@
+// ワークスペース内の全文検索
+// 除外ルールに従ってファイルを集め、スレッドに分けて読む。バイナリや大きすぎるファイルは飛ばす。
+// 一致の判定は search::Matcher（大文字小文字・単語単位の設定に従う）
+use crate::{
+    grapheme, ignore,
+    results::ResultItem,
+    search::{Matcher, SearchOptions},
+    writer, App, PopupMode,
+};
+use crossterm::event::KeyCode;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    thread,
+};
+
+const MAX_FILES: usize = 50_000;
+const MAX_FILE_BYTES: u64 = 10 * 1024 * 1024;
+
+#[derive(Clone)]
+pub struct GrepHit {
+    pub path: PathBuf,
+    pub line: usize, // 0始まり
+    pub col: usize,  // バイト位置
+    pub text: String,
+}
+
+pub fn search(root: &Path, query: &str, opts: SearchOptions, limit: usize, show_ignored: bool) -> Vec<GrepHit> {
+    if query.is_empty() { return Vec::new(); }
+    let files = ignore::walk(root, MAX_FILES, show_ignored);
+    let workers = thread::available_parallelism().map(|n| n.get()).unwrap_or(4).min(files.len().max(1));
+    let chunk = files.len().div_ceil(workers).max(1);
+    let matcher = Matcher::new(query, opts);
+    let mut hits: Vec<GrepHit> = thread::scope(|scope| {
+        let handles: Vec<_> = files
+            .chunks(chunk)
+            .map(|part| {
+                let matcher = &matcher;
+                scope.spawn(move || {
+                    let mut found = Vec::new();
+                    for path in part {
+                        search_file(path, matcher, limit, &mut found);
+                        if found.len() >= limit { break; }
+                    }
+                    found
+                })
+            })
+            .collect();
+        handles.into_iter().flat_map(|h| h.join().unwrap_or_default()).collect()
+    });
+    hits.sort_by(|a, b| a.path.cmp(&b.path).then(a.line.cmp(&b.line)));
+    hits.truncate(limit);
+    hits
+}
+
+fn search_file(path: &Path, matcher: &Matcher, limit: usize, found: &mut Vec<GrepHit>) {
+    if std::fs::metadata(path).map(|m| m.len() > MAX_FILE_BYTES).unwrap_or(true) { return; }
+    let Ok(bytes) = std::fs::read(path) else { return };
+    // 先頭 8KB に NUL があればバイナリとみなす
+    if bytes.iter().take(8192).any(|&b| b == 0) { return; }
+    let content = String::from_utf8_lossy(&bytes);
+    for (i, line) in content.lines().enumerate() {
+        if let Some(col) = matcher.find(line) {
+            found.push(GrepHit { path: path.to_path_buf(), line: i, col, text: line.to_string() });
+            if found.len() >= limit { return; }
+        }
+    }
+}
+
+// --- 検索ポップアップ ---
+#[derive(Clone, Default)]
+pub struct GrepState {
+    pub query: String, // hits を得たときのクエリ
+    pub hits: Vec<GrepHit>,
+    pub opts: SearchOptions, // hits を得たときの検索オプション
+    pub replacement: String,
+    pub chosen: Vec<bool>, // 置換する結果
+}
+
+const MAX_HITS: usize = 5_000;
+
+impl App {
+    pub fn open_grep(&mut self) {
+        self.popup = Some(PopupMode::Grep);
+        self.popup_input = self.grep.query.clone();
+        self.popup_selected = 0;
+    }
+
+    // 入力が前回の検索と同じなら選択中の結果へ移動、違えば検索する
+    pub fn grep_enter(&mut self) {
+        if self.popup_input.is_empty() { return; }
+        if self.grep.query != self.popup_input {
+            self.grep.hits = search(&self.finder.root, &self.popup_input, self.search_opts, MAX_HITS, self.finder.show_ignored);
+            self.grep.query = self.popup_input.clone();
+            self.grep.opts = self.search_opts;
+            self.popup_selected = 0;
+            // 結果パネルにも残す（Alt+Q で開き直せる）
+            let items = self
+                .grep
+                .hits
+                .iter()
+                .map(|h| ResultItem { path: Some(h.path.clone()), line: h.line, col: h.col, text: h.text.clone() })
+                .collect();
+            self.set_results(format!("Search in files: {}", self.grep.query), items, 0);
+            return;
+        }
+        let selected = self.popup_selected.min(self.grep.hits.len().saturating_sub(1));
+        let Some(hit) = self.grep.hits.get(selected).cloned() else { return };
+        self.popup = None;
+        self.popup_input.clear();
+        self.results.selected = selected;
+        self.record_jump();
+        self.goto_location(&hit.path, hit.line, hit.col);
+    }
+
+    // ポップアップに並べる行（入力が検索済みのクエリと違うときは空）
+    pub fn grep_items(&self) -> Vec<String> {
+        if self.grep.query != self.popup_input { return Vec::new(); }
+        self.grep
+            .hits
+            .iter()
+            .map(|h| {
+                let rel = h.path.strip_prefix(&self.finder.root).unwrap_or(&h.path);
+                let preview: String = h.text.trim().chars().take(200).collect();
+                format!("{}:{}: {}", rel.display(), h.line + 1, preview)
+            })
+            .collect()
+    }
+}
+
+// --- 置換 ---
+// 検索結果から Ctrl+R で置換文字列を入力 → 一覧で Space で個別に選択/解除 → Enter でまとめて適用
+impl App {
+    pub fn start_replace(&mut self) {
+        if self.grep.hits.is_empty() || self.grep.query != self.popup_input { return; }
+        self.grep.chosen = vec![true; self.grep.hits.len()];
+        self.popup = Some(PopupMode::ReplaceWith);
+        self.popup_input.clear();
+    }
+
+    pub fn show_replace_preview(&mut self) {
+        self.grep.replacement = std::mem::take(&mut self.popup_input);
+        self.popup = Some(PopupMode::ReplacePreview);
+        self.popup_selected = 0;
+    }
+
+    pub fn replace_items(&self) -> Vec<String> {
+        let matcher = Matcher::new(&self.grep.query, self.grep.opts);
+        self.grep
+            .hits
+            .iter()
+            .zip(&self.grep.chosen)
+            .map(|(h, &chosen)| {
+                let rel = h.path.strip_prefix(&self.finder.root).unwrap_or(&h.path);
+                let (after, _) = matcher.replace_all(&h.text, &self.grep.replacement);
+                format!(
+                    "[{}] {}:{}: {}  =>  {}",
+                    if chosen { "x" } else { " " },
+                    rel.display(),
+                    h.line + 1,
+                    h.text.trim(),
+                    after.trim()
+                )
+            })
+            .collect()
+    }
+
+    pub fn handle_replace_key(&mut self, code: KeyCode) {
+        let len = self.grep.hits.len();
+        match code {
+            KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+            KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(len.saturating_sub(1)),
+            KeyCode::Char(' ') => {
+                if let Some(c) = self.grep.chosen.get_mut(self.popup_selected) {
+                    *c = !*c;
+                }
+            }
+            // 全部選択済みなら全解除、そうでなければ全選択
+            KeyCode::Char('a') => {
+                let all = self.grep.chosen.iter().all(|&c| c);
+                self.grep.chosen.iter_mut().for_each(|c| *c = !all);
+            }
+            KeyCode::Enter => {
+                self.popup = None;
+                self.apply_replace();
+            }
+            KeyCode::Esc => self.popup = None,
+            _ => {}
+        }
+    }
+
+    fn apply_replace(&mut self) {
+        let matcher = Matcher::new(&self.grep.query, self.grep.opts);
+        let replacement = self.grep.replacement.clone();
+        let mut by_file: BTreeMap<PathBuf, Vec<usize>> = BTreeMap::new();
+        for (h, _) in self.grep.hits.iter().zip(&self.grep.chosen).filter(|(_, c)| **c) {
+            by_file.entry(h.path.clone()).or_default().push(h.line);
+        }
+        let current = self.current_file.as_ref().and_then(|p| std::fs::canonicalize(p).ok());
+        let (mut files, mut lines_changed, mut count, mut failed) = (0, 0, 0, Vec::new());
+        let mut skipped = false;
+        for (path, lines) in by_file {
+            let replace = |text: &mut Vec<String>| {
+                let (mut changed, mut n) = (0, 0);
+                for &l in &lines {
+                    let Some(line) = text.get_mut(l) else { continue };
+                    let (after, k) = matcher.replace_all(line, &replacement);
+                    if k > 0 {
+                        *line = after;
+                        changed += 1;
+                        n += k;
+                    }
+                }
+                (changed, n)
+            };
+            if current.is_some() && std::fs::canonicalize(&path).ok() == current {
+                // 一致の行番号はディスク上の内容のものなので、未保存の変更があるバッファや
+                // 保存できないバッファ（16進表示・読み取り専用）は書き換えない
+                if self.modified || self.hex.is_some() || self.buffer_bytes().is_err() {
+                    skipped = true;
+                    continue;
+                }
+                // undo できるように書き換えて、保存キューで保存する
+                let mut text = self.lines.clone();
+                let (changed, n) = replace(&mut text);
+                if changed == 0 { continue; }
+                self.save_undo();
+                *self.lines = text;
+                self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+                match self.buffer_bytes() {
+                    Ok(content) => self.enqueue_save(path, content),
+                    Err(_) => {
+                        failed.push(path);
+                        continue;
+                    }
+                }
+                files += 1;
+                lines_changed += changed;
+                count += n;
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                failed.push(path);
+                continue;
+            };
+            let mut text: Vec<String> = content.split('\n').map(|s| s.to_string()).collect();
+            let (changed, n) = replace(&mut text);
+            if changed == 0 { continue; }
+            if writer::write_atomic(&path, text.join("\n").as_bytes()).is_err() {
+                failed.push(path);
+                continue;
+            }
+            files += 1;
+            lines_changed += changed;
+            count += n;
+        }
+        // 結果は古くなったので次の Enter で検索し直す
+        self.grep.query.clear();
+        let mut msg = format!("Replaced {} occurrences on {} lines in {} files", count, lines_changed, files);
+        if skipped { msg.push_str(" (skipped the current buffer: save it and search again)"); }
+        if !failed.is_empty() { msg.push_str(&format!(", {} files failed", failed.len())); }
+        self.message = Some(msg);
+    }
+}
@@ -0,0 +1,296 @@
+// バイナリの16進表示と編集 (F8 でバッファごとに切り替え)
+// 左からオフセット、16 バイトずつの16進、ASCII の欄。Tab で16進と ASCII の欄を行き来し、
+// 16進の欄では 0-9a-f で半バイトずつ、ASCII の欄では文字で1バイトずつ上書きする（末尾では追加）。
+// Insert で 00 を挿入、Delete/Backspace で削除、Ctrl+Z で取り消し、Ctrl+G でオフセットへ移動、
+// Ctrl+F でバイト列（`de ad be ef` か `"text"`）を検索し F3 で次へ。保存はバイト列をそのまま書く
+use crate::{prompt::Prompt, App};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+pub const ROW: usize = 16;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Pane {
+    Hex,
+    Ascii,
+}
+
+// 取り消し用の記録
+#[derive(Clone)]
+enum Edit {
+    Set(usize, u8), // 上書き前の値
+    Insert(usize),
+    Delete(usize, u8),
+}
+
+#[derive(Clone)]
+pub struct HexView {
+    pub bytes: Vec<u8>,
+    pub cursor: usize, // bytes.len() なら末尾（入力すると追加）
+    pub scroll: usize, // 先頭に出す行
+    pub height: usize, // 前回描画した行数（PageUp/PageDown 用）
+    pub pane: Pane,
+    pub low: bool,     // 上位の半バイトを入力済みで、次は下位
+    pub edited: bool,  // バッファの行から変わった（抜けるときに行へ戻す）
+    pattern: Vec<u8>,  // 直前に検索したバイト列
+    undo: Vec<Edit>,
+}
+
+impl HexView {
+    fn new(bytes: Vec<u8>) -> Self {
+        HexView { bytes, cursor: 0, scroll: 0, height: 16, pane: Pane::Hex, low: false, edited: false, pattern: Vec::new(), undo: Vec::new() }
+    }
+
+    fn move_to(&mut self, pos: usize) {
+        self.cursor = pos.min(self.bytes.len());
+        self.low = false;
+    }
+
+    fn move_by(&mut self, delta: isize) {
+        self.move_to(self.cursor.saturating_add_signed(delta));
+    }
+
+    // カーソルの行が見えるようにする
+    pub fn adjust_scroll(&mut self, height: usize) {
+        self.height = height.max(1);
+        let row = self.cursor / ROW;
+        if row < self.scroll {
+            self.scroll = row;
+        } else if row >= self.scroll + self.height {
+            self.scroll = row + 1 - self.height;
+        }
+    }
+
+    // カーソルの位置を value にする（末尾なら追加）
+    fn write(&mut self, value: u8) {
+        match self.bytes.get_mut(self.cursor) {
+            Some(b) => {
+                // 下位の半バイトは同じバイトの続きなので記録しない
+                if !self.low { self.undo.push(Edit::Set(self.cursor, *b)); }
+                *b = value;
+            }
+            None => {
+                self.bytes.push(value);
+                self.undo.push(Edit::Insert(self.cursor));
+            }
+        }
+        self.edited = true;
+    }
+
+    fn type_nibble(&mut self, digit: u8) {
+        let old = self.bytes.get(self.cursor).copied().unwrap_or(0);
+        if self.low {
+            self.write(old & 0xf0 | digit);
+            self.move_by(1);
+        } else {
+            self.write(digit << 4 | old & 0x0f);
+            self.low = true;
+        }
+    }
+
+    fn insert(&mut self) {
+        self.bytes.insert(self.cursor, 0);
+        self.undo.push(Edit::Insert(self.cursor));
+        self.low = false;
+        self.edited = true;
+    }
+
+    fn delete(&mut self, pos: usize) -> bool {
+        if pos >= self.bytes.len() { return false; }
+        let old = self.bytes.remove(pos);
+        self.undo.push(Edit::Delete(pos, old));
+        self.move_to(pos);
+        self.edited = true;
+        true
+    }
+
+    fn undo(&mut self) -> bool {
+        let Some(edit) = self.undo.pop() else { return false };
+        let pos = match edit {
+            Edit::Set(pos, old) => {
+                self.bytes[pos] = old;
+                pos
+            }
+            Edit::Insert(pos) => {
+                self.bytes.remove(pos);
+                pos
+            }
+            Edit::Delete(pos, old) => {
+                self.bytes.insert(pos, old);
+                pos
+            }
+        };
+        self.move_to(pos);
+        self.edited = true;
+        true
+    }
+
+    // カーソルの次から探し、なければ先頭から
+    fn find_next(&self) -> Option<usize> {
+        let n = self.pattern.len();
+        if n == 0 || n > self.bytes.len() { return None; }
+        let last = self.bytes.len() - n;
+        (self.cursor + 1..=last).chain(0..=self.cursor.min(last)).find(|&i| self.bytes[i..i + n] == self.pattern[..])
+    }
+}
+
+// `de ad be ef`・`deadbeef` か `"text"`
+fn parse_pattern(input: &str) -> Result<Vec<u8>, String> {
+    let input = input.trim();
+    if let Some(text) = input.strip_prefix('"') {
+        return Ok(text.strip_suffix('"').unwrap_or(text).as_bytes().to_vec());
+    }
+    let digits: String = input.chars().filter(|c| !c.is_whitespace()).collect();
+    if !digits.len().is_multiple_of(2) || !digits.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(format!("Not a byte sequence: {}", input));
+    }
+    Ok((0..digits.len()).step_by(2).map(|i| u8::from_str_radix(&digits[i..i + 2], 16).unwrap()).collect())
+}
+
+// 10進か 0x 付きの16進
+fn parse_offset(input: &str) -> Option<usize> {
+    let input = input.trim();
+    match input.strip_prefix("0x").or_else(|| input.strip_prefix("0X")) {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => input.parse().ok(),
+    }
+}
+
+impl App {
+    // F8: 16進表示に入る・戻る。未編集のファイルはディスクのバイト列をそのまま読む
+    pub fn toggle_hex(&mut self) {
+        let Some(hex) = self.hex.take() else {
+            if self.partial.is_some() {
+                self.message = Some("Only part of the file is loaded; reopen it whole to edit its bytes".to_string());
+                return;
+            }
+            let disk = self.current_file.as_ref().filter(|_| !self.modified).and_then(|p| std::fs::read(p).ok());
+            let bytes = disk.unwrap_or_else(|| {
+                let text = self.lines.join("\n");
+                self.encoding.encode(&text).unwrap_or_else(|_| text.into_bytes())
+            });
+            self.hex = Some(HexView::new(bytes));
+            self.selection_reset();
+            return;
+        };
+        if !hex.edited { return; }
+        // 編集したバイト列を行に戻す。文字コードとして読めなければ 16進表示のまま
+        let text = match self.encoding.decode(&hex.bytes) {
+            Ok(text) => text,
+            Err(e) => {
+                self.message = Some(format!("Cannot show as {} text: {}; staying in hex view", self.encoding.label(), e));
+                self.hex = Some(hex);
+                return;
+            }
+        };
+        self.read_only = false;
+        let modified = self.modified;
+        let mut lines: Vec<String> = text.lines().map(|s| s.to_string()).collect();
+        if lines.is_empty() { lines.push(String::new()); }
+        self.replace_all_lines(lines);
+        self.modified = modified;
+    }
+
+    pub fn handle_hex_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        if code == KeyCode::Char('g') && ctrl {
+            let prompt = Prompt::text("Go to Offset: decimal or 0x hex", |app| {
+                let input = std::mem::take(&mut app.popup_input);
+                app.hex_goto(&input);
+            });
+            self.open_prompt(prompt, "");
+            return;
+        }
+        let Some(hex) = self.hex.as_mut() else { return };
+        let page = (hex.height * ROW) as isize;
+        let row = ROW as isize;
+        let mut changed = false;
+        match code {
+            KeyCode::Left => hex.move_by(-1),
+            KeyCode::Right => hex.move_by(1),
+            KeyCode::Up => hex.move_by(-row),
+            KeyCode::Down => hex.move_by(row),
+            KeyCode::PageUp => hex.move_by(-page),
+            KeyCode::PageDown => hex.move_by(page),
+            KeyCode::Home if ctrl => hex.move_to(0),
+            KeyCode::End if ctrl => hex.move_to(hex.bytes.len()),
+            KeyCode::Home => hex.move_to(hex.cursor / ROW * ROW),
+            KeyCode::End => hex.move_to((hex.cursor / ROW * ROW + ROW - 1).min(hex.bytes.len())),
+            KeyCode::Tab | KeyCode::BackTab => {
+                hex.pane = if hex.pane == Pane::Hex { Pane::Ascii } else { Pane::Hex };
+                hex.low = false;
+            }
+            KeyCode::Insert => {
+                hex.insert();
+                changed = true;
+            }
+            KeyCode::Delete => changed = hex.delete(hex.cursor),
+            KeyCode::Backspace => changed = hex.cursor > 0 && hex.delete(hex.cursor - 1),
+            KeyCode::Char(c) if !ctrl && hex.pane == Pane::Hex && c.is_ascii_hexdigit() => {
+                hex.type_nibble(c.to_digit(16).unwrap() as u8);
+                changed = true;
+            }
+            KeyCode::Char(c) if !ctrl && hex.pane == Pane::Ascii && (' '..='~').contains(&c) => {
+                hex.write(c as u8);
+                hex.move_by(1);
+                changed = true;
+            }
+            _ => {}
+        }
+        if changed { self.modified = true; }
+    }
+
+    pub fn hex_undo(&mut self) {
+        let Some(hex) = self.hex.as_mut() else { return };
+        if hex.undo() {
+            self.modified = true;
+        } else {
+            self.message = Some("Nothing to undo".to_string());
+        }
+    }
+
+    pub fn hex_goto(&mut self, input: &str) {
+        let Some(hex) = self.hex.as_mut() else { return };
+        match parse_offset(input) {
+            Some(pos) if pos <= hex.bytes.len() => hex.move_to(pos),
+            Some(pos) => self.message = Some(format!("Offset {:#x} is past the end ({:#x})", pos, hex.bytes.len())),
+            None => self.message = Some(format!("Not an offset: {}", input.trim())),
+        }
+    }
+
+    pub fn hex_search_prompt(&mut self) {
+        let prompt = Prompt::text("Search Bytes: hex like de ad be ef, or \"text\" (F3 next)", |app| {
+            let input = std::mem::take(&mut app.popup_input);
+            app.hex_search(&input);
+        });
+        self.open_prompt(prompt, "");
+    }
+
+    pub fn hex_search(&mut self, input: &str) {
+        let Some(hex) = self.hex.as_mut() else { return };
+        match parse_pattern(input) {
+            Ok(pattern) if !pattern.is_empty() => {
+                hex.pattern = pattern;
+                self.hex_search_next();
+            }
+            Ok(_) => {}
+            Err(e) => self.message = Some(e),
+        }
+    }
+
+    // F3: 同じバイト列を次へ
+    pub fn hex_search_next(&mut self) {
+        let Some(hex) = self.hex.as_mut() else { return };
+        if hex.pattern.is_empty() {
+            self.message = Some("No previous search".to_string());
+            return;
+        }
+        match hex.find_next() {
+            Some(pos) => {
+                let wrapped = pos <= hex.cursor;
+                hex.move_to(pos);
+                self.message = Some(format!("Found at {:#x}{}", pos, if wrapped { " (wrapped)" } else { "" }));
+            }
+            None => self.message = Some("Byte sequence not found".to_string()),
+        }
+    }
+}
@@ -0,0 +1,163 @@
+// .gitignore 形式の除外ルール
+// 各ディレクトリの .gitignore（走査を始めたディレクトリより上、リポジトリのルートまでを含む）、
+// リポジトリの .git/info/exclude、グローバルの ~/.config/git/ignore を読む。
+// 後に書かれたルールほど優先し、`!` で除外を取り消せる。.git は常に除外
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+struct Rule {
+    base: PathBuf,        // ルールを書いたファイルのあるディレクトリ（グローバルは空）
+    pattern: Vec<char>,
+    negate: bool,
+    dir_only: bool,       // 末尾が `/`
+    anchored: bool,       // 途中に `/` がある → base からの相対パス全体と照合
+}
+
+#[derive(Clone, Default)]
+pub struct Ignore {
+    rules: Vec<Rule>,
+}
+
+impl Ignore {
+    // dir 用のルール一式（グローバル、.git/info/exclude、リポジトリのルートから dir までの .gitignore）
+    pub fn for_dir(dir: &Path) -> Self {
+        let mut ignore = Ignore::default();
+        if let Some(global) = global_ignore_path() {
+            ignore.add_file(&global, Path::new(""));
+        }
+        let repo = dir.ancestors().find(|a| a.join(".git").exists());
+        let dirs: Vec<&Path> = match repo {
+            Some(repo) => {
+                ignore.add_file(&repo.join(".git").join("info").join("exclude"), repo);
+                dir.ancestors().take_while(|a| a.starts_with(repo)).collect()
+            }
+            None => vec![dir],
+        };
+        for d in dirs.into_iter().rev() {
+            ignore.add_dir(d);
+        }
+        ignore
+    }
+
+    // dir/.gitignore を追加する（走査でディレクトリに入るたびに呼ぶ）
+    pub fn add_dir(&mut self, dir: &Path) {
+        self.add_file(&dir.join(".gitignore"), dir);
+    }
+
+    fn add_file(&mut self, file: &Path, base: &Path) {
+        let Ok(content) = std::fs::read_to_string(file) else { return };
+        for line in content.lines() {
+            let line = line.trim_end();
+            if line.is_empty() || line.starts_with('#') { continue; }
+            let (negate, line) = match line.strip_prefix('!') {
+                Some(rest) => (true, rest),
+                None => (false, line.strip_prefix('\\').unwrap_or(line)),
+            };
+            let (dir_only, line) = match line.strip_suffix('/') {
+                Some(rest) => (true, rest),
+                None => (false, line),
+            };
+            let anchored = line.contains('/');
+            let line = line.trim_start_matches('/');
+            if line.is_empty() { continue; }
+            self.rules.push(Rule { base: base.to_path_buf(), pattern: line.chars().collect(), negate, dir_only, anchored });
+        }
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path.file_name().is_some_and(|n| n == ".git") { return true; }
+        let name: Vec<char> = path.file_name().map(|n| n.to_string_lossy().chars().collect()).unwrap_or_default();
+        for rule in self.rules.iter().rev() {
+            if rule.dir_only && !is_dir { continue; }
+            let Ok(rel) = path.strip_prefix(&rule.base) else { continue };
+            let matched = if rule.anchored {
+                let rel: Vec<char> = rel.to_string_lossy().replace('\\', "/").chars().collect();
+                glob_match(&rule.pattern, &rel)
+            } else {
+                glob_match(&rule.pattern, &name)
+            };
+            if matched { return !rule.negate; }
+        }
+        false
+    }
+}
+
+fn global_ignore_path() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".config"),
+    };
+    Some(base.join("git").join("ignore"))
+}
+
+// `*` と `?` は `/` をまたがない。`**` はまたぐ。`[a-z]` `[!x]` の文字クラス
+fn glob_match(pat: &[char], s: &[char]) -> bool {
+    match pat.first() {
+        None => s.is_empty(),
+        Some('*') if pat.get(1) == Some(&'*') => {
+            let rest = pat[2..].strip_prefix(&['/']).unwrap_or(&pat[2..]);
+            (0..=s.len()).any(|i| glob_match(rest, &s[i..]))
+        }
+        Some('*') => {
+            for i in 0..=s.len() {
+                if glob_match(&pat[1..], &s[i..]) { return true; }
+                if i < s.len() && s[i] == '/' { break; }
+            }
+            false
+        }
+        Some('?') => !s.is_empty() && s[0] != '/' && glob_match(&pat[1..], &s[1..]),
+        Some('[') => {
+            let Some(close) = pat.iter().skip(2).position(|&c| c == ']').map(|p| p + 2) else {
+                return s.first() == Some(&'[') && glob_match(&pat[1..], &s[1..]);
+            };
+            let Some(&c) = s.first() else { return false };
+            let class = &pat[1..close];
+            let (negate, class) = match class.first() {
+                Some('!') | Some('^') => (true, &class[1..]),
+                _ => (false, class),
+            };
+            let mut hit = false;
+            let mut i = 0;
+            while i < class.len() {
+                if i + 2 < class.len() && class[i + 1] == '-' {
+                    hit |= class[i] <= c && c <= class[i + 2];
+                    i += 3;
+                } else {
+                    hit |= class[i] == c;
+                    i += 1;
+                }
+            }
+            hit != negate && glob_match(&pat[close + 1..], &s[1..])
+        }
+        Some(&p) => s.first() == Some(&p) && glob_match(&pat[1..], &s[1..]),
+    }
+}
+
+// root 配下のファイルを除外ルールに従って列挙する（シンボリックリンクは辿らない）。
+// show_ignored なら .git 以外は除外しない
+pub fn walk(root: &Path, limit: usize, show_ignored: bool) -> Vec<PathBuf> {
+    let mut ignore = Ignore::for_dir(root);
+    let mut files = Vec::new();
+    let mut stack = vec![root.to_path_buf()];
+    while let Some(dir) = stack.pop() {
+        if dir != root {
+            ignore.add_dir(&dir);
+        }
+        let Ok(read) = std::fs::read_dir(&dir) else { continue };
+        let mut entries: Vec<_> = read.filter_map(|e| e.ok()).collect();
+        entries.sort_by_key(|e| e.file_name());
+        for entry in entries.iter().rev() {
+            let Ok(file_type) = entry.file_type() else { continue };
+            let path = entry.path();
+            let ignored = if show_ignored { entry.file_name() == ".git" } else { ignore.is_ignored(&path, file_type.is_dir()) };
+            if ignored { continue; }
+            if file_type.is_dir() {
+                stack.push(path);
+            } else if file_type.is_file() {
+                files.push(path);
+                if files.len() >= limit { return files; }
+            }
+        }
+    }
+    files
+}
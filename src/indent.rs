@@ -0,0 +1,202 @@
+// インデントの設定（1段の幅とタブ／空白）
+// 開いたファイルがすでに字下げされていれば、その字下げ（タブか空白か、空白なら何桁ずつか）に合わせて、1つのファイルに流儀を混ぜない。
+// 推定できなければ [ファイルタイプ] の indent_width / indent_style > 言語ごとの既定（Makefile と Go はタブ、YAML は2桁の空白）
+// > 全体の indent_width / indent_style（既定は4桁の空白）の順に決める。
+// Tab は選択がなければカーソル位置に1段（空白なら次の段の桁まで）を入れ、選択があればかかる行を1段下げる。
+// Shift+Tab はカーソル行（選択があればかかる行）を1段戻す。Enter の自動インデントと整形もこの1段を使う。
+// Ctrl+Enter / Ctrl+Shift+Enter はカーソルの位置にかかわらず下／上に字下げした空行を開く
+use crate::{grapheme, App};
+use std::{cmp::Reverse, collections::HashMap};
+
+// 推定に見る行数
+const SAMPLE_LINES: usize = 1000;
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct Indent {
+    pub width: usize,
+    pub tabs: bool,
+}
+
+impl Indent {
+    pub fn unit(self) -> String {
+        if self.tabs { "\t".to_string() } else { " ".repeat(self.width) }
+    }
+}
+
+// ファイルの字下げから推定したもの
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Detected {
+    Tabs,
+    Spaces(usize),
+}
+
+// タブで始まる行と空白で始まる行の多い方。空白なら、前の行より深くなるときの差で一番多いもの（2〜8）を1段とする。
+// ブロックコメントの続き（" * "）は数えない。字下げされた行がなければ None
+pub fn detect(lines: &[String]) -> Option<Detected> {
+    let (mut tabs, mut spaces) = (0, 0);
+    let mut steps: HashMap<usize, usize> = HashMap::new();
+    let mut prev = 0;
+    for line in lines.iter().take(SAMPLE_LINES).filter(|l| !l.trim().is_empty()) {
+        if line.starts_with('\t') {
+            tabs += 1;
+            continue;
+        }
+        let n = line.len() - line.trim_start_matches(' ').len();
+        if line[n..].starts_with('*') { continue; }
+        if n > 0 { spaces += 1; }
+        if n > prev { *steps.entry(n - prev).or_default() += 1; }
+        prev = n;
+    }
+    if tabs == 0 && spaces == 0 { return None; }
+    if tabs > spaces { return Some(Detected::Tabs); }
+    steps
+        .into_iter()
+        .filter(|(step, _)| (2..=8).contains(step))
+        .max_by_key(|&(step, count)| (count, Reverse(step)))
+        .map(|(step, _)| Detected::Spaces(step))
+}
+
+// 言語の決まりごと（幅, タブか）
+fn builtin(filetype: &str) -> (Option<usize>, Option<bool>) {
+    match filetype {
+        "make" | "go" => (None, Some(true)),
+        "yaml" => (Some(2), Some(false)),
+        _ => (None, None),
+    }
+}
+
+// 行頭から1段分（タブ1つか width 桁までの空白）の長さ
+fn outdent_len(line: &str, indent: Indent) -> usize {
+    if line.starts_with('\t') { return 1; }
+    line.bytes().take(indent.width).take_while(|&b| b == b' ').count()
+}
+
+impl App {
+    pub fn indent(&self) -> Indent {
+        let filetype = self.filetype();
+        let config = self.config.filetype(filetype);
+        let (width, tabs) = builtin(filetype);
+        let width = config.indent_width.or(width).or(self.config.indent_width).unwrap_or(4);
+        match self.file_indent {
+            Some(Detected::Spaces(width)) => Indent { width, tabs: false },
+            Some(Detected::Tabs) => Indent { width, tabs: true },
+            None => Indent { width, tabs: config.indent_tabs.or(tabs).or(self.config.indent_tabs).unwrap_or(false) },
+        }
+    }
+
+    // Tab キー
+    pub fn tab_key(&mut self) {
+        let indent = self.indent();
+        if let Some((first, last)) = self.selected_lines() {
+            self.shift_lines(first, last, true);
+            return;
+        }
+        let text = if indent.tabs {
+            "\t".to_string()
+        } else {
+            let col = grapheme::width(&self.lines[self.cursor_y], self.cursor_x) + self.virtual_cols();
+            " ".repeat(indent.width - col % indent.width)
+        };
+        self.save_undo();
+        self.fill_virtual_space();
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        self.lines[self.cursor_y].insert_str(self.cursor_x, &text);
+        self.cursor_x += text.len();
+        self.adjust_h_scroll(0);
+    }
+
+    // Shift+Tab
+    pub fn outdent_key(&mut self) {
+        let (first, last) = self.selected_lines().unwrap_or((self.cursor_y, self.cursor_y));
+        self.shift_lines(first, last, false);
+    }
+
+    // 下に開く行は Enter と同じ字下げ（{ や : で終わる行の下なら1段深く）、なければ今の行と同じ。
+    // 上に開く行は今の行と同じ字下げで、今の行が閉じ括弧で始まればその中なので1段深くする
+    pub fn open_line(&mut self, above: bool) {
+        self.selection_reset();
+        let line = &self.lines[self.cursor_y];
+        let base: String = line.chars().take_while(|&c| c == ' ' || c == '\t').collect();
+        if above {
+            let closer = line.trim_start().starts_with(['}', ']', ')']);
+            let indent = if closer { base.clone() + &self.indent().unit() } else { base };
+            self.save_undo();
+            self.lines.insert(self.cursor_y, indent.clone());
+            self.cursor_x = indent.len();
+        } else {
+            self.cursor_x = self.lines[self.cursor_y].len();
+            if self.syntax_enter() { return; }
+            self.insert_newline();
+            self.lines[self.cursor_y] = base;
+            self.cursor_x = self.lines[self.cursor_y].len();
+        }
+        self.adjust_h_scroll(0);
+    }
+
+    // 行を1段下げる（空行はそのまま）か戻す。選択とカーソルは文字に付いて動く（行頭にあるものは行頭のまま）
+    fn shift_lines(&mut self, first: usize, last: usize, deeper: bool) {
+        let indent = self.indent();
+        let unit = indent.unit();
+        let changes: Vec<usize> = (first..=last)
+            .map(|y| match deeper {
+                true if self.lines[y].is_empty() => 0,
+                true => unit.len(),
+                false => outdent_len(&self.lines[y], indent),
+            })
+            .collect();
+        if changes.iter().all(|&n| n == 0) { return; }
+        self.save_undo();
+        for (y, &n) in (first..=last).zip(&changes) {
+            if deeper {
+                self.lines[y].insert_str(0, &unit[..n]);
+            } else {
+                self.lines[y].replace_range(..n, "");
+            }
+        }
+        let moved = |(y, x): (usize, usize)| -> (usize, usize) {
+            if y < first || y > last || x == 0 { return (y, x); }
+            let n = changes[y - first];
+            (y, if deeper { x + n } else { x.saturating_sub(n) })
+        };
+        self.sel_start = self.sel_start.map(moved);
+        self.sel_end = self.sel_end.map(moved);
+        (self.cursor_y, self.cursor_x) = moved((self.cursor_y, self.cursor_x));
+        self.adjust_h_scroll(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn detect_in(text: &str) -> Option<Detected> {
+        let lines: Vec<String> = text.lines().map(str::to_string).collect();
+        detect(&lines)
+    }
+
+    #[test]
+    fn detects_tabs() {
+        assert_eq!(detect_in("fn a() {\n\tb();\n\tif c {\n\t\td();\n\t}\n}"), Some(Detected::Tabs));
+    }
+
+    #[test]
+    fn detects_the_space_width() {
+        assert_eq!(detect_in("a:\n  b:\n    c: 1\n  d: 2"), Some(Detected::Spaces(2)));
+        assert_eq!(detect_in("fn a() {\n    b();\n    if c {\n        d();\n    }\n}"), Some(Detected::Spaces(4)));
+        // 継続行の深い字下げより、よく使う段の幅
+        assert_eq!(detect_in("a(\n    b,\n            c)\nd(\n    e)"), Some(Detected::Spaces(4)));
+    }
+
+    #[test]
+    fn more_lines_win_between_tabs_and_spaces() {
+        assert_eq!(detect_in("a\n\tb\n\tc\n  d"), Some(Detected::Tabs));
+        assert_eq!(detect_in("a\n\tb\n  c\n  d"), Some(Detected::Spaces(2)));
+    }
+
+    #[test]
+    fn skips_block_comments_and_flat_files() {
+        assert_eq!(detect_in("/**\n * doc\n */\nfn a() {}"), None);
+        assert_eq!(detect_in("a\nb\n\nc"), None);
+        assert_eq!(detect_in(""), None);
+    }
+}
@@ -0,0 +1,320 @@
+// 最小限の JSON（LSP のメッセージ用）
+// オブジェクトはキーの順番を保つ。数値はすべて f64
+use std::fmt;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    pub fn obj(fields: Vec<(&str, Json)>) -> Json {
+        Json::Object(fields.into_iter().map(|(k, v)| (k.to_string(), v)).collect())
+    }
+
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        match self {
+            Json::Object(fields) => fields.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_u64(&self) -> Option<u64> {
+        match *self {
+            Json::Number(n) if n >= 0.0 && n.fract() == 0.0 => Some(n as u64),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(fields) => Some(fields),
+            _ => None,
+        }
+    }
+
+    pub fn parse(text: &str) -> Option<Json> {
+        let mut p = Parser { s: text.as_bytes(), i: 0 };
+        let value = p.value()?;
+        p.ws();
+        (p.i == p.s.len()).then_some(value)
+    }
+}
+
+impl From<&str> for Json {
+    fn from(s: &str) -> Self {
+        Json::String(s.to_string())
+    }
+}
+
+impl From<String> for Json {
+    fn from(s: String) -> Self {
+        Json::String(s)
+    }
+}
+
+impl From<bool> for Json {
+    fn from(b: bool) -> Self {
+        Json::Bool(b)
+    }
+}
+
+impl From<usize> for Json {
+    fn from(n: usize) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<i64> for Json {
+    fn from(n: i64) -> Self {
+        Json::Number(n as f64)
+    }
+}
+
+impl From<Vec<Json>> for Json {
+    fn from(items: Vec<Json>) -> Self {
+        Json::Array(items)
+    }
+}
+
+pub fn write_str(f: &mut impl fmt::Write, s: &str) -> fmt::Result {
+    f.write_char('"')?;
+    for c in s.chars() {
+        match c {
+            '"' => f.write_str("\\\"")?,
+            '\\' => f.write_str("\\\\")?,
+            '\n' => f.write_str("\\n")?,
+            '\r' => f.write_str("\\r")?,
+            '\t' => f.write_str("\\t")?,
+            c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+            c => f.write_char(c)?,
+        }
+    }
+    f.write_char('"')
+}
+
+impl fmt::Display for Json {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Json::Null => f.write_str("null"),
+            Json::Bool(b) => write!(f, "{}", b),
+            Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+            Json::Number(n) if n.is_finite() => write!(f, "{}", n),
+            Json::Number(_) => f.write_str("null"),
+            Json::String(s) => write_str(f, s),
+            Json::Array(items) => {
+                f.write_str("[")?;
+                for (i, item) in items.iter().enumerate() {
+                    if i > 0 { f.write_str(",")?; }
+                    write!(f, "{}", item)?;
+                }
+                f.write_str("]")
+            }
+            Json::Object(fields) => {
+                f.write_str("{")?;
+                for (i, (k, v)) in fields.iter().enumerate() {
+                    if i > 0 { f.write_str(",")?; }
+                    write_str(f, k)?;
+                    write!(f, ":{}", v)?;
+                }
+                f.write_str("}")
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+
+impl Parser<'_> {
+    fn ws(&mut self) {
+        while self.i < self.s.len() && matches!(self.s[self.i], b' ' | b'\t' | b'\n' | b'\r') { self.i += 1; }
+    }
+
+    fn eat(&mut self, lit: &str) -> bool {
+        if self.s[self.i..].starts_with(lit.as_bytes()) {
+            self.i += lit.len();
+            true
+        } else {
+            false
+        }
+    }
+
+    fn value(&mut self) -> Option<Json> {
+        self.ws();
+        match *self.s.get(self.i)? {
+            b'n' => self.eat("null").then_some(Json::Null),
+            b't' => self.eat("true").then_some(Json::Bool(true)),
+            b'f' => self.eat("false").then_some(Json::Bool(false)),
+            b'"' => self.string().map(Json::String),
+            b'[' => {
+                self.i += 1;
+                let mut items = Vec::new();
+                self.ws();
+                if self.eat("]") { return Some(Json::Array(items)); }
+                loop {
+                    items.push(self.value()?);
+                    self.ws();
+                    if self.eat("]") { return Some(Json::Array(items)); }
+                    if !self.eat(",") { return None; }
+                }
+            }
+            b'{' => {
+                self.i += 1;
+                let mut fields = Vec::new();
+                self.ws();
+                if self.eat("}") { return Some(Json::Object(fields)); }
+                loop {
+                    self.ws();
+                    let key = self.string()?;
+                    self.ws();
+                    if !self.eat(":") { return None; }
+                    fields.push((key, self.value()?));
+                    self.ws();
+                    if self.eat("}") { return Some(Json::Object(fields)); }
+                    if !self.eat(",") { return None; }
+                }
+            }
+            _ => self.number(),
+        }
+    }
+
+    fn number(&mut self) -> Option<Json> {
+        let start = self.i;
+        while self.i < self.s.len() && matches!(self.s[self.i], b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9') { self.i += 1; }
+        std::str::from_utf8(&self.s[start..self.i]).ok()?.parse().ok().map(Json::Number)
+    }
+
+    fn hex4(&mut self) -> Option<u32> {
+        let digits = std::str::from_utf8(self.s.get(self.i..self.i + 4)?).ok()?;
+        self.i += 4;
+        u32::from_str_radix(digits, 16).ok()
+    }
+
+    fn string(&mut self) -> Option<String> {
+        if !self.eat("\"") { return None; }
+        let mut out = String::new();
+        loop {
+            // エスケープや終端までの部分はそのまま（UTF-8 のまま）写す
+            let start = self.i;
+            while self.i < self.s.len() && !matches!(self.s[self.i], b'"' | b'\\') { self.i += 1; }
+            out.push_str(std::str::from_utf8(&self.s[start..self.i]).ok()?);
+            match *self.s.get(self.i)? {
+                b'"' => {
+                    self.i += 1;
+                    return Some(out);
+                }
+                _ => {
+                    self.i += 1;
+                    let c = *self.s.get(self.i)?;
+                    self.i += 1;
+                    match c {
+                        b'"' => out.push('"'),
+                        b'\\' => out.push('\\'),
+                        b'/' => out.push('/'),
+                        b'b' => out.push('\u{8}'),
+                        b'f' => out.push('\u{c}'),
+                        b'n' => out.push('\n'),
+                        b'r' => out.push('\r'),
+                        b't' => out.push('\t'),
+                        b'u' => {
+                            let mut code = self.hex4()?;
+                            // サロゲートペア
+                            if (0xD800..0xDC00).contains(&code) && self.eat("\\u") {
+                                let low = self.hex4()?;
+                                code = 0x10000 + ((code - 0xD800) << 10) + (low.wrapping_sub(0xDC00) & 0x3FF);
+                            }
+                            out.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        _ => return None,
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_nested_values_in_order() {
+        let value = Json::parse(r#" {"b": [1, -1.5e3, true, null], "a": {"c": "x"}, "e": []} "#).unwrap();
+        let Json::Object(fields) = &value else { panic!("not an object: {}", value) };
+        let keys: Vec<&str> = fields.iter().map(|(k, _)| k.as_str()).collect();
+        assert_eq!(keys, ["b", "a", "e"]);
+        assert_eq!(
+            value.get("b").unwrap().as_array().unwrap(),
+            [Json::Number(1.0), Json::Number(-1500.0), Json::Bool(true), Json::Null]
+        );
+        assert_eq!(value.get("a").and_then(|a| a.get("c")).and_then(Json::as_str), Some("x"));
+        assert_eq!(value.get("e").and_then(Json::as_array), Some(&[][..]));
+        assert_eq!(value.get("missing"), None);
+    }
+
+    #[test]
+    fn parses_string_escapes() {
+        let value = Json::parse(r#""a\"b\\c\/\n\t\u00e9\ud83d\ude00日本""#).unwrap();
+        assert_eq!(value.as_str(), Some("a\"b\\c/\n\té😀日本"));
+    }
+
+    #[test]
+    fn rejects_invalid_input() {
+        for text in ["", "tru", "[1,", "[1 2]", "{\"a\" 1}", "{a: 1}", "\"abc", "\"\\q\"", "\"\\u12\"", "[1] x"] {
+            assert_eq!(Json::parse(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn accessors_check_the_type() {
+        assert_eq!(Json::Number(42.0).as_u64(), Some(42));
+        assert_eq!(Json::Number(-1.0).as_u64(), None);
+        assert_eq!(Json::Number(1.5).as_u64(), None);
+        assert_eq!(Json::Number(1.0).as_str(), None);
+        assert_eq!(Json::Array(vec![]).get("a"), None);
+    }
+
+    #[test]
+    fn writes_compact_json() {
+        let value = Json::obj(vec![
+            ("s", "a\"\\\n\u{1}é".into()),
+            ("n", 3usize.into()),
+            ("i", (-2i64).into()),
+            ("f", Json::Number(1.5)),
+            ("nan", Json::Number(f64::NAN)),
+            ("l", vec![Json::Null, true.into()].into()),
+        ]);
+        assert_eq!(value.to_string(), r#"{"s":"a\"\\\n\u0001é","n":3,"i":-2,"f":1.5,"nan":null,"l":[null,true]}"#);
+        let mut out = String::new();
+        write_str(&mut out, "tab\there").unwrap();
+        assert_eq!(out, r#""tab\there""#);
+    }
+
+    #[test]
+    fn display_and_parse_round_trip() {
+        let value = Json::obj(vec![("k", "\u{7}\r\"".into()), ("v", vec![Json::Number(0.25), Json::Object(vec![])].into())]);
+        assert_eq!(Json::parse(&value.to_string()), Some(value));
+    }
+}
@@ -0,0 +1,267 @@
+// JSON の整形と検査 (Alt+z)
+// 選択範囲（なければバッファ全体）を JSON として読み、字下げして整形する。すでに整形済みなら1行に詰める。
+// 数値と文字列は元の書き方のまま写す（桁あふれやエスケープの書き換えはしない）。
+// 読めなければ誤りの場所へカーソルを移し、行・桁と理由を出す
+use crate::{
+    grapheme,
+    lsp::{self, TextEdit},
+    App,
+};
+
+struct Formatter<'a> {
+    text: &'a str,
+    s: &'a [u8],
+    i: usize,
+    out: String,
+    indent: Option<String>, // None なら詰める
+    base: String,           // 改行の後に付ける字下げ（選択範囲の行の字下げ）
+    depth: usize,
+}
+
+type Error = (usize, String); // (バイト位置, 理由)
+
+impl Formatter<'_> {
+    fn err<T>(&self, msg: &str) -> Result<T, Error> {
+        Err((self.i, msg.to_string()))
+    }
+
+    fn ws(&mut self) {
+        while self.i < self.s.len() && matches!(self.s[self.i], b' ' | b'\t' | b'\n' | b'\r') { self.i += 1; }
+    }
+
+    fn newline(&mut self) {
+        let Some(ref indent) = self.indent else { return };
+        self.out.push('\n');
+        self.out.push_str(&self.base);
+        for _ in 0..self.depth {
+            self.out.push_str(indent);
+        }
+    }
+
+    fn value(&mut self) -> Result<(), Error> {
+        self.ws();
+        match self.s.get(self.i) {
+            None => self.err("unexpected end, expected a value"),
+            Some(b'{') => self.container(b'}'),
+            Some(b'[') => self.container(b']'),
+            Some(b'"') => self.string(),
+            Some(b'-' | b'0'..=b'9') => self.number(),
+            Some(_) => {
+                for lit in ["true", "false", "null"] {
+                    if self.s[self.i..].starts_with(lit.as_bytes()) {
+                        self.i += lit.len();
+                        self.out.push_str(lit);
+                        return Ok(());
+                    }
+                }
+                self.err("expected a value")
+            }
+        }
+    }
+
+    fn container(&mut self, close: u8) -> Result<(), Error> {
+        let object = close == b'}';
+        self.out.push(self.s[self.i] as char);
+        self.i += 1;
+        self.ws();
+        if self.s.get(self.i) == Some(&close) {
+            self.i += 1;
+            self.out.push(close as char);
+            return Ok(());
+        }
+        self.depth += 1;
+        loop {
+            self.newline();
+            if object {
+                self.ws();
+                if self.s.get(self.i) != Some(&b'"') { return self.err("expected a string key"); }
+                self.string()?;
+                self.ws();
+                if self.s.get(self.i) != Some(&b':') { return self.err("expected ':'"); }
+                self.i += 1;
+                self.out.push_str(if self.indent.is_some() { ": " } else { ":" });
+            }
+            self.value()?;
+            self.ws();
+            match self.s.get(self.i) {
+                Some(b',') => {
+                    self.i += 1;
+                    self.out.push(',');
+                }
+                Some(&c) if c == close => {
+                    self.i += 1;
+                    self.depth -= 1;
+                    self.newline();
+                    self.out.push(close as char);
+                    return Ok(());
+                }
+                _ => return self.err(if object { "expected ',' or '}'" } else { "expected ',' or ']'" }),
+            }
+        }
+    }
+
+    fn string(&mut self) -> Result<(), Error> {
+        let start = self.i;
+        self.i += 1;
+        loop {
+            match self.s.get(self.i) {
+                None => return Err((start, "unterminated string".to_string())),
+                Some(b'"') => break,
+                Some(b'\\') => match self.s.get(self.i + 1) {
+                    Some(b'"' | b'\\' | b'/' | b'b' | b'f' | b'n' | b'r' | b't') => self.i += 2,
+                    Some(b'u') if self.s.get(self.i + 2..self.i + 6).is_some_and(|h| h.iter().all(u8::is_ascii_hexdigit)) => self.i += 6,
+                    _ => return self.err("invalid escape in string"),
+                },
+                Some(&c) if c < 0x20 => return self.err("control character in string (use \\n, \\t, ...)"),
+                Some(_) => self.i += 1,
+            }
+        }
+        self.i += 1;
+        self.out.push_str(&self.text[start..self.i]);
+        Ok(())
+    }
+
+    // -? (0 | [1-9][0-9]*) (.[0-9]+)? ([eE][+-]?[0-9]+)?
+    fn number(&mut self) -> Result<(), Error> {
+        let start = self.i;
+        let digits = |f: &mut Self| {
+            let from = f.i;
+            while f.s.get(f.i).is_some_and(u8::is_ascii_digit) { f.i += 1; }
+            f.i > from
+        };
+        if self.s[self.i] == b'-' { self.i += 1; }
+        if self.s.get(self.i) == Some(&b'0') {
+            self.i += 1;
+        } else if !digits(self) {
+            return self.err("expected a digit");
+        }
+        if self.s.get(self.i) == Some(&b'.') {
+            self.i += 1;
+            if !digits(self) { return self.err("expected a digit after '.'"); }
+        }
+        if matches!(self.s.get(self.i), Some(b'e' | b'E')) {
+            self.i += 1;
+            if matches!(self.s.get(self.i), Some(b'+' | b'-')) { self.i += 1; }
+            if !digits(self) { return self.err("expected a digit in the exponent"); }
+        }
+        self.out.push_str(&self.text[start..self.i]);
+        Ok(())
+    }
+}
+
+// indent が None なら1行に詰める
+fn reformat(text: &str, indent: Option<String>, base: String) -> Result<String, Error> {
+    let mut f = Formatter { text, s: text.as_bytes(), i: 0, out: String::new(), indent, base, depth: 0 };
+    f.value()?;
+    f.ws();
+    if f.i < f.s.len() { return f.err("unexpected text after the value"); }
+    Ok(f.out)
+}
+
+impl App {
+    pub fn format_json(&mut self) {
+        let (start, end, text, selected) = match (self.sel_start, self.sel_end) {
+            (Some(a), Some(b)) if a != b => (a.min(b), a.max(b), self.get_selected_text().unwrap_or_default(), true),
+            _ => {
+                let last = self.lines.len() - 1;
+                ((0, 0), (last, self.lines[last].len()), self.lines.join("\n"), false)
+            }
+        };
+        // 整形した結果に続く行の字下げは、選択範囲の始まる行に合わせる
+        let line = &self.lines[start.0];
+        let base = line[..line.len() - line.trim_start().len()].to_string();
+        let pretty = reformat(text.trim(), Some(self.indent().unit()), base);
+        let (output, what) = match pretty {
+            Ok(pretty) if pretty == text.trim() => (reformat(&pretty, None, String::new()).unwrap_or(pretty), "Minified JSON"),
+            Ok(pretty) => (pretty, "Formatted JSON"),
+            Err((offset, reason)) => {
+                // 前後の空白を除いた分だけずらして、バッファの位置に直す
+                let offset = offset + (text.len() - text.trim_start().len());
+                let (y, x) = lsp::end_of_insert(start, &text[..offset]);
+                (self.cursor_y, self.cursor_x) = (y, x);
+                self.selection_reset();
+                self.adjust_h_scroll(0);
+                self.message = Some(format!("Invalid JSON at line {}, column {}: {}", self.display_line_number(y), x + 1, reason));
+                return;
+            }
+        };
+        // 前後の空白はそのまま残す
+        let lead = &text[..text.len() - text.trim_start().len()];
+        let trail = &text[text.trim_end().len()..];
+        let output = format!("{}{}{}", lead, output, trail);
+        let new_end = lsp::end_of_insert(start, &output);
+        self.save_undo();
+        lsp::apply_text_edits(&mut self.lines, vec![TextEdit { start, end, text: output }]);
+        self.selection_reset();
+        if selected {
+            self.sel_start = Some(start);
+            self.sel_end = Some(new_end);
+            self.shift_selection = true;
+            (self.cursor_y, self.cursor_x) = new_end;
+        } else {
+            self.cursor_y = self.cursor_y.min(self.lines.len() - 1);
+            self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        }
+        self.adjust_h_scroll(0);
+        self.message = Some(what.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pretty(text: &str) -> Result<String, Error> {
+        reformat(text, Some("  ".to_string()), String::new())
+    }
+
+    fn minify(text: &str) -> Result<String, Error> {
+        reformat(text, None, String::new())
+    }
+
+    #[test]
+    fn formats_nested_values() {
+        assert_eq!(
+            pretty(r#"{"a":[1,2],"b":{"c":null},"d":[],"e":{}}"#).unwrap(),
+            "{\n  \"a\": [\n    1,\n    2\n  ],\n  \"b\": {\n    \"c\": null\n  },\n  \"d\": [],\n  \"e\": {}\n}"
+        );
+    }
+
+    #[test]
+    fn minify_and_format_round_trip() {
+        let text = r#"{"name": "rwe", "tags": ["a", "b"], "n": -1.5e+3, "ok": true}"#;
+        let formatted = pretty(text).unwrap();
+        let minified = minify(&formatted).unwrap();
+        assert_eq!(minified, r#"{"name":"rwe","tags":["a","b"],"n":-1.5e+3,"ok":true}"#);
+        assert_eq!(pretty(&minified).unwrap(), formatted);
+    }
+
+    #[test]
+    fn continuation_lines_get_the_base_indent() {
+        assert_eq!(reformat("[1]", Some("\t".to_string()), "    ".to_string()).unwrap(), "[\n    \t1\n    ]");
+    }
+
+    #[test]
+    fn keeps_strings_and_numbers_as_written() {
+        let text = r#"["a\"b\\c\/é\n", 1E400, 0.10]"#;
+        assert_eq!(minify(text).unwrap(), text.replace(", ", ","));
+    }
+
+    #[test]
+    fn reports_the_error_position() {
+        assert_eq!(minify(r#"{"a" 1}"#), Err((5, "expected ':'".to_string())));
+        assert_eq!(minify("[1,]"), Err((3, "expected a value".to_string())));
+        assert_eq!(minify("[1 2]"), Err((3, "expected ',' or ']'".to_string())));
+        assert_eq!(minify("{1:2}"), Err((1, "expected a string key".to_string())));
+        assert_eq!(minify(r#""abc"#), Err((0, "unterminated string".to_string())));
+        assert_eq!(minify(r#""\x""#), Err((1, "invalid escape in string".to_string())));
+        assert_eq!(minify(r#""\u12""#), Err((1, "invalid escape in string".to_string())));
+        assert_eq!(minify("\"a\tb\""), Err((2, "control character in string (use \\n, \\t, ...)".to_string())));
+        assert_eq!(minify("01"), Err((1, "unexpected text after the value".to_string())));
+        assert_eq!(minify("1."), Err((2, "expected a digit after '.'".to_string())));
+        assert_eq!(minify("1e"), Err((2, "expected a digit in the exponent".to_string())));
+        assert_eq!(minify("-"), Err((1, "expected a digit".to_string())));
+        assert_eq!(minify(""), Err((0, "unexpected end, expected a value".to_string())));
+        assert_eq!(minify("nul"), Err((0, "expected a value".to_string())));
+    }
+}
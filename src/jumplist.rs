@@ -0,0 +1,81 @@
+// ジャンプリスト
+// 検索・行ジャンプ・ファイルを開くなどの大きな移動の直前の位置を覚えておき、Alt+, で戻って Alt+. で進む
+// （Vim キーマップのノーマルモードでは Ctrl+O / Tab も）。別ファイルの位置ならそのファイルを開き直す
+use crate::{grapheme, App};
+use std::path::PathBuf;
+
+const MAX_JUMPS: usize = 100;
+
+#[derive(Clone, PartialEq)]
+pub struct Jump {
+    pub path: Option<PathBuf>, // None は名前のないバッファ
+    pub line: usize,
+    pub col: usize, // バイト位置
+}
+
+#[derive(Clone, Default)]
+pub struct JumpList {
+    back: Vec<Jump>,
+    forward: Vec<Jump>,
+}
+
+impl App {
+    fn current_jump(&self) -> Jump {
+        Jump { path: self.current_file.clone(), line: self.cursor_y, col: self.cursor_x }
+    }
+
+    // 大きな移動の直前に呼ぶ。新しく記録したら進む側は捨てる
+    pub fn record_jump(&mut self) {
+        let here = self.current_jump();
+        self.jumps.forward.clear();
+        if self.jumps.back.last() == Some(&here) { return; }
+        self.jumps.back.push(here);
+        if self.jumps.back.len() > MAX_JUMPS { self.jumps.back.remove(0); }
+    }
+
+    pub fn jump_back(&mut self) {
+        self.jump(true);
+    }
+
+    pub fn jump_forward(&mut self) {
+        self.jump(false);
+    }
+
+    fn jump(&mut self, back: bool) {
+        let here = self.current_jump();
+        let list = if back { &mut self.jumps.back } else { &mut self.jumps.forward };
+        // 今いる位置と同じ記録は飛ばす
+        while list.last() == Some(&here) { list.pop(); }
+        let Some(target) = list.pop() else {
+            self.message = Some(if back { "Already at oldest jump" } else { "Already at newest jump" }.to_string());
+            return;
+        };
+        let other_file = target.path != here.path;
+        let failed = match &target.path {
+            _ if other_file && self.modified => Some("Unsaved changes; save before jumping to another file".to_string()),
+            None if other_file => Some("Jump target was an unnamed buffer".to_string()),
+            None => {
+                self.cursor_y = target.line.min(self.lines.len() - 1);
+                self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], target.col);
+                self.selection_reset();
+                None
+            }
+            Some(path) => {
+                self.goto_location(path, target.line, target.col);
+                None
+            }
+        };
+        if let Some(msg) = failed {
+            self.message = Some(msg);
+            // 未保存で移動できなかったときは記録を残しておく
+            if other_file && self.modified {
+                let list = if back { &mut self.jumps.back } else { &mut self.jumps.forward };
+                list.push(target);
+            }
+            return;
+        }
+        let other = if back { &mut self.jumps.forward } else { &mut self.jumps.back };
+        other.push(here);
+        self.adjust_h_scroll(0);
+    }
+}
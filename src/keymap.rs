@@ -0,0 +1,361 @@
+// エディタのキーバインド表
+// プリセット（config: keymap = default / vim / emacs）ごとにキー → Action の表を組み立てる
+// 名前のある Action は config の `key.<名前> = Ctrl+Shift+K` で別のキーに付け替えられる（none で外す）。
+// 同じ名前を何行か書けばどのキーでも動く（既定のキーは最初の行で外れる）
+use crate::config::Keymap;
+use crossterm::event::{KeyCode, KeyModifiers};
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum Action {
+    Save,
+    ScrollUp,
+    ScrollDown,
+    CenterCursor,
+    Search,
+    Copy,
+    Cut,
+    Paste,
+    SelectAll,
+    SelectLine,
+    DeleteLine,
+    OpenLineBelow,
+    OpenLineAbove,
+    Undo,
+    Redo,
+    NewFile,
+    Rename,
+    Delete,
+    WordLeft,
+    WordRight,
+    DeleteWordLeft,
+    DeleteWordRight,
+    AltLeft,
+    AltRight,
+    LineStart,
+    LineEnd,
+    KillLine,
+    LineOffset,
+    Reload,
+    ToggleWrap,
+    ToggleWhitespace,
+    ToggleOverwrite,
+    ToggleVirtualSpace,
+    NextHeading,
+    PrevHeading,
+    Outline,
+    FindFile,
+    GrepProject,
+    ToggleResults,
+    SearchNext,
+    SearchPrev,
+    ClearHighlight,
+    JumpBack,
+    JumpForward,
+    SelectInsideBrackets,
+    SelectInsideQuotes,
+    SelectWord,
+    SelectNextOccurrence,
+    SelectParagraph,
+    Complete,
+    GotoDefinition,
+    RenameSymbol,
+    FormatDocument,
+    ExpandSelection,
+    ShrinkSelection,
+    NextHunk,
+    PrevHunk,
+    GitCommit,
+    DiffWithDisk,
+    PipeSelection,
+    InsertCommandOutput,
+    RunTask,
+    ToggleTaskPanel,
+    SpellingSuggestions,
+    GotoFile,
+    OpenUrl,
+    ToggleHex,
+    ToggleMarkdownPreview,
+    FormatJson,
+    TransformSelection,
+    LineCommands,
+    Reflow,
+    InsertUnicode,
+    ToggleCharInfo,
+    ShowMessages,
+    OpenFilePrompt,
+}
+
+#[derive(Clone)]
+pub struct KeyBinding {
+    pub code: KeyCode,
+    pub modifiers: KeyModifiers,
+    pub action: Action,
+}
+
+fn bind(code: KeyCode, modifiers: KeyModifiers, action: Action) -> KeyBinding {
+    KeyBinding { code, modifiers, action }
+}
+
+fn default_bindings() -> Vec<KeyBinding> {
+    let ctrl = KeyModifiers::CONTROL;
+    let alt = KeyModifiers::ALT;
+    let none = KeyModifiers::NONE;
+    vec![
+        bind(KeyCode::Char('s'), ctrl, Action::Save),
+        // 表にする前は Ctrl が押されていれば他の修飾キーがあってもスクロールしていた
+        bind(KeyCode::Up, ctrl, Action::ScrollUp),
+        bind(KeyCode::Down, ctrl, Action::ScrollDown),
+        bind(KeyCode::Up, ctrl | KeyModifiers::SHIFT, Action::ScrollUp),
+        bind(KeyCode::Down, ctrl | KeyModifiers::SHIFT, Action::ScrollDown),
+        bind(KeyCode::Char('l'), ctrl, Action::CenterCursor),
+        bind(KeyCode::Char('f'), ctrl, Action::Search),
+        bind(KeyCode::Char('c'), ctrl, Action::Copy),
+        bind(KeyCode::Char('x'), ctrl, Action::Cut),
+        bind(KeyCode::Char('v'), ctrl, Action::Paste),
+        bind(KeyCode::Char('a'), ctrl, Action::SelectAll),
+        bind(KeyCode::Char('e'), ctrl, Action::SelectLine),
+        // 多くの端末は Ctrl+Shift+K を Ctrl+K として送る
+        bind(KeyCode::Char('K'), ctrl | KeyModifiers::SHIFT, Action::DeleteLine),
+        bind(KeyCode::Char('k'), ctrl, Action::DeleteLine),
+        // Ctrl+Enter は Ctrl+J、Ctrl+Shift+Enter は Ctrl+Enter として届く端末が多いので Alt+Enter でも開く
+        bind(KeyCode::Enter, ctrl, Action::OpenLineBelow),
+        bind(KeyCode::Char('j'), ctrl, Action::OpenLineBelow),
+        bind(KeyCode::Enter, ctrl | KeyModifiers::SHIFT, Action::OpenLineAbove),
+        bind(KeyCode::Enter, alt, Action::OpenLineAbove),
+        bind(KeyCode::Char('z'), ctrl, Action::Undo),
+        bind(KeyCode::Char('r'), ctrl, Action::Redo),
+        bind(KeyCode::Char('n'), none, Action::NewFile),
+        bind(KeyCode::Char('m'), none, Action::Rename),
+        bind(KeyCode::Delete, none, Action::Delete),
+        bind(KeyCode::Delete, KeyModifiers::SHIFT, Action::Delete),
+        bind(KeyCode::Left, ctrl, Action::WordLeft),
+        bind(KeyCode::Right, ctrl, Action::WordRight),
+        // Ctrl+Backspace を Ctrl+H として送る端末もあるが、Backspace 自体を ^H で送る端末もあるので既定では付けない
+        // （key.delete_word_left = Ctrl+H で付けられる）
+        bind(KeyCode::Backspace, ctrl, Action::DeleteWordLeft),
+        bind(KeyCode::Backspace, alt, Action::DeleteWordLeft),
+        bind(KeyCode::Delete, ctrl, Action::DeleteWordRight),
+        bind(KeyCode::Delete, ctrl | KeyModifiers::SHIFT, Action::KillLine),
+        bind(KeyCode::Left, alt, Action::AltLeft),
+        bind(KeyCode::Right, alt, Action::AltRight),
+        bind(KeyCode::Char('l'), alt, Action::LineOffset),
+        bind(KeyCode::Char('r'), alt, Action::Reload),
+        bind(KeyCode::Char('w'), alt, Action::ToggleWrap),
+        bind(KeyCode::Char('v'), alt, Action::ToggleWhitespace),
+        bind(KeyCode::Insert, none, Action::ToggleOverwrite),
+        bind(KeyCode::Insert, alt, Action::ToggleVirtualSpace),
+        bind(KeyCode::Down, alt, Action::NextHeading),
+        bind(KeyCode::Up, alt, Action::PrevHeading),
+        bind(KeyCode::Char('o'), alt, Action::Outline),
+        bind(KeyCode::Char('p'), ctrl, Action::FindFile),
+        bind(KeyCode::Char('g'), alt, Action::GrepProject),
+        bind(KeyCode::Char('q'), alt, Action::ToggleResults),
+        bind(KeyCode::F(3), none, Action::SearchNext),
+        bind(KeyCode::F(3), KeyModifiers::SHIFT, Action::SearchPrev),
+        bind(KeyCode::Char('h'), alt, Action::ClearHighlight),
+        bind(KeyCode::Char(','), alt, Action::JumpBack),
+        bind(KeyCode::Char('.'), alt, Action::JumpForward),
+        bind(KeyCode::Char('i'), alt, Action::SelectInsideBrackets),
+        bind(KeyCode::Char('\''), alt, Action::SelectInsideQuotes),
+        bind(KeyCode::Char('d'), alt, Action::SelectWord),
+        bind(KeyCode::Char('d'), ctrl, Action::SelectNextOccurrence),
+        bind(KeyCode::Char('p'), alt, Action::SelectParagraph),
+        bind(KeyCode::Char(' '), ctrl, Action::Complete),
+        bind(KeyCode::Char('b'), ctrl, Action::GotoDefinition),
+        bind(KeyCode::Char('n'), alt, Action::RenameSymbol),
+        bind(KeyCode::Char('='), alt, Action::FormatDocument),
+        bind(KeyCode::Char('s'), alt, Action::ExpandSelection),
+        bind(KeyCode::Char('a'), alt, Action::ShrinkSelection),
+        bind(KeyCode::Char('j'), alt, Action::NextHunk),
+        bind(KeyCode::Char('k'), alt, Action::PrevHunk),
+        bind(KeyCode::Char('c'), alt, Action::GitCommit),
+        bind(KeyCode::Char('u'), alt, Action::DiffWithDisk),
+        bind(KeyCode::Char('|'), alt, Action::PipeSelection),
+        bind(KeyCode::Char('!'), alt, Action::InsertCommandOutput),
+        bind(KeyCode::Char('t'), alt, Action::RunTask),
+        bind(KeyCode::Char('y'), alt, Action::ToggleTaskPanel),
+        bind(KeyCode::Char('m'), alt, Action::SpellingSuggestions),
+        bind(KeyCode::Char('e'), alt, Action::GotoFile),
+        bind(KeyCode::Char('x'), alt, Action::OpenUrl),
+        bind(KeyCode::F(8), none, Action::ToggleHex),
+        bind(KeyCode::F(9), none, Action::ToggleMarkdownPreview),
+        bind(KeyCode::Char('z'), alt, Action::FormatJson),
+        bind(KeyCode::Char('t'), ctrl, Action::TransformSelection),
+        bind(KeyCode::Char('g'), ctrl, Action::LineCommands),
+        bind(KeyCode::Char('w'), ctrl, Action::Reflow),
+        bind(KeyCode::Char('u'), ctrl, Action::InsertUnicode),
+        bind(KeyCode::Char(';'), alt, Action::ToggleCharInfo),
+        bind(KeyCode::Char('/'), alt, Action::ShowMessages),
+        bind(KeyCode::Char('o'), ctrl, Action::OpenFilePrompt),
+    ]
+}
+
+// Emacs 風: 既定の表を上書きする
+fn emacs_bindings() -> Vec<KeyBinding> {
+    let ctrl = KeyModifiers::CONTROL;
+    let alt = KeyModifiers::ALT;
+    vec![
+        bind(KeyCode::Char('a'), ctrl, Action::LineStart),
+        bind(KeyCode::Char('e'), ctrl, Action::LineEnd),
+        bind(KeyCode::Char('k'), ctrl, Action::KillLine),
+        bind(KeyCode::Char('y'), ctrl, Action::Paste),
+        bind(KeyCode::Char('f'), alt, Action::WordRight),
+        bind(KeyCode::Char('b'), alt, Action::WordLeft),
+    ]
+}
+
+// config の key.<名前> で付け替えられる Action
+const NAMED: [(&str, Action); 8] = [
+    ("delete_line", Action::DeleteLine),
+    ("kill_line", Action::KillLine),
+    ("delete_word_left", Action::DeleteWordLeft),
+    ("delete_word_right", Action::DeleteWordRight),
+    ("open_line_below", Action::OpenLineBelow),
+    ("open_line_above", Action::OpenLineAbove),
+    ("toggle_overwrite", Action::ToggleOverwrite),
+    ("toggle_virtual_space", Action::ToggleVirtualSpace),
+];
+
+// keys は config の (名前, キー) を書いた順に
+pub fn bindings(keymap: Keymap, keys: &[(String, String)]) -> Vec<KeyBinding> {
+    let mut table = default_bindings();
+    if keymap == Keymap::Emacs {
+        for b in emacs_bindings() {
+            table.retain(|e| !(e.code == b.code && e.modifiers == b.modifiers));
+            table.push(b);
+        }
+    }
+    let mut rebound = Vec::new();
+    for (name, key) in keys {
+        let Some(&(_, action)) = NAMED.iter().find(|(n, _)| n == name) else { continue };
+        let Some(key) = parse_key(key) else { continue };
+        if !rebound.contains(&action) {
+            rebound.push(action);
+            table.retain(|e| e.action != action);
+        }
+        let Some((code, modifiers)) = key else { continue };
+        table.retain(|e| !(e.code == code && e.modifiers == modifiers));
+        table.push(bind(code, modifiers, action));
+        // Ctrl+Shift+文字 は Ctrl+文字 としても届くので、そちらが空いていれば付ける
+        if let KeyCode::Char(c) = code
+            && modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::SHIFT)
+        {
+            let (code, modifiers) = (KeyCode::Char(c.to_ascii_lowercase()), modifiers - KeyModifiers::SHIFT);
+            if lookup(&table, code, modifiers).is_none() { table.push(bind(code, modifiers, action)); }
+        }
+    }
+    table
+}
+
+// describe の表記を読む（大文字小文字は問わない）。none は Some(None)、読めなければ None
+fn parse_key(text: &str) -> Option<Option<(KeyCode, KeyModifiers)>> {
+    if text.eq_ignore_ascii_case("none") { return Some(None); }
+    let mut modifiers = KeyModifiers::NONE;
+    let mut parts: Vec<&str> = text.split('+').map(str::trim).collect();
+    let key = parts.pop()?;
+    for part in parts {
+        modifiers |= match part.to_ascii_lowercase().as_str() {
+            "ctrl" | "control" => KeyModifiers::CONTROL,
+            "alt" | "meta" => KeyModifiers::ALT,
+            "shift" => KeyModifiers::SHIFT,
+            _ => return None,
+        };
+    }
+    let lower = key.to_ascii_lowercase();
+    let code = match lower.as_str() {
+        "space" => KeyCode::Char(' '),
+        "left" => KeyCode::Left,
+        "right" => KeyCode::Right,
+        "up" => KeyCode::Up,
+        "down" => KeyCode::Down,
+        "home" => KeyCode::Home,
+        "end" => KeyCode::End,
+        "pageup" | "pgup" => KeyCode::PageUp,
+        "pagedown" | "pgdn" => KeyCode::PageDown,
+        "enter" => KeyCode::Enter,
+        "tab" => KeyCode::Tab,
+        "backspace" => KeyCode::Backspace,
+        "delete" | "del" => KeyCode::Delete,
+        "insert" | "ins" => KeyCode::Insert,
+        _ if lower.len() > 1 && lower.starts_with('f') => KeyCode::F(lower[1..].parse().ok()?),
+        _ => {
+            let mut chars = lower.chars();
+            let c = chars.next()?;
+            if chars.next().is_some() { return None; }
+            // Shift 付きの文字は大文字で届く
+            KeyCode::Char(if modifiers.contains(KeyModifiers::SHIFT) { c.to_ascii_uppercase() } else { c })
+        }
+    };
+    Some(Some((code, modifiers)))
+}
+
+pub fn lookup(table: &[KeyBinding], code: KeyCode, modifiers: KeyModifiers) -> Option<Action> {
+    table
+        .iter()
+        .find(|b| b.code == code && b.modifiers == modifiers)
+        .map(|b| b.action)
+}
+
+// 計測ログなどに出すキーの表記（例: Ctrl+s, Alt+Left, F4）
+pub fn describe(code: KeyCode, modifiers: KeyModifiers) -> String {
+    let mut text = String::new();
+    for (m, name) in [(KeyModifiers::CONTROL, "Ctrl+"), (KeyModifiers::ALT, "Alt+"), (KeyModifiers::SHIFT, "Shift+")] {
+        if modifiers.contains(m) { text.push_str(name); }
+    }
+    match code {
+        KeyCode::Char(' ') => text.push_str("Space"),
+        KeyCode::Char(c) => text.push(c),
+        KeyCode::F(n) => text.push_str(&format!("F{}", n)),
+        other => text.push_str(&format!("{:?}", other)),
+    }
+    text
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_key_specs() {
+        let ctrl = KeyModifiers::CONTROL;
+        assert_eq!(parse_key("Ctrl+Shift+k"), Some(Some((KeyCode::Char('K'), ctrl | KeyModifiers::SHIFT))));
+        assert_eq!(parse_key("Alt+Enter"), Some(Some((KeyCode::Enter, KeyModifiers::ALT))));
+        assert_eq!(parse_key("control + Space"), Some(Some((KeyCode::Char(' '), ctrl))));
+        assert_eq!(parse_key("meta+PgDn"), Some(Some((KeyCode::PageDown, KeyModifiers::ALT))));
+        assert_eq!(parse_key("F5"), Some(Some((KeyCode::F(5), KeyModifiers::NONE))));
+        assert_eq!(parse_key("NONE"), Some(None));
+    }
+
+    #[test]
+    fn rejects_unknown_key_specs() {
+        for text in ["", "Ctrl+", "Hyper+k", "Ctrl+ab", "Fx"] {
+            assert_eq!(parse_key(text), None, "{:?}", text);
+        }
+    }
+
+    #[test]
+    fn keeps_the_keys_that_ignored_extra_modifiers() {
+        let table = bindings(Keymap::Default, &[]);
+        let ctrl_shift = KeyModifiers::CONTROL | KeyModifiers::SHIFT;
+        assert_eq!(lookup(&table, KeyCode::Up, ctrl_shift), Some(Action::ScrollUp));
+        assert_eq!(lookup(&table, KeyCode::Down, ctrl_shift), Some(Action::ScrollDown));
+        assert_eq!(lookup(&table, KeyCode::Delete, KeyModifiers::SHIFT), Some(Action::Delete));
+        assert_eq!(lookup(&table, KeyCode::Delete, ctrl_shift), Some(Action::KillLine));
+    }
+
+    #[test]
+    fn config_keys_replace_the_defaults() {
+        let (ctrl, none) = (KeyModifiers::CONTROL, KeyModifiers::NONE);
+        let keys = |pairs: &[(&str, &str)]| -> Vec<(String, String)> {
+            pairs.iter().map(|(n, k)| (n.to_string(), k.to_string())).collect()
+        };
+        let table = bindings(Keymap::Default, &keys(&[("delete_word_left", "Ctrl+H"), ("delete_word_left", "Alt+Backspace")]));
+        assert_eq!(lookup(&table, KeyCode::Char('h'), ctrl), Some(Action::DeleteWordLeft));
+        assert_eq!(lookup(&table, KeyCode::Backspace, KeyModifiers::ALT), Some(Action::DeleteWordLeft));
+        assert_eq!(lookup(&table, KeyCode::Backspace, ctrl), None);
+        let table = bindings(Keymap::Default, &keys(&[("delete_word_right", "none"), ("unknown", "Ctrl+Q"), ("delete_word_left", "Bad+X")]));
+        assert_eq!(lookup(&table, KeyCode::Delete, ctrl), None);
+        assert_eq!(lookup(&table, KeyCode::Backspace, ctrl), Some(Action::DeleteWordLeft));
+        assert_eq!(lookup(&table, KeyCode::Char('h'), none), None);
+    }
+}
@@ -0,0 +1,177 @@
+// 行単位のコマンド (Ctrl+G で一覧から選ぶ)
+// 選択がかかる行（選択がなければバッファ全体）を対象にし、まとめて置き換えるので1回の undo で戻せる。
+// 何をしたか（消した行数など）はメッセージに出す
+use crate::{grapheme, indent::Detected, prompt::Prompt, App};
+use std::collections::{HashMap, HashSet};
+use unicode_width::UnicodeWidthChar;
+
+// 対象の行を受け取り、置き換える行と報告を返す（インデントの設定を読み書きするものは App も使う）
+type LineCommand = fn(&mut App, Vec<String>) -> (Vec<String>, String);
+
+const COMMANDS: [(&str, LineCommand); 6] = [
+    ("Remove duplicate lines (keep the first)", |_, lines| remove_duplicates(lines)),
+    ("Keep only unique lines", |_, lines| keep_unique(lines)),
+    ("Reverse lines", |_, lines| reverse(lines)),
+    ("Retab: indent with spaces", |app, lines| app.retab(lines, false)),
+    ("Retab: indent with tabs", |app, lines| app.retab(lines, true)),
+    ("Retab: expand all tabs to spaces, also inside lines", |app, lines| app.expand_tabs(lines)),
+];
+
+fn lines_word(n: usize) -> &'static str {
+    if n == 1 { "line" } else { "lines" }
+}
+
+fn remove_duplicates(lines: Vec<String>) -> (Vec<String>, String) {
+    let before = lines.len();
+    let mut seen = HashSet::new();
+    let kept: Vec<String> = lines.into_iter().filter(|l| seen.insert(l.clone())).collect();
+    let removed = before - kept.len();
+    (kept, format!("Removed {} duplicate {}", removed, lines_word(removed)))
+}
+
+// 2回以上出てくる行は最初のものも含めて消す
+fn keep_unique(lines: Vec<String>) -> (Vec<String>, String) {
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in &lines {
+        *counts.entry(line).or_default() += 1;
+    }
+    let kept: Vec<String> = lines.iter().filter(|l| counts[l.as_str()] == 1).cloned().collect();
+    let removed = lines.len() - kept.len();
+    (kept, format!("Removed {} repeated {}", removed, lines_word(removed)))
+}
+
+fn reverse(mut lines: Vec<String>) -> (Vec<String>, String) {
+    lines.reverse();
+    let n = lines.len();
+    (lines, format!("Reversed {} {}", n, lines_word(n)))
+}
+
+// 行頭の空白の表示桁（タブは次の width の倍数まで進む）と、その長さ
+fn leading_columns(line: &str, width: usize) -> (usize, usize) {
+    let mut col = 0;
+    for (i, c) in line.char_indices() {
+        match c {
+            '\t' => col = (col / width + 1) * width,
+            ' ' => col += 1,
+            _ => return (col, i),
+        }
+    }
+    (col, line.len())
+}
+
+// 変えた行の数
+fn changed(before: &[String], after: &[String]) -> usize {
+    before.iter().zip(after).filter(|(a, b)| a != b).count()
+}
+
+impl App {
+    // 行頭の空白だけを tabs ならタブ（端数は空白）に、そうでなければ空白にする。行の中のタブはそのまま。
+    // これからの Tab や自動インデントも同じ流儀にする
+    fn retab(&mut self, lines: Vec<String>, tabs: bool) -> (Vec<String>, String) {
+        let width = self.indent().width;
+        let output: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                let (col, len) = leading_columns(line, width);
+                let lead = if tabs { "\t".repeat(col / width) + &" ".repeat(col % width) } else { " ".repeat(col) };
+                lead + &line[len..]
+            })
+            .collect();
+        self.file_indent = Some(if tabs { Detected::Tabs } else { Detected::Spaces(width) });
+        let n = changed(&lines, &output);
+        let style = if tabs { "tabs".to_string() } else { format!("{} spaces", width) };
+        (output, format!("Retabbed {} {} to {}", n, lines_word(n), style))
+    }
+
+    // 行の中も含めてすべてのタブを次のタブ位置までの空白にする。これからは空白で字下げする
+    fn expand_tabs(&mut self, lines: Vec<String>) -> (Vec<String>, String) {
+        let width = self.indent().width;
+        let output: Vec<String> = lines
+            .iter()
+            .map(|line| {
+                let mut out = String::new();
+                let mut col = 0;
+                for c in line.chars() {
+                    if c == '\t' {
+                        let next = (col / width + 1) * width;
+                        out.push_str(&" ".repeat(next - col));
+                        col = next;
+                    } else {
+                        out.push(c);
+                        col += c.width().unwrap_or(0);
+                    }
+                }
+                out
+            })
+            .collect();
+        self.file_indent = Some(Detected::Spaces(width));
+        let n = changed(&lines, &output);
+        (output, format!("Expanded tabs on {} {}", n, lines_word(n)))
+    }
+
+    pub fn open_line_commands(&mut self) {
+        let (first, last) = self.line_command_range();
+        let title = if self.has_selection() {
+            format!("Line Commands ({} selected {}): type to filter, Enter to apply", last - first + 1, lines_word(last - first + 1))
+        } else {
+            "Line Commands (whole buffer): type to filter, Enter to apply".to_string()
+        };
+        self.open_prompt(Prompt::list(&title, App::line_command_items, App::line_command_accept), "");
+    }
+
+    fn has_selection(&self) -> bool {
+        matches!((self.sel_start, self.sel_end), (Some(a), Some(b)) if a != b)
+    }
+
+    // 選択がかかる行 [first, last]。選択が次の行の頭で終わっていれば（Ctrl+E の行選択など）その行は含めない
+    pub fn selected_lines(&self) -> Option<(usize, usize)> {
+        let (Some(a), Some(b)) = (self.sel_start, self.sel_end) else { return None };
+        if a == b { return None; }
+        let (s, e) = (a.min(b), a.max(b));
+        Some((s.0, if e.1 == 0 && e.0 > s.0 { e.0 - 1 } else { e.0 }))
+    }
+
+    fn line_command_range(&self) -> (usize, usize) {
+        self.selected_lines().unwrap_or((0, self.lines.len() - 1))
+    }
+
+    fn line_command_matches(&self) -> Vec<(&'static str, LineCommand)> {
+        let query = self.popup_input.to_lowercase();
+        COMMANDS.into_iter().filter(|(name, _)| name.to_lowercase().contains(&query)).collect()
+    }
+
+    fn line_command_items(&self) -> Vec<String> {
+        self.line_command_matches().iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    fn line_command_accept(&mut self) {
+        let matches = self.line_command_matches();
+        let Some(&(name, command)) = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))) else { return };
+        let (first, last) = self.line_command_range();
+        let (output, report) = command(self, self.lines[first..=last].to_vec());
+        if output == self.lines[first..=last] {
+            self.message = Some(format!("{}: no changes", name));
+            return;
+        }
+        let selected = self.has_selection();
+        let count = output.len();
+        self.save_undo();
+        self.lines.splice(first..=last, output);
+        if self.lines.is_empty() { self.lines.push(String::new()); }
+        self.selection_reset();
+        // 選択していたなら置き換えた行を選び直す
+        if selected && count > 0 {
+            let end_line = self.lines.len() - 1;
+            let end = if first + count <= end_line { (first + count, 0) } else { (end_line, self.lines[end_line].len()) };
+            self.sel_start = Some((first, 0));
+            self.sel_end = Some(end);
+            self.shift_selection = true;
+            (self.cursor_y, self.cursor_x) = end;
+        } else {
+            self.cursor_y = self.cursor_y.min(self.lines.len() - 1);
+            self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        }
+        self.adjust_h_scroll(0);
+        self.message = Some(report);
+    }
+}
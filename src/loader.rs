@@ -0,0 +1,159 @@
+// バックグラウンド読み込み
+// ファイルを開くときの読み込みは1本のスレッドに任せ、少しずつ読んで読んだバイト数を数える。
+// NFS などで遅くても画面の描画とキー操作は止まらず、ステータスバーに進み具合を出す（Esc で読むのをやめる）。
+// 読み終わった中身は poll_load で受け取り、これまで通りテキストとして開けるかを見てからバッファに入れる
+use crate::{encoding, format_size, wake, App};
+use std::{
+    fs::File,
+    io::{self, Read},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, AtomicU64, Ordering},
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    thread,
+};
+
+const CHUNK: usize = 256 * 1024;
+const SHOW_SAVE_OVER: u64 = 1024 * 1024;
+
+// どこまで読むか
+#[derive(Clone, Copy, PartialEq)]
+pub enum Extent {
+    Checked(u64), // このバイト数より大きければ読まずに TooLarge を返す（0 なら確かめない）
+    Whole,
+    Head(u64), // 頭のこのバイト数まで
+}
+
+pub enum Loaded {
+    Bytes { bytes: Vec<u8>, size: u64 }, // size はファイル全体のバイト数
+    TooLarge(u64),
+    Failed(io::Error),
+}
+
+pub struct Load {
+    pub path: PathBuf,
+    pub extent: Extent,
+    done: Arc<AtomicU64>,
+    total: Arc<AtomicU64>,
+    cancel: Arc<AtomicBool>,
+    rx: Receiver<Loaded>,
+}
+
+impl Load {
+    pub fn start(path: PathBuf, extent: Extent) -> Self {
+        let (tx, rx) = mpsc::channel();
+        let done = Arc::new(AtomicU64::new(0));
+        let total = Arc::new(AtomicU64::new(0));
+        let cancel = Arc::new(AtomicBool::new(false));
+        let (d, t, c, p) = (Arc::clone(&done), Arc::clone(&total), Arc::clone(&cancel), path.clone());
+        thread::spawn(move || {
+            let loaded = read(&p, extent, &d, &t, &c).unwrap_or_else(Loaded::Failed);
+            if !c.load(Ordering::Relaxed) {
+                let _ = tx.send(loaded);
+                wake::wake();
+            }
+        });
+        Load { path, extent, done, total, cancel, rx }
+    }
+
+    pub fn try_recv(&self) -> Option<Loaded> {
+        self.rx.try_recv().ok()
+    }
+
+    // --deterministic では読み終わるまで待つ
+    pub fn wait(&self) -> Loaded {
+        self.rx.recv().unwrap_or_else(|_| Loaded::Failed(io::Error::other("the reader thread stopped")))
+    }
+
+    // 読んだバイト数と全体（まだ分からなければ 0）
+    pub fn progress(&self) -> (u64, u64) {
+        (self.done.load(Ordering::Relaxed), self.total.load(Ordering::Relaxed))
+    }
+
+    // スレッドは次の塊を読んだところで止まる
+    pub fn cancel(&self) {
+        self.cancel.store(true, Ordering::Relaxed);
+    }
+}
+
+fn read(path: &Path, extent: Extent, done: &AtomicU64, total: &AtomicU64, cancel: &AtomicBool) -> io::Result<Loaded> {
+    let mut file = File::open(path)?;
+    let size = file.metadata()?.len();
+    let want = match extent {
+        Extent::Checked(limit) if limit > 0 && size > limit => return Ok(Loaded::TooLarge(size)),
+        Extent::Head(limit) => size.min(limit),
+        _ => size,
+    };
+    total.store(want, Ordering::Relaxed);
+    let mut bytes = Vec::with_capacity(want as usize);
+    let mut buf = vec![0; CHUNK];
+    let head = matches!(extent, Extent::Head(_));
+    loop {
+        if cancel.load(Ordering::Relaxed) {
+            return Err(io::Error::new(io::ErrorKind::Interrupted, "cancelled"));
+        }
+        let room = if head { CHUNK.min((want - bytes.len() as u64) as usize) } else { CHUNK };
+        if room == 0 { break; }
+        let n = file.read(&mut buf[..room])?;
+        if n == 0 { break; }
+        bytes.extend_from_slice(&buf[..n]);
+        done.store(bytes.len() as u64, Ordering::Relaxed);
+    }
+    if head { encoding::drop_partial_line(&mut bytes); }
+    Ok(Loaded::Bytes { bytes, size })
+}
+
+// 読んだ量（全体が分かれば割合も）
+fn fraction(done: u64, total: u64) -> String {
+    if total == 0 { return format_size(done); }
+    format!("{} of {} ({}%)", format_size(done), format_size(total), done * 100 / total)
+}
+
+fn file_name(path: &Path) -> String {
+    path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default()
+}
+
+impl App {
+    // 前の読み込みは捨てて読み始める
+    pub fn start_load(&mut self, path: PathBuf, extent: Extent) {
+        if let Some(old) = self.loading.take() {
+            old.cancel();
+        }
+        let load = Load::start(path, extent);
+        if self.deterministic {
+            let loaded = load.wait();
+            self.open_loaded(load.path, extent, loaded);
+            return;
+        }
+        self.loading = Some(load);
+    }
+
+    pub fn poll_load(&mut self) {
+        if self.loading.is_none() { return; }
+        self.dirty = true; // 進み具合が変わる
+        let Some(loaded) = self.loading.as_ref().and_then(Load::try_recv) else { return };
+        let Some(load) = self.loading.take() else { return };
+        self.open_loaded(load.path, load.extent, loaded);
+    }
+
+    // Esc: 読むのをやめる。読んでいなければ false
+    pub fn cancel_load(&mut self) -> bool {
+        let Some(load) = self.loading.take() else { return false };
+        load.cancel();
+        self.message = Some(format!("Stopped opening {}", load.path.display()));
+        true
+    }
+
+    // ステータスバーに出す読み込みと保存の進み具合。小さな保存は一瞬で終わるので出さない
+    pub fn io_progress(&self) -> Option<String> {
+        if let Some(ref load) = self.loading {
+            let (done, total) = load.progress();
+            return Some(format!("Opening {}: {} (Esc stops)", file_name(&load.path), fraction(done, total)));
+        }
+        let (path, done, total) = self.save_queue.as_ref()?.progress()?;
+        if self.deterministic || total < SHOW_SAVE_OVER { return None; }
+        Some(format!("Saving {}: {}", file_name(path), fraction(done, total)))
+    }
+}
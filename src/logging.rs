@@ -0,0 +1,97 @@
+// ログファイル ($XDG_CACHE_HOME/rwe/rwe.log、未設定なら ~/.cache/rwe/rwe.log)
+// 画面を占有する TUI では println で調べられないので、起動と終了・ファイル操作・通知・エラー・パニックを1行1件で追記する。
+// 書式は `1700000000.123 level=info event=open path=/tmp/a.txt lines=12` のような key=value（空白を含む値は "" で囲む）。
+// 詳しさは設定の log_level = off / error / info / debug（既定は info。キー入力と Action は debug のときだけ）
+use crate::config::Config;
+use std::{
+    fmt::Display,
+    fs::File,
+    io::Write,
+    sync::Mutex,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+#[derive(Clone, Copy, PartialEq, PartialOrd, Default)]
+pub enum Level {
+    Off,
+    Error,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl Level {
+    pub fn parse(value: &str) -> Option<Level> {
+        match value {
+            "off" => Some(Level::Off),
+            "error" => Some(Level::Error),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            _ => None,
+        }
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            Level::Off => "off",
+            Level::Error => "error",
+            Level::Info => "info",
+            Level::Debug => "debug",
+        }
+    }
+}
+
+struct Logger {
+    level: Level,
+    file: File,
+}
+
+// 保存のワーカーやパニックのフックからも書くので1つだけ持つ
+static LOGGER: Mutex<Option<Logger>> = Mutex::new(None);
+
+pub fn init(level: Level) {
+    if level == Level::Off { return; }
+    let Some(dir) = Config::cache_dir() else { return };
+    if std::fs::create_dir_all(&dir).is_err() { return; }
+    let path = dir.join("rwe.log");
+    // 大きくなりすぎたら1世代だけ残して作り直す
+    if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+        let _ = std::fs::rename(&path, dir.join("rwe.log.old"));
+    }
+    let Ok(file) = std::fs::OpenOptions::new().create(true).append(true).open(&path) else { return };
+    *LOGGER.lock().unwrap_or_else(|e| e.into_inner()) = Some(Logger { level, file });
+}
+
+// 空白・引用符・= を含む値と空の値は Rust の文字列リテラルの形で書く
+fn quote(value: &str) -> String {
+    if value.is_empty() || value.contains([' ', '"', '=', '\\']) || value.chars().any(char::is_control) {
+        format!("{:?}", value)
+    } else {
+        value.to_string()
+    }
+}
+
+fn write(level: Level, event: &str, fields: &[(&str, &dyn Display)]) {
+    let mut logger = LOGGER.lock().unwrap_or_else(|e| e.into_inner());
+    let Some(logger) = logger.as_mut().filter(|l| level <= l.level) else { return };
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let mut line = format!("{}.{:03} level={} event={}", now.as_secs(), now.subsec_millis(), level.label(), event);
+    for (key, value) in fields {
+        line.push_str(&format!(" {}={}", key, quote(&value.to_string())));
+    }
+    let _ = writeln!(logger.file, "{}", line);
+}
+
+pub fn error(event: &str, fields: &[(&str, &dyn Display)]) {
+    write(Level::Error, event, fields);
+}
+
+pub fn info(event: &str, fields: &[(&str, &dyn Display)]) {
+    write(Level::Info, event, fields);
+}
+
+pub fn debug(event: &str, fields: &[(&str, &dyn Display)]) {
+    write(Level::Debug, event, fields);
+}
@@ -0,0 +1,757 @@
+// Language Server Protocol クライアント
+// ファイルタイプごとの設定 `lsp = <コマンド>`（例: [rust] の下に `lsp = rust-analyzer`）で、
+// そのファイルタイプのファイルを最初に開いたときに起動する。標準入出力で JSON-RPC をやり取りし、
+// 受信は別スレッドで読んでメインループ（poll_lsp）で処理する。文書は全文同期で、
+// 変更があればリクエストの直前にまとめて送る
+use crate::{grapheme, json::Json, prompt::Prompt, results::ResultItem, textobj::TextObject, wake, writer, App, Goto, TextPos};
+use std::{
+    collections::{HashMap, HashSet},
+    io::{self, BufRead, BufReader, Write},
+    path::{Path, PathBuf},
+    process::{Child, ChildStdin, Command, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+// 応答を待っているリクエストの種類
+pub enum Request {
+    Initialize,
+    Completion { line: usize },
+    Definition,
+    Rename { name: String },
+    Format { text: String }, // 要求したときの内容（応答までに変わっていたら適用しない）
+}
+
+struct Document {
+    uri: String,
+    version: u64,
+    text: String,
+}
+
+pub struct LspClient {
+    child: Child,
+    stdin: ChildStdin,
+    rx: Receiver<Json>,
+    next_id: u64,
+    pending: HashMap<u64, Request>,
+    ready: bool,      // initialize の応答を受け取った
+    queue: Vec<Json>, // ready になるまで送らずに貯めておくメッセージ
+    doc: Option<Document>,
+    pub trigger_chars: Vec<char>, // 補完を自動で始める文字（サーバーが指定）
+}
+
+impl LspClient {
+    fn start(command: &str, root: &Path) -> io::Result<Self> {
+        let mut parts = command.split_whitespace();
+        let program = parts.next().ok_or_else(|| io::Error::other("empty lsp command"))?;
+        let mut child = Command::new(program)
+            .args(parts)
+            .current_dir(root)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()?;
+        let stdin = child.stdin.take().ok_or_else(|| io::Error::other("no stdin"))?;
+        let stdout = child.stdout.take().ok_or_else(|| io::Error::other("no stdout"))?;
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            let mut reader = BufReader::new(stdout);
+            while let Some(msg) = read_message(&mut reader) {
+                if tx.send(msg).is_err() { break; }
+                wake::wake();
+            }
+        });
+        let mut client = LspClient {
+            child,
+            stdin,
+            rx,
+            next_id: 1,
+            pending: HashMap::new(),
+            ready: false,
+            queue: Vec::new(),
+            doc: None,
+            trigger_chars: Vec::new(),
+        };
+        let capabilities = Json::obj(vec![
+            (
+                "textDocument",
+                Json::obj(vec![
+                    (
+                        "completion",
+                        Json::obj(vec![("completionItem", Json::obj(vec![("snippetSupport", true.into())]))]),
+                    ),
+                    ("definition", Json::obj(vec![("linkSupport", true.into())])),
+                    ("rename", Json::obj(vec![])),
+                    ("formatting", Json::obj(vec![])),
+                    ("rangeFormatting", Json::obj(vec![])),
+                    ("synchronization", Json::obj(vec![("didSave", false.into())])),
+                ]),
+            ),
+            ("workspace", Json::obj(vec![("workspaceEdit", Json::obj(vec![("documentChanges", true.into())]))])),
+            ("general", Json::obj(vec![("positionEncodings", vec!["utf-16".into()].into())])),
+        ]);
+        let params = Json::obj(vec![
+            ("processId", (std::process::id() as usize).into()),
+            ("rootUri", path_to_uri(root).into()),
+            ("capabilities", capabilities),
+        ]);
+        let id = client.take_id(Request::Initialize);
+        client.write(&Json::obj(vec![
+            ("jsonrpc", "2.0".into()),
+            ("id", id.into()),
+            ("method", "initialize".into()),
+            ("params", params),
+        ]))?;
+        Ok(client)
+    }
+
+    fn take_id(&mut self, kind: Request) -> usize {
+        let id = self.next_id;
+        self.next_id += 1;
+        self.pending.insert(id, kind);
+        id as usize
+    }
+
+    fn write(&mut self, msg: &Json) -> io::Result<()> {
+        let body = msg.to_string();
+        write!(self.stdin, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+        self.stdin.flush()
+    }
+
+    fn send(&mut self, msg: Json) {
+        if self.ready {
+            let _ = self.write(&msg);
+        } else {
+            self.queue.push(msg);
+        }
+    }
+
+    pub fn request(&mut self, method: &str, params: Json, kind: Request) {
+        let id = self.take_id(kind);
+        self.send(Json::obj(vec![("jsonrpc", "2.0".into()), ("id", id.into()), ("method", method.into()), ("params", params)]));
+    }
+
+    fn notify(&mut self, method: &str, params: Json) {
+        self.send(Json::obj(vec![("jsonrpc", "2.0".into()), ("method", method.into()), ("params", params)]));
+    }
+
+    fn respond(&mut self, id: Json, result: Json) {
+        let _ = self.write(&Json::obj(vec![("jsonrpc", "2.0".into()), ("id", id), ("result", result)]));
+    }
+
+    // 同じ種類のリクエストがまだ応答待ちか
+    pub fn is_pending(&self, f: impl Fn(&Request) -> bool) -> bool {
+        self.pending.values().any(f)
+    }
+
+    fn open(&mut self, uri: String, language_id: &str, text: String) {
+        self.close();
+        let item = Json::obj(vec![
+            ("uri", uri.clone().into()),
+            ("languageId", language_id.into()),
+            ("version", 1usize.into()),
+            ("text", text.clone().into()),
+        ]);
+        self.notify("textDocument/didOpen", Json::obj(vec![("textDocument", item)]));
+        self.doc = Some(Document { uri, version: 1, text });
+    }
+
+    fn close(&mut self) {
+        if let Some(doc) = self.doc.take() {
+            let id = Json::obj(vec![("uri", doc.uri.into())]);
+            self.notify("textDocument/didClose", Json::obj(vec![("textDocument", id)]));
+        }
+    }
+
+    // 前回送った内容から変わっていれば全文を送る
+    fn sync(&mut self, text: String) {
+        let Some(doc) = self.doc.as_mut() else { return };
+        if doc.text == text { return; }
+        doc.version += 1;
+        doc.text = text.clone();
+        let id = Json::obj(vec![("uri", doc.uri.clone().into()), ("version", (doc.version as usize).into())]);
+        let change = Json::obj(vec![("text", text.into())]);
+        self.notify("textDocument/didChange", Json::obj(vec![("textDocument", id), ("contentChanges", vec![change].into())]));
+    }
+
+    // textDocument と position を持つパラメータ
+    pub fn position_params(&self, pos: Json) -> Option<Json> {
+        let uri = self.doc.as_ref()?.uri.clone();
+        Some(Json::obj(vec![("textDocument", Json::obj(vec![("uri", uri.into())])), ("position", pos)]))
+    }
+}
+
+impl Drop for LspClient {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+fn read_message(reader: &mut impl BufRead) -> Option<Json> {
+    loop {
+        let mut len = None;
+        loop {
+            let mut header = String::new();
+            if reader.read_line(&mut header).ok()? == 0 { return None; }
+            let header = header.trim_end();
+            if header.is_empty() { break; }
+            if let Some((name, value)) = header.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                len = value.trim().parse::<usize>().ok();
+            }
+        }
+        let Some(len) = len else { continue };
+        let mut body = vec![0; len];
+        reader.read_exact(&mut body).ok()?;
+        if let Some(msg) = std::str::from_utf8(&body).ok().and_then(Json::parse) {
+            return Some(msg);
+        }
+    }
+}
+
+// 起動中のサーバー（ファイルタイプごと）
+#[derive(Default)]
+pub struct Lsp {
+    clients: HashMap<String, LspClient>,
+    failed: HashSet<String>, // 起動に失敗したファイルタイプ（何度も試さない）
+}
+
+impl Lsp {
+    pub fn client_for(&self, filetype: &str) -> Option<&LspClient> {
+        self.clients.get(filetype).filter(|c| c.doc.is_some())
+    }
+}
+
+// --- 位置と URI の変換（LSP の桁は UTF-16 単位） ---
+pub fn to_lsp_position(lines: &[String], (y, x): TextPos) -> Json {
+    let line = lines.get(y).map(String::as_str).unwrap_or("");
+    let col = line[..x.min(line.len())].encode_utf16().count();
+    Json::obj(vec![("line", y.into()), ("character", col.into())])
+}
+
+pub fn from_lsp_position(lines: &[String], pos: &Json) -> TextPos {
+    let y = pos.get("line").and_then(Json::as_u64).unwrap_or(0) as usize;
+    let col = pos.get("character").and_then(Json::as_u64).unwrap_or(0) as usize;
+    let Some(line) = lines.get(y) else {
+        let last = lines.len().saturating_sub(1);
+        return (last, lines.get(last).map_or(0, String::len));
+    };
+    (y, utf16_to_byte(line, col))
+}
+
+// UTF-16 単位の列をバイト位置に直す
+pub fn utf16_to_byte(line: &str, col: usize) -> usize {
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= col { return i; }
+        units += c.len_utf16();
+    }
+    line.len()
+}
+
+pub fn path_to_uri(path: &Path) -> String {
+    let path = std::fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+    let mut uri = String::from("file://");
+    for &b in path.to_string_lossy().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+    uri
+}
+
+pub fn uri_to_path(uri: &str) -> Option<PathBuf> {
+    let rest = uri.strip_prefix("file://")?.as_bytes();
+    let mut bytes = Vec::with_capacity(rest.len());
+    let mut i = 0;
+    while i < rest.len() {
+        if rest[i] == b'%'
+            && let Some(b) = std::str::from_utf8(&rest[(i + 1).min(rest.len())..(i + 3).min(rest.len())])
+                .ok()
+                .and_then(|h| u8::from_str_radix(h, 16).ok())
+        {
+            bytes.push(b);
+            i += 3;
+            continue;
+        }
+        bytes.push(rest[i]);
+        i += 1;
+    }
+    Some(PathBuf::from(String::from_utf8(bytes).ok()?))
+}
+
+// --- 編集の適用 ---
+#[derive(Clone)]
+pub struct TextEdit {
+    pub start: TextPos,
+    pub end: TextPos,
+    pub text: String,
+}
+
+pub fn parse_text_edits(lines: &[String], edits: &Json) -> Vec<TextEdit> {
+    edits
+        .as_array()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|e| {
+            let range = e.get("range")?;
+            Some(TextEdit {
+                start: from_lsp_position(lines, range.get("start")?),
+                end: from_lsp_position(lines, range.get("end")?),
+                text: e.get("newText")?.as_str()?.to_string(),
+            })
+        })
+        .collect()
+}
+
+// 後ろの編集から順に適用するので、どの位置も元の文書の座標のままでよい。
+// 同じ位置への挿入は配列の順に並ぶよう、後ろのものから先に入れる
+pub fn apply_text_edits(lines: &mut Vec<String>, edits: Vec<TextEdit>) {
+    let mut edits: Vec<(usize, TextEdit)> = edits.into_iter().enumerate().collect();
+    edits.sort_by_key(|(i, e)| std::cmp::Reverse((e.start, *i)));
+    for (_, edit) in edits {
+        let (sy, sx) = edit.start;
+        let (ey, ex) = edit.end.max(edit.start);
+        if sy >= lines.len() { continue; }
+        let ey = ey.min(lines.len() - 1);
+        let head = lines[sy][..sx.min(lines[sy].len())].to_string();
+        let tail = lines[ey][ex.min(lines[ey].len())..].to_string();
+        let text = format!("{}{}{}", head, edit.text.replace("\r\n", "\n"), tail);
+        lines.splice(sy..=ey, text.split('\n').map(str::to_string));
+    }
+}
+
+// 挿入した text の終わりの位置
+pub fn end_of_insert((y, x): TextPos, text: &str) -> TextPos {
+    match text.rfind('\n') {
+        Some(i) => (y + text.matches('\n').count(), text.len() - i - 1),
+        None => (y, x + text.len()),
+    }
+}
+
+fn language_id(filetype: &str) -> &str {
+    match filetype {
+        "shell" => "shellscript",
+        other => other,
+    }
+}
+
+impl App {
+    // 開いたファイルをそのファイルタイプのサーバーに知らせる（必要なら起動する）
+    pub fn lsp_open_document(&mut self) {
+        for client in self.lsp.clients.values_mut() {
+            client.close();
+        }
+        // 読み取り専用（頭だけ・UTF-8 でない）のバッファはサーバーに渡さない
+        if self.deterministic || self.read_only { return; }
+        let Some(path) = self.current_file.clone() else { return };
+        let ft = self.filetype();
+        let Some(command) = self.config.filetype(ft).lsp else { return };
+        if !self.lsp.clients.contains_key(ft) {
+            if self.lsp.failed.contains(ft) { return; }
+            match LspClient::start(&command, &self.finder.root) {
+                Ok(client) => {
+                    self.lsp.clients.insert(ft.to_string(), client);
+                }
+                Err(e) => {
+                    self.lsp.failed.insert(ft.to_string());
+                    self.message = Some(format!("Cannot start language server {:?}: {}", command, e));
+                    return;
+                }
+            }
+        }
+        let text = self.lines.join("\n");
+        if let Some(client) = self.lsp.clients.get_mut(ft) {
+            client.open(path_to_uri(&path), language_id(ft), text);
+        }
+    }
+
+    pub fn lsp_attached(&self) -> bool {
+        self.lsp.client_for(self.filetype()).is_some()
+    }
+
+    // 今のバッファを受け持つサーバー。最新の内容を送ってから返す
+    pub fn lsp_client(&mut self) -> Option<&mut LspClient> {
+        let ft = self.filetype();
+        let text = self.lines.join("\n");
+        let client = self.lsp.clients.get_mut(ft).filter(|c| c.doc.is_some());
+        match client {
+            Some(client) => {
+                client.sync(text);
+                Some(client)
+            }
+            None => {
+                self.message = Some(format!("No language server for {} (set `lsp = <command>` under [{}])", ft, ft));
+                None
+            }
+        }
+    }
+
+    // カーソル位置の textDocument/position パラメータ
+    pub fn lsp_cursor_params(&self) -> Json {
+        to_lsp_position(&self.lines, (self.cursor_y, self.cursor_x))
+    }
+
+    // 届いたメッセージを処理する（メインループから毎回呼ぶ）
+    pub fn poll_lsp(&mut self) {
+        let mut responses = Vec::new();
+        let mut messages = Vec::new();
+        for client in self.lsp.clients.values_mut() {
+            while let Ok(msg) = client.rx.try_recv() {
+                let id = msg.get("id").cloned();
+                match (msg.get("method").and_then(Json::as_str), id) {
+                    // サーバーからのリクエストには空の応答を返す
+                    (Some(method), Some(id)) => {
+                        let result = match method {
+                            "workspace/configuration" => {
+                                let n = msg.get("params").and_then(|p| p.get("items")).and_then(Json::as_array).map_or(0, |a| a.len());
+                                Json::Array(vec![Json::Null; n])
+                            }
+                            _ => Json::Null,
+                        };
+                        client.respond(id, result);
+                    }
+                    (Some("window/showMessage"), None) => {
+                        if let Some(text) = msg.get("params").and_then(|p| p.get("message")).and_then(Json::as_str) {
+                            messages.push(text.to_string());
+                        }
+                    }
+                    (Some(_), None) => {}
+                    (None, Some(id)) => {
+                        let Some(kind) = id.as_u64().and_then(|id| client.pending.remove(&id)) else { continue };
+                        let result = match msg.get("error") {
+                            Some(err) => Err(err.get("message").and_then(Json::as_str).unwrap_or("error").to_string()),
+                            None => Ok(msg.get("result").cloned().unwrap_or(Json::Null)),
+                        };
+                        if let Request::Initialize = kind {
+                            client.initialized(result.as_ref().ok());
+                            continue;
+                        }
+                        responses.push((kind, result));
+                    }
+                    (None, None) => {}
+                }
+            }
+        }
+        if !messages.is_empty() || !responses.is_empty() {
+            self.dirty = true;
+        }
+        if let Some(text) = messages.pop() {
+            self.message = Some(text);
+        }
+        for (kind, result) in responses {
+            match result {
+                Err(e) => self.message = Some(format!("Language server: {}", e)),
+                Ok(result) => match kind {
+                    Request::Initialize => {}
+                    Request::Completion { line } => self.show_completion(line, &result),
+                    Request::Definition => self.goto_definition_result(&result),
+                    Request::Rename { name } => self.rename_symbol_result(&name, &result),
+                    Request::Format { text } => self.format_result(&text, &result),
+                },
+            }
+        }
+    }
+}
+
+impl LspClient {
+    // initialize の応答を受けたら、貯めておいたメッセージを送る
+    fn initialized(&mut self, result: Option<&Json>) {
+        let caps = result.and_then(|r| r.get("capabilities"));
+        self.trigger_chars = caps
+            .and_then(|c| c.get("completionProvider"))
+            .and_then(|c| c.get("triggerCharacters"))
+            .and_then(Json::as_array)
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|t| t.as_str()?.chars().next())
+            .collect();
+        let _ = self.write(&Json::obj(vec![("jsonrpc", "2.0".into()), ("method", "initialized".into()), ("params", Json::obj(vec![]))]));
+        self.ready = true;
+        for msg in std::mem::take(&mut self.queue) {
+            let _ = self.write(&msg);
+        }
+    }
+}
+
+// --- 定義へ移動 ---
+impl App {
+    pub fn goto_definition(&mut self) {
+        let pos = self.lsp_cursor_params();
+        let Some(client) = self.lsp_client() else { return };
+        if let Some(params) = client.position_params(pos) {
+            client.request("textDocument/definition", params, Request::Definition);
+        }
+    }
+
+    // Location / Location[] / LocationLink[] のどれか。複数なら結果パネルにも並べる
+    fn goto_definition_result(&mut self, result: &Json) {
+        let list = match result {
+            Json::Array(items) => items.clone(),
+            Json::Null => Vec::new(),
+            single => vec![single.clone()],
+        };
+        let targets: Vec<(PathBuf, Json)> = list
+            .iter()
+            .filter_map(|loc| {
+                let uri = loc.get("targetUri").or_else(|| loc.get("uri"))?.as_str()?;
+                let range = loc.get("targetSelectionRange").or_else(|| loc.get("range"))?;
+                Some((uri_to_path(uri)?, range.get("start")?.clone()))
+            })
+            .collect();
+        let Some((path, pos)) = targets.first().cloned() else {
+            self.message = Some("No definition found".to_string());
+            return;
+        };
+        let same = self.current_file.as_ref().is_some_and(|cur| {
+            std::fs::canonicalize(cur).ok() == std::fs::canonicalize(&path).ok()
+        });
+        if !same && self.modified {
+            self.message = Some("Unsaved changes; save before jumping to another file".to_string());
+            return;
+        }
+        if targets.len() > 1 {
+            let items = targets
+                .iter()
+                .map(|(path, pos)| {
+                    // 一覧に出す行だけなので、UTF-8 でないファイルも読めるところまで見せる
+                    let text = std::fs::read(path).map(|bytes| String::from_utf8_lossy(&bytes).into_owned()).unwrap_or_default();
+                    let lines: Vec<String> = text.lines().map(str::to_string).collect();
+                    let (line, col) = from_lsp_position(&lines, pos);
+                    let text = lines.get(line).cloned().unwrap_or_default();
+                    ResultItem { path: Some(path.clone()), line, col, text }
+                })
+                .collect();
+            self.set_results(format!("Definitions: {}", targets.len()), items, 0);
+        }
+        self.record_jump();
+        let line = pos.get("line").and_then(Json::as_u64).unwrap_or(0) as usize;
+        let col = pos.get("character").and_then(Json::as_u64).unwrap_or(0) as usize;
+        self.goto(Goto { path, line, col, utf16: true });
+    }
+}
+
+// --- シンボル名の変更 ---
+impl App {
+    pub fn open_rename_symbol(&mut self) {
+        if self.lsp_client().is_none() { return; }
+        let word = self
+            .text_object(TextObject::Word)
+            .map(|(s, e)| self.lines[s.0][s.1..e.1].to_string())
+            .filter(|w| w.chars().any(|c| c.is_alphanumeric() || c == '_'))
+            .unwrap_or_default();
+        let prompt = Prompt::text("Rename Symbol: Enter new name (all references are updated)", |app| {
+            let name = app.popup_input.trim().to_string();
+            app.rename_symbol(name);
+        });
+        self.open_prompt(prompt, &word);
+    }
+
+    pub fn rename_symbol(&mut self, name: String) {
+        if name.is_empty() { return; }
+        let pos = self.lsp_cursor_params();
+        let Some(client) = self.lsp_client() else { return };
+        if let Some(Json::Object(mut fields)) = client.position_params(pos) {
+            fields.push(("newName".to_string(), name.clone().into()));
+            client.request("textDocument/rename", Json::Object(fields), Request::Rename { name });
+        }
+    }
+
+    fn rename_symbol_result(&mut self, name: &str, result: &Json) {
+        let (files, edits, mut notes) = self.apply_workspace_edit(result);
+        if edits == 0 && notes.is_empty() {
+            self.message = Some("Nothing to rename".to_string());
+            return;
+        }
+        let mut msg = format!("Renamed to {}: {} edits in {} files", name, edits, files);
+        if !notes.is_empty() {
+            notes.insert(0, String::new());
+            msg.push_str(&notes.join(", "));
+        }
+        self.message = Some(msg);
+    }
+
+    // WorkspaceEdit を適用する。開いているバッファは undo できるように書き換え、未保存の変更がなければ保存する。
+    // 戻り値は (変更したファイル数, 編集の数, 注記)
+    pub fn apply_workspace_edit(&mut self, edit: &Json) -> (usize, usize, Vec<String>) {
+        let mut by_file: Vec<(PathBuf, &Json)> = Vec::new();
+        let mut notes = Vec::new();
+        for (uri, edits) in edit.get("changes").and_then(Json::as_object).unwrap_or_default() {
+            if let Some(path) = uri_to_path(uri) { by_file.push((path, edits)); }
+        }
+        for change in edit.get("documentChanges").and_then(Json::as_array).unwrap_or_default() {
+            let uri = change.get("textDocument").and_then(|d| d.get("uri")).and_then(Json::as_str);
+            match (uri.and_then(uri_to_path), change.get("edits")) {
+                (Some(path), Some(edits)) => by_file.push((path, edits)),
+                // ファイルの作成・移動・削除には対応していない
+                _ => notes.push(format!("skipped {} file operation", change.get("kind").and_then(Json::as_str).unwrap_or("unknown"))),
+            }
+        }
+        let current = self.current_file.as_ref().and_then(|p| std::fs::canonicalize(p).ok());
+        let (mut files, mut count) = (0, 0);
+        let mut failed = 0;
+        for (path, edits) in by_file {
+            if current.is_some() && std::fs::canonicalize(&path).ok() == current {
+                // 16進表示や読み取り専用のバッファは書き換えない（保存できないので）
+                if self.hex.is_some() {
+                    notes.push("current buffer is in the hex view".to_string());
+                    continue;
+                }
+                if let Err(e) = self.buffer_bytes() {
+                    notes.push(format!("current buffer skipped: {}", e));
+                    continue;
+                }
+                let edits = parse_text_edits(&self.lines, edits);
+                if edits.is_empty() { continue; }
+                let was_modified = self.modified;
+                count += edits.len();
+                self.save_undo();
+                apply_text_edits(&mut self.lines, edits);
+                if self.lines.is_empty() { self.lines.push(String::new()); }
+                self.cursor_y = self.cursor_y.min(self.lines.len() - 1);
+                self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+                if was_modified {
+                    notes.push("current buffer not saved".to_string());
+                } else {
+                    // 保存キューを通す（開いたときの文字コードで書き、バックアップも取る）
+                    match self.buffer_bytes() {
+                        Ok(content) => self.enqueue_save(path, content),
+                        Err(e) => notes.push(format!("current buffer not saved: {}", e)),
+                    }
+                }
+                files += 1;
+                continue;
+            }
+            let Ok(content) = std::fs::read_to_string(&path) else {
+                failed += 1;
+                continue;
+            };
+            let mut lines: Vec<String> = content.split('\n').map(str::to_string).collect();
+            let edits = parse_text_edits(&lines, edits);
+            if edits.is_empty() { continue; }
+            let n = edits.len();
+            apply_text_edits(&mut lines, edits);
+            if writer::write_atomic(&path, lines.join("\n").as_bytes()).is_err() {
+                failed += 1;
+                continue;
+            }
+            files += 1;
+            count += n;
+        }
+        if failed > 0 { notes.push(format!("{} files failed", failed)); }
+        (files, count, notes)
+    }
+}
+
+// --- 整形 ---
+impl App {
+    // 選択範囲があればその範囲だけ、なければ文書全体を整形する
+    pub fn format_document(&mut self) {
+        let indent = self.indent();
+        let options = Json::obj(vec![("tabSize", indent.width.into()), ("insertSpaces", (!indent.tabs).into())]);
+        let range = match (self.sel_start, self.sel_end) {
+            (Some(a), Some(b)) if a != b => {
+                let (s, e) = if a <= b { (a, b) } else { (b, a) };
+                Some(Json::obj(vec![("start", to_lsp_position(&self.lines, s)), ("end", to_lsp_position(&self.lines, e))]))
+            }
+            _ => None,
+        };
+        let text = self.lines.join("\n");
+        let Some(client) = self.lsp_client() else { return };
+        let Some(uri) = client.doc.as_ref().map(|d| d.uri.clone()) else { return };
+        let mut params = vec![("textDocument", Json::obj(vec![("uri", uri.into())])), ("options", options)];
+        let method = match range {
+            Some(range) => {
+                params.push(("range", range));
+                "textDocument/rangeFormatting"
+            }
+            None => "textDocument/formatting",
+        };
+        client.request(method, Json::obj(params), Request::Format { text });
+    }
+
+    // 整形結果はまとめて1回の undo で戻せる
+    fn format_result(&mut self, text: &str, result: &Json) {
+        if self.lines.join("\n") != text {
+            self.message = Some("Buffer changed while formatting; not applied".to_string());
+            return;
+        }
+        let edits = parse_text_edits(&self.lines, result);
+        if edits.is_empty() {
+            self.message = Some("Already formatted".to_string());
+            return;
+        }
+        let before = self.lines.clone();
+        let mut lines = self.lines.clone();
+        apply_text_edits(&mut lines, edits);
+        if lines == before {
+            self.message = Some("Already formatted".to_string());
+            return;
+        }
+        self.save_undo();
+        *self.lines = lines;
+        self.selection_reset();
+        self.cursor_y = self.cursor_y.min(self.lines.len() - 1);
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        self.adjust_h_scroll(0);
+        self.message = Some("Formatted".to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &[&str]) -> Vec<String> {
+        text.iter().map(|s| s.to_string()).collect()
+    }
+
+    fn edit(start: TextPos, end: TextPos, text: &str) -> TextEdit {
+        TextEdit { start, end, text: text.to_string() }
+    }
+
+    #[test]
+    fn applies_edits_from_the_back() {
+        let mut text = lines(&["hello world"]);
+        apply_text_edits(&mut text, vec![edit((0, 0), (0, 5), "bye"), edit((0, 6), (0, 11), "all")]);
+        assert_eq!(text, ["bye all"]);
+    }
+
+    #[test]
+    fn inserts_at_the_same_position_keep_their_order() {
+        let mut text = lines(&["x"]);
+        apply_text_edits(&mut text, vec![edit((0, 0), (0, 0), "a"), edit((0, 0), (0, 0), "b"), edit((0, 1), (0, 1), "c")]);
+        assert_eq!(text, ["abxc"]);
+    }
+
+    #[test]
+    fn applies_edits_across_lines() {
+        let mut text = lines(&["fn a() {", "}", "end"]);
+        apply_text_edits(&mut text, vec![edit((0, 8), (1, 0), "\r\n    x\r\n"), edit((1, 1), (2, 0), "")]);
+        assert_eq!(text, ["fn a() {", "    x", "}end"]);
+    }
+
+    #[test]
+    fn converts_utf16_columns() {
+        // 😀 は UTF-16 で2単位、UTF-8 で4バイト
+        let text = lines(&["a😀b", "é"]);
+        let at = |line: usize, character: usize| {
+            from_lsp_position(&text, &Json::obj(vec![("line", line.into()), ("character", character.into())]))
+        };
+        assert_eq!(at(0, 1), (0, 1));
+        assert_eq!(at(0, 3), (0, 5));
+        assert_eq!(at(0, 4), (0, 6));
+        assert_eq!(at(0, 99), (0, 6));
+        assert_eq!(at(1, 1), (1, 2));
+        // 行が範囲外なら最後の行の末尾
+        assert_eq!(at(5, 0), (1, 2));
+        let character = |pos| to_lsp_position(&text, pos).get("character").and_then(Json::as_u64);
+        assert_eq!(character((0, 5)), Some(3));
+        assert_eq!(character((0, 6)), Some(4));
+        assert_eq!(character((1, 2)), Some(1));
+    }
+}
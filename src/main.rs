@@ -1,14 +1,17 @@
-use crossterm::{
-    event::{poll, read, Event, KeyCode, KeyEvent, KeyModifiers},
-    execute,
-    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
-};
+use crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers};
 use copypasta::{ClipboardContext, ClipboardProvider};
+use signal_hook::consts::signal::{SIGHUP, SIGTERM};
 use std::{
+    collections::HashSet,
     convert::TryInto,
-    io::{self, Write},
-    path::PathBuf,
-    time::Duration,
+    ffi::OsString,
+    io,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{Duration, Instant, SystemTime},
 };
 use tui::{
     backend::CrosstermBackend,
@@ -21,13 +24,133 @@ use tui::{
 use unicode_segmentation::UnicodeSegmentation;
 use unicode_width::UnicodeWidthStr;
 
+mod backup;
+mod buffer;
+mod commit;
+mod completion;
+mod config;
+mod diff;
+mod encoding;
+mod external;
+mod filetype;
+mod fileops;
+mod finder;
+mod frecency;
+mod fuzzy;
+mod git;
+mod gotofile;
+mod grapheme;
+mod grep;
+mod hex;
+mod ignore;
+mod indent;
+mod json;
+mod jsonfmt;
+mod jumplist;
+mod lines;
+mod logging;
+mod loader;
+mod lsp;
+mod markdown;
+mod multicursor;
+mod mouse;
+mod keymap;
+mod notify;
+mod outline;
+mod pathinput;
+mod peek;
+mod perf;
+mod prompt;
+mod reflow;
+mod results;
+mod search;
+mod sort;
+mod spell;
+mod syntax;
+mod task;
+mod term;
+mod textobj;
+mod trash;
+mod transform;
+mod unicode;
+mod url;
+mod vim;
+mod virtual_space;
+mod wake;
+mod watch;
+mod writer;
+
+use buffer::Lines;
+use commit::CommitPanel;
+use completion::Completion;
+use config::{Config, Keymap};
+use encoding::{Encoding, OpenAs};
+use external::ExternalJob;
+use finder::Finder;
+use frecency::Frecency;
+use git::{FileStatus, GitGutter, Mark, RepoStatus};
+use grep::GrepState;
+use hex::HexView;
+use ignore::Ignore;
+use indent::Detected;
+use jumplist::JumpList;
+use keymap::{Action, KeyBinding};
+use loader::{Extent, Load};
+use lsp::Lsp;
+use notify::{Level, Notices};
+use peek::Peek;
+use perf::Perf;
+use prompt::Prompt;
+use results::{ResultItem, ResultsPanel};
+use search::{Matcher, SearchOptions};
+use sort::{SortMode, Sortable};
+use spell::{Misspellings, Speller};
+use syntax::{Highlights, Syntax};
+use task::{TaskJob, TaskPanel, TaskStatus};
+use textobj::TextObject;
+use vim::{VimMode, VimState};
+use wake::Wake;
+use watch::DirWatch;
+use writer::{SaveJob, SaveQueue, SaveResult};
+
+// バッファ内の位置 (行, バイト位置)
+type TextPos = (usize, usize);
+
+// ステータスバーの一致件数のもと。検索語・設定・バッファの世代が同じあいだは数え直さない
+#[derive(Clone)]
+struct MatchCount {
+    query: String,
+    opts: SearchOptions,
+    generation: u64,
+    starts: Vec<TextPos>, // 一致の先頭（並んだ順）
+}
+
+// 別のファイルの位置へ移るときは、読み終わってからその位置へ移る
+#[derive(Clone)]
+struct Goto {
+    path: PathBuf,
+    line: usize,
+    col: usize,
+    utf16: bool, // 列が LSP の UTF-16 単位で数えたもの
+}
+
 // ポップアップモードの定義
 #[derive(Clone, PartialEq)]
 enum PopupMode {
-    ExitPrompt,  // 終了／保存確認
-    NewFile,     // 新規作成
-    Rename,      // 移動／リネーム
-    SaveFile,    // 保存時の名前入力
+    Prompt(Prompt), // 入力欄・一覧・確認の共通の形（prompt.rs）
+    Finder,         // ファイル検索 (Ctrl+P)
+    Search,         // バッファ内検索 (Ctrl+F)
+    Grep,           // ワークスペースの全文検索
+    ReplaceWith,    // 全文検索の置換文字列入力
+    ReplacePreview, // 置換する箇所の確認と選択
+    GitCommit,      // 変更のステージとコミット
+    OverwriteDiff { path: PathBuf, preview: Vec<String> }, // 別内容のファイルへ保存する前の確認
+    DiffView { title: String, lines: Vec<String> },         // 差分の表示（popup_selected がスクロール位置）
+    RenameEntry { path: PathBuf }, // FileTree で選んだ項目の新しい名前
+    Spelling { line: usize, start: usize, end: usize, word: String, suggestions: Vec<String> }, // 綴りの候補
+    ConfirmDelete { paths: Vec<PathBuf>, preview: Vec<String>, outside: bool }, // FileTree で消す前の確認（popup_selected がスクロール位置。outside はリンクを辿ってツリーの外）
+    TransferEntries { paths: Vec<PathBuf>, copy: bool }, // FileTree でコピー・移動する先のディレクトリ
+    OpenChoice { path: PathBuf, reason: String, choices: Vec<OpenAs> }, // テキストとして開けない・大きすぎるファイルの開き方
 }
 
 #[derive(Clone)]
@@ -36,34 +159,340 @@ enum Mode {
     FileTree,
 }
 
+// FileTree の表示切替
+#[derive(Clone, Copy, PartialEq)]
+enum TreeView {
+    Directory, // 現在のディレクトリ
+    Recent,    // 配下で最近更新されたファイル
+}
+
+#[derive(Clone)]
+struct TreeEntry {
+    path: PathBuf,
+    modified: Option<SystemTime>,
+    size: Option<u64>, // ディレクトリは None
+    frecency: u64,
+    is_dir: bool,
+    link: Option<PathBuf>, // シンボリックリンクならリンク先
+    broken: bool,          // リンク先がない
+    cycle: bool,           // 開くと祖先のディレクトリに戻るリンク（中身は出さない）
+    depth: usize, // Directory 表示での深さ（current_path の直下が 0）
+}
+
+impl TreeEntry {
+    fn path(&self) -> PathBuf {
+        self.path.clone()
+    }
+    fn file_name(&self) -> OsString {
+        self.path.file_name().map(|s| s.to_os_string()).unwrap_or_default()
+    }
+    fn new(path: PathBuf, meta: Option<std::fs::Metadata>) -> Self {
+        TreeEntry {
+            is_dir: path.is_dir(),
+            link: std::fs::read_link(&path).ok(),
+            broken: !path.exists(),
+            cycle: false,
+            path,
+            modified: meta.as_ref().and_then(|m| m.modified().ok()),
+            size: meta.filter(|m| m.is_file()).map(|m| m.len()),
+            frecency: 0,
+            depth: 0,
+        }
+    }
+    // シンボリックリンクはリンク先の大きさと更新日時を使う（リンク切れならリンク自身）
+    fn from_dir_entry(entry: &std::fs::DirEntry) -> Self {
+        let path = entry.path();
+        let meta = std::fs::metadata(&path).or_else(|_| entry.metadata()).ok();
+        TreeEntry::new(path, meta)
+    }
+    // 詳細表示の種類の欄
+    fn kind(&self) -> &'static str {
+        match (self.link.is_some(), self.is_dir) {
+            (true, _) => "link",
+            (false, true) => "dir",
+            (false, false) => "file",
+        }
+    }
+    // リンクなら名前の後ろに ` -> リンク先` を添える
+    fn link_suffix(&self) -> String {
+        let Some(target) = &self.link else { return String::new() };
+        let note = if self.broken { " (broken)" } else if self.cycle { " (loop, not expanded)" } else { "" };
+        format!(" -> {}{}", target.display(), note)
+    }
+}
+
+impl Sortable for TreeEntry {
+    fn sort_name(&self) -> String {
+        self.file_name().to_string_lossy().to_string()
+    }
+    fn sort_modified(&self) -> Option<SystemTime> {
+        self.modified
+    }
+    fn sort_size(&self) -> Option<u64> {
+        self.size
+    }
+    fn sort_frecency(&self) -> u64 {
+        self.frecency
+    }
+}
+
+#[derive(Clone)]
 struct FileTree {
     current_path: PathBuf,
-    entries: Vec<std::fs::DirEntry>,
+    entries: Vec<TreeEntry>,
     selected: usize,
     scroll_offset: usize,
+    view: TreeView,
+    recent_limit: usize,
+    // 並び順は表示ごとに覚えておく
+    dir_sort: SortMode,
+    recent_sort: SortMode,
+    git: Option<RepoStatus>, // current_path がリポジトリの中なら git status
+    show_ignored: bool,      // Ctrl+G: .gitignore で除外したものも表示する
+    expanded: HashSet<PathBuf>, // その場で開いているディレクトリ
+    filter: String,             // 入力した文字であいまい一致した項目だけを一致の良い順に出す
+    marked: HashSet<PathBuf>,   // Space で印を付けた項目（まとめて削除・コピー・移動する）
+    peek: Option<Peek>,         // 選択中の項目の中身（左側に出す）
+    details: bool,              // Ctrl+D: 種類・大きさ・更新からの経過時間を右端に並べる
+    watched: Vec<(PathBuf, Option<SystemTime>)>, // 一覧を作ったときの表示中ディレクトリの更新日時
+    watch: DirWatch,
+    frecency: Frecency, // よく使うファイルの記録（FileTree を開いたときに読む）
+    error: Option<String>, // 表示中のディレクトリを読めなかった理由
 }
 
 impl FileTree {
     fn new() -> Self {
-        let current_path = std::env::current_dir().unwrap();
+        // 作業ディレクトリが消されていればルートから
+        let current_path = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
         let mut ft = FileTree {
             current_path,
             entries: Vec::new(),
             selected: 0,
             scroll_offset: 0,
+            view: TreeView::Directory,
+            recent_limit: 50,
+            dir_sort: SortMode::Name,
+            recent_sort: SortMode::Frecency,
+            git: None,
+            show_ignored: false,
+            expanded: HashSet::new(),
+            filter: String::new(),
+            marked: HashSet::new(),
+            peek: None,
+            details: false,
+            watched: Vec::new(),
+            watch: DirWatch::new(),
+            frecency: Frecency::load(),
+            error: None,
         };
         ft.refresh();
         ft
     }
     fn refresh(&mut self) {
-        self.entries = std::fs::read_dir(&self.current_path)
-            .unwrap()
-            .filter_map(|e| e.ok())
-            .collect();
-        self.entries.sort_by_key(|e| e.path());
+        self.load_entries();
+        self.selected = 0;
+        self.scroll_offset = 0;
+        self.refresh_git();
+    }
+    // 選択中の項目を選んだまま読み直す（なくなっていたら同じ位置の項目）
+    fn reload(&mut self) {
+        let selected = self.entries.get(self.selected).map(|e| e.path.clone());
+        self.load_entries();
+        let pos = selected.and_then(|p| self.entries.iter().position(|e| e.path == p));
+        self.selected = pos.unwrap_or(self.selected).min(self.entries.len().saturating_sub(1));
+        self.refresh_git();
+    }
+    // FileTree を開いたとき。開いたファイルが増えているので記録を読み直して並べ直す
+    fn open(&mut self) {
+        self.frecency = Frecency::load();
+        self.reload();
+    }
+    // 絞り込みの入力や読み直しのたびに記録は読まない（open で読んだものを使う）
+    fn load_entries(&mut self) {
+        let frecency = std::mem::take(&mut self.frecency);
+        self.peek = None;
+        match self.view {
+            TreeView::Directory => {
+                let mut ignore = (!self.show_ignored).then(|| Ignore::for_dir(&self.current_path));
+                let mut entries = Vec::new();
+                let mut ancestors: Vec<PathBuf> = std::fs::canonicalize(&self.current_path).into_iter().collect();
+                self.error = std::fs::read_dir(&self.current_path).err().map(|e| e.to_string());
+                self.push_children(&self.current_path, 0, &mut ignore, &frecency, &mut ancestors, &mut entries);
+                self.entries = entries;
+                self.watched = self.watch_stamps();
+            }
+            TreeView::Recent => {
+                self.entries = self.recent_entries(&frecency);
+                for entry in &mut self.entries {
+                    entry.frecency = frecency.score(&entry.path);
+                }
+                sort::sort_items(&mut self.entries, self.recent_sort);
+            }
+        }
+        if !self.filter.is_empty() {
+            let mut matches: Vec<(i64, TreeEntry)> = std::mem::take(&mut self.entries)
+                .into_iter()
+                .filter_map(|e| Some((fuzzy::score(&self.filter, &self.display_name(&e))?, e)))
+                .collect();
+            matches.sort_by_key(|(score, _)| std::cmp::Reverse(*score));
+            self.entries = matches.into_iter().map(|(_, e)| e).collect();
+        }
+        self.frecency = frecency;
+    }
+    // 表示中のディレクトリ（ルートと開いているもの）の更新日時。中身が増減するとディレクトリの更新日時が変わる
+    fn watch_stamps(&self) -> Vec<(PathBuf, Option<SystemTime>)> {
+        std::iter::once(&self.current_path)
+            .chain(self.expanded.iter().filter(|p| p.starts_with(&self.current_path)))
+            .map(|dir| (dir.clone(), dir.metadata().and_then(|m| m.modified()).ok()))
+            .collect()
+    }
+    // 外で項目が増減していたら選択を保ったまま読み直す（FileTree を表示している間、メインループで呼ぶ）。読み直したら true。
+    // 増減はファイル監視（watch.rs）が知らせる。見張り始めたディレクトリは、一覧を作ってからの増減を更新日時で確かめる
+    fn poll_changes(&mut self) -> bool {
+        if self.view != TreeView::Directory { return false; }
+        let started = self.watch.watch(self.watched.iter().map(|(dir, _)| dir.clone()).collect());
+        let changed = self.watch.take_changed() || started && {
+            let mut stamps = self.watch_stamps();
+            let mut watched = self.watched.clone();
+            stamps.sort();
+            watched.sort();
+            stamps != watched
+        };
+        if !changed { return false; }
+        self.reload();
+        true
+    }
+    // 更新からの経過時間を出している（時間が経つだけで表示が変わる）
+    fn shows_ages(&self) -> bool {
+        self.details || matches!(self.sort_mode(), SortMode::Modified | SortMode::Frecency)
+    }
+    // 絞り込みを変えたら一番よく一致する項目を選ぶ
+    fn set_filter(&mut self, filter: String) {
+        self.filter = filter;
+        self.load_entries();
         self.selected = 0;
         self.scroll_offset = 0;
     }
+    // 絞り込みをやめても選んでいた項目は選んだまま
+    fn clear_filter(&mut self) {
+        self.filter.clear();
+        self.reload();
+    }
+    // dir の中身を並べ、開いているディレクトリはその直後に字下げして中身を続ける。
+    // ancestors は辿っている途中のディレクトリの実体のパスで、そこへ戻るリンクは開かない
+    fn push_children(&self, dir: &Path, depth: usize, ignore: &mut Option<Ignore>, frecency: &Frecency, ancestors: &mut Vec<PathBuf>, out: &mut Vec<TreeEntry>) {
+        if depth > 0
+            && let Some(ignore) = ignore.as_mut()
+        {
+            ignore.add_dir(dir);
+        }
+        let Ok(read) = std::fs::read_dir(dir) else { return };
+        let mut children: Vec<TreeEntry> = read
+            .filter_map(|e| e.ok())
+            .filter(|e| !ignore.as_ref().is_some_and(|i| i.is_ignored(&e.path(), e.file_type().is_ok_and(|t| t.is_dir()))))
+            .map(|e| TreeEntry { depth, frecency: frecency.score(&e.path()), ..TreeEntry::from_dir_entry(&e) })
+            .collect();
+        sort::sort_items(&mut children, self.dir_sort);
+        for mut child in children {
+            let open = child.is_dir && self.expanded.contains(&child.path);
+            let real = if open { std::fs::canonicalize(&child.path).ok() } else { None };
+            child.cycle = real.as_ref().is_some_and(|r| ancestors.contains(r));
+            let path = child.path.clone();
+            out.push(child);
+            if let Some(real) = real.filter(|r| !ancestors.contains(r)) {
+                ancestors.push(real);
+                self.push_children(&path, depth + 1, ignore, frecency, ancestors, out);
+                ancestors.pop();
+            }
+        }
+    }
+    fn refresh_git(&mut self) {
+        self.git = RepoStatus::load(&self.current_path);
+    }
+    // current_path 配下を再帰的に走査し、更新日時の新しい順に recent_limit 件。
+    // よく開くファイルは更新が古くても加える
+    fn recent_entries(&self, frecency: &Frecency) -> Vec<TreeEntry> {
+        const MAX_VISITED: usize = 50_000;
+        let mut files = Vec::new();
+        let mut stack = vec![self.current_path.clone()];
+        let mut visited = 0;
+        let mut ignore = Ignore::for_dir(&self.current_path);
+        while let Some(dir) = stack.pop() {
+            if dir != self.current_path { ignore.add_dir(&dir); }
+            let Ok(read) = std::fs::read_dir(&dir) else { continue };
+            for entry in read.filter_map(|e| e.ok()) {
+                visited += 1;
+                if visited > MAX_VISITED { break; }
+                // 隠しディレクトリ (.git など) とシンボリックリンクは辿らない
+                if entry.file_name().to_string_lossy().starts_with('.') { continue; }
+                let Ok(file_type) = entry.file_type() else { continue };
+                if !self.show_ignored && ignore.is_ignored(&entry.path(), file_type.is_dir()) { continue; }
+                if file_type.is_dir() {
+                    stack.push(entry.path());
+                } else if file_type.is_file() {
+                    files.push(TreeEntry::from_dir_entry(&entry));
+                }
+            }
+        }
+        files.sort_by_key(|e| std::cmp::Reverse(e.modified));
+        files.truncate(self.recent_limit);
+        for path in frecency.paths_under(&self.current_path) {
+            let known = files.iter().any(|e| std::fs::canonicalize(&e.path).is_ok_and(|p| p == path));
+            if !known && path.is_file() {
+                let meta = std::fs::metadata(&path).ok();
+                files.push(TreeEntry::new(path, meta));
+            }
+        }
+        files
+    }
+    fn toggle_ignored(&mut self) {
+        self.show_ignored = !self.show_ignored;
+        self.reload();
+    }
+    fn toggle_recent(&mut self) {
+        self.view = match self.view {
+            TreeView::Directory => TreeView::Recent,
+            TreeView::Recent => TreeView::Directory,
+        };
+        self.refresh();
+    }
+    fn sort_mode(&self) -> SortMode {
+        match self.view {
+            TreeView::Directory => self.dir_sort,
+            TreeView::Recent => self.recent_sort,
+        }
+    }
+    // 並び順を切り替え、選択中のエントリは選択したままにする
+    // 木はディレクトリごとに、絞り込み中は一致の良い順のまま並べ直すので読み直す
+    fn cycle_sort(&mut self) {
+        match self.view {
+            TreeView::Directory => self.dir_sort = self.dir_sort.next(),
+            TreeView::Recent => self.recent_sort = self.recent_sort.next(),
+        }
+        self.reload();
+    }
+    // Directory 表示での字下げと、ディレクトリの開閉の印
+    fn tree_prefix(&self, entry: &TreeEntry, ascii: bool) -> String {
+        if self.view == TreeView::Recent || !self.filter.is_empty() { return String::new(); }
+        let marker = match (entry.is_dir, self.expanded.contains(&entry.path)) {
+            (false, _) => "  ",
+            (true, true) => if ascii { "- " } else { "▾ " },
+            (true, false) => if ascii { "+ " } else { "▸ " },
+        };
+        format!("{}{}", "  ".repeat(entry.depth), marker)
+    }
+    // 一覧に表示する名前（Recent と絞り込み中はルートからの相対パス）
+    fn display_name(&self, entry: &TreeEntry) -> String {
+        match self.view {
+            TreeView::Directory if self.filter.is_empty() => entry.file_name().into_string().unwrap_or_default(),
+            _ => entry
+                .path
+                .strip_prefix(&self.current_path)
+                .unwrap_or(&entry.path)
+                .to_string_lossy()
+                .to_string(),
+        }
+    }
     fn move_up(&mut self) {
         if self.selected > 0 {
             self.selected -= 1;
@@ -74,6 +503,7 @@ impl FileTree {
             self.selected += 1;
         }
     }
+    // 選択中のディレクトリを一覧のルートにする
     fn enter(&mut self) {
         if self.entries.is_empty() {
             return;
@@ -85,10 +515,49 @@ impl FileTree {
             self.refresh();
         }
     }
+    // 親ディレクトリをルートにする。元のディレクトリは開いたまま選択して位置を失わないようにする
     fn go_up(&mut self) {
-        if let Some(parent) = self.current_path.parent() {
-            self.current_path = parent.to_path_buf();
+        if let Some(parent) = self.current_path.parent().map(Path::to_path_buf) {
+            let prev = std::mem::replace(&mut self.current_path, parent);
+            if self.view == TreeView::Directory { self.expanded.insert(prev.clone()); }
             self.refresh();
+            if let Some(pos) = self.entries.iter().position(|e| e.path == prev) {
+                self.selected = pos;
+            }
+        }
+    }
+    fn toggle_expand(&mut self) {
+        let Some(entry) = self.entries.get(self.selected).filter(|e| e.is_dir) else { return };
+        let path = entry.path.clone();
+        if !self.expanded.remove(&path) {
+            self.expanded.insert(path);
+        }
+        self.reload();
+    }
+    // Right: 閉じたディレクトリを開く。開いていれば最初の子へ
+    fn expand(&mut self) {
+        let Some(entry) = self.entries.get(self.selected).filter(|e| e.is_dir) else { return };
+        if self.expanded.insert(entry.path.clone()) {
+            self.reload();
+        } else if self.entries.get(self.selected + 1).is_some_and(|e| e.depth > entry.depth) {
+            self.selected += 1;
+        }
+    }
+    // Left: 開いたディレクトリを閉じる。そうでなければ親のディレクトリへ（一番上なら一覧のルートを上げる）
+    fn collapse(&mut self) {
+        let Some(entry) = self.entries.get(self.selected) else {
+            self.go_up();
+            return;
+        };
+        if self.view == TreeView::Directory && entry.is_dir && self.expanded.remove(&entry.path) {
+            self.reload();
+        } else if entry.depth > 0 && self.filter.is_empty() {
+            let depth = entry.depth;
+            if let Some(pos) = self.entries[..self.selected].iter().rposition(|e| e.depth < depth) {
+                self.selected = pos;
+            }
+        } else {
+            self.go_up();
         }
     }
     fn update_scroll(&mut self, visible: usize) {
@@ -100,21 +569,10 @@ impl FileTree {
     }
 }
 
-impl Clone for FileTree {
-    fn clone(&self) -> Self {
-        let mut ft = FileTree::new();
-        ft.current_path = self.current_path.clone();
-        ft.refresh();
-        ft.selected = self.selected;
-        ft.scroll_offset = self.scroll_offset;
-        ft
-    }
-}
-
 struct App {
     mode: Mode,
     // Editor state
-    lines: Vec<String>,
+    lines: Lines,
     cursor_x: usize,
     cursor_y: usize,
     scroll_offset: usize,
@@ -123,12 +581,26 @@ struct App {
     sel_start: Option<(usize, usize)>,
     sel_end: Option<(usize, usize)>,
     current_file: Option<PathBuf>,
+    modified: bool, // 未保存の変更あり
+    line_offset: isize, // 表示用の行番号オフセット（切り出した断片の元の行に合わせる）
+    soft_wrap: bool,    // 長い行を折り返して表示
+    wrap_width: usize,  // 直近に描画したテキスト欄の幅
+    view_height: usize, // 直近に描画したテキスト欄の高さ（PageUp/PageDown で動く行数）
+    show_whitespace: bool, // 空白・タブを記号で表示
+    overwrite: bool,       // 上書きモード（Insert キーで切り替え）
+    virtual_space: bool,   // 行末より右へもカーソルを進める（Alt+Insert で切り替え）
+    virtual_x: usize,      // 行末より右に進んだ桁数
     // Clipboard (system)
     clipboard_ctx: Option<ClipboardContext>,
+    clipboard: String, // 最後にコピーしたもの（システムのクリップボードが使えないときはこれを貼る）
+    kill_chain: bool,  // 直前のキーも行末までの切り取りだった（続けて切り取った分はつなげる）
     // Undo/Redo
     undo_stack: Vec<Vec<String>>,
     redo_stack: Vec<Vec<String>>,
     help_visible: bool,
+    quit: bool,            // メインループを抜けて終了する（端末は TermGuard が戻す）
+    exit_after_save: bool, // 終了の確認で保存を選び、保存先を聞いている
+    debug_overlay: bool, // F12: 計測結果の表示
     // FileTree state
     file_tree: FileTree,
     // ALT加速用
@@ -136,13 +608,58 @@ struct App {
     // ポップアップ用
     popup: Option<PopupMode>,
     popup_input: String,
+    popup_selected: usize, // リスト型ポップアップの選択行
+    finder: Finder,
+    grep: GrepState,
+    search_query: String,       // 直前のバッファ内検索
+    search_highlight: bool,     // search_query の一致を強調表示中
+    search_opts: SearchOptions, // 検索の大文字小文字・単語単位の設定（検索系で共通）
+    match_count: Option<MatchCount>,
+    results: ResultsPanel,
+    jumps: JumpList,
+    // 言語サーバーと補完ポップアップ
+    lsp: Lsp,
+    completion: Option<Completion>,
+    syntax: Option<Syntax>, // 構文木（対応言語のみ）
+    gutter: GitGutter,      // HEAD との差分の印
+    spell: Speller,         // スペルチェックの辞書
+    commit_panel: Option<CommitPanel>, // コミット画面（メッセージを書いている間も残す）
+    external_job: Option<ExternalJob>, // 実行中の外部コマンド（整形・パイプ・挿入）
+    task: TaskPanel,                   // タスクの出力パネル
+    task_job: Option<TaskJob>,         // 実行中のタスク
+    cursor_screen: Option<(u16, u16)>, // 前回描画したカーソルの画面位置（補完ポップアップの位置合わせ）
+    scrollbar_area: Option<Rect>,      // 前回描画したエディタのスクロールバー（クリックの判定）
+    scrollbar_grab: Option<usize>,     // つまみをつかんだ位置（つまみの上から何行目か）
+    extra_selections: Vec<(TextPos, TextPos)>, // Ctrl+D で足した選択（入力の後はカーソル）。空なら選択は1つ
+    hex: Option<HexView>,              // 16進表示・編集中（F8）
+    encoding: Encoding,                // 開いたときの文字コード（同じもので保存する）
+    read_only: bool,                   // UTF-8 として読めないファイルを読み取り専用で開いた
+    partial: Option<u64>,              // 大きなファイルの頭だけを読み取り専用で開いた（ファイル全体のバイト数）
+    file_indent: Option<Detected>,     // 開いたファイルの字下げから推定したインデント
+    md_preview: bool,                  // Markdown のプレビューを右側に出す（F9）
+    char_info: bool,                   // カーソル位置の文字の情報をステータスバーに出す（Alt+;）
+    open_history: Vec<PathBuf>,        // Ctrl+O で開いたファイル（新しい順）
+    // 設定・キーマップ
+    config: Config,
+    bindings: Vec<KeyBinding>,
+    vim: VimState,
+    // 保存キュー（バックグラウンド書き込み）とステータスバーのメッセージ
+    save_queue: Option<SaveQueue>,
+    loading: Option<Load>, // 読んでいる途中のファイル（バックグラウンド読み込み）
+    goto_after_load: Option<Goto>, // 読み終わったら移る位置
+    message: Option<String>,
+    notices: Notices, // 保存やファイル操作の結果の通知と履歴
+    // --deterministic: 時刻に依存する表示をせず、UI を ASCII のみにする
+    deterministic: bool,
+    perf: Perf,
+    dirty: bool, // 前の描画から画面に出すものが変わった（キー・端末の大きさ・バックグラウンドの結果）
 }
 
 impl Clone for App {
     fn clone(&self) -> Self {
         App {
             mode: self.mode.clone(),
-            lines: self.lines.clone(),
+            lines: self.lines.duplicate(),
             cursor_x: self.cursor_x,
             cursor_y: self.cursor_y,
             scroll_offset: self.scroll_offset,
@@ -151,23 +668,79 @@ impl Clone for App {
             sel_start: self.sel_start,
             sel_end: self.sel_end,
             current_file: self.current_file.clone(),
+            modified: self.modified,
+            line_offset: self.line_offset,
+            soft_wrap: self.soft_wrap,
+            show_whitespace: self.show_whitespace,
+            overwrite: self.overwrite,
+            virtual_space: self.virtual_space,
+            virtual_x: self.virtual_x,
+            wrap_width: self.wrap_width,
+            view_height: self.view_height,
             clipboard_ctx: None, // not cloned
+            clipboard: self.clipboard.clone(),
+            kill_chain: self.kill_chain,
             undo_stack: self.undo_stack.clone(),
             redo_stack: self.redo_stack.clone(),
             help_visible: self.help_visible,
+            quit: self.quit,
+            exit_after_save: self.exit_after_save,
+            debug_overlay: self.debug_overlay,
             file_tree: self.file_tree.clone(),
             alt_n: self.alt_n,
             popup: self.popup.clone(),
             popup_input: self.popup_input.clone(),
+            popup_selected: self.popup_selected,
+            finder: self.finder.clone(),
+            grep: self.grep.clone(),
+            search_query: self.search_query.clone(),
+            search_highlight: self.search_highlight,
+            search_opts: self.search_opts,
+            match_count: self.match_count.clone(),
+            results: self.results.clone(),
+            jumps: self.jumps.clone(),
+            lsp: Lsp::default(), // not cloned
+            completion: self.completion.clone(),
+            syntax: None, // not cloned
+            gutter: self.gutter.clone(),
+            spell: self.spell.clone(),
+            commit_panel: self.commit_panel.clone(),
+            external_job: None, // not cloned
+            task: self.task.clone(),
+            task_job: None, // not cloned
+            cursor_screen: self.cursor_screen,
+            scrollbar_area: self.scrollbar_area,
+            scrollbar_grab: self.scrollbar_grab,
+            extra_selections: self.extra_selections.clone(),
+            hex: self.hex.clone(),
+            encoding: self.encoding,
+            read_only: self.read_only,
+            partial: self.partial,
+            file_indent: self.file_indent,
+            md_preview: self.md_preview,
+            char_info: self.char_info,
+            open_history: self.open_history.clone(),
+            config: self.config.clone(),
+            bindings: self.bindings.clone(),
+            vim: self.vim.clone(),
+            save_queue: None, // not cloned
+            loading: None,    // not cloned
+            goto_after_load: self.goto_after_load.clone(),
+            message: self.message.clone(),
+            notices: self.notices.clone(),
+            deterministic: self.deterministic,
+            perf: self.perf.clone(),
+            dirty: self.dirty,
         }
     }
 }
 
 impl App {
     fn new() -> Self {
+        let config = Config::load();
         App {
             mode: Mode::Editor,
-            lines: vec![String::new()],
+            lines: Lines::new(),
             cursor_x: 0,
             cursor_y: 0,
             scroll_offset: 0,
@@ -176,29 +749,92 @@ impl App {
             sel_start: None,
             sel_end: None,
             current_file: None,
+            modified: false,
+            line_offset: 0,
+            soft_wrap: config.soft_wrap,
+            show_whitespace: config.show_whitespace,
+            overwrite: false,
+            virtual_space: config.virtual_space,
+            virtual_x: 0,
+            wrap_width: 80,
+            view_height: 24,
             clipboard_ctx: ClipboardContext::new().ok(),
+            clipboard: String::new(),
+            kill_chain: false,
             undo_stack: Vec::new(),
             redo_stack: Vec::new(),
             help_visible: false,
-            file_tree: FileTree::new(),
+            quit: false,
+            exit_after_save: false,
+            debug_overlay: false,
+            file_tree: {
+                let mut ft = FileTree::new();
+                ft.recent_limit = config.recent_limit;
+                ft
+            },
             alt_n: 8,
             popup: None,
             popup_input: String::new(),
+            popup_selected: 0,
+            finder: Finder::new(),
+            grep: GrepState::default(),
+            search_query: String::new(),
+            search_highlight: false,
+            search_opts: SearchOptions::default(),
+            match_count: None,
+            results: ResultsPanel::default(),
+            jumps: JumpList::default(),
+            lsp: Lsp::default(),
+            completion: None,
+            syntax: None,
+            gutter: GitGutter::default(),
+            spell: Speller::default(),
+            commit_panel: None,
+            external_job: None,
+            task: TaskPanel::default(),
+            task_job: None,
+            cursor_screen: None,
+            scrollbar_area: None,
+            scrollbar_grab: None,
+            extra_selections: Vec::new(),
+            hex: None,
+            encoding: Encoding::Utf8,
+            read_only: false,
+            partial: None,
+            file_indent: None,
+            md_preview: false,
+            char_info: false,
+            open_history: Vec::new(),
+            bindings: keymap::bindings(config.keymap, &config.keys),
+            perf: Perf::new(Duration::from_millis(config.frame_budget_ms), config.perf_log),
+            config,
+            vim: VimState::new(),
+            save_queue: Some(SaveQueue::new()),
+            loading: None,
+            goto_after_load: None,
+            message: None,
+            notices: Notices::default(),
+            deterministic: false,
+            dirty: true,
         }
     }
 
     // --- Editor operations ---
+    // 上書きモードでは選択がなければカーソルの1文字（書記素クラスタ）を置き換える。行末では足していく
     fn insert_char(&mut self, c: char) {
-        if self.sel_start.is_some() && self.sel_end.is_some() && self.sel_start != self.sel_end {
+        let selected = self.sel_start.is_some() && self.sel_end.is_some() && self.sel_start != self.sel_end;
+        if selected {
             self.delete_selection();
         }
         self.save_undo();
-        let line_len = self.lines[self.cursor_y].len();
-        if self.cursor_x > line_len {
-            self.cursor_x = line_len;
+        self.fill_virtual_space();
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        if self.overwrite && !selected {
+            let end = grapheme::next(&self.lines[self.cursor_y], self.cursor_x);
+            self.lines[self.cursor_y].replace_range(self.cursor_x..end, "");
         }
         self.lines[self.cursor_y].insert(self.cursor_x, c);
-        self.cursor_x += 1;
+        self.cursor_x += c.len_utf8();
         self.adjust_h_scroll(0);
     }
 
@@ -207,10 +843,7 @@ impl App {
             self.delete_selection();
         }
         self.save_undo();
-        let line_len = self.lines[self.cursor_y].len();
-        if self.cursor_x > line_len {
-            self.cursor_x = line_len;
-        }
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
         let tail = self.lines[self.cursor_y].split_off(self.cursor_x);
         self.cursor_y += 1;
         self.lines.insert(self.cursor_y, tail);
@@ -218,6 +851,60 @@ impl App {
         self.adjust_h_scroll(0);
     }
 
+    // Enter キー: Markdown のリスト中なら記号を引き継ぐ
+    fn enter_key(&mut self) {
+        if self.sel_start == self.sel_end && self.list_continuation_enabled() && self.continue_list() {
+            return;
+        }
+        if self.sel_start == self.sel_end && self.syntax_enter() {
+            return;
+        }
+        self.insert_newline();
+    }
+
+    fn list_continuation_enabled(&self) -> bool {
+        let ft = self.filetype();
+        self.config.filetype(ft).list_continuation.unwrap_or(ft == "markdown")
+    }
+
+    // リスト項目の行で改行したら次の記号を入れる。空の項目なら記号を消す。処理したら true
+    fn continue_list(&mut self) -> bool {
+        let line = &self.lines[self.cursor_y];
+        let indent_len = line.len() - line.trim_start().len();
+        let rest = &line[indent_len..];
+        let digits = rest.chars().take_while(|c| c.is_ascii_digit()).count();
+        let (marker_len, next_marker) = if rest.starts_with("- ") || rest.starts_with("* ") || rest.starts_with("+ ") {
+            let bullet = &rest[..2];
+            // タスクリスト "- [ ] " / "- [x] "
+            if rest[2..].starts_with("[ ] ") || rest[2..].starts_with("[x] ") || rest[2..].starts_with("[X] ") {
+                (6, format!("{}[ ] ", bullet))
+            } else {
+                (2, bullet.to_string())
+            }
+        } else if digits > 0 && (rest[digits..].starts_with(". ") || rest[digits..].starts_with(") ")) {
+            let n: u64 = rest[..digits].parse().unwrap_or(0);
+            (digits + 2, format!("{}{}", n + 1, &rest[digits..digits + 2]))
+        } else {
+            return false;
+        };
+        let content_start = indent_len + marker_len;
+        if self.cursor_x < content_start { return false; }
+        if line[content_start..].trim().is_empty() {
+            // 空の項目で Enter → 記号を消してリストを抜ける
+            self.save_undo();
+            self.lines[self.cursor_y].truncate(indent_len);
+            self.cursor_x = indent_len;
+            self.adjust_h_scroll(0);
+            return true;
+        }
+        let prefix = format!("{}{}", &line[..indent_len], next_marker);
+        self.insert_newline();
+        self.lines[self.cursor_y].insert_str(0, &prefix);
+        self.cursor_x = prefix.len();
+        self.adjust_h_scroll(0);
+        true
+    }
+
     fn backspace(&mut self) {
         if self.sel_start.is_some() && self.sel_end.is_some() && self.sel_start != self.sel_end {
             self.delete_selection();
@@ -226,8 +913,10 @@ impl App {
         if self.cursor_x == 0 && self.cursor_y == 0 { return; }
         self.save_undo();
         if self.cursor_x > 0 {
-            self.cursor_x -= 1;
-            self.lines[self.cursor_y].remove(self.cursor_x);
+            // 直前の見た目の1文字（書記素クラスタ）を消す
+            let end = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+            self.cursor_x = grapheme::prev(&self.lines[self.cursor_y], end);
+            self.lines[self.cursor_y].replace_range(self.cursor_x..end, "");
         } else if self.cursor_y > 0 {
             let current_line = self.lines.remove(self.cursor_y);
             self.cursor_y -= 1;
@@ -283,12 +972,53 @@ impl App {
         self.shift_selection = true;
     }
 
+    // Ctrl+E: カーソル行（選択があれば選択のかかる行）を改行まで選ぶ。行単位の選択ならもう1行下へ広げる
+    fn select_line(&mut self) {
+        let cursor = (self.cursor_y, self.cursor_x);
+        let (start, end) = match (self.sel_start, self.sel_end) {
+            (Some(s), Some(e)) => (s.min(e), s.max(e)),
+            _ => (cursor, cursor),
+        };
+        // 行単位の選択の終わりは次の行の頭なので、その行まで含めれば1行広がる
+        let last = self.lines.len() - 1;
+        self.sel_start = Some((start.0, 0));
+        self.sel_end = Some(if end.0 < last { (end.0 + 1, 0) } else { (last, self.lines[last].len()) });
+        (self.cursor_y, self.cursor_x) = self.sel_end.unwrap_or(cursor);
+        self.shift_selection = true;
+        self.adjust_h_scroll(0);
+    }
+
+    // カーソル行（選択があればかかる行すべて）を消し、次の行の同じ表示桁に置く
+    fn delete_lines(&mut self) {
+        let (first, last) = self.selected_lines().unwrap_or((self.cursor_y, self.cursor_y));
+        let col = grapheme::width(&self.lines[self.cursor_y], self.cursor_x);
+        self.save_undo();
+        self.lines.drain(first..=last);
+        if self.lines.is_empty() { self.lines.push(String::new()); }
+        self.selection_reset();
+        self.cursor_y = first.min(self.lines.len() - 1);
+        self.cursor_x = grapheme::at_width(&self.lines[self.cursor_y], col);
+        self.adjust_h_scroll(0);
+    }
+
     // --- Clipboard operations ---
     fn copy_selection(&mut self) {
         if let Some(text) = self.get_selected_text() {
-            if let Some(ctx) = self.clipboard_ctx.as_mut() {
-                let _ = ctx.set_contents(text);
-            }
+            self.set_clipboard(text);
+        }
+    }
+
+    fn set_clipboard(&mut self, text: String) {
+        if let Some(ctx) = self.clipboard_ctx.as_mut() {
+            let _ = ctx.set_contents(text.clone());
+        }
+        self.clipboard = text;
+    }
+
+    fn clipboard_text(&mut self) -> String {
+        match self.clipboard_ctx.as_mut().map(|ctx| ctx.get_contents()) {
+            Some(Ok(contents)) => contents,
+            _ => self.clipboard.clone(),
         }
     }
 
@@ -297,21 +1027,45 @@ impl App {
         self.delete_selection();
     }
 
+    // カーソルから行末までを切り取る（行末なら改行を切り取って次の行をつなげる）。
+    // 続けて押したときは切り取った分をクリップボードの後ろに足していく
+    fn kill_line(&mut self) {
+        let line_len = self.lines[self.cursor_y].len();
+        self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+        self.sel_start = Some((self.cursor_y, self.cursor_x));
+        if self.cursor_x < line_len {
+            self.sel_end = Some((self.cursor_y, line_len));
+        } else if self.cursor_y + 1 < self.lines.len() {
+            self.sel_end = Some((self.cursor_y + 1, 0));
+        } else {
+            self.selection_reset();
+            return;
+        }
+        let text = self.get_selected_text().unwrap_or_default();
+        let text = if self.kill_chain { self.clipboard_text() + &text } else { text };
+        self.set_clipboard(text);
+        self.delete_selection();
+        self.kill_chain = true;
+    }
+
     fn paste_clipboard(&mut self) {
-        if let Some(ctx) = self.clipboard_ctx.as_mut() {
-            if let Ok(contents) = ctx.get_contents() {
-                self.save_undo();
-                let mut lines_iter = contents.split('\n').peekable();
-                while let Some(text_part) = lines_iter.next() {
-                    let line_len = self.lines[self.cursor_y].len();
-                    if self.cursor_x > line_len { self.cursor_x = line_len; }
-                    self.lines[self.cursor_y].insert_str(self.cursor_x, text_part);
-                    self.cursor_x += text_part.len();
-                    if lines_iter.peek().is_some() { self.insert_newline(); }
-                }
-                self.adjust_h_scroll(0);
-            }
+        let contents = self.clipboard_text();
+        if !contents.is_empty() {
+            self.insert_text(&contents);
+        }
+    }
+
+    // 複数行テキストをカーソル位置に挿入
+    fn insert_text(&mut self, contents: &str) {
+        self.save_undo();
+        let mut lines_iter = contents.split('\n').peekable();
+        while let Some(text_part) = lines_iter.next() {
+            self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+            self.lines[self.cursor_y].insert_str(self.cursor_x, text_part);
+            self.cursor_x += text_part.len();
+            if lines_iter.peek().is_some() { self.insert_newline(); }
         }
+        self.adjust_h_scroll(0);
     }
 
     fn get_selected_text(&self) -> Option<String> {
@@ -338,24 +1092,31 @@ impl App {
 
     // --- Undo/Redo ---
     fn save_undo(&mut self) {
-        self.undo_stack.push(self.lines.clone());
+        self.undo_stack.push(self.lines.to_vec());
         self.redo_stack.clear();
+        self.modified = true;
     }
     fn undo(&mut self) {
         if let Some(prev) = self.undo_stack.pop() {
-            self.redo_stack.push(self.lines.clone());
-            self.lines = prev;
+            self.redo_stack.push(self.lines.to_vec());
+            *self.lines = prev;
+            self.modified = true;
             self.cursor_y = self.cursor_y.min(self.lines.len().saturating_sub(1));
-            self.cursor_x = self.cursor_x.min(self.lines[self.cursor_y].len());
+            self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+            // 選択の端は前の内容のバイト位置なので文字の途中に来ることがある
+            self.selection_reset();
             self.adjust_h_scroll(0);
         }
     }
     fn redo(&mut self) {
         if let Some(next) = self.redo_stack.pop() {
-            self.undo_stack.push(self.lines.clone());
-            self.lines = next;
+            self.undo_stack.push(self.lines.to_vec());
+            *self.lines = next;
+            self.modified = true;
             self.cursor_y = self.cursor_y.min(self.lines.len().saturating_sub(1));
-            self.cursor_x = self.cursor_x.min(self.lines[self.cursor_y].len());
+            self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], self.cursor_x);
+            // 選択の端は前の内容のバイト位置なので文字の途中に来ることがある
+            self.selection_reset();
             self.adjust_h_scroll(0);
         }
     }
@@ -363,10 +1124,7 @@ impl App {
     // --- Horizontal scroll (Editor) ---
     fn adjust_h_scroll(&mut self, available_width: usize) {
         let avail = if available_width == 0 { 80 } else { available_width };
-        let line = &self.lines[self.cursor_y];
-        let graphemes: Vec<&str> = line.graphemes(true).collect();
-        let current_width: usize = graphemes[..self.cursor_x.min(graphemes.len())]
-            .iter().map(|g| g.width()).sum();
+        let current_width = grapheme::width(&self.lines[self.cursor_y], self.cursor_x) + self.virtual_cols();
         if current_width < self.h_scroll_offset {
             self.h_scroll_offset = current_width;
         } else if current_width >= self.h_scroll_offset + avail {
@@ -375,24 +1133,37 @@ impl App {
     }
 
     // --- Cursor movement (Editor) ---
-    fn handle_arrow_key(&mut self, code: KeyCode) {
+    fn handle_arrow_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
         let old = (self.cursor_y, self.cursor_x);
+        let ctrl = modifiers.contains(KeyModifiers::CONTROL);
+        let alt = modifiers.contains(KeyModifiers::ALT);
         match code {
+            KeyCode::Left if self.virtual_cols() > 0 => self.virtual_x -= 1,
+            KeyCode::Right if self.can_enter_virtual_space() => self.virtual_x += 1,
             KeyCode::Left => self.move_left(),
             KeyCode::Right => self.move_right(),
+            KeyCode::Up if self.soft_wrap => self.move_visual_up(),
+            KeyCode::Down if self.soft_wrap => self.move_visual_down(),
             KeyCode::Up => self.move_up(),
             KeyCode::Down => self.move_down(),
+            KeyCode::Home if ctrl => self.move_document(false),
+            KeyCode::End if ctrl => self.move_document(true),
+            KeyCode::Home => self.move_smart_home(),
+            KeyCode::End => self.move_line_end(),
+            KeyCode::PageUp if alt => self.move_half_page(false),
+            KeyCode::PageDown if alt => self.move_half_page(true),
+            KeyCode::PageUp => self.move_page(false),
+            KeyCode::PageDown => self.move_page(true),
             _ => {}
         }
         if self.shift_selection {
-            if self.sel_start.is_none() { self.sel_start = Some(old); }
-            self.sel_end = Some((self.cursor_y, self.cursor_x));
+            self.update_selection(old);
         }
         self.adjust_h_scroll(0);
     }
     fn move_left(&mut self) {
         if self.cursor_x > 0 {
-            self.cursor_x -= 1;
+            self.cursor_x = grapheme::prev(&self.lines[self.cursor_y], self.cursor_x);
         } else if self.cursor_y > 0 {
             self.cursor_y -= 1;
             self.cursor_x = self.lines[self.cursor_y].len();
@@ -401,7 +1172,7 @@ impl App {
     fn move_right(&mut self) {
         let line_len = self.lines[self.cursor_y].len();
         if self.cursor_x < line_len {
-            self.cursor_x += 1;
+            self.cursor_x = grapheme::next(&self.lines[self.cursor_y], self.cursor_x);
         } else if self.cursor_y + 1 < self.lines.len() {
             self.cursor_y += 1;
             self.cursor_x = 0;
@@ -409,17 +1180,62 @@ impl App {
     }
     fn move_up(&mut self) {
         if self.cursor_y > 0 {
-            self.cursor_y -= 1;
-            let line_len = self.lines[self.cursor_y].len();
-            self.cursor_x = self.cursor_x.min(line_len);
+            self.move_to_line(self.cursor_y - 1);
         }
     }
     fn move_down(&mut self) {
         if self.cursor_y + 1 < self.lines.len() {
-            self.cursor_y += 1;
-            let line_len = self.lines[self.cursor_y].len();
-            self.cursor_x = self.cursor_x.min(line_len);
+            self.move_to_line(self.cursor_y + 1);
+        }
+    }
+    fn move_page(&mut self, down: bool) {
+        self.move_rows(self.view_height.max(1), down);
+    }
+    // Alt+PgUp / Alt+PgDn（vim では Ctrl+U / Ctrl+D）
+    pub fn move_half_page(&mut self, down: bool) {
+        self.move_rows((self.view_height / 2).max(1), down);
+    }
+    // n 行上下に動かし、表示も同じだけ送る。先頭・末尾の行ならその行の端へ
+    fn move_rows(&mut self, n: usize, down: bool) {
+        let last = self.lines.len() - 1;
+        let y = if down { (self.cursor_y + n).min(last) } else { self.cursor_y.saturating_sub(n) };
+        if y == self.cursor_y {
+            self.cursor_x = if down { self.lines[y].len() } else { 0 };
+            return;
+        }
+        self.scroll_offset = if down {
+            (self.scroll_offset + n).min(self.lines.len().saturating_sub(self.view_height.max(1))).max(self.scroll_offset)
+        } else {
+            self.scroll_offset.saturating_sub(n)
+        };
+        self.move_to_line(y);
+    }
+    // Ctrl+Home / Ctrl+End: バッファの先頭・末尾へ。表示は描画のときにカーソルへ付いてくる
+    fn move_document(&mut self, end: bool) {
+        self.record_jump();
+        self.cursor_y = if end { self.lines.len() - 1 } else { 0 };
+        self.cursor_x = if end { self.lines[self.cursor_y].len() } else { 0 };
+    }
+    // 別の行へ移るときは同じ表示桁（全角や結合文字があってもバイト位置ではなく見た目で揃える）
+    fn move_to_line(&mut self, y: usize) {
+        let col = grapheme::width(&self.lines[self.cursor_y], self.cursor_x) + self.virtual_cols();
+        self.cursor_y = y;
+        self.cursor_x = grapheme::at_width(&self.lines[y], col);
+        self.virtual_reach(col);
+    }
+    // Ctrl+Backspace / Ctrl+Delete: 単語の移動と同じ区切りまで消す（選択があれば選択を消す）
+    fn delete_word(&mut self, forward: bool) {
+        if self.sel_start.is_some() && self.sel_end.is_some() && self.sel_start != self.sel_end {
+            self.delete_selection();
+            return;
         }
+        let from = (self.cursor_y, self.cursor_x);
+        if forward { self.move_word_right() } else { self.move_word_left() }
+        let to = (self.cursor_y, self.cursor_x);
+        if from == to { return; }
+        self.sel_start = Some(from);
+        self.sel_end = Some(to);
+        self.delete_selection();
     }
     fn move_word_left(&mut self) {
         if self.cursor_x == 0 && self.cursor_y == 0 { return; }
@@ -429,13 +1245,13 @@ impl App {
             return;
         }
         let line = &self.lines[self.cursor_y];
-        let mut idx = self.cursor_x;
+        let mut idx = grapheme::index(line, self.cursor_x);
         let graphemes: Vec<&str> = line.graphemes(true).collect();
         while idx > 0 {
             idx -= 1;
             if graphemes[idx] == " " || graphemes[idx] == "\t" { break; }
         }
-        self.cursor_x = idx;
+        self.cursor_x = grapheme::byte(line, idx);
     }
     fn move_word_right(&mut self) {
         let line_len = self.lines[self.cursor_y].len();
@@ -447,7 +1263,7 @@ impl App {
         }
         let line = &self.lines[self.cursor_y];
         let graphemes: Vec<&str> = line.graphemes(true).collect();
-        let mut idx = self.cursor_x;
+        let mut idx = grapheme::index(line, self.cursor_x);
         while idx < graphemes.len() {
             idx += 1;
             if idx >= graphemes.len() { break; }
@@ -456,7 +1272,19 @@ impl App {
                 break;
             }
         }
-        self.cursor_x = idx.min(line_len);
+        self.cursor_x = grapheme::byte(line, idx);
+    }
+    fn move_line_start(&mut self) {
+        self.cursor_x = 0;
+    }
+    // Home: 行頭の空白の後ろへ。すでにそこにいれば行頭へ（押すたびに行き来する）
+    fn move_smart_home(&mut self) {
+        let line = &self.lines[self.cursor_y];
+        let first = line.len() - line.trim_start().len();
+        self.cursor_x = if self.cursor_x == first { 0 } else { first };
+    }
+    fn move_line_end(&mut self) {
+        self.cursor_x = self.lines[self.cursor_y].len();
     }
     fn move_alt_left(&mut self) {
         for _ in 0..self.alt_n { self.move_left(); }
@@ -467,6 +1295,103 @@ impl App {
         self.alt_n = (self.alt_n * 2).min(1024);
     }
 
+    // --- Key bindings ---
+    fn run_action(&mut self, action: Action) {
+        self.perf.annotate(&format!("{:?}", action));
+        logging::debug("action", &[("action", &format_args!("{:?}", action))]);
+        match action {
+            Action::Save => self.save_file(),
+            Action::ScrollUp => self.scroll_up(),
+            Action::ScrollDown => self.scroll_down(),
+            Action::CenterCursor => self.center_cursor(),
+            Action::Search => self.open_search(),
+            Action::SearchNext => self.search_next(true),
+            Action::SearchPrev => self.search_next(false),
+            Action::ClearHighlight => self.search_highlight = false,
+            Action::JumpBack => self.jump_back(),
+            Action::JumpForward => self.jump_forward(),
+            Action::Complete => self.request_completion(true),
+            Action::GotoDefinition => self.goto_definition(),
+            Action::RenameSymbol => self.open_rename_symbol(),
+            Action::FormatDocument => match self.external_formatter() {
+                Some(command) => self.run_formatter(command),
+                None => self.format_document(),
+            },
+            Action::ExpandSelection => self.expand_selection(),
+            Action::ShrinkSelection => self.shrink_selection(),
+            Action::NextHunk => self.goto_hunk(true),
+            Action::PrevHunk => self.goto_hunk(false),
+            Action::GitCommit => self.git_commit_key(),
+            Action::DiffWithDisk => self.show_disk_diff(),
+            Action::PipeSelection => self.open_pipe_prompt(),
+            Action::InsertCommandOutput => self.open_insert_command_prompt(),
+            Action::RunTask => self.open_task_list(),
+            Action::ToggleTaskPanel => self.toggle_task_panel(),
+            Action::SpellingSuggestions => self.spelling_suggestions(),
+            Action::GotoFile => self.goto_file(),
+            Action::OpenUrl => self.open_url(),
+            Action::ToggleHex => self.toggle_hex(),
+            Action::ToggleMarkdownPreview => self.toggle_markdown_preview(),
+            Action::FormatJson => self.format_json(),
+            Action::TransformSelection => self.open_transforms(),
+            Action::LineCommands => self.open_line_commands(),
+            Action::Reflow => self.reflow_paragraph(),
+            Action::InsertUnicode => self.open_unicode_input(),
+            Action::ToggleCharInfo => self.char_info = !self.char_info,
+            Action::ShowMessages => self.show_notices(),
+            Action::OpenFilePrompt => self.open_file_prompt(),
+            Action::SelectInsideBrackets => self.select_text_object(TextObject::Bracket(None)),
+            Action::SelectInsideQuotes => self.select_text_object(TextObject::Quote(None)),
+            Action::SelectWord => self.select_text_object(TextObject::Word),
+            Action::SelectNextOccurrence => self.select_next_occurrence(),
+            Action::SelectParagraph => self.select_text_object(TextObject::Paragraph),
+            Action::Copy => self.copy_selection(),
+            Action::Cut => self.cut_selection(),
+            Action::Paste => self.paste_clipboard(),
+            Action::SelectAll => self.select_all(),
+            Action::SelectLine => self.select_line(),
+            Action::DeleteLine => self.delete_lines(),
+            Action::OpenLineBelow => self.open_line(false),
+            Action::OpenLineAbove => self.open_line(true),
+            Action::Undo => self.undo(),
+            Action::Redo => self.redo(),
+            Action::NewFile => self.open_new_file_prompt(""),
+            Action::Rename => self.open_prompt(Prompt::text("Rename/Move: Enter new name", App::rename_accept), ""),
+            Action::Delete => self.backspace(),
+            Action::WordLeft => self.move_word_left(),
+            Action::WordRight => self.move_word_right(),
+            Action::DeleteWordLeft => self.delete_word(false),
+            Action::DeleteWordRight => self.delete_word(true),
+            Action::AltLeft => self.move_alt_left(),
+            Action::AltRight => self.move_alt_right(),
+            Action::LineStart => self.move_line_start(),
+            Action::LineEnd => self.move_line_end(),
+            Action::KillLine => self.kill_line(),
+            Action::Reload => self.reload_file(),
+            Action::ToggleWrap => self.toggle_soft_wrap(),
+            Action::ToggleWhitespace => self.show_whitespace = !self.show_whitespace,
+            Action::ToggleOverwrite => self.overwrite = !self.overwrite,
+            Action::ToggleVirtualSpace => self.toggle_virtual_space(),
+            Action::NextHeading => self.next_heading(),
+            Action::PrevHeading => self.prev_heading(),
+            Action::Outline => self.open_outline(),
+            Action::FindFile => self.open_finder(),
+            Action::GrepProject => self.open_grep(),
+            Action::ToggleResults => self.toggle_results(),
+            Action::LineOffset => {
+                let prompt = Prompt::text("Line Offset: Enter number added to displayed line numbers", App::line_offset_accept).check(|app| {
+                    let input = app.popup_input.trim();
+                    match input.is_empty() || input.trim_start_matches('+').parse::<isize>().is_ok() {
+                        true => Ok(()),
+                        false => Err(format!("not a number: {}", input)),
+                    }
+                });
+                let offset = self.line_offset.to_string();
+                self.open_prompt(prompt, &offset);
+            }
+        }
+    }
+
     // --- Scrolling ---
     fn scroll_up(&mut self) {
         if self.scroll_offset > 0 { self.scroll_offset -= 1; }
@@ -474,173 +1399,970 @@ impl App {
     fn scroll_down(&mut self) {
         if self.scroll_offset < self.lines.len().saturating_sub(1) { self.scroll_offset += 1; }
     }
+    // Ctrl+L: カーソルの行が画面の真ん中に来るように送る
+    fn center_cursor(&mut self) {
+        self.scroll_offset = self.cursor_y.saturating_sub(self.view_height / 2);
+    }
+    // カーソルの上下に見せておく行数（画面が低ければ半分まで）
+    fn scroll_margin(&self, visible_height: usize) -> usize {
+        self.config.scrolloff.min(visible_height.saturating_sub(1) / 2)
+    }
     fn adjust_scroll(&mut self, visible_height: usize) {
-        if self.cursor_y < self.scroll_offset {
-            self.scroll_offset = self.cursor_y;
-        } else if self.cursor_y >= self.scroll_offset + visible_height {
-            self.scroll_offset = self.cursor_y.saturating_sub(visible_height - 1);
+        let margin = self.scroll_margin(visible_height);
+        // 下はファイルの末尾より先まで空けない
+        let below = margin.min(self.lines.len().saturating_sub(self.cursor_y + 1));
+        if self.cursor_y < self.scroll_offset + margin {
+            self.scroll_offset = self.cursor_y.saturating_sub(margin);
+        } else if self.cursor_y + below >= self.scroll_offset + visible_height {
+            self.scroll_offset = (self.cursor_y + below + 1).saturating_sub(visible_height);
         }
     }
+    // スクロールバーのつまみ（--deterministic では ASCII）
+    fn scrollbar_thumb(&self) -> &'static str {
+        if self.deterministic { "#" } else { "█" }
+    }
     fn line_number_width(&self) -> usize {
-        let total = self.lines.len();
-        format!("{}", total).len().max(2)
+        let first = self.display_line_number(0);
+        let last = self.display_line_number(self.lines.len().saturating_sub(1));
+        format!("{}", first).len().max(format!("{}", last).len()).max(2)
+    }
+    // 画面に表示する行番号（line_offset を加味）
+    fn display_line_number(&self, row: usize) -> isize {
+        row as isize + 1 + self.line_offset
     }
 
-    // --- Search & Save ---
-    fn search(&mut self) {
-        let mut query = String::new();
-        loop {
-            if let Event::Key(KeyEvent { code, .. }) = read().unwrap() {
-                match code {
-                    KeyCode::Enter => break,
-                    KeyCode::Esc => { query.clear(); break; },
-                    KeyCode::Backspace => { query.pop(); },
-                    KeyCode::Char(c) => { query.push(c); },
-                    _ => {}
-                }
-            }
+    // --- Filetype ---
+    fn filetype(&self) -> &'static str {
+        match self.current_file {
+            Some(ref path) => filetype::detect(path),
+            None => "text",
         }
-        if query.is_empty() { return; }
-        let mut found = false;
-        for (i, line) in self.lines.iter().enumerate().skip(self.cursor_y) {
-            if let Some(pos) = line.find(&query) {
-                self.cursor_y = i;
-                self.cursor_x = pos;
-                found = true;
-                break;
-            }
+    }
+
+    // --- Outline ---
+    fn headings(&self) -> Vec<outline::Heading> {
+        outline::headings(&self.lines, self.filetype())
+    }
+    fn next_heading(&mut self) {
+        if let Some(h) = self.headings().into_iter().find(|h| h.line > self.cursor_y) {
+            self.cursor_y = h.line;
+            self.cursor_x = 0;
         }
-        if !found {
-            for (i, line) in self.lines.iter().enumerate().take(self.cursor_y) {
-                if let Some(pos) = line.find(&query) {
-                    self.cursor_y = i;
-                    self.cursor_x = pos;
-                    break;
-                }
-            }
+    }
+    fn prev_heading(&mut self) {
+        if let Some(h) = self.headings().into_iter().rev().find(|h| h.line < self.cursor_y) {
+            self.cursor_y = h.line;
+            self.cursor_x = 0;
         }
-        self.adjust_h_scroll(0);
     }
-    fn save_file(&mut self) {
-        let content = self.lines.join("\n");
-        if let Some(ref path) = self.current_file {
-            let _ = std::fs::write(path, content);
-        } else {
-            self.popup = Some(PopupMode::SaveFile);
-            self.popup_input = String::from("output.txt");
+    fn open_outline(&mut self) {
+        self.open_prompt(Prompt::list("Outline: type to filter, Enter to jump", App::outline_items, App::outline_accept), "");
+        // 現在位置の見出しを選択しておく
+        self.popup_selected = self.headings().iter().rposition(|h| h.line <= self.cursor_y).unwrap_or(0);
+    }
+    fn outline_items(&self) -> Vec<String> {
+        self.outline_matches()
+            .iter()
+            .map(|h| format!("{}{} (line {})", "  ".repeat(h.level - 1), h.title, self.display_line_number(h.line)))
+            .collect()
+    }
+    fn outline_accept(&mut self) {
+        let matches = self.outline_matches();
+        if let Some(h) = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))).cloned() {
+            self.record_jump();
+            self.cursor_y = h.line;
+            self.cursor_x = 0;
+            self.selection_reset();
         }
     }
-    fn exit_prompt(&mut self) -> Option<String> {
-        self.popup = Some(PopupMode::ExitPrompt);
-        self.popup_input.clear();
-        None
+    // 入力で絞り込んだ見出し（大文字小文字は区別しない）
+    fn outline_matches(&self) -> Vec<outline::Heading> {
+        let query = self.popup_input.to_lowercase();
+        self.headings().into_iter().filter(|h| h.title.to_lowercase().contains(&query)).collect()
     }
 
-    // --- Popup handling ---
-    fn handle_popup(&mut self, key: KeyCode) {
-        match key {
-            KeyCode::Enter => {
-                match self.popup.clone().unwrap() {
-                    PopupMode::ExitPrompt => {
-                        let choice = self.popup_input.trim().to_lowercase();
-                        self.popup = None;
-                        match choice.as_str() {
-                            "e" | "exit" => std::process::exit(0),
-                            "s" | "save" => { self.save_file(); },
-                            "c" | "cancel" => {},
-                            _ => {},
-                        }
-                        self.popup_input.clear();
-                    }
-                    PopupMode::NewFile => {
-                        let filename = self.popup_input.trim();
-                        if !filename.is_empty() {
-                            if let Some(parent) = PathBuf::from(filename).parent() {
-                                let _ = std::fs::create_dir_all(parent);
-                            }
-                            let _ = std::fs::write(filename, "");
-                            self.current_file = Some(PathBuf::from(filename));
-                            self.lines = vec![String::new()];
-                        }
-                        self.popup = None;
-                        self.popup_input.clear();
-                    }
-                    PopupMode::Rename => {
-                        let newname = self.popup_input.trim();
-                        if !newname.is_empty() {
-                            if let Some(ref old) = self.current_file {
-                                if let Ok(_) = std::fs::rename(old, newname) {
-                                    self.current_file = Some(PathBuf::from(newname));
-                                    if let Some(parent) = PathBuf::from(newname).parent() {
-                                        self.file_tree.current_path = parent.to_path_buf();
-                                        self.file_tree.refresh();
-                                        if let Some(pos) = self.file_tree.entries.iter().position(|e| e.path() == PathBuf::from(newname)) {
-                                            self.file_tree.selected = pos;
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                        self.popup = None;
-                        self.popup_input.clear();
+    // --- Commit message ---
+    // git のコミットメッセージ編集用ファイルか
+    fn is_commit_message(&self) -> bool {
+        self.filetype() == "gitcommit"
+    }
+    // 行ごとの桁ガイド（表示桁、0始まり）。設定の color_column に加えて、
+    // コミットメッセージでは件名に50桁、本文に72桁を引く
+    fn guide_columns(&self, row: usize) -> Vec<usize> {
+        let cols = self.config.filetype(self.filetype()).color_columns.unwrap_or_else(|| self.config.color_columns.clone());
+        let mut guides: Vec<usize> = cols.iter().map(|c| c - 1).collect();
+        if let Some(limit) = self.column_limit(row) {
+            guides.push(limit);
+            guides.push(72);
+        }
+        guides.sort_unstable();
+        guides.dedup();
+        guides
+    }
+    // これを超えた部分を色付けする文字数の上限（コミットメッセージのみ、コメント行は除く）
+    fn column_limit(&self, row: usize) -> Option<usize> {
+        if !self.is_commit_message() || self.lines[row].starts_with('#') {
+            return None;
+        }
+        Some(if row == 0 { 50 } else { 72 })
+    }
+    // ステータスバー用: 件名の文字数と警告
+    fn commit_message_status(&self) -> Option<String> {
+        if !self.is_commit_message() { return None; }
+        let len = self.lines[0].chars().count();
+        let mut status = format!("Subject {}/50", len);
+        if len > 72 {
+            status.push_str(" (too long!)");
+        } else if len > 50 {
+            status.push_str(" (long)");
+        }
+        if self.lines.len() > 1 && !self.lines[1].is_empty() && !self.lines[1].starts_with('#') {
+            status.push_str(" | line 2 should be blank");
+        }
+        Some(status)
+    }
+
+    // --- Bracket matching ---
+    // カーソル位置（なければ直前）の括弧と、その相方の位置 (行, バイト位置)
+    fn find_matching_bracket(&self) -> Option<(TextPos, Option<TextPos>)> {
+        const MAX_LINES: usize = 10_000;
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        let at = |x: usize| line.get(x..).and_then(|t| t.chars().next());
+        let before = line.get(..x).and_then(|t| t.char_indices().next_back());
+        let (bx, ch) = match at(x) {
+            Some(c) if "()[]{}".contains(c) => (x, c),
+            _ => match before {
+                Some((i, c)) if "()[]{}".contains(c) => (i, c),
+                _ => return None,
+            },
+        };
+        let (open, close, forward) = match ch {
+            '(' => ('(', ')', true),
+            '[' => ('[', ']', true),
+            '{' => ('{', '}', true),
+            ')' => ('(', ')', false),
+            ']' => ('[', ']', false),
+            _ => ('{', '}', false),
+        };
+        let pos = (self.cursor_y, bx);
+        let mut depth = 0usize;
+        if forward {
+            let last = (self.cursor_y + MAX_LINES).min(self.lines.len());
+            for y in self.cursor_y..last {
+                let from = if y == self.cursor_y { bx } else { 0 };
+                for (i, c) in self.lines[y][from..].char_indices() {
+                    if c == open { depth += 1; }
+                    if c == close {
+                        depth -= 1;
+                        if depth == 0 { return Some((pos, Some((y, from + i)))); }
                     }
-                    PopupMode::SaveFile => {
-                        let filename = self.popup_input.trim();
-                        if !filename.is_empty() {
-                            self.current_file = Some(PathBuf::from(filename));
-                            let content = self.lines.join("\n");
-                            let _ = std::fs::write(filename, content);
-                        }
-                        self.popup = None;
-                        self.popup_input.clear();
+                }
+            }
+        } else {
+            let first = self.cursor_y.saturating_sub(MAX_LINES);
+            for y in (first..=self.cursor_y).rev() {
+                let to = if y == self.cursor_y { bx + 1 } else { self.lines[y].len() };
+                for (i, c) in self.lines[y][..to].char_indices().rev() {
+                    if c == close { depth += 1; }
+                    if c == open {
+                        depth -= 1;
+                        if depth == 0 { return Some((pos, Some((y, i)))); }
                     }
                 }
             }
-            KeyCode::Esc => { self.popup = None; self.popup_input.clear(); }
-            KeyCode::Backspace => { self.popup_input.pop(); }
-            KeyCode::Char(c) => { self.popup_input.push(c); }
-            _ => {}
         }
+        Some((pos, None))
     }
 
-    // --- FileTree mode operations ---
-    fn file_tree_move_up(&mut self) {
-        self.file_tree.move_up();
+    // --- Search & Save ---
+    // --- Search ---
+    fn open_search(&mut self) {
+        self.popup = Some(PopupMode::Search);
+        self.popup_input = self.search_query.clone();
     }
-    fn file_tree_move_down(&mut self) {
-        self.file_tree.move_down();
+    // すべての一致を結果パネルに残し、カーソル行以降の最初の一致へ移動する
+    fn run_search(&mut self, query: String) {
+        self.search_query = query.clone();
+        self.search_highlight = !query.is_empty();
+        if query.is_empty() { return; }
+        let matcher = Matcher::new(&query, self.search_opts);
+        let items: Vec<ResultItem> = self
+            .lines
+            .iter()
+            .enumerate()
+            .flat_map(|(i, line)| matcher.find_all(line).into_iter().map(move |(pos, _)| (i, pos, line)))
+            .take(10_000)
+            .map(|(i, pos, line)| ResultItem { path: self.current_file.clone(), line: i, col: pos, text: line.clone() })
+            .collect();
+        if items.is_empty() {
+            self.message = Some(format!("Not found: {}", query));
+            return;
+        }
+        let selected = items.iter().position(|m| m.line >= self.cursor_y).unwrap_or(0);
+        self.record_jump();
+        self.cursor_y = items[selected].line;
+        self.cursor_x = items[selected].col;
+        self.set_results(format!("Search: {} {}", query, self.search_opts.label()), items, selected);
+        self.adjust_h_scroll(0);
     }
-    fn file_tree_enter(&mut self) {
-        if self.file_tree.entries.is_empty() { return; }
-        let entry = &self.file_tree.entries[self.file_tree.selected];
-        let path = entry.path();
-        if path.is_dir() {
-            self.file_tree.enter();
+    // 直前の検索語の次（前）の一致へ。端まで行ったら反対側から探す
+    fn search_next(&mut self, forward: bool) {
+        if self.search_query.is_empty() {
+            self.message = Some("No previous search".to_string());
+            return;
+        }
+        let matcher = Matcher::new(&self.search_query, self.search_opts);
+        let n = self.lines.len();
+        // k = 0 はカーソル行のカーソルより先、k = n は一周してカーソル行の残り
+        let mut found = None;
+        for k in 0..=n {
+            let y = if forward { (self.cursor_y + k) % n } else { (self.cursor_y + n - k % n) % n };
+            let mut starts = matcher.find_all(&self.lines[y]).into_iter().map(|m| m.0);
+            let ok = |x: usize| match k {
+                0 if forward => x > self.cursor_x,
+                0 => x < self.cursor_x,
+                _ => true,
+            };
+            found = if forward { starts.find(|&x| ok(x)) } else { starts.rfind(|&x| ok(x)) }.map(|x| (y, x));
+            if found.is_some() { break; }
+        }
+        match found {
+            Some((y, x)) => {
+                self.record_jump();
+                self.cursor_y = y;
+                self.cursor_x = x;
+                self.selection_reset();
+                self.search_highlight = true;
+                self.adjust_h_scroll(0);
+            }
+            None => self.message = Some(format!("Not found: {}", self.search_query)),
+        }
+    }
+    // 件数を出す検索語。入力中は入力中の語
+    fn counted_query(&self) -> Option<&String> {
+        match self.popup {
+            Some(PopupMode::Search) => Some(&self.popup_input),
+            _ if self.search_highlight => Some(&self.search_query),
+            _ => None,
+        }
+        .filter(|query| !query.is_empty())
+    }
+    // 描画の前に一致を数え直す（検索語か設定か内容が変わったときだけ）
+    fn sync_match_count(&mut self) {
+        let Some(query) = self.counted_query().cloned() else {
+            self.match_count = None;
+            return;
+        };
+        let generation = self.lines.generation();
+        if self.match_count.as_ref().is_some_and(|c| c.query == query && c.opts == self.search_opts && c.generation == generation) {
+            return;
+        }
+        let matcher = Matcher::new(&query, self.search_opts);
+        let starts = self
+            .lines
+            .iter()
+            .enumerate()
+            .flat_map(|(y, line)| matcher.find_all(line).into_iter().map(move |(start, _)| (y, start)))
+            .collect();
+        self.match_count = Some(MatchCount { query, opts: self.search_opts, generation, starts });
+    }
+    // ステータスバーの「match 3 of 17」。カーソルが一致上になければ件数だけ
+    fn match_counter(&self) -> Option<String> {
+        // 数えたあとで検索語や内容が変わっていれば次の描画まで出さない
+        let count = self.match_count.as_ref().filter(|c| {
+            self.counted_query() == Some(&c.query) && c.opts == self.search_opts && c.generation == self.lines.generation()
+        })?;
+        let total = count.starts.len();
+        let current = count.starts.binary_search(&(self.cursor_y, self.cursor_x)).ok().map(|i| i + 1);
+        Some(match current {
+            Some(i) => format!("match {} of {}", i, total),
+            None if total == 1 => "1 match".to_string(),
+            None => format!("{} matches", total),
+        })
+    }
+    // 保存する中身。16進表示中はバイト列、それ以外は開いたときの文字コードで
+    fn buffer_bytes(&self) -> Result<Vec<u8>, String> {
+        if let Some(total) = self.partial {
+            return Err(format!("Read-only: only the beginning of this {} file is loaded", format_size(total)));
+        }
+        if let Some(ref hex) = self.hex {
+            return Ok(hex.bytes.clone());
+        }
+        if self.read_only {
+            return Err("Read-only: the file is not UTF-8 text (F8 edits its bytes)".to_string());
+        }
+        self.encoding.encode(&self.lines.join("\n"))
+    }
+    fn save_file(&mut self) {
+        let content = match self.buffer_bytes() {
+            Ok(content) => content,
+            Err(e) => {
+                self.notify_err(e);
+                return;
+            }
+        };
+        if let Some(path) = self.current_file.clone() {
+            self.enqueue_save(path, content);
+        } else {
+            self.open_save_as("output.txt");
+        }
+    }
+    // 名前を付けて保存。同じ内容のファイルなら書かず、別内容なら差分を見せて確認する
+    fn save_as(&mut self, path: PathBuf) {
+        let content = match self.buffer_bytes() {
+            Ok(content) => content,
+            Err(e) => {
+                self.notify_err(e);
+                return;
+            }
+        };
+        let existing = match std::fs::read(&path) {
+            Ok(existing) => existing,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                self.current_file = Some(path.clone());
+                self.enqueue_save(path, content);
+                return;
+            }
+            // あるのに読めないファイルは比べられないので上書きしない
+            Err(e) => {
+                self.notify_err(format!("Not saved: cannot read the existing {}: {}", path.display(), e));
+                return;
+            }
+        };
+        // 同じかどうかは書き込むバイト列で比べる（改行や文字コードが違えば別内容）
+        if existing == content {
+            self.current_file = Some(path.clone());
+            self.modified = false;
+            self.message = Some(format!("No changes to write: {} has the same contents", path.display()));
+            return;
+        }
+        // 見せる差分はテキスト同士なら行で。バイナリや 16進表示は大きさだけ
+        let old: Option<Vec<String>> = std::str::from_utf8(&existing)
+            .ok()
+            .filter(|_| self.hex.is_none())
+            .map(|text| text.lines().map(|s| s.to_string()).collect());
+        let new: &[String] = if *self.lines == [""] { &[] } else { &self.lines };
+        let mut preview = match old {
+            Some(old) => diff::preview(&diff::diff_lines(&old, new), &old, new, 500),
+            None => vec![format!("- {} bytes on disk", existing.len()), format!("+ {} bytes in the buffer", content.len())],
+        };
+        if preview.is_empty() {
+            preview.push("(same lines; the line endings, final newline or encoding differ)".to_string());
+        }
+        self.popup = Some(PopupMode::OverwriteDiff { path, preview });
+    }
+    // 保存前の確認用: ディスク上のファイルとバッファの unified diff を表示する
+    fn show_disk_diff(&mut self) {
+        let Some(path) = self.current_file.clone() else {
+            self.message = Some("Buffer has no file on disk".to_string());
+            return;
+        };
+        // まだ存在しないファイルは空として比べる
+        let old: Vec<String> = std::fs::read_to_string(&path).unwrap_or_default().lines().map(|s| s.to_string()).collect();
+        let new: &[String] = if *self.lines == [""] { &[] } else { &self.lines };
+        if old == new {
+            self.message = Some("Buffer matches the file on disk".to_string());
+            return;
+        }
+        let ops = diff::diff_lines(&old, new);
+        let mut lines = vec![format!("--- {} (disk)", path.display()), format!("+++ {} (buffer)", path.display())];
+        lines.extend(diff::unified(&ops, &old, new, 3));
+        let title = format!("Diff: {} on disk vs buffer (Up/Down/PgUp/PgDn scroll, Esc close)", path.display());
+        self.popup = Some(PopupMode::DiffView { title, lines });
+        self.popup_selected = 0;
+    }
+    // 保存はキューに積んでワーカーに任せる。結果は poll_saves で受け取る
+    fn enqueue_save(&mut self, path: PathBuf, content: Vec<u8>) {
+        let Some(queue) = self.save_queue.as_mut() else { return };
+        let backup = self.config.backup.then_some(self.config.backup_policy);
+        queue.submit(SaveJob { path, content, backup });
+        self.modified = false;
+    }
+    fn poll_saves(&mut self) {
+        while let Some(res) = self.save_queue.as_mut().and_then(|q| q.try_recv()) {
+            self.handle_save_result(res);
+            self.dirty = true;
+        }
+        // 書いている途中なら進み具合を出し直す
+        if self.io_progress().is_some() { self.dirty = true; }
+    }
+    // 終了前に保存待ちを片付ける
+    fn flush_saves(&mut self) {
+        let results = match self.save_queue.as_mut() {
+            Some(queue) => queue.wait_idle(Duration::from_secs(5)),
+            None => return,
+        };
+        for res in results {
+            self.handle_save_result(res);
+        }
+    }
+    fn handle_save_result(&mut self, res: SaveResult) {
+        match res.result {
+            Ok(()) => {
+                if self.current_file.as_ref() == Some(&res.path) {
+                    let _ = std::fs::remove_file(self.recovery_path());
+                }
+                let mut msg = format!("Saved {} bytes to {}", res.bytes, res.path.display());
+                if !self.deterministic {
+                    msg.push_str(&format!(" ({} ms)", res.elapsed.as_millis()));
+                }
+                self.notify_ok(msg);
+            }
+            Err(e) => {
+                if self.current_file.as_ref() == Some(&res.path) {
+                    self.modified = true;
+                }
+                self.notify_err(format!("Save failed: {}: {}", res.path.display(), e));
+            }
+        }
+    }
+
+    // ディスク上の内容で読み直す。行の差分でカーソルや選択を同じ文に留め、undo で元に戻せる
+    fn reload_file(&mut self) {
+        let Some(path) = self.current_file.clone() else { return };
+        // 頭だけ開いたファイルは頭だけ読み直す
+        let bytes = match self.partial {
+            Some(_) => encoding::read_head(&path, self.large_file_bytes()),
+            None => std::fs::read(&path),
+        };
+        let bytes = match bytes {
+            Ok(bytes) => bytes,
+            Err(e) => {
+                self.notify_err(format!("Cannot reload {}: {}", path.display(), e));
+                return;
+            }
+        };
+        let content = if self.read_only {
+            String::from_utf8_lossy(&bytes).to_string()
+        } else {
+            match self.encoding.decode(&bytes) {
+                Ok(content) => content,
+                Err(e) => {
+                    self.notify_err(format!("Cannot reload {} as {}: {}", path.display(), self.encoding.label(), e));
+                    return;
+                }
+            }
+        };
+        let mut new_lines: Vec<String> = content.lines().map(|s| s.to_string()).collect();
+        if new_lines.is_empty() { new_lines.push(String::new()); }
+        if new_lines != *self.lines {
+            self.replace_all_lines(new_lines);
+        }
+        self.modified = false;
+    }
+    // 内容をまとめて置き換える（1回の undo で戻せる）。カーソルと選択は対応する行へ移す
+    fn replace_all_lines(&mut self, new_lines: Vec<String>) {
+        let ops = diff::diff_lines(&self.lines, &new_lines);
+        let map = diff::line_map(&ops, self.lines.len());
+        let last = new_lines.len() - 1;
+        let remap = |pos: Option<(usize, usize)>| {
+            pos.map(|(y, x)| {
+                let y = map[y].min(last);
+                (y, grapheme::floor(&new_lines[y], x))
+            })
+        };
+        self.save_undo();
+        self.sel_start = remap(self.sel_start);
+        self.sel_end = remap(self.sel_end);
+        self.cursor_y = map[self.cursor_y].min(last);
+        self.cursor_x = grapheme::floor(&new_lines[self.cursor_y], self.cursor_x);
+        self.scroll_offset = map[self.scroll_offset.min(map.len() - 1)].min(last);
+        *self.lines = new_lines;
+        self.adjust_h_scroll(0);
+    }
+    // Esc: 未保存の変更がなければそのまま終了し、あれば保存するか聞く
+    fn request_exit(&mut self) {
+        if !self.modified {
+            self.quit = true;
+            return;
+        }
+        let name = self.current_file.as_ref().map(|p| p.display().to_string()).unwrap_or_else(|| "The new buffer".to_string());
+        let question = format!("{} has unsaved changes.", name);
+        let keys = vec![('y', "save and exit"), ('n', "exit without saving")];
+        self.open_prompt(Prompt::keys("Exit", question, keys, "cancel", App::exit_prompt_answer), "");
+    }
+    fn exit_prompt_answer(&mut self, key: char) {
+        if key == 'n' {
+            self.quit = true;
+            return;
+        }
+        self.save_file();
+        // 名前がなければ Save As で聞いてから（メインループで続きを見る）
+        if self.popup.is_some() {
+            self.exit_after_save = true;
         } else {
-            if let Ok(content) = std::fs::read_to_string(&path) {
-                self.lines = content.lines().map(|s| s.to_string()).collect();
-                if self.lines.is_empty() { self.lines.push(String::new()); }
-                self.cursor_x = 0;
-                self.cursor_y = 0;
-                self.current_file = Some(path);
-                self.mode = Mode::Editor;
+            self.exit_when_saved();
+        }
+    }
+    // 保存を書き終えてから終了する。書けなければ（通知が出る）留まる
+    fn exit_when_saved(&mut self) {
+        self.flush_saves();
+        if !self.modified { self.quit = true; }
+    }
+
+    // --- Soft wrap ---
+    // F9: Markdown のプレビューを開閉する
+    fn toggle_markdown_preview(&mut self) {
+        if !self.md_preview && self.filetype() != "markdown" {
+            self.message = Some("Preview is only for Markdown buffers".to_string());
+            return;
+        }
+        self.md_preview = !self.md_preview;
+    }
+    fn toggle_soft_wrap(&mut self) {
+        self.soft_wrap = !self.soft_wrap;
+        self.h_scroll_offset = 0;
+    }
+    fn visual_rows(&self, row: usize) -> Vec<(usize, usize)> {
+        let graphemes: Vec<&str> = self.lines[row].graphemes(true).collect();
+        wrap_rows(&graphemes, self.wrap_width)
+    }
+    // カーソルの (行内の表示行, 表示桁)
+    fn cursor_visual(&self) -> (usize, usize) {
+        let graphemes: Vec<&str> = self.lines[self.cursor_y].graphemes(true).collect();
+        let cx = grapheme::index(&self.lines[self.cursor_y], self.cursor_x);
+        let rows = wrap_rows(&graphemes, self.wrap_width);
+        for (r, &(from, to)) in rows.iter().enumerate() {
+            if cx < to || r + 1 == rows.len() {
+                return (r, graphemes[from..cx].iter().map(|g| g.width()).sum());
+            }
+        }
+        (0, 0)
+    }
+    // 表示行 row の中で、表示桁 col に一番近い位置へカーソルを置く
+    fn set_cursor_visual(&mut self, row: usize, col: usize) {
+        let graphemes: Vec<&str> = self.lines[self.cursor_y].graphemes(true).collect();
+        let rows = wrap_rows(&graphemes, self.wrap_width);
+        let (from, to) = rows[row.min(rows.len() - 1)];
+        let last_row = row + 1 >= rows.len();
+        let mut x = from;
+        let mut w = 0;
+        while x < to && w + graphemes[x].width() <= col {
+            w += graphemes[x].width();
+            x += 1;
+        }
+        // 折り返し位置にカーソルを置くと次の表示行に見えるので1つ手前に
+        if !last_row && x == to && x > from { x -= 1; }
+        self.cursor_x = grapheme::byte(&self.lines[self.cursor_y], x);
+    }
+    fn move_visual_up(&mut self) {
+        let (row, col) = self.cursor_visual();
+        if row > 0 {
+            self.set_cursor_visual(row - 1, col);
+        } else if self.cursor_y > 0 {
+            self.cursor_y -= 1;
+            let last = self.visual_rows(self.cursor_y).len() - 1;
+            self.set_cursor_visual(last, col);
+        }
+    }
+    fn move_visual_down(&mut self) {
+        let (row, col) = self.cursor_visual();
+        if row + 1 < self.visual_rows(self.cursor_y).len() {
+            self.set_cursor_visual(row + 1, col);
+        } else if self.cursor_y + 1 < self.lines.len() {
+            self.cursor_y += 1;
+            self.set_cursor_visual(0, col);
+        }
+    }
+    // 折り返し表示でカーソルの表示行が画面内に収まるよう scroll_offset を調整
+    // 上の余白は行、下の余白は表示行で数える
+    fn adjust_scroll_wrapped(&mut self, visible_height: usize) {
+        let margin = self.scroll_margin(visible_height);
+        if self.cursor_y < self.scroll_offset + margin {
+            self.scroll_offset = self.cursor_y.saturating_sub(margin);
+        }
+        let cursor_row = self.cursor_visual().0;
+        let mut below = self.visual_rows(self.cursor_y).len() - cursor_row - 1;
+        let mut y = self.cursor_y + 1;
+        while below < margin && y < self.lines.len() {
+            below += self.visual_rows(y).len();
+            y += 1;
+        }
+        let below = below.min(margin);
+        let mut rows_above: usize = (self.scroll_offset..self.cursor_y).map(|l| self.visual_rows(l).len()).sum();
+        rows_above += cursor_row;
+        while rows_above + below >= visible_height && self.scroll_offset < self.cursor_y {
+            rows_above -= self.visual_rows(self.scroll_offset).len();
+            self.scroll_offset += 1;
+        }
+    }
+
+    // --- Recovery (SIGHUP/SIGTERM) ---
+    fn recovery_path(&self) -> PathBuf {
+        match self.current_file {
+            Some(ref path) => {
+                let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+                path.with_file_name(format!(".{}.rwe-swp", name))
             }
+            None => std::env::current_dir()
+                .unwrap_or_else(|_| std::env::temp_dir())
+                .join(format!("rwe-recovery-{}.txt", std::process::id())),
         }
     }
-    fn file_tree_go_up(&mut self) {
-        self.file_tree.go_up();
+    fn write_recovery(&self) -> io::Result<()> {
+        // 未保存の変更があるときだけスワップファイルを書き出す
+        if !self.modified { return Ok(()); }
+        std::fs::write(self.recovery_path(), self.lines.join("\n"))
     }
-    fn file_tree_delete(&mut self) {
+    // 端末への書き込みや端末からの読み込みに失敗した（端末が閉じた）ので終わる。変更はスワップファイルに残す
+    fn lost_terminal(&mut self, e: io::Error) -> io::Error {
+        self.flush_saves();
+        if let Err(err) = self.write_recovery() {
+            logging::error("recovery", &[("path", &self.recovery_path().display()), ("error", &err)]);
+        }
+        logging::error("exit", &[("reason", &"terminal"), ("error", &e)]);
+        e
+    }
+
+    // --- Popup handling ---
+    fn handle_popup(&mut self, key: KeyCode, modifiers: KeyModifiers) {
+        if self.popup == Some(PopupMode::Finder) && key == KeyCode::Char('t') && modifiers == KeyModifiers::CONTROL {
+            self.finder_cycle_sort();
+            return;
+        }
+        // 検索オプション: Alt+C で大文字小文字の扱い、Alt+W で単語単位を切り替える
+        if matches!(self.popup, Some(PopupMode::Search) | Some(PopupMode::Grep)) && modifiers == KeyModifiers::ALT {
+            match key {
+                KeyCode::Char('c') => self.search_opts.cycle_case(),
+                KeyCode::Char('w') => self.search_opts.whole_word = !self.search_opts.whole_word,
+                _ => return,
+            }
+            // 全文検索の結果は古くなるので次の Enter で検索し直す
+            self.grep.query.clear();
+            return;
+        }
+        if matches!(self.popup, Some(PopupMode::Finder) | Some(PopupMode::Grep)) && key == KeyCode::Char('g') && modifiers == KeyModifiers::CONTROL {
+            self.toggle_show_ignored();
+            return;
+        }
+        if self.popup == Some(PopupMode::Grep) && key == KeyCode::Char('r') && modifiers == KeyModifiers::CONTROL {
+            self.start_replace();
+            return;
+        }
+        if let Some(PopupMode::Prompt(_)) = self.popup {
+            self.handle_prompt_key(key);
+            return;
+        }
+        if self.popup == Some(PopupMode::ReplacePreview) {
+            self.handle_replace_key(key);
+            return;
+        }
+        if self.popup == Some(PopupMode::GitCommit) {
+            self.handle_commit_key(key);
+            return;
+        }
+        if let Some(PopupMode::Spelling { .. }) = self.popup {
+            self.handle_spelling_key(key);
+            return;
+        }
+        if let Some(PopupMode::DiffView { ref lines, .. }) = self.popup {
+            let last = lines.len().saturating_sub(1);
+            match key {
+                KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+                KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(last),
+                KeyCode::PageUp => self.popup_selected = self.popup_selected.saturating_sub(20),
+                KeyCode::PageDown => self.popup_selected = (self.popup_selected + 20).min(last),
+                KeyCode::Esc | KeyCode::Enter | KeyCode::Char('q') => self.popup = None,
+                _ => {}
+            }
+            return;
+        }
+        if let Some(PopupMode::OpenChoice { path, choices, .. }) = self.popup.clone() {
+            match key {
+                KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+                KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(choices.len() - 1),
+                KeyCode::Enter => {
+                    self.popup = None;
+                    self.open_as(&path, &choices[self.popup_selected]);
+                }
+                KeyCode::Esc => self.popup = None,
+                _ => {}
+            }
+            return;
+        }
+        if let Some(PopupMode::ConfirmDelete { paths, preview, .. }) = self.popup.clone() {
+            match key {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.popup = None;
+                    self.confirm_delete(&paths);
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.popup = None,
+                KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+                KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(preview.len().saturating_sub(1)),
+                _ => {}
+            }
+            return;
+        }
+        if let Some(PopupMode::OverwriteDiff { path, .. }) = self.popup.clone() {
+            match key {
+                KeyCode::Char('y') | KeyCode::Char('Y') => {
+                    self.popup = None;
+                    match self.buffer_bytes() {
+                        Ok(content) => {
+                            self.current_file = Some(path.clone());
+                            self.enqueue_save(path, content);
+                        }
+                        Err(e) => self.message = Some(e),
+                    }
+                }
+                KeyCode::Char('n') | KeyCode::Char('N') | KeyCode::Esc => self.popup = None,
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            KeyCode::Enter => {
+                match self.popup.clone().unwrap() {
+                    PopupMode::RenameEntry { path } => {
+                        self.popup = None;
+                        let name = std::mem::take(&mut self.popup_input).trim().to_string();
+                        self.file_tree_rename(&path, &name);
+                    }
+                    PopupMode::TransferEntries { paths, copy } => {
+                        self.popup = None;
+                        let dest = std::mem::take(&mut self.popup_input).trim().to_string();
+                        self.file_tree_transfer(&paths, &dest, copy);
+                    }
+                    PopupMode::Prompt(_) | PopupMode::OverwriteDiff { .. } | PopupMode::DiffView { .. } | PopupMode::ConfirmDelete { .. } | PopupMode::OpenChoice { .. } => {}
+                    PopupMode::Finder => self.finder_accept(),
+                    PopupMode::Search => {
+                        self.popup = None;
+                        let query = std::mem::take(&mut self.popup_input);
+                        self.run_search(query);
+                    }
+                    PopupMode::Grep => self.grep_enter(),
+                    PopupMode::ReplaceWith => self.show_replace_preview(),
+                    PopupMode::ReplacePreview | PopupMode::GitCommit | PopupMode::Spelling { .. } => {}
+                }
+            }
+            KeyCode::Esc => { self.popup = None; self.popup_input.clear(); }
+            KeyCode::Backspace => { self.popup_input.pop(); self.popup_selected = 0; }
+            KeyCode::Char(c) => { self.popup_input.push(c); self.popup_selected = 0; }
+            KeyCode::Up => { self.popup_selected = self.popup_selected.saturating_sub(1); }
+            KeyCode::Down => { self.popup_selected += 1; }
+            _ => {}
+        }
+    }
+
+    fn open_new_file_prompt(&mut self, name: &str) {
+        self.open_prompt(Prompt::text("New File: Enter file name", App::new_file_accept), name);
+    }
+    // 既にあるファイルは空にする前に聞く（Esc で名前の入力に戻る）
+    fn new_file_accept(&mut self) {
+        let filename = self.popup_input.trim().to_string();
+        if filename.is_empty() { return; }
+        let path = PathBuf::from(&filename);
+        if !path.is_file() {
+            self.create_new_file(path);
+            return;
+        }
+        let size = std::fs::metadata(&path).map(|m| m.len()).unwrap_or(0);
+        let question = format!("{} already exists ({} bytes).", path.display(), size);
+        let keys = vec![('y', "replace with an empty file"), ('o', "open it")];
+        let prompt = Prompt::keys("New File", question, keys, "choose another name", App::confirm_new_file).on_cancel(|app| {
+            let name = app.popup_input.clone();
+            app.open_new_file_prompt(&name);
+        });
+        self.open_prompt(prompt, &filename);
+    }
+    fn confirm_new_file(&mut self, key: char) {
+        let path = PathBuf::from(self.popup_input.trim());
+        if key == 'y' {
+            self.create_new_file(path);
+            return;
+        }
+        self.record_jump();
+        self.open_path(path);
+    }
+    fn rename_accept(&mut self) {
+        let newname = PathBuf::from(self.popup_input.trim());
+        if newname.as_os_str().is_empty() { return; }
+        let Some(old) = self.current_file.clone() else { return };
+        if let Err(e) = std::fs::rename(&old, &newname) {
+            self.notify_err(format!("Cannot rename {} to {}: {}", old.display(), newname.display(), e));
+            return;
+        }
+        self.notify_ok(format!("Renamed {} to {}", old.display(), newname.display()));
+        if let Some(parent) = newname.parent() {
+            self.file_tree.current_path = parent.to_path_buf();
+            self.file_tree.refresh();
+            if let Some(pos) = self.file_tree.entries.iter().position(|e| e.path() == newname) {
+                self.file_tree.selected = pos;
+            }
+        }
+        self.current_file = Some(newname);
+    }
+    fn line_offset_accept(&mut self) {
+        self.line_offset = self.popup_input.trim().trim_start_matches('+').parse().unwrap_or(0);
+    }
+
+    // 空のファイルを作って開く。作れなくてもバッファは開く（保存するときにもう一度書く）
+    fn create_new_file(&mut self, path: PathBuf) {
+        let created = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => std::fs::create_dir_all(parent),
+            _ => Ok(()),
+        }
+        .and_then(|_| std::fs::write(&path, ""));
+        match created {
+            Ok(()) => self.notify_ok(format!("Created {}", path.display())),
+            Err(e) => self.notify_err(format!("Cannot create {}: {}", path.display(), e)),
+        }
+        self.current_file = Some(path);
+        *self.lines = vec![String::new()];
+        self.file_indent = None;
+        self.modified = false;
+        self.line_offset = 0;
+        self.load_git_base();
+    }
+
+    // --- File open ---
+    // content は encoding で読んだ中身。保存するときも同じ文字コードで書く
+    fn load_buffer(&mut self, path: PathBuf, content: &str, encoding: Encoding) {
+        *self.lines = content.lines().map(|s| s.to_string()).collect();
+        if self.lines.is_empty() { self.lines.push(String::new()); }
+        self.cursor_x = 0;
+        self.cursor_y = 0;
+        self.scroll_offset = 0;
+        self.h_scroll_offset = 0;
+        self.selection_reset();
+        self.undo_stack.clear();
+        self.redo_stack.clear();
+        Frecency::record(&path);
+        logging::info("open", &[("path", &path.display()), ("lines", &self.lines.len())]);
+        self.current_file = Some(path);
+        self.modified = false;
+        self.line_offset = 0;
+        self.completion = None;
+        self.hex = None;
+        self.encoding = encoding;
+        self.read_only = false;
+        self.partial = None;
+        self.file_indent = indent::detect(&self.lines);
+        self.load_git_base();
+        if let Some(goto) = self.goto_after_load.take().filter(|g| self.current_file.as_ref() == Some(&g.path)) {
+            self.move_to(&goto);
+        }
+    }
+
+    // 指定位置へ移動する。別のファイルなら FileTree と同じように開き、読み終わってから移る
+    fn goto_location(&mut self, path: &std::path::Path, line: usize, col: usize) {
+        self.goto(Goto { path: path.to_path_buf(), line, col, utf16: false });
+    }
+    fn goto(&mut self, goto: Goto) {
+        let same = self.current_file.as_ref().is_some_and(|cur| {
+            std::fs::canonicalize(cur).ok() == std::fs::canonicalize(&goto.path).ok()
+        });
+        if same {
+            self.move_to(&goto);
+            return;
+        }
+        let (path, limit) = (goto.path.clone(), self.large_file_bytes());
+        self.goto_after_load = Some(goto);
+        self.start_load(path, Extent::Checked(limit));
+    }
+    fn move_to(&mut self, goto: &Goto) {
+        self.mode = Mode::Editor;
+        self.cursor_y = goto.line.min(self.lines.len() - 1);
+        let line = &self.lines[self.cursor_y];
+        let col = if goto.utf16 { lsp::utf16_to_byte(line, goto.col) } else { goto.col };
+        self.cursor_x = grapheme::floor(line, col);
+        self.selection_reset();
+        self.adjust_h_scroll(0);
+    }
+
+    // --- FileTree mode operations ---
+    fn file_tree_move_up(&mut self) {
+        self.file_tree.move_up();
+    }
+    fn file_tree_move_down(&mut self) {
+        self.file_tree.move_down();
+    }
+    // ディレクトリなら開閉、ファイルならエディタで開く。絞り込み中は絞り込みをやめてディレクトリを開く
+    fn file_tree_enter(&mut self) {
         if self.file_tree.entries.is_empty() { return; }
+        let filtering = !self.file_tree.filter.is_empty();
+        if filtering { self.file_tree.clear_filter(); }
         let entry = &self.file_tree.entries[self.file_tree.selected];
         let path = entry.path();
-        if path.is_dir() {
-            let _ = std::fs::remove_dir_all(&path);
-        } else {
-            let _ = std::fs::remove_file(&path);
+        if entry.is_dir {
+            if filtering { self.file_tree.expand(); } else { self.file_tree.toggle_expand(); }
+            return;
+        }
+        self.record_jump();
+        self.open_path(path);
+    }
+    fn file_tree_right(&mut self) {
+        match self.file_tree.entries.get(self.file_tree.selected) {
+            Some(entry) if entry.is_dir => self.file_tree.expand(),
+            Some(_) => self.file_tree_enter(),
+            None => {}
         }
-        self.file_tree.refresh();
     }
+    fn file_tree_left(&mut self) {
+        self.file_tree.collapse();
+    }
+    // current_path の下にディレクトリを作り（途中のディレクトリも作る）、作った項目を選択する
+    fn file_tree_mkdir(&mut self, name: &str) {
+        if name.is_empty() { return; }
+        let path = self.file_tree.current_path.join(name);
+        if path.exists() {
+            self.message = Some(format!("{} already exists", path.display()));
+            return;
+        }
+        if let Err(e) = std::fs::create_dir_all(&path) {
+            self.notify_err(format!("Cannot create {}: {}", path.display(), e));
+            return;
+        }
+        self.file_tree.reload();
+        // a/b/c なら一覧に出ている一番深いもの（閉じていれば a）を選ぶ
+        let shown = path.ancestors().find_map(|p| self.file_tree.entries.iter().position(|e| e.path == p));
+        if let Some(pos) = shown {
+            self.file_tree.selected = pos;
+        }
+        self.notify_ok(format!("Created {}", path.display()));
+    }
+
+    // F6: 選択中の項目（ファイルでもディレクトリでも）の名前を変える
+    fn file_tree_rename_prompt(&mut self) {
+        let Some(entry) = self.file_tree.entries.get(self.file_tree.selected) else { return };
+        self.popup_input = entry.file_name().to_string_lossy().to_string();
+        self.popup = Some(PopupMode::RenameEntry { path: entry.path() });
+    }
+
+    // 同じディレクトリの中で名前を変える（a/b のように書けば下へ移す）。開いているファイルも追従する
+    fn file_tree_rename(&mut self, path: &Path, name: &str) {
+        let Some(dir) = path.parent() else { return };
+        let target = dir.join(name);
+        if name.is_empty() || target == path { return; }
+        if target.exists() {
+            self.message = Some(format!("{} already exists", target.display()));
+            return;
+        }
+        if let Some(parent) = target.parent()
+            && let Err(e) = std::fs::create_dir_all(parent)
+        {
+            self.notify_err(format!("Cannot create {}: {}", parent.display(), e));
+            return;
+        }
+        if let Err(e) = std::fs::rename(path, &target) {
+            self.notify_err(format!("Cannot rename {}: {}", path.display(), e));
+            return;
+        }
+        if let Some(rest) = self.current_file.as_ref().and_then(|f| f.strip_prefix(path).ok()) {
+            self.current_file = Some(if rest.as_os_str().is_empty() { target.clone() } else { target.join(rest) });
+        }
+        if self.file_tree.expanded.remove(path) {
+            self.file_tree.expanded.insert(target.clone());
+        }
+        self.file_tree.reload();
+        let shown = target.ancestors().find_map(|p| self.file_tree.entries.iter().position(|e| e.path == p));
+        if let Some(pos) = shown {
+            self.file_tree.selected = pos;
+        }
+        self.notify_ok(format!("Renamed to {}", target.display()));
+    }
+
 }
 
 // --- Drawing functions ---
@@ -656,7 +2378,11 @@ fn draw_header<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, area:
         } else {
             full_path.to_string()
         };
-        format!("File: {} | {}", file_name, truncated)
+        let mut notes = String::new();
+        if app.read_only { notes.push_str(" [read-only]"); }
+        if let Some(total) = app.partial { notes.push_str(&format!(" [first {} of {}]", format_size(app.large_file_bytes()), format_size(total))); }
+        if app.encoding != Encoding::Utf8 { notes.push_str(&format!(" [{}]", app.encoding.label())); }
+        format!("File: {} | {}{}", file_name, truncated, notes)
     } else {
         "New File".to_string()
     };
@@ -665,6 +2391,204 @@ fn draw_header<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, area:
     frame.render_widget(paragraph, area);
 }
 
+// 1画面の描画で共通に使う情報
+struct LineContext {
+    selection: Option<(TextPos, TextPos)>,       // 選択範囲（正規化済み）
+    bracket: Option<(TextPos, Option<TextPos>)>, // カーソル位置の括弧と相方
+    indent_unit: Option<usize>,                  // インデントガイドの間隔（無効なら None）
+    search: Option<Matcher>,                     // 一致を強調表示する検索語
+    highlights: Highlights,                      // 構文の色付け（表示中の行）
+    misspellings: Misspellings,                  // 綴りの誤り（表示中の行）
+}
+
+const GUIDE_BG: Color = Color::Rgb(50, 56, 64);
+
+// 行番号の右の git の印（追加・変更・削除）
+fn gutter_span(mark: Option<Mark>, ascii: bool) -> Span<'static> {
+    let (symbol, ascii_symbol, color) = match mark {
+        Some(Mark::Added) => ("▎", "+", Color::Green),
+        Some(Mark::Modified) => ("▎", "~", Color::Yellow),
+        Some(Mark::Deleted) => ("▁", "_", Color::Red),
+        None => return Span::raw(" "),
+    };
+    Span::styled(if ascii { ascii_symbol } else { symbol }, Style::default().fg(color))
+}
+
+// 1行分のグラフェムごとのスタイル（空白・選択・括弧・桁ガイド）。base は行全体の下地
+fn line_styles(app: &App, real_line: usize, line: &str, graphemes: &[&str], base: Style, ctx: &LineContext) -> Vec<Style> {
+    let mut styles = vec![base; graphemes.len()];
+    let mut paint = |from: usize, to: usize, style: Style| {
+        for s in styles.iter_mut().take(to).skip(from) {
+            *s = s.patch(style);
+        }
+    };
+    // 構文の色付け
+    for &(start, end, color) in ctx.highlights.line(real_line) {
+        let (start, end) = (start.min(line.len()), end.min(line.len()));
+        if start < end && line.is_char_boundary(start) && line.is_char_boundary(end) {
+            let from = line[..start].graphemes(true).count();
+            let to = from + line[start..end].graphemes(true).count();
+            paint(from, to, Style::default().fg(color));
+        }
+    }
+    // 綴りの誤りに下線
+    for &(start, end) in ctx.misspellings.line(real_line) {
+        let from = line[..start].graphemes(true).count();
+        let to = from + line[start..end].graphemes(true).count();
+        paint(from, to, Style::default().fg(Color::LightRed).add_modifier(Modifier::UNDERLINED));
+    }
+    // URL に下線
+    for (start, end) in url::find_urls(line) {
+        let from = line[..start].graphemes(true).count();
+        let to = from + line[start..end].graphemes(true).count();
+        paint(from, to, Style::default().add_modifier(Modifier::UNDERLINED));
+    }
+    // インデントガイド
+    if let Some(unit) = ctx.indent_unit {
+        for k in indent_guide_positions(graphemes, unit) {
+            paint(k, k + 1, Style::default().fg(Color::Rgb(70, 76, 84)));
+        }
+    }
+    // 空白の記号は控えめに、行末の空白は警告色にする
+    if app.show_whitespace {
+        let is_space = |g: &str| g == " " || g == "\t";
+        for (k, g) in graphemes.iter().enumerate() {
+            if is_space(g) {
+                paint(k, k + 1, Style::default().fg(Color::DarkGray));
+            }
+        }
+        let trailing = graphemes.iter().rev().take_while(|g| is_space(g)).count();
+        paint(graphemes.len() - trailing, graphemes.len(), Style::default().bg(Color::Rgb(110, 40, 40)).fg(Color::LightRed));
+    }
+    // 検索語の一致
+    if let Some(ref matcher) = ctx.search {
+        for (start, end) in matcher.find_all(line) {
+            let from = line[..start].graphemes(true).count();
+            let to = from + line[start..end].graphemes(true).count();
+            paint(from, to, Style::default().bg(Color::Rgb(110, 95, 40)).fg(Color::White));
+        }
+    }
+    // 選択範囲がこの行にある場合、部分的にハイライトする
+    if let Some(((sel_line_start, sel_col_start), (sel_line_end, sel_col_end))) = ctx.selection
+        && real_line >= sel_line_start && real_line <= sel_line_end
+    {
+        // この行での選択開始・終了位置（バイト位置をグラフェム単位に直す）
+        let line_len = graphemes.len();
+        let sel_start_idx = if real_line == sel_line_start { grapheme::index(line, sel_col_start) } else { 0 };
+        let sel_end_idx = if real_line == sel_line_end { grapheme::index(line, sel_col_end) } else { line_len };
+        paint(sel_start_idx, sel_end_idx, Style::default().bg(Color::White).fg(Color::Black));
+    }
+    // Ctrl+D で足した選択
+    for &((y, from), (_, to)) in &app.extra_selections {
+        if y == real_line && from < to {
+            paint(grapheme::index(line, from), grapheme::index(line, to), Style::default().bg(Color::White).fg(Color::Black));
+        }
+    }
+    // 対応する括弧
+    if let Some((pos, partner)) = ctx.bracket {
+        let style = match partner {
+            Some(_) => Style::default().bg(Color::Rgb(70, 80, 90)).add_modifier(Modifier::BOLD),
+            None => Style::default().fg(Color::Red).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+        };
+        for (y, x) in std::iter::once(pos).chain(partner) {
+            if y == real_line {
+                let idx = line[..x].graphemes(true).count();
+                paint(idx, idx + 1, style);
+            }
+        }
+    }
+    // 桁ガイドと、上限を超えた部分の色付け
+    let guides = app.guide_columns(real_line);
+    if let Some(limit) = app.column_limit(real_line) {
+        paint(limit.min(graphemes.len()), graphemes.len(), Style::default().fg(Color::Yellow));
+    }
+    let mut col = 0;
+    for (k, g) in graphemes.iter().enumerate() {
+        if guides.contains(&col) {
+            styles[k] = styles[k].bg(GUIDE_BG);
+        }
+        col += g.width();
+    }
+    styles
+}
+
+// インデントガイドを描くグラフェム位置。先頭の空白のうち、unit 桁ごとの段の頭（タブは1つで1段）
+fn indent_guide_positions(graphemes: &[&str], unit: usize) -> Vec<usize> {
+    let mut positions = Vec::new();
+    let mut col = 0;
+    for (k, g) in graphemes.iter().enumerate() {
+        match *g {
+            "\t" => {
+                positions.push(k);
+                col = (col / unit + 1) * unit;
+            }
+            " " => {
+                if col % unit == 0 { positions.push(k); }
+                col += 1;
+            }
+            _ => return positions,
+        }
+    }
+    // 空白だけの行にはガイドを描かない
+    Vec::new()
+}
+
+// 空白を記号に置き換えた表示用グラフェム（--deterministic では ASCII）
+fn visible_whitespace(graphemes: Vec<&str>, ascii: bool) -> Vec<&str> {
+    let (space, tab) = if ascii { (".", ">") } else { ("·", "→") };
+    graphemes
+        .into_iter()
+        .map(|g| match g {
+            " " => space,
+            "\t" => tab,
+            _ => g,
+        })
+        .collect()
+}
+
+// 折り返し表示での1行の分割 (開始, 終了) グラフェム位置。できるだけ空白の後ろで折る
+fn wrap_rows(graphemes: &[&str], width: usize) -> Vec<(usize, usize)> {
+    let width = width.max(1);
+    let mut rows = Vec::new();
+    let mut from = 0;
+    let mut last_width = 0;
+    while from < graphemes.len() {
+        let mut w = 0;
+        let mut to = from;
+        let mut last_space = None;
+        while to < graphemes.len() {
+            let gw = graphemes[to].width();
+            if w + gw > width && to > from { break; }
+            w += gw;
+            if graphemes[to] == " " || graphemes[to] == "\t" { last_space = Some(to + 1); }
+            to += 1;
+        }
+        if to < graphemes.len()
+            && let Some(sp) = last_space
+        {
+            to = sp;
+        }
+        last_width = graphemes[from..to].iter().map(|g| g.width()).sum();
+        rows.push((from, to));
+        from = to;
+    }
+    // 行末のカーソルを置けるよう、幅いっぱいの行の後ろには空の行を足す
+    if rows.is_empty() || last_width >= width {
+        rows.push((graphemes.len(), graphemes.len()));
+    }
+    rows
+}
+
+// スクロールバーのつまみが占める行。大きさは見えている割合、位置はスクロール量に比例させる（全部見えていれば空）
+fn scrollbar_rows(total: usize, visible: usize, offset: usize) -> std::ops::Range<usize> {
+    if total <= visible || visible == 0 { return 0..0; }
+    // 隠れている行があれば全部は埋めない（動いて見えるように）
+    let size = (visible * visible / total).clamp(1, visible.saturating_sub(1).max(1));
+    let max_scroll = total - visible;
+    let top = (offset.min(max_scroll) * (visible - size) + max_scroll / 2) / max_scroll;
+    top..top + size
+}
+
 fn draw_editor<B: tui::backend::Backend>(
     frame: &mut Frame<B>,
     app: &mut App,
@@ -672,157 +2596,314 @@ fn draw_editor<B: tui::backend::Backend>(
     update_state: bool,
 ) {
     let editor_height = chunks[1].height as usize;
+    let available_width = chunks[1].width as usize;
     if update_state {
-        app.adjust_scroll(editor_height);
-        app.adjust_h_scroll(chunks[1].width as usize);
-    }
-    let start = app.scroll_offset;
-    let end = (start + editor_height).min(app.lines.len());
-    let display_lines = &app.lines[start..end];
-
-    // --- 行番号欄 ---
-    let mut line_no_spans = Vec::new();
-    let digits = app.line_number_width();
-    for (i, _) in display_lines.iter().enumerate() {
-        let real_line = start + i;
-        let lineno_text = format!("{:>width$}", real_line + 1, width = digits);
-        if real_line == app.cursor_y {
-            line_no_spans.push(Spans::from(Span::styled(
-                lineno_text,
-                Style::default().bg(Color::White).fg(Color::Black),
-            )));
+        app.wrap_width = available_width;
+        app.view_height = editor_height;
+        if app.soft_wrap {
+            app.h_scroll_offset = 0;
+            app.adjust_scroll_wrapped(editor_height);
         } else {
-            line_no_spans.push(Spans::from(Span::raw(lineno_text)));
+            app.adjust_scroll(editor_height);
+            app.adjust_h_scroll(available_width);
         }
     }
-    let paragraph_line_no = Paragraph::new(line_no_spans).wrap(Wrap { trim: false });
-    frame.render_widget(paragraph_line_no, chunks[0]);
-
-    // --- テキスト欄 (横スクロール対応) ---
-    let available_width = chunks[1].width as usize;
-    let mut text_spans = Vec::new();
+    let start = app.scroll_offset;
+    let highlights = app.syntax_highlights(start, start + editor_height);
     // selection を (start_line, start_col) <= (end_line, end_col) に正規化
-    let selection = match (app.sel_start, app.sel_end) {
-        (Some(s), Some(e)) => Some(if s <= e { (s, e) } else { (e, s) }),
-        _ => None,
+    let ctx = LineContext {
+        selection: match (app.sel_start, app.sel_end) {
+            (Some(s), Some(e)) => Some(if s <= e { (s, e) } else { (e, s) }),
+            _ => None,
+        },
+        bracket: app.find_matching_bracket(),
+        indent_unit: app.config.indent_guides.then(|| app.indent().width),
+        search: (app.search_highlight && !app.search_query.is_empty()).then(|| Matcher::new(&app.search_query, app.search_opts)),
+        misspellings: app.misspellings(start, start + editor_height, &highlights),
+        highlights,
     };
-    
-    for (i, line) in display_lines.iter().enumerate() {
-        let real_line = start + i;
+    let digits = app.line_number_width();
+
+    let mut line_no_spans = Vec::new();
+    let mut text_spans = Vec::new();
+    let mut cursor_screen = None; // (画面上の行, 桁)
+    let mut overflow = Vec::new(); // (画面上の行, 左に隠れている, 右に隠れている)
+    let mut carets = Vec::new(); // Ctrl+D で足したカーソル (画面上の行, 桁, 文字)
+    let mut real_line = start;
+    while text_spans.len() < editor_height && real_line < app.lines.len() {
+        let line = &app.lines[real_line];
         let graphemes: Vec<&str> = line.graphemes(true).collect();
-        // 横スクロール：h_scroll_offset に合わせ、表示開始インデックスを求める
-        let mut cum = 0;
-        let mut disp_start_idx = 0;
-        for (j, g) in graphemes.iter().enumerate() {
-            cum += g.width();
-            if cum > app.h_scroll_offset {
-                disp_start_idx = j;
-                break;
+        let is_cursor_line = real_line == app.cursor_y;
+        // カーソル行はペイン幅いっぱいに背景色を敷く
+        let base = if is_cursor_line && app.config.highlight_current_line {
+            Style::default().bg(app.config.theme.current_line)
+        } else {
+            Style::default()
+        };
+        let styles = line_styles(app, real_line, line, &graphemes, base, &ctx);
+        let mut graphemes = if app.show_whitespace { visible_whitespace(graphemes, app.deterministic) } else { graphemes };
+        if let Some(unit) = ctx.indent_unit {
+            for k in indent_guide_positions(&line.graphemes(true).collect::<Vec<_>>(), unit) {
+                graphemes[k] = if app.deterministic { "|" } else { "│" };
             }
         }
-        // 表示可能な範囲を取得
-        let mut disp_text = String::new();
-        let mut width = 0;
-        let mut disp_end_idx = disp_start_idx;
-        for g in graphemes.iter().skip(disp_start_idx) {
-            let w = g.width();
-            if width + w > available_width {
-                break;
-            }
-            disp_text.push_str(g);
-            width += w;
-            disp_end_idx += 1;
-        }
-        // 選択範囲がこの行にある場合、部分的にハイライトする
-        if let Some(((sel_line_start, sel_col_start), (sel_line_end, sel_col_end))) = selection {
-            if real_line >= sel_line_start && real_line <= sel_line_end {
-                // この行での選択開始・終了位置（グラフェム単位）
-                let line_len = graphemes.len();
-                let sel_start_idx = if real_line == sel_line_start { sel_col_start } else { 0 };
-                let sel_end_idx = if real_line == sel_line_end { sel_col_end } else { line_len };
-                // 表示範囲と選択範囲の交差部分
-                let disp_sel_start = sel_start_idx.max(disp_start_idx);
-                let disp_sel_end = sel_end_idx.min(disp_end_idx);
-                let mut spans = Vec::new();
-                // pre
-                if disp_sel_start > disp_start_idx {
-                    let pre: String = graphemes[disp_start_idx..disp_sel_start].concat();
-                    spans.push(Span::raw(pre));
+        let cursor_idx = grapheme::index(line, app.cursor_x);
+        let extra_idx: Vec<usize> = app
+            .extra_selections
+            .iter()
+            .filter(|&&(s, e)| s == e && s.0 == real_line)
+            .map(|&(_, (_, x))| grapheme::index(line, x))
+            .collect();
+        // --- 行番号欄 ---
+        let lineno_text = format!("{:>width$}", app.display_line_number(real_line), width = digits);
+        let lineno_style = if is_cursor_line {
+            Style::default().bg(Color::White).fg(Color::Black)
+        } else {
+            Style::default()
+        };
+        let mark = app.gutter.marks.get(real_line).copied().flatten();
+        line_no_spans.push(Spans::from(vec![Span::styled(lineno_text, lineno_style), gutter_span(mark, app.deterministic)]));
+
+        if app.soft_wrap {
+            // --- テキスト欄 (折り返し表示) ---
+            let rows = wrap_rows(&graphemes, available_width);
+            for (r, &(from, to)) in rows.iter().enumerate() {
+                if r > 0 {
+                    if text_spans.len() >= editor_height { break; }
+                    line_no_spans.push(Spans::from(" ".repeat(digits)));
                 }
-                // selected
-                if disp_sel_start < disp_sel_end {
-                    let selected: String = graphemes[disp_sel_start..disp_sel_end].concat();
-                    spans.push(Span::styled(selected, Style::default().bg(Color::White).fg(Color::Black)));
+                let last_row = r + 1 == rows.len();
+                if is_cursor_line && cursor_idx >= from && (cursor_idx < to || last_row) {
+                    let x: usize = graphemes[from..cursor_idx].iter().map(|g| g.width()).sum();
+                    cursor_screen = Some((text_spans.len(), x));
                 }
-                // post
-                if disp_sel_end < disp_end_idx {
-                    let post: String = graphemes[disp_sel_end..disp_end_idx].concat();
-                    spans.push(Span::raw(post));
+                for &k in extra_idx.iter().filter(|&&k| k >= from && (k < to || last_row)) {
+                    let x: usize = graphemes[from..k].iter().map(|g| g.width()).sum();
+                    carets.push((text_spans.len(), x, graphemes.get(k).copied().unwrap_or(" ")));
+                }
+                let mut spans = styled_spans(&graphemes[from..to], &styles[from..to]);
+                let row_width: usize = graphemes[from..to].iter().map(|g| g.width()).sum();
+                if base != Style::default() && row_width < available_width {
+                    spans.push(Span::styled(" ".repeat(available_width - row_width), base));
                 }
                 text_spans.push(Spans::from(spans));
-                continue;
             }
+        } else {
+            // --- テキスト欄 (横スクロール対応) ---
+            // h_scroll_offset に合わせ、表示開始インデックスを求める
+            let mut cum = 0;
+            let mut disp_start_idx = graphemes.len(); // 行全体が左に隠れていれば何も描かない
+            for (j, g) in graphemes.iter().enumerate() {
+                cum += g.width();
+                if cum > app.h_scroll_offset {
+                    disp_start_idx = j;
+                    break;
+                }
+            }
+            // 表示可能な範囲を取得
+            let mut width = 0;
+            let mut disp_end_idx = disp_start_idx;
+            for g in graphemes.iter().skip(disp_start_idx) {
+                let w = g.width();
+                if width + w > available_width {
+                    break;
+                }
+                width += w;
+                disp_end_idx += 1;
+            }
+            let start_col: usize = graphemes[..disp_start_idx].iter().map(|g| g.width()).sum();
+            let mut spans = styled_spans(&graphemes[disp_start_idx..disp_end_idx], &styles[disp_start_idx..disp_end_idx]);
+            // 行末より右にあるガイドは空白で埋めて描く
+            let mut col = start_col + width;
+            for guide in app.guide_columns(real_line) {
+                if guide >= col && guide < start_col + available_width {
+                    spans.push(Span::styled(" ".repeat(guide - col), base));
+                    spans.push(Span::styled(" ", Style::default().bg(GUIDE_BG)));
+                    col = guide + 1;
+                }
+            }
+            if base != Style::default() && col < start_col + available_width {
+                spans.push(Span::styled(" ".repeat(start_col + available_width - col), base));
+            }
+            if is_cursor_line {
+                let x: usize = graphemes[..cursor_idx].iter().map(|g| g.width()).sum::<usize>() + app.virtual_cols();
+                cursor_screen = Some((text_spans.len(), x.saturating_sub(app.h_scroll_offset)));
+            }
+            for &k in &extra_idx {
+                let x: usize = graphemes[..k].iter().map(|g| g.width()).sum();
+                if x >= app.h_scroll_offset && x < app.h_scroll_offset + available_width {
+                    carets.push((text_spans.len(), x - app.h_scroll_offset, graphemes.get(k).copied().unwrap_or(" ")));
+                }
+            }
+            if disp_start_idx > 0 || disp_end_idx < graphemes.len() {
+                overflow.push((text_spans.len(), disp_start_idx > 0, disp_end_idx < graphemes.len()));
+            }
+            text_spans.push(Spans::from(spans));
         }
-        // 選択がなければそのまま表示
-        text_spans.push(Spans::from(Span::raw(disp_text)));
+        real_line += 1;
     }
-    let paragraph_text = Paragraph::new(text_spans).wrap(Wrap { trim: false });
+    let paragraph_line_no = Paragraph::new(line_no_spans);
+    frame.render_widget(paragraph_line_no, chunks[0]);
+    // 行は表示幅に切り詰め済み。折り返すと行末の空白（ガイド）が落ちるので wrap しない
+    let paragraph_text = Paragraph::new(text_spans);
     frame.render_widget(paragraph_text, chunks[1]);
-
-    // --- スクロールバー (Editor) ---
-    let total_lines = app.lines.len();
-    let mut scrollbar_spans = Vec::new();
-    if total_lines <= editor_height {
-        for _ in 0..editor_height { scrollbar_spans.push(Spans::from(" ")); }
-    } else {
-        let max_scroll = total_lines.saturating_sub(editor_height);
-        let ratio = app.scroll_offset as f32 / max_scroll as f32;
-        let thumb_row = (ratio * (editor_height - 1) as f32).round() as usize;
-        for row in 0..editor_height {
-            if row == thumb_row { scrollbar_spans.push(Spans::from("█")); }
-            else { scrollbar_spans.push(Spans::from(" ")); }
+    // 横にはみ出した行は端に印を重ねる（カーソルのある桁には重ねない）
+    let (left_mark, right_mark) = if app.deterministic { ("<", ">") } else { ("«", "»") };
+    for (row, left, right) in overflow {
+        let edges = [(left, 0, left_mark), (right, available_width.saturating_sub(1), right_mark)];
+        for (hidden, col, mark) in edges {
+            if !hidden || cursor_screen == Some((row, col)) { continue; }
+            let area = Rect::new(chunks[1].x + col as u16, chunks[1].y + row as u16, 1, 1);
+            frame.render_widget(Paragraph::new(Span::styled(mark, Style::default().fg(Color::Yellow))), area);
         }
     }
+
+    // 足したカーソルはその文字を反転して見せる
+    for (row, col, g) in carets {
+        if col + g.width().max(1) > available_width { continue; }
+        let area = Rect::new(chunks[1].x + col as u16, chunks[1].y + row as u16, g.width().max(1) as u16, 1);
+        frame.render_widget(Paragraph::new(Span::styled(g.to_string(), Style::default().bg(Color::White).fg(Color::Black))), area);
+    }
+
+    // --- スクロールバー (Editor) ---
+    let thumb = scrollbar_rows(app.lines.len(), editor_height, app.scroll_offset);
+    let scrollbar_spans: Vec<Spans> = (0..editor_height)
+        .map(|row| Spans::from(if thumb.contains(&row) { app.scrollbar_thumb() } else { " " }))
+        .collect();
     let paragraph_scrollbar = Paragraph::new(scrollbar_spans).wrap(Wrap { trim: false });
     frame.render_widget(paragraph_scrollbar, chunks[2]);
 
-    // --- カーソル位置 (横スクロール対応) ---
-    if app.cursor_y >= start && app.cursor_y < end {
-        let row_in_view = app.cursor_y - start;
-        let line = &app.lines[app.cursor_y];
-        let graphemes: Vec<&str> = line.graphemes(true).collect();
-        let mut cum = 0;
-        for (j, g) in graphemes.iter().enumerate() {
-            cum += g.width();
-            if j == app.cursor_x { break; }
-        }
-        let cursor_screen_x = if app.cursor_x < graphemes.len() {
-            // 行中の場合は1セル左にずらす
-            cum.saturating_sub(app.h_scroll_offset).saturating_sub(1)
-        } else {
-            // 行末の場合はそのままの位置
-            cum.saturating_sub(app.h_scroll_offset)
-        } as u16;
-        let cursor_x = chunks[1].x + cursor_screen_x;
-        let cursor_y = chunks[1].y + row_in_view as u16;
-        frame.set_cursor(cursor_x, cursor_y);
-    } else {
-        frame.set_cursor(0, 0);
+    // --- カーソル位置 ---
+    let pos = cursor_screen.map(|(row, x)| (chunks[1].x + x as u16, chunks[1].y + row as u16));
+    match pos {
+        Some((x, y)) => frame.set_cursor(x, y),
+        None => frame.set_cursor(0, 0),
+    }
+    if update_state {
+        app.cursor_screen = pos;
+        app.scrollbar_area = Some(chunks[2]);
+    }
+}
+
+// Markdown のプレビュー。エディタの一番上の行から描く
+fn draw_markdown_preview<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, area: Rect) {
+    let block = Block::default().borders(Borders::LEFT).border_style(Style::default().fg(Color::DarkGray));
+    let inner = block.inner(area);
+    let ascii = app.deterministic;
+    let rows = markdown::render(&app.lines, app.scroll_offset, inner.width as usize, inner.height as usize, ascii);
+    let lines: Vec<Spans> = rows.into_iter().map(|r| r.spans).collect();
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// 16進表示: オフセット、16 バイトの16進（8 バイトごとに空ける）、ASCII の欄
+fn draw_hex<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    let Some(hex) = app.hex.as_mut() else { return };
+    hex.adjust_scroll(area.height as usize);
+    let cursor_style = Style::default().fg(Color::Black).bg(Color::Yellow);
+    let other_style = Style::default().bg(Color::DarkGray);
+    let mut rows = Vec::new();
+    let mut cursor = None;
+    for (i, row) in (hex.scroll..).take(area.height as usize).enumerate() {
+        let start = row * hex::ROW;
+        if start > hex.bytes.len() || (start == hex.bytes.len() && start > 0 && hex.cursor < start) { break; }
+        let mut hex_spans = vec![Span::styled(format!("{:08x}  ", start), Style::default().fg(Color::DarkGray))];
+        let mut ascii_spans = vec![Span::raw(" |")];
+        for col in 0..hex::ROW {
+            let pos = start + col;
+            let byte = hex.bytes.get(pos).copied();
+            let at = pos == hex.cursor;
+            let (text, ch) = match byte {
+                Some(b) => (format!("{:02x}", b), if b.is_ascii_graphic() || b == b' ' { b as char } else { '.' }),
+                None => ("  ".to_string(), ' '),
+            };
+            let color = match byte {
+                Some(0) => Color::DarkGray,
+                Some(b) if b.is_ascii_graphic() || b == b' ' => Color::White,
+                _ => Color::LightBlue,
+            };
+            let (hex_style, ascii_style) = match (at, hex.pane) {
+                (true, hex::Pane::Hex) => (cursor_style, other_style),
+                (true, hex::Pane::Ascii) => (other_style, cursor_style),
+                _ => (Style::default().fg(color), Style::default().fg(color)),
+            };
+            if at {
+                // 端末のカーソルは入力する欄に置く
+                let x = match hex.pane {
+                    hex::Pane::Hex => 10 + col * 3 + usize::from(col >= 8) + usize::from(hex.low),
+                    hex::Pane::Ascii => 10 + hex::ROW * 3 + 1 + 2 + col,
+                };
+                cursor = Some((area.x + x as u16, area.y + i as u16));
+            }
+            hex_spans.push(Span::styled(text, hex_style));
+            hex_spans.push(Span::raw(if col == 7 { "  " } else { " " }));
+            ascii_spans.push(Span::styled(ch.to_string(), ascii_style));
+        }
+        ascii_spans.push(Span::raw("|"));
+        hex_spans.extend(ascii_spans);
+        rows.push(Spans::from(hex_spans));
     }
+    frame.render_widget(Paragraph::new(rows), area);
+    if let Some((x, y)) = cursor {
+        frame.set_cursor(x, y);
+    }
+}
 
+// 同じスタイルが続くグラフェムをまとめて Span にする
+fn styled_spans(graphemes: &[&str], styles: &[Style]) -> Vec<Span<'static>> {
+    let mut spans = Vec::new();
+    let mut run = String::new();
+    let mut run_style = Style::default();
+    for (g, &style) in graphemes.iter().zip(styles) {
+        if style != run_style && !run.is_empty() {
+            spans.push(Span::styled(std::mem::take(&mut run), run_style));
+        }
+        run_style = style;
+        run.push_str(g);
+    }
+    if !run.is_empty() {
+        spans.push(Span::styled(run, run_style));
+    }
+    spans
 }
 
 fn draw_status_bar<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, area: Rect) {
     let total_lines = app.lines.len();
-    let (cur_line, cur_col) = (app.cursor_y + 1, app.cursor_x + 1);
+    let cur_line = app.display_line_number(app.cursor_y);
+    // 表示桁（全角は2桁、タブは次のタブ位置まで）。show_byte_offset なら行の中のバイト位置も出す
+    let mut cur_col = (grapheme::column(&app.lines[app.cursor_y], app.cursor_x, app.indent().width) + app.virtual_cols() + 1).to_string();
+    if app.config.show_byte_offset {
+        cur_col.push_str(&format!(", Byte {}", app.cursor_x + 1));
+    }
     let mode_text = match app.mode {
+        Mode::Editor if app.config.keymap == Keymap::Vim => match app.vim.mode {
+            VimMode::Normal => "Editor -- NORMAL --",
+            VimMode::Insert => "Editor -- INSERT --",
+            VimMode::Visual => "Editor -- VISUAL --",
+        },
         Mode::Editor => "Editor",
         Mode::FileTree => "FileTree",
     };
-    let status_text = format!(
-        "[RWE] {} | lines: {}  Ln {}, Col {}  (Ctrl+S=Save, Esc=Popup, F4=Help, F2=FileTree, F1=Editor)",
-        mode_text, total_lines, cur_line, cur_col
-    );
+    let overwrite = match app.mode {
+        Mode::Editor => format!("{}{}", if app.overwrite { " [OVR]" } else { "" }, if app.virtual_space { " [VS]" } else { "" }),
+        Mode::FileTree => String::new(),
+    };
+    let hint = match (app.io_progress(), &app.message, app.commit_message_status()) {
+        (Some(progress), _, _) => progress,
+        (None, Some(msg), _) => msg.clone(),
+        (None, None, Some(commit)) => commit,
+        (None, None, None) => "(Ctrl+S=Save, Esc=Popup, F4=Help, F2=FileTree, F1=Editor)".to_string(),
+    };
+    let matches = app.match_counter().map(|m| format!("[{}]  ", m)).unwrap_or_default();
+    let status_text = match app.hex {
+        Some(ref hex) => {
+            let value = hex.bytes.get(hex.cursor).map(|b| format!("  byte {:#04x} ({})", b, b)).unwrap_or_default();
+            format!("[RWE] {} -- HEX -- | offset {:#x} of {} bytes{}  {}", mode_text, hex.cursor, hex.bytes.len(), value, hint)
+        }
+        None => {
+            let info = if app.char_info { format!("[{}]  ", app.char_info()) } else { String::new() };
+            format!("[RWE] {}{} | lines: {}  Ln {}, Col {}  {}{}{}", mode_text, overwrite, total_lines, cur_line, cur_col, info, matches, hint)
+        }
+    };
     let style = match app.mode {
         Mode::FileTree => Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::LightBlue),
         _ => Style::default(),
@@ -838,32 +2919,161 @@ r#"=== Key Bindings Help ===
 
 -- General --
 F4 ....................... Toggle Help
-Esc ....................... Show popup (exit/save/cancel)
+F12 ...................... Toggle latency overlay (slow frames go to perf.log)
+Esc ....................... Exit; with unsaved changes asks: Y save and exit,
+                            N exit without saving, Esc cancel
+                            While a file is loading, Esc stops loading it (the status
+                            bar shows how much is read; large saves show progress too)
+Mouse ..................... Click the scrollbar to jump, drag its thumb, wheel scrolls
+                            (mouse = false in the config leaves the mouse to the terminal)
+Status bar Col ............ Display column: wide characters count 2, tabs go to the next
+                            tab stop (show_byte_offset = true adds the byte in the line)
 
 -- Editor Mode --
 Arrow keys ................ Move cursor (with horizontal scrolling)
 Shift + Arrow ............. Select region (highlighted in LightBlue)
+Home / End ................ First non-blank character (again: line start) / line end
+                            (with Shift: extend the selection)
+PgUp / PgDn ............... Move by a screen (with Shift: extend the selection)
+Ctrl + Home / End ......... Buffer start / end (with Shift: extend the selection)
+Alt + PgUp / PgDn ......... Move by half a screen (with Shift: extend the selection)
+Ctrl + l .................. Center the view on the cursor
 Ctrl + Left/Right ......... Move by word
+Ctrl + Backspace / Delete . Delete the word before / after the cursor (Alt+Backspace also
+                            deletes the word before). If the terminal sends Ctrl+Backspace
+                            as Ctrl+H: key.delete_word_left = Ctrl+H and
+                            key.delete_word_left = Ctrl+Backspace in the config
 Alt + Left/Right .......... Jump with acceleration (2^n)
 Ctrl + c .................. Copy
 Ctrl + x .................. Cut
 Ctrl + v .................. Paste
 Ctrl + a .................. Select all
+Ctrl + e .................. Select the line; again: extend by one line
+Ctrl + Shift + k .......... Delete the line (or the selected lines); many terminals send
+                            it as Ctrl+K. key.delete_line = <key> in the config rebinds it
+Ctrl + Shift + Delete ..... Cut to the end of the line (at the end: join the next line);
+                            repeated presses collect into one clipboard entry
+                            (key.kill_line = <key> rebinds it)
+Ctrl + Enter .............. Open an indented blank line below, wherever the cursor is
+                            (Ctrl+J also works)
+Ctrl + Shift + Enter ...... Open an indented blank line above (Alt+Enter also works)
 Ctrl + z / r .............. Undo / Redo
+Insert .................... Toggle overwrite mode: typing replaces the character at the
+                            cursor ([OVR] in the status bar)
+Alt + Insert .............. Toggle virtual space: the cursor goes past the end of the
+                            line and typing there pads with spaces ([VS]; config:
+                            virtual_space = true)
+Tab / Shift + Tab ......... Indent / outdent (the selected lines); the width and tabs or
+                            spaces come from indent_width / indent_style (per filetype)
 Ctrl + Up/Down ............ Scroll view
-Ctrl + f .................. Search text
+Ctrl + f .................. Search text (Alt+C case mode, Alt+W whole word)
+F3 / Shift + F3 ........... Next / previous match (Vim: n / N)
+Alt + h ................... Clear search highlights
 Ctrl + S .................. Save file
-n ......................... New file (popup)
+Alt + l ................... Set line number offset (popup)
+Alt + r ................... Reload file from disk (undoable)
+Alt + w ................... Toggle soft wrap
+F9 ........................ Markdown preview on the right (follows the editor's scrolling)
+Alt + v ................... Toggle whitespace markers (· space, → tab)
+Alt + Up/Down ............. Previous / next heading or section
+Alt + o ................... Jump to heading (outline picker)
+Ctrl + p .................. Find file by fuzzy name (Ctrl+T cycles sort)
+Ctrl + o .................. Open a file by path (Tab completes, ~ works; with nothing
+                            typed, Up/Down picks from the files opened this way)
+Ctrl + g .................. Show / hide .gitignore'd files (FileTree, Ctrl+P, Alt+G)
+Alt + g ................... Search in files (Enter searches, Enter again jumps)
+                            Ctrl+R in results: replace (preview, Space to pick)
+Alt + q ................... Open / close search results panel (Up/Down, Enter jumps)
+Alt + , / Alt + . ......... Jump back / forward (positions before searches, file opens)
+Alt + i / Alt + ' ......... Select inside brackets / quotes
+Alt + d / Alt + p ......... Select word / paragraph
+Ctrl + d .................. Select the word; again: add its next occurrence as another
+                            selection. Typing, Backspace and Delete then edit all of them
+                            at once; any other key (Esc too) goes back to one selection
+Ctrl + Space .............. Completion from language server (Up/Down, Enter/Tab inserts)
+Ctrl + b .................. Go to definition (language server; Alt+, returns)
+Alt + e ................... Open the file path under the cursor (path:line:col works)
+Alt + x ................... Open the URL under the cursor in the browser ($BROWSER)
+F8 ........................ Hex view / byte editor for this buffer (Tab switches the hex
+                            and ASCII panes, Insert/Del add/remove bytes, Ctrl+G goes to
+                            an offset, Ctrl+F searches bytes, F8 again returns to text)
+                            Files that are not UTF-8 text ask how to open: hex view,
+                            read-only, or as Latin-1 / UTF-16 (saved back the same way)
+                            Files over large_file_mb (config, default 50) ask first: open
+                            the whole file, or only the beginning read-only
+Alt + n ................... Rename symbol across files (language server)
+Alt + = ................... Format with the filetype's formatter command, else the
+                            language server (only the selection, if any)
+Alt + s / Alt + a ......... Expand / shrink selection to the enclosing syntax node
+Alt + j / Alt + k ......... Next / previous change against git HEAD (gutter markers)
+Alt + c ................... Git: stage files and write a commit message (Alt+c again commits)
+Alt + u ................... Diff of unsaved changes against the file on disk
+Alt + z ................... Format the selection (or whole buffer) as JSON; again to
+                            minify. Invalid JSON moves the cursor to the error
+Ctrl + t .................. Transform the selection: base64, URL or hex encode / decode
+Ctrl + g .................. Line commands on the selected lines (or the whole buffer):
+                            remove duplicate lines, keep only unique lines, reverse lines,
+                            retab the indentation to spaces or tabs, expand all tabs
+Ctrl + w .................. Reflow the paragraph (or selected lines) to text_width,
+                            keeping indentation, comment markers and list items
+Ctrl + u .................. Insert a character by hex codepoint (00E9, U+1F600) or pick
+                            a symbol by name
+Alt + ; ................... Show codepoints, UTF-8 bytes and width of the character at
+                            the cursor in the status bar (toggle)
+Alt + / ................... Recent messages: saves, file operations and their errors
+Alt + | ................... Pipe selection (or whole buffer) through a shell command
+Alt + ! ................... Insert output of a shell command at the cursor
+Alt + t ................... Run a task from the config (task.<name> = <command>)
+Alt + y ................... Open / close task output (Up/Down scroll, Ctrl+C stops)
+Alt + m ................... Spelling suggestions for the word at the cursor, or add it
+                            to the dictionary (underlines need spell_check = true)
+n ......................... New file (popup; an existing name asks: Y empty it,
+                            O open it, Esc pick another name)
 m ......................... Rename/Move (popup)
-Del ....................... Delete (in FileTree mode)
+Left / Right / Enter ...... Collapse / expand directories in place (in FileTree mode;
+                            Left on a top-level entry moves the root up)
+Ctrl + Right .............. Make the selected directory the FileTree root
+Type letters .............. Filter FileTree entries (fuzzy); Enter opens the best match,
+                            Esc clears the filter
+Space ..................... Mark / unmark the entry for Del, F5 and F6 (in FileTree mode;
+                            Esc clears the marks)
+Del ....................... Move to trash after confirming (in FileTree mode;
+                            delete_permanently = true in the config skips the trash)
+F5 ........................ Copy the marked (or selected) entries (in FileTree mode)
+F6 ........................ Rename the selected entry, or move the marked ones (in FileTree mode)
+F7 ........................ New directory under the current path (in FileTree mode)
+Ctrl + d .................. Toggle detail view: type, size, age (in FileTree mode)
+
+-- Vim Keymap (config: keymap = vim) --
+i / a / I / A / o / O ..... Enter insert mode (Esc returns to normal)
+h j k l / w b e / 0 $ ..... Motions (gg / G jump to first / last line)
+gd ........................ Go to definition (language server)
+gf ........................ Open the file path under the cursor
+gx ........................ Open the URL under the cursor
+Ctrl + o / Tab ............ Jump back / forward
+Ctrl + d / u .............. Half a screen down / up
+d / y / c + motion ........ Delete / yank / change (dd, yy, cc = line)
+x / D / C / p / P / u ..... Delete char, to EOL, put, undo
+v ......................... Visual mode (d / y / c on selection)
+i( i[ i{ i" i' iw ip ...... Text objects after d / y / c or in visual mode
+[count] ................... Prefix counts, e.g. 3dw, 5j
+
+-- Emacs Keymap (config: keymap = emacs) --
+Ctrl + a / e .............. Line start / end
+Ctrl + k .................. Kill to end of line (repeated kills are yanked together)
+Ctrl + y .................. Yank (paste)
+Alt + f / b ............... Move by word
 
 -- FileTree Mode --
 F2 ....................... Switch to FileTree mode
-Number key (1-9) ........ Open corresponding file (by line number)
+Number key (1-9) ........ Open corresponding file (by line number; while filtering,
+                           digits go to the filter)
 Up/Down .................. Navigate entries
 Right ..................... Enter directory
 Left ...................... Go up a directory
 Enter .................... Open selected file
+Ctrl + r ................. Toggle recently modified files view
+Ctrl + t ................. Cycle sort order (name / mtime / size / frecency)
 F1 ....................... Switch to Editor mode
 "#
     );
@@ -883,7 +3093,12 @@ fn draw_file_tree<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, are
         .constraints([Constraint::Length(2), Constraint::Min(1), Constraint::Length(1)].as_ref())
         .split(area);
     // ヘッダー：パス表示（2行、折り返し）
-    let header = Paragraph::new(format!("Path: {}", app.file_tree.current_path.display()))
+    let path = app.file_tree.current_path.display();
+    let header_text = match app.file_tree.git {
+        Some(ref git) => format!("Path: {} | git: {}", path, git.branch),
+        None => format!("Path: {}", path),
+    };
+    let header = Paragraph::new(header_text)
         .wrap(Wrap { trim: true })
         .style(Style::default().fg(Color::White).bg(Color::Rgb(33, 40, 48)));
     frame.render_widget(header, chunks[0]);
@@ -899,54 +3114,138 @@ fn draw_file_tree<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, are
     ft_clone.update_scroll(visible);
     for (i, entry) in ft_clone.entries.iter().enumerate().skip(ft_clone.scroll_offset).take(visible) {
         let idx = i + 1;
-        let file_name = entry.file_name().into_string().unwrap_or_default();
-        let text = format!("{}: {}", idx, file_name);
-        let style = if i == ft_clone.selected {
-            Style::default().bg(Color::Gray).fg(Color::Black)
-        } else {
-            Style::default().fg(Color::White)
+        let file_name = format!("{}{}{}", ft_clone.tree_prefix(entry, app.deterministic), ft_clone.display_name(entry), entry.link_suffix());
+        // 詳細表示なら右端に揃えて出す。そうでなければ並び順に合わせて更新からの経過時間かサイズを添える
+        if ft_clone.details {
+            let age = entry.modified.filter(|_| !app.deterministic).and_then(|t| t.elapsed().ok()).map(format_age);
+            let detail = format!(
+                " {:>4} {:>6} {:>4}",
+                entry.kind(),
+                entry.size.map(format_size).unwrap_or_else(|| "-".to_string()),
+                age.unwrap_or_else(|| "-".to_string())
+            );
+            // 名前が長ければ詳細の欄を残して切り詰める
+            let room = (list_chunks[0].width as usize).saturating_sub(detail.width());
+            let mark = if ft_clone.marked.contains(&entry.path) { '*' } else { ' ' };
+            let mut left = format!("{}:{}{}", idx, mark, file_name);
+            while left.width() > room && left.pop().is_some() {}
+            let pad = room.saturating_sub(left.width());
+            items.push(Spans::from(Span::styled(format!("{}{}{}", left, " ".repeat(pad), detail), entry_style(&ft_clone, entry, i))));
+            continue;
+        }
+        let detail = match ft_clone.sort_mode() {
+            SortMode::Size => entry.size.map(format_size),
+            SortMode::Modified | SortMode::Frecency => entry
+                .modified
+                .filter(|_| !app.deterministic)
+                .and_then(|t| t.elapsed().ok())
+                .map(|age| format!("{} ago", format_age(age))),
+            SortMode::Name => None,
+        };
+        // 印を付けた項目は番号の後ろに *
+        let mark = if ft_clone.marked.contains(&entry.path) { '*' } else { ' ' };
+        let text = match detail {
+            Some(detail) => format!("{}:{}{} ({})", idx, mark, file_name, detail),
+            None => format!("{}:{}{}", idx, mark, file_name),
         };
-        items.push(Spans::from(Span::styled(text, style)));
+        items.push(Spans::from(Span::styled(text, entry_style(&ft_clone, entry, i))));
+    }
+    if let Some(ref error) = ft_clone.error
+        && ft_clone.view == TreeView::Directory
+    {
+        items.push(Spans::from(Span::styled(format!("Cannot read this directory: {}", error), Style::default().fg(Color::LightRed))));
     }
     let list = Paragraph::new(items)
         .wrap(Wrap { trim: true })
         .style(Style::default().bg(Color::Rgb(33, 40, 48)));
     frame.render_widget(list, list_chunks[0]);
     // スクロールバー
-    let total_entries = ft_clone.entries.len();
-    let mut sb_items = Vec::new();
-    if total_entries <= visible {
-        for _ in 0..visible { sb_items.push(Spans::from(" ")); }
-    } else {
-        let max_scroll = total_entries.saturating_sub(visible);
-        let ratio = ft_clone.scroll_offset as f32 / max_scroll as f32;
-        let thumb = (ratio * (visible - 1) as f32).round() as usize;
-        for i in 0..visible {
-            if i == thumb { sb_items.push(Spans::from("█")); }
-            else { sb_items.push(Spans::from(" ")); }
-        }
-    }
+    let thumb = scrollbar_rows(ft_clone.entries.len(), visible, ft_clone.scroll_offset);
+    let sb_items: Vec<Spans> = (0..visible)
+        .map(|i| Spans::from(if thumb.contains(&i) { app.scrollbar_thumb() } else { " " }))
+        .collect();
     let sb = Paragraph::new(sb_items)
         .wrap(Wrap { trim: true })
         .style(Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::LightBlue));
     frame.render_widget(sb, list_chunks[1]);
     // 下部ステータスバー（FileTree用）
-    let status = Paragraph::new(format!("FileTree: {} entries", ft_clone.entries.len()))
+    let view_text = match ft_clone.view {
+        TreeView::Directory => "",
+        TreeView::Recent => " (recently modified)",
+    };
+    let ignored_text = if ft_clone.show_ignored { ", ignored shown" } else { "" };
+    let filter_text = if ft_clone.filter.is_empty() { String::new() } else { format!(", filter: {} (Esc clears)", ft_clone.filter) };
+    let marked_text = if ft_clone.marked.is_empty() { String::new() } else { format!(", {} marked", ft_clone.marked.len()) };
+    let status = Paragraph::new(format!(
+        "FileTree: {} entries{}, sort: {}{}{}{}",
+        ft_clone.entries.len(),
+        view_text,
+        ft_clone.sort_mode().label(),
+        ignored_text,
+        filter_text,
+        marked_text
+    ))
         .style(Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::LightBlue));
     frame.render_widget(status, chunks[2]);
 }
 
+// FileTree の項目の色。選択中は反転し、それ以外は git の状態で色分け
+fn entry_style(ft: &FileTree, entry: &TreeEntry, i: usize) -> Style {
+    if i == ft.selected {
+        return Style::default().bg(Color::Gray).fg(Color::Black);
+    }
+    let color = match ft.git.as_ref().and_then(|git| git.status(&entry.path)) {
+        Some(FileStatus::Modified) => Color::Yellow,
+        Some(FileStatus::Staged) => Color::LightGreen,
+        Some(FileStatus::Untracked) => Color::LightRed,
+        Some(FileStatus::Ignored) => Color::DarkGray,
+        None if entry.broken => Color::Magenta,
+        None if entry.link.is_some() => Color::Cyan,
+        None => Color::White,
+    };
+    Style::default().fg(color)
+}
+
+// ファイルサイズを短く表示 (例: 512B, 1.5K, 12M)
+fn format_size(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["K", "M", "G", "T"];
+    if bytes < 1024 { return format!("{}B", bytes); }
+    let mut size = bytes as f64 / 1024.0;
+    let mut unit = 0;
+    while size >= 1024.0 && unit + 1 < UNITS.len() {
+        size /= 1024.0;
+        unit += 1;
+    }
+    if size < 10.0 { format!("{:.1}{}", size, UNITS[unit]) } else { format!("{:.0}{}", size, UNITS[unit]) }
+}
+
+// 経過時間を短く表示 (例: 42s, 5m, 3h, 2d)
+fn format_age(age: Duration) -> String {
+    let secs = age.as_secs();
+    match secs {
+        0..=59 => format!("{}s", secs),
+        60..=3599 => format!("{}m", secs / 60),
+        3600..=86399 => format!("{}h", secs / 3600),
+        _ => format!("{}d", secs / 86400),
+    }
+}
+
 fn draw_file_tree_mode<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
     let size = frame.size();
     let chunks = Layout::default()
         .direction(Direction::Horizontal)
         .constraints([Constraint::Percentage(70), Constraint::Percentage(30)].as_ref())
         .split(size);
-    // 左側：エディタプレビュー（状態更新なし）
+    // 左側：選択中の項目の中身。開いているファイルを選んでいればエディタ（状態更新なし）
     let vertical_chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)])
         .split(chunks[0]);
+    if let Some(peek) = app.file_tree.peek.as_ref().filter(|p| app.current_file.as_ref() != Some(&p.path)) {
+        draw_peek(frame, app, peek, vertical_chunks.as_slice());
+        draw_file_tree(frame, app, chunks[1]);
+        return;
+    }
     draw_header(frame, app, vertical_chunks[0]);
     let editor_chunks_vec = Layout::default()
         .direction(Direction::Horizontal)
@@ -963,7 +3262,219 @@ fn draw_file_tree_mode<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App
     draw_file_tree(frame, app, chunks[1]);
 }
 
+// FileTree で選んでいる項目の中身（ファイルは行番号つき、折り返さない）
+fn draw_peek<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App, peek: &Peek, areas: &[Rect]) {
+    let bg = Style::default().bg(Color::Rgb(33, 40, 48));
+    let name = peek.path.strip_prefix(&app.file_tree.current_path).unwrap_or(&peek.path);
+    let header = Paragraph::new(format!("Preview: {}", name.display())).style(bg.fg(Color::Rgb(222, 165, 132)));
+    frame.render_widget(header, areas[0]);
+    let body: Vec<Spans> = match &peek.note {
+        Some(note) => vec![Spans::from(Span::styled(note.clone(), Style::default().fg(Color::DarkGray)))],
+        None => {
+            let width = peek.lines.len().to_string().len();
+            peek.lines
+                .iter()
+                .take(areas[1].height as usize)
+                .enumerate()
+                .map(|(i, line)| match peek.dir {
+                    true => Spans::from(Span::styled(line.clone(), Style::default().fg(Color::LightBlue))),
+                    false => Spans::from(vec![
+                        Span::styled(format!("{:>width$} ", i + 1), Style::default().fg(Color::DarkGray)),
+                        Span::raw(line.clone()),
+                    ]),
+                })
+                .collect()
+        }
+    };
+    frame.render_widget(Paragraph::new(body), areas[1]);
+    let hint = app.message.clone().unwrap_or_else(|| "Enter opens a file or expands a directory".to_string());
+    let status = Paragraph::new(format!("[RWE] FileTree | preview  {}", hint)).style(bg.fg(Color::LightBlue));
+    frame.render_widget(status, areas[2]);
+}
+
+// 入力欄つきのリスト型ポップアップ
+fn draw_list_popup<B: tui::backend::Backend>(frame: &mut Frame<B>, title: &str, input: &str, items: &[String], selected: usize) {
+    let size = frame.size();
+    let area = Rect {
+        x: size.width / 10,
+        y: size.height / 10,
+        width: size.width - size.width / 5,
+        height: size.height - size.height / 5,
+    };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(3), Constraint::Min(1)])
+        .split(area);
+    let bg = Style::default().bg(Color::Rgb(33, 40, 48));
+    let input_box = Paragraph::new(input.to_string())
+        .block(Block::default().title(title).borders(Borders::ALL).style(bg));
+    frame.render_widget(input_box, chunks[0]);
+    let visible = chunks[1].height.saturating_sub(2) as usize;
+    let selected = selected.min(items.len().saturating_sub(1));
+    let skip = (selected + 1).saturating_sub(visible);
+    let lines: Vec<Spans> = items
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(i, item)| {
+            let style = if i == selected {
+                Style::default().bg(Color::Gray).fg(Color::Black)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            Spans::from(Span::styled(item.clone(), style))
+        })
+        .collect();
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).style(bg));
+    frame.render_widget(list, chunks[1]);
+}
+
+// 差分の表示（上書き確認など）。削除行を赤、追加行を緑で表示
+// 中央に出す確認。choices は (キー, 説明)
+fn draw_confirm_dialog<B: tui::backend::Backend>(frame: &mut Frame<B>, title: &str, text: &str, choices: &[(String, &str)]) {
+    let size = frame.size();
+    let key = Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD);
+    let mut keys = Vec::new();
+    for (i, (k, label)) in choices.iter().enumerate() {
+        keys.push(Span::styled(k.clone(), key));
+        keys.push(Span::raw(if i + 1 < choices.len() { format!(" {}   ", label) } else { format!(" {}", label) }));
+    }
+    let lines = vec![Spans::from(text.to_string()), Spans::from(""), Spans::from(keys)];
+    let width = (lines.iter().map(|l| l.width()).max().unwrap_or(0) as u16 + 4).min(size.width);
+    let height = 5.min(size.height);
+    let area = Rect { x: (size.width - width) / 2, y: (size.height - height) / 2, width, height };
+    let block = Block::default()
+        .title(format!(" {} ", title))
+        .borders(Borders::ALL)
+        .border_style(Style::default().fg(Color::LightRed))
+        .style(Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::White));
+    frame.render_widget(Paragraph::new(lines).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_diff_popup<B: tui::backend::Backend>(frame: &mut Frame<B>, title: &str, preview: &[String], scroll: usize) {
+    let size = frame.size();
+    let area = Rect {
+        x: size.width / 10,
+        y: size.height / 10,
+        width: size.width - size.width / 5,
+        height: size.height - size.height / 5,
+    };
+    let lines: Vec<Spans> = preview
+        .iter()
+        .skip(scroll.min(preview.len().saturating_sub(1)))
+        .map(|line| {
+            let style = match line.chars().next() {
+                Some('-') => Style::default().fg(Color::LightRed),
+                Some('+') => Style::default().fg(Color::LightGreen),
+                Some('@') => Style::default().fg(Color::Cyan),
+                Some('!') => Style::default().fg(Color::Yellow),
+                _ => Style::default().fg(Color::White),
+            };
+            Spans::from(Span::styled(line.clone(), style))
+        })
+        .collect();
+    let block = Block::default().title(title).borders(Borders::ALL).style(Style::default().bg(Color::Rgb(33, 40, 48)));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
 fn draw_popup<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
+    if let Some(PopupMode::Prompt(ref prompt)) = app.popup {
+        let title = prompt.title(app);
+        match prompt.kind {
+            prompt::Kind::Text => draw_input_popup(frame, &title, &app.popup_input),
+            prompt::Kind::List(items) => draw_list_popup(frame, &title, &app.popup_input, &items(app), app.popup_selected),
+            prompt::Kind::Keys { ref question, ref keys, esc, .. } => {
+                let mut choices: Vec<(String, &str)> = keys.iter().map(|&(k, label)| (k.to_uppercase().to_string(), label)).collect();
+                choices.push(("Esc".to_string(), esc));
+                draw_confirm_dialog(frame, &title, question, &choices);
+            }
+        }
+        return;
+    }
+    if let Some(PopupMode::Finder) = app.popup {
+        let matches = app.finder_matches();
+        let items: Vec<String> = matches.iter().map(|e| e.display.clone()).collect();
+        let title = format!(
+            "Find File: {}/{} (Ctrl+T sort: {}, Ctrl+G ignored: {})",
+            matches.len(),
+            app.finder.entries.len(),
+            app.finder.sort.label(),
+            if app.finder.show_ignored { "shown" } else { "hidden" }
+        );
+        draw_list_popup(frame, &title, &app.popup_input, &items, app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::Grep) = app.popup {
+        let items = app.grep_items();
+        let opts = app.search_opts.label();
+        let title = if app.grep.query == app.popup_input && !app.popup_input.is_empty() {
+            format!("Search in Files {}: {} matches (Enter to jump, Ctrl+R to replace)", opts, items.len())
+        } else {
+            let ignored = if app.finder.show_ignored { "shown" } else { "hidden" };
+            format!("Search in Files {}: Enter to search, Alt+C case, Alt+W word, Ctrl+G ignored: {}", opts, ignored)
+        };
+        draw_list_popup(frame, &title, &app.popup_input, &items, app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::ReplacePreview) = app.popup {
+        let chosen = app.grep.chosen.iter().filter(|&&c| c).count();
+        let title = format!(
+            "Replace {:?} with {:?}: {}/{} selected (Space toggle, a all, Enter apply, Esc cancel)",
+            app.grep.query,
+            app.grep.replacement,
+            chosen,
+            app.grep.hits.len()
+        );
+        draw_list_popup(frame, &title, "", &app.replace_items(), app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::Spelling { ref word, ref suggestions, .. }) = app.popup {
+        let title = if suggestions.is_empty() {
+            format!("Spelling: no suggestions for \"{}\" (Enter to add, Esc to cancel)", word)
+        } else {
+            format!("Spelling: replace \"{}\" with (Enter to pick, Esc to cancel)", word)
+        };
+        draw_list_popup(frame, &title, "", &app.spelling_items(), app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::GitCommit) = app.popup {
+        let title = "Git: Space stage/unstage, a all, Enter write message, Esc close";
+        draw_list_popup(frame, title, "", &app.commit_items(), app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::OverwriteDiff { ref path, ref preview }) = app.popup {
+        let title = format!("Overwrite {}? (y)es / (n)o - contents differ", path.display());
+        draw_diff_popup(frame, &title, preview, 0);
+        return;
+    }
+    if let Some(PopupMode::OpenChoice { ref choices, .. }) = app.popup {
+        let items: Vec<String> = choices.iter().map(OpenAs::label).collect();
+        draw_list_popup(frame, &app.open_choice_title(), "", &items, app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::ConfirmDelete { ref preview, .. }) = app.popup {
+        draw_diff_popup(frame, &app.delete_title(), preview, app.popup_selected);
+        return;
+    }
+    if let Some(PopupMode::DiffView { ref title, ref lines }) = app.popup {
+        draw_diff_popup(frame, title, lines, app.popup_selected);
+        return;
+    }
+    let search_title = format!("Search {}: Enter to find, Alt+C case, Alt+W word", app.search_opts.label());
+    let transfer_title = app.transfer_title();
+    let title = match app.popup.clone().unwrap() {
+        PopupMode::RenameEntry { .. } => "Rename: Enter new name (relative to the entry's directory)",
+        PopupMode::ReplaceWith => "Replace in Files: Enter replacement text",
+        PopupMode::Search => &search_title,
+        PopupMode::TransferEntries { .. } => &transfer_title,
+        PopupMode::Prompt(_) | PopupMode::Finder | PopupMode::Grep | PopupMode::ReplacePreview | PopupMode::GitCommit | PopupMode::OverwriteDiff { .. } | PopupMode::DiffView { .. } | PopupMode::Spelling { .. } | PopupMode::ConfirmDelete { .. } | PopupMode::OpenChoice { .. } => "",
+    };
+    draw_input_popup(frame, title, &app.popup_input);
+}
+
+// 1行の入力欄（画面の中央）
+fn draw_input_popup<B: tui::backend::Backend>(frame: &mut Frame<B>, title: &str, input: &str) {
     let size = frame.size();
     let popup_area = Layout::default()
         .direction(Direction::Vertical)
@@ -981,225 +3492,590 @@ fn draw_popup<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
             Constraint::Percentage(20),
         ])
         .split(popup_area)[1];
-    let title = match app.popup.clone().unwrap() {
-        PopupMode::ExitPrompt => "Exit Options: (e)xit, (s)ave, (c)ancel",
-        PopupMode::NewFile => "New File: Enter file name",
-        PopupMode::Rename => "Rename/Move: Enter new name",
-        PopupMode::SaveFile => "Save As: Enter file name",
-    };
     let block = Block::default().title(title).borders(Borders::ALL).style(Style::default().bg(Color::Rgb(33, 40, 48)));
-    let paragraph = Paragraph::new(app.popup_input.clone())
+    let paragraph = Paragraph::new(input.to_string())
         .block(block)
         .wrap(Wrap { trim: true });
     frame.render_widget(paragraph, popup_area);
 }
 
+// 検索結果パネル。フォーカス中は枠を強調する
+fn draw_results_panel<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    let height = area.height.saturating_sub(2) as usize;
+    app.results.update_scroll(height);
+    let panel = &app.results;
+    let current = app.current_file.as_ref();
+    let lines: Vec<Spans> = panel
+        .items
+        .iter()
+        .enumerate()
+        .skip(panel.scroll)
+        .take(height)
+        .map(|(i, item)| {
+            let location = match item.path {
+                Some(ref p) if Some(p) != current => {
+                    let rel = p.strip_prefix(&app.finder.root).unwrap_or(p);
+                    format!("{}:{}:{}", rel.display(), item.line + 1, item.col + 1)
+                }
+                _ => format!("{}:{}", app.display_line_number(item.line), item.col + 1),
+            };
+            let style = if i == panel.selected {
+                Style::default().bg(Color::Gray).fg(Color::Black)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            Spans::from(vec![
+                Span::styled(format!("{}: ", location), style.fg(if i == panel.selected { Color::Black } else { Color::LightBlue })),
+                Span::styled(item.text.trim().to_string(), style),
+            ])
+        })
+        .collect();
+    let border = if panel.focused { Style::default().fg(Color::Yellow) } else { Style::default().fg(Color::DarkGray) };
+    let title = format!(" {} ({}/{}) Alt+Q ", panel.title, panel.selected + 1, panel.items.len());
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(border)
+        .style(Style::default().bg(Color::Rgb(33, 40, 48)));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// タスクの出力パネル。タイトルに実行中・成功・失敗を色で出す
+fn draw_task_panel<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &mut App, area: Rect) {
+    let height = area.height.saturating_sub(2) as usize;
+    app.task.update_scroll(height);
+    let panel = &app.task;
+    let lines: Vec<Spans> = panel
+        .lines
+        .iter()
+        .skip(panel.scroll)
+        .take(height)
+        .map(|line| Spans::from(Span::styled(line.clone(), Style::default().fg(Color::White))))
+        .collect();
+    let (status, color) = match panel.status {
+        TaskStatus::Running => ("running".to_string(), Color::Yellow),
+        TaskStatus::Success => ("ok".to_string(), Color::LightGreen),
+        TaskStatus::Failed(Some(code)) => (format!("failed: exit {}", code), Color::LightRed),
+        TaskStatus::Failed(None) => ("stopped".to_string(), Color::LightRed),
+    };
+    let title = Spans::from(vec![
+        Span::raw(format!(" Task: {} ", panel.name)),
+        Span::styled(format!("[{}]", status), Style::default().fg(color).add_modifier(Modifier::BOLD)),
+        Span::raw(" Alt+Y "),
+    ]);
+    let border = if panel.focused { Style::default().fg(Color::Yellow) } else { Style::default().fg(Color::DarkGray) };
+    let block = Block::default()
+        .title(title)
+        .borders(Borders::ALL)
+        .border_style(border)
+        .style(Style::default().bg(Color::Rgb(33, 40, 48)));
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// 右上に重ねる計測表示。直前のフレームが予算を超えたら警告色
+// 補完候補。カーソルの下（入らなければ上）に出す
+fn draw_completion<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
+    let (Some(comp), Some((cx, cy))) = (app.completion.as_ref(), app.cursor_screen) else { return };
+    let size = frame.size();
+    let rows: Vec<(String, String)> = comp.visible_items().map(|item| (item.label.clone(), item.detail.clone())).collect();
+    let label_width = rows.iter().map(|r| r.0.width()).max().unwrap_or(0).min(40);
+    let width = rows
+        .iter()
+        .map(|r| label_width + if r.1.is_empty() { 0 } else { r.1.width() + 2 })
+        .max()
+        .unwrap_or(0)
+        .min(70) as u16
+        + 2;
+    let width = width.min(size.width);
+    let height = (rows.len() as u16).min(10) + 2;
+    let y = if cy + 1 + height <= size.height { cy + 1 } else { cy.saturating_sub(height) };
+    let x = cx.min(size.width - width);
+    let area = Rect { x, y, width, height: height.min(size.height - y) };
+    let visible = area.height.saturating_sub(2) as usize;
+    let skip = (comp.selected + 1).saturating_sub(visible);
+    let lines: Vec<Spans> = rows
+        .iter()
+        .enumerate()
+        .skip(skip)
+        .take(visible)
+        .map(|(i, (label, detail))| {
+            let style = if i == comp.selected {
+                Style::default().bg(Color::Gray).fg(Color::Black)
+            } else {
+                Style::default().fg(Color::White)
+            };
+            let label: String = label.chars().take(40).collect();
+            let pad = " ".repeat(label_width.saturating_sub(label.width()));
+            Spans::from(vec![
+                Span::styled(format!("{}{}", label, pad), style),
+                Span::styled(format!("  {}", detail), style.fg(if i == comp.selected { Color::Black } else { Color::DarkGray })),
+            ])
+        })
+        .collect();
+    frame.render_widget(tui::widgets::Clear, area);
+    let list = Paragraph::new(lines).block(Block::default().borders(Borders::ALL).style(Style::default().bg(Color::Rgb(33, 40, 48))));
+    frame.render_widget(list, area);
+}
+
+// 保存やファイル操作の結果をステータスバーの上、右端に出す（失敗は赤）
+fn draw_notice<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
+    let Some(ref notice) = app.notices.current else { return };
+    let size = frame.size();
+    if size.height < 6 || size.width < 12 { return; }
+    // 幅は画面の 2/3 まで。収まらなければ3行まで折り返す
+    let inner = (notice.text.width() as u16).clamp(1, size.width * 2 / 3 - 2);
+    let rows = ((notice.text.width() as u16).div_ceil(inner)).clamp(1, 3);
+    let area = Rect { x: size.width - inner - 2, y: size.height - rows - 3, width: inner + 2, height: rows + 2 };
+    let (title, color) = match notice.level {
+        Level::Ok => (" ok ", Color::LightGreen),
+        Level::Error => (" error ", Color::LightRed),
+    };
+    let border = Style::default().fg(color).add_modifier(Modifier::BOLD);
+    let block = Block::default()
+        .title(Span::styled(title, border))
+        .borders(Borders::ALL)
+        .border_style(border)
+        .style(Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::White));
+    frame.render_widget(tui::widgets::Clear, area);
+    frame.render_widget(Paragraph::new(notice.text.as_str()).block(block).wrap(Wrap { trim: false }), area);
+}
+
+fn draw_debug_overlay<B: tui::backend::Backend>(frame: &mut Frame<B>, app: &App) {
+    let size = frame.size();
+    let width = 56.min(size.width);
+    let area = Rect { x: size.width - width, y: 1.min(size.height), width, height: 5.min(size.height) };
+    let perf = &app.perf;
+    let describe = |s: &perf::FrameStats| {
+        format!("{:>5.1}ms (upd {:.1} / draw {:.1})", perf::ms(s.total()), perf::ms(s.update), perf::ms(s.draw))
+    };
+    let lines = vec![
+        Spans::from(match perf.last {
+            Some(ref s) => format!("last  {} {}", describe(s), s.trigger),
+            None => "last  -".to_string(),
+        }),
+        Spans::from(match perf.worst {
+            Some(ref s) => format!("worst {} {}", describe(s), s.trigger),
+            None => "worst -".to_string(),
+        }),
+        Spans::from(format!("slow  {}/{} frames over {}ms", perf.slow_frames, perf.frames, perf.budget.as_millis())),
+    ];
+    let (title, border) = if perf.over_budget() {
+        (" ! over budget ", Style::default().fg(Color::LightRed).add_modifier(Modifier::BOLD))
+    } else {
+        (" perf ", Style::default().fg(Color::LightBlue))
+    };
+    let block = Block::default()
+        .title(Span::styled(title, border))
+        .borders(Borders::ALL)
+        .border_style(border)
+        .style(Style::default().bg(Color::Rgb(33, 40, 48)).fg(Color::White));
+    frame.render_widget(tui::widgets::Clear, area);
+    frame.render_widget(Paragraph::new(lines).block(block), area);
+}
+
+// コマンドライン引数: rwe [--deterministic] [FILE]
+struct Args {
+    deterministic: bool,
+    file: Option<PathBuf>,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args { deterministic: false, file: None };
+    for arg in std::env::args().skip(1) {
+        match arg.as_str() {
+            "--deterministic" => args.deterministic = true,
+            s if s.starts_with("--") => return Err(format!("unknown option: {}", s)),
+            _ if args.file.is_none() => args.file = Some(PathBuf::from(arg)),
+            _ => return Err("only one file can be given".to_string()),
+        }
+    }
+    Ok(args)
+}
+
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    let args = match parse_args() {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("rwe: {}\nusage: rwe [--deterministic] [FILE]", e);
+            std::process::exit(2);
+        }
+    };
+    term::install_panic_hook();
+    let _guard = term::TermGuard::enter()?;
+    let backend = CrosstermBackend::new(io::stdout());
     let mut terminal = Terminal::new(backend)?;
     let mut app = App::new();
+    logging::init(app.config.log_level);
+    logging::info("start", &[("version", &env!("CARGO_PKG_VERSION")), ("file", &args.file.as_deref().unwrap_or(Path::new("")).display())]);
+    if args.deterministic {
+        // 外部状態（システムのクリップボード）にも依存しない
+        app.deterministic = true;
+        app.clipboard_ctx = None;
+    }
+    if app.config.mouse {
+        term::enable_mouse()?;
+    }
+    if let Some(path) = args.file {
+        if path.exists() {
+            app.open_path(path);
+        } else {
+            app.current_file = Some(path);
+        }
+    }
 
+    // SIGHUP（端末切断）/ SIGTERM を受けたらリカバリファイルを書いて終了
+    let terminate = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&terminate))?;
+    signal_hook::flag::register(SIGTERM, Arc::clone(&terminate))?;
+    let wakeups = wake::start(&[SIGHUP, SIGTERM])?;
+
+    let mut last_draw = Instant::now();
     'main_loop: loop {
-        terminal.draw(|frame| {
-            if let Some(_) = app.popup {
-                draw_popup(frame, &app);
-            } else if app.help_visible {
-                draw_help_screen(frame, &app);
-            } else if let Mode::FileTree = app.mode {
-                draw_file_tree_mode(frame, &app);
-            } else {
-                let size = frame.size();
-                let vertical_chunks = Layout::default()
-                    .direction(Direction::Vertical)
-                    .constraints([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)])
-                    .split(size);
-                draw_header(frame, &app, vertical_chunks[0]);
-                let editor_chunks_vec = Layout::default()
-                    .direction(Direction::Horizontal)
-                    .constraints([
-                        Constraint::Length(app.line_number_width() as u16 + 1),
-                        Constraint::Min(1),
-                        Constraint::Length(1),
-                    ])
-                    .split(vertical_chunks[1]);
-                let editor_chunks: [Rect; 3] = editor_chunks_vec.try_into().unwrap();
-                draw_editor(frame, &mut app, editor_chunks, true);
-                draw_status_bar(frame, &app, vertical_chunks[2]);
+        app.poll_saves();
+        app.poll_load();
+        app.poll_lsp();
+        app.poll_external();
+        app.poll_task();
+        app.sync_syntax();
+        app.sync_match_count();
+        app.sync_git_gutter();
+        app.load_spelling();
+        app.keep_read_only();
+        app.expire_notice(false);
+        if let Mode::FileTree = app.mode {
+            if app.file_tree.poll_changes() { app.dirty = true; }
+            if app.file_tree.sync_peek() { app.dirty = true; }
+            // 経過時間の表示は1秒ごとに描き直す
+            if app.file_tree.shows_ages() && !app.deterministic && last_draw.elapsed() >= Duration::from_secs(1) {
+                app.dirty = true;
             }
-        })?;
+        }
+        if terminate.load(Ordering::Relaxed) {
+            app.flush_saves();
+            if let Err(e) = app.write_recovery() {
+                logging::error("recovery", &[("path", &app.recovery_path().display()), ("error", &e)]);
+            }
+            logging::info("exit", &[("reason", &"signal")]);
+            break 'main_loop;
+        }
+        // 終了の確認で保存を選び、Save As を閉じた。保存したなら書き終えてから終わる
+        if app.exit_after_save && app.popup.is_none() {
+            app.exit_after_save = false;
+            if !app.modified { app.exit_when_saved(); }
+        }
+        if app.quit {
+            app.flush_saves();
+            logging::info("exit", &[]);
+            break 'main_loop;
+        }
 
-        if poll(Duration::from_millis(100))? {
-            if let Some(_) = app.popup {
-                if let Event::Key(KeyEvent { code, .. }) = read()? {
-                    app.handle_popup(code);
+        // 何も変わっていなければ描き直さない
+        if app.dirty {
+            app.dirty = false;
+            last_draw = Instant::now();
+            app.perf.update_done();
+            terminal.draw(|frame| {
+                if app.popup.is_some() {
+                    draw_popup(frame, &app);
+                } else if app.help_visible {
+                    draw_help_screen(frame, &app);
+                } else if let Mode::FileTree = app.mode {
+                    draw_file_tree_mode(frame, &app);
+                } else {
+                    let size = frame.size();
+                    let vertical_chunks = Layout::default()
+                        .direction(Direction::Vertical)
+                        .constraints([Constraint::Length(1), Constraint::Min(1), Constraint::Length(1)])
+                        .split(size);
+                    draw_header(frame, &app, vertical_chunks[0]);
+                    // 結果パネルはエディタの下に最大10行
+                    let mut editor_area = vertical_chunks[1];
+                    if app.results.visible {
+                        let height = (app.results.items.len() as u16 + 2).min(12).min(editor_area.height / 2);
+                        let split = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Min(1), Constraint::Length(height)])
+                            .split(editor_area);
+                        editor_area = split[0];
+                        draw_results_panel(frame, &mut app, split[1]);
+                    }
+                    // タスクの出力パネルはさらにその下
+                    if app.task.visible {
+                        let height = 12.min(editor_area.height / 2);
+                        let split = Layout::default()
+                            .direction(Direction::Vertical)
+                            .constraints([Constraint::Min(1), Constraint::Length(height)])
+                            .split(editor_area);
+                        editor_area = split[0];
+                        draw_task_panel(frame, &mut app, split[1]);
+                    }
+                    // Markdown のプレビューは右半分
+                    if app.md_preview && app.hex.is_none() && app.filetype() == "markdown" {
+                        let split = Layout::default()
+                            .direction(Direction::Horizontal)
+                            .constraints([Constraint::Percentage(50), Constraint::Percentage(50)])
+                            .split(editor_area);
+                        editor_area = split[0];
+                        draw_markdown_preview(frame, &app, split[1]);
+                    }
+                    let editor_chunks_vec = Layout::default()
+                        .direction(Direction::Horizontal)
+                        .constraints([
+                            Constraint::Length(app.line_number_width() as u16 + 1),
+                            Constraint::Min(1),
+                            Constraint::Length(1),
+                        ])
+                        .split(editor_area);
+                    let editor_chunks: [Rect; 3] = editor_chunks_vec.try_into().unwrap();
+                    if app.hex.is_some() {
+                        draw_hex(frame, &mut app, editor_area);
+                    } else {
+                        draw_editor(frame, &mut app, editor_chunks, true);
+                    }
+                    draw_status_bar(frame, &app, vertical_chunks[2]);
+                    if app.completion.is_some() {
+                        draw_completion(frame, &app);
+                    }
+                }
+                if app.debug_overlay {
+                    draw_debug_overlay(frame, &app);
                 }
+                if app.popup.is_none() {
+                    draw_notice(frame, &app);
+                }
+            })
+            .map_err(|e| app.lost_terminal(e))?;
+            app.perf.frame_done();
+        }
+
+        // 入力かバックグラウンドの知らせが来るか、次の時刻まで眠る
+        let event = match wake::wait(&wakeups, app.next_wakeup()) {
+            Some(Wake::Input(event)) => event.map_err(|e| app.lost_terminal(e))?,
+            Some(Wake::Work) | None => continue,
+        };
+        // キーでも端末の大きさの変化でも描き直す
+        app.dirty = true;
+        if app.popup.is_some() {
+            if let Event::Key(KeyEvent { code, modifiers, .. }) = event {
+                app.perf.begin(keymap::describe(code, modifiers));
+                logging::debug("key", &[("key", &keymap::describe(code, modifiers)), ("popup", &true)]);
+                app.handle_popup(code, modifiers);
+            }
+            continue;
+        }
+        if let Event::Mouse(mouse) = event {
+            app.handle_mouse(mouse);
+            continue;
+        }
+        if let Event::Key(KeyEvent { code, modifiers, .. }) = event {
+            app.perf.begin(keymap::describe(code, modifiers));
+            logging::debug("key", &[("key", &keymap::describe(code, modifiers))]);
+            app.message = None;
+            app.expire_notice(true);
+            if keymap::lookup(&app.bindings, code, modifiers) != Some(Action::KillLine) {
+                app.kill_chain = false;
+            }
+            app.virtual_space_key(code, modifiers);
+            // ファイルを読んでいる途中の Esc は読むのをやめる
+            if code == KeyCode::Esc && app.cancel_load() {
                 continue;
             }
-            if let Event::Key(KeyEvent { code, modifiers, .. }) = read()? {
-                // Esc キーはどのモードでもポップアップ表示
-                if code == KeyCode::Esc && !modifiers.contains(KeyModifiers::CONTROL) {
-                    app.popup = Some(PopupMode::ExitPrompt);
-                    app.popup_input.clear();
-                    continue;
+            // タスクの出力パネルにフォーカスがあるときはパネルの操作
+            if let Mode::Editor = app.mode
+                && app.task.focused
+            {
+                if keymap::lookup(&app.bindings, code, modifiers) == Some(Action::ToggleTaskPanel) {
+                    app.toggle_task_panel();
+                } else {
+                    app.handle_task_key(code, modifiers);
                 }
-                // F4: ヘルプ切替
-                if code == KeyCode::F(4) {
-                    app.help_visible = !app.help_visible;
-                    continue;
-                }
-                // モード切替：F2でFileTree、F1でEditor
-                if code == KeyCode::F(2) {
-                    app.mode = Mode::FileTree;
-                    continue;
+                continue;
+            }
+            // 結果パネルにフォーカスがあるときはパネルの操作
+            if let Mode::Editor = app.mode
+                && app.results.focused
+            {
+                if keymap::lookup(&app.bindings, code, modifiers) == Some(Action::ToggleResults) {
+                    app.toggle_results();
+                } else {
+                    app.handle_results_key(code);
                 }
-                if code == KeyCode::F(1) {
-                    app.mode = Mode::Editor;
-                    continue;
+                continue;
+            }
+            // 補完ポップアップが開いていれば選択・確定のキーを先に処理
+            if let Mode::Editor = app.mode
+                && app.handle_completion_key(code, modifiers)
+            {
+                continue;
+            }
+            // Vim キーマップ: ノーマル／ビジュアルモードのキーを先に処理
+            if let Mode::Editor = app.mode
+                && app.config.keymap == Keymap::Vim
+                && app.hex.is_none()
+                && app.handle_vim_key(code, modifiers)
+            {
+                continue;
+            }
+            // FileTree で絞り込み中の Esc は絞り込みをやめる。印があれば印を消す
+            if let Mode::FileTree = app.mode
+                && code == KeyCode::Esc
+                && (!app.file_tree.filter.is_empty() || !app.file_tree.marked.is_empty())
+            {
+                if app.file_tree.filter.is_empty() {
+                    app.file_tree.marked.clear();
+                } else {
+                    app.file_tree.clear_filter();
                 }
-                match app.mode {
-                    Mode::Editor => {
-                        if !modifiers.contains(KeyModifiers::ALT) { app.alt_n = 8; }
-                        if code == KeyCode::Char('s') && modifiers == KeyModifiers::CONTROL {
-                            app.save_file();
-                            continue;
-                        }
-                        if code == KeyCode::Up && modifiers.contains(KeyModifiers::CONTROL) {
-                            app.scroll_up();
-                            continue;
-                        }
-                        if code == KeyCode::Down && modifiers.contains(KeyModifiers::CONTROL) {
-                            app.scroll_down();
-                            continue;
-                        }
-                        if code == KeyCode::Char('f') && modifiers == KeyModifiers::CONTROL {
-                            app.search();
-                            continue;
-                        }
-                        if code == KeyCode::Char('c') && modifiers == KeyModifiers::CONTROL {
-                            app.copy_selection();
-                            continue;
-                        }
-                        if code == KeyCode::Char('x') && modifiers == KeyModifiers::CONTROL {
-                            app.cut_selection();
-                            continue;
-                        }
-                        if code == KeyCode::Char('v') && modifiers == KeyModifiers::CONTROL {
-                            app.paste_clipboard();
-                            continue;
-                        }
-                        if code == KeyCode::Char('a') && modifiers == KeyModifiers::CONTROL {
-                            app.select_all();
-                            continue;
-                        }
-                        if code == KeyCode::Char('z') && modifiers == KeyModifiers::CONTROL {
-                            app.undo();
-                            continue;
-                        }
-                        if code == KeyCode::Char('r') && modifiers == KeyModifiers::CONTROL {
-                            app.redo();
-                            continue;
+                continue;
+            }
+            // 選択が複数あれば入力はすべての選択へ（ほかのキーで1つに戻る）
+            if let Mode::Editor = app.mode
+                && app.hex.is_none()
+                && app.handle_multi_key(code, modifiers)
+            {
+                continue;
+            }
+            // Esc キーはどのモードでも終了（未保存の変更があれば確認）
+            if code == KeyCode::Esc && !modifiers.contains(KeyModifiers::CONTROL) {
+                app.request_exit();
+                continue;
+            }
+            // F4: ヘルプ切替
+            if code == KeyCode::F(4) {
+                app.help_visible = !app.help_visible;
+                continue;
+            }
+            // F12: 計測オーバーレイ切替
+            if code == KeyCode::F(12) {
+                app.debug_overlay = !app.debug_overlay;
+                continue;
+            }
+            // モード切替：F2でFileTree、F1でEditor
+            if code == KeyCode::F(2) {
+                app.mode = Mode::FileTree;
+                app.file_tree.open();
+                continue;
+            }
+            if code == KeyCode::F(1) {
+                app.mode = Mode::Editor;
+                continue;
+            }
+            match app.mode {
+                Mode::Editor => {
+                    // 16進表示中は保存・取り消し・検索だけをキーマップから受ける
+                    if app.hex.is_some() {
+                        match keymap::lookup(&app.bindings, code, modifiers) {
+                            Some(Action::Save) => app.save_file(),
+                            Some(Action::ToggleHex) => app.toggle_hex(),
+                            Some(Action::Undo) => app.hex_undo(),
+                            Some(Action::Search) => app.hex_search_prompt(),
+                            Some(Action::SearchNext) => app.hex_search_next(),
+                            _ => app.handle_hex_key(code, modifiers),
                         }
-                        if code == KeyCode::Char('n') && modifiers == KeyModifiers::NONE {
-                            app.popup = Some(PopupMode::NewFile);
-                            app.popup_input.clear();
-                            continue;
+                        continue;
+                    }
+                    if !modifiers.contains(KeyModifiers::ALT) { app.alt_n = 8; }
+                    if let Some(action) = keymap::lookup(&app.bindings, code, modifiers) {
+                        app.run_action(action);
+                        continue;
+                    }
+                    let motion = matches!(
+                        code,
+                        KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::Home | KeyCode::End | KeyCode::PageUp | KeyCode::PageDown
+                    );
+                    if motion && modifiers.contains(KeyModifiers::SHIFT) {
+                        app.shift_selection = true;
+                        app.handle_arrow_key(code, modifiers);
+                        continue;
+                    }
+                    if motion {
+                        app.shift_selection = false;
+                        app.selection_reset();
+                        app.handle_arrow_key(code, modifiers);
+                        continue;
+                    }
+                    match code {
+                        KeyCode::Char(c) => {
+                            app.insert_char(c);
+                            if !modifiers.contains(KeyModifiers::SHIFT) {
+                                app.shift_selection = false;
+                                app.selection_reset();
+                            }
+                            app.completion_after_edit(Some(c));
                         }
-                        if code == KeyCode::Char('m') && modifiers == KeyModifiers::NONE {
-                            app.popup = Some(PopupMode::Rename);
-                            app.popup_input.clear();
-                            continue;
+                        KeyCode::Enter => {
+                            app.enter_key();
+                            if !modifiers.contains(KeyModifiers::SHIFT) {
+                                app.shift_selection = false;
+                                app.selection_reset();
+                            }
                         }
-                        if code == KeyCode::Delete {
+                        KeyCode::Tab => app.tab_key(),
+                        KeyCode::BackTab => app.outdent_key(),
+                        KeyCode::Backspace => {
                             app.backspace();
-                            continue;
-                        }
-                        if code == KeyCode::Left && modifiers == KeyModifiers::CONTROL {
-                            app.move_word_left();
-                            continue;
-                        }
-                        if code == KeyCode::Right && modifiers == KeyModifiers::CONTROL {
-                            app.move_word_right();
-                            continue;
-                        }
-                        if code == KeyCode::Left && modifiers == KeyModifiers::ALT {
-                            app.move_alt_left();
-                            continue;
-                        }
-                        if code == KeyCode::Right && modifiers == KeyModifiers::ALT {
-                            app.move_alt_right();
-                            continue;
-                        }
-                        if (code == KeyCode::Left || code == KeyCode::Right || code == KeyCode::Up || code == KeyCode::Down)
-                            && modifiers.contains(KeyModifiers::SHIFT)
-                        {
-                            app.shift_selection = true;
-                            app.handle_arrow_key(code);
-                            continue;
-                        }
-                        if code == KeyCode::Left || code == KeyCode::Right || code == KeyCode::Up || code == KeyCode::Down {
-                            app.shift_selection = false;
-                            app.selection_reset();
-                            app.handle_arrow_key(code);
-                            continue;
-                        }
-                        match code {
-                            KeyCode::Char(c) => {
-                                app.insert_char(c);
-                                if !modifiers.contains(KeyModifiers::SHIFT) {
-                                    app.shift_selection = false;
-                                    app.selection_reset();
-                                }
-                            }
-                            KeyCode::Enter => {
-                                app.insert_newline();
-                                if !modifiers.contains(KeyModifiers::SHIFT) {
-                                    app.shift_selection = false;
-                                    app.selection_reset();
-                                }
+                            if !modifiers.contains(KeyModifiers::SHIFT) {
+                                app.shift_selection = false;
+                                app.selection_reset();
                             }
-                            KeyCode::Backspace => {
-                                app.backspace();
-                                if !modifiers.contains(KeyModifiers::SHIFT) {
-                                    app.shift_selection = false;
-                                    app.selection_reset();
-                                }
-                            }
-                            _ => {}
+                            app.completion_after_edit(None);
                         }
+                        _ => {}
                     }
-                    Mode::FileTree => {
-                        if let KeyCode::Char(c) = code {
-                            if c.is_digit(10) {
-                                let idx = c.to_digit(10).unwrap() as usize;
-                                let visible = (terminal.size().unwrap().height.saturating_sub(3)) as usize;
-                                let target = app.file_tree.scroll_offset + idx - 1;
-                                if target < app.file_tree.entries.len() {
-                                    app.file_tree.selected = target;
-                                    app.file_tree_enter();
-                                }
-                                continue;
-                            }
+                }
+                Mode::FileTree => {
+                    // 1〜9 は表示中の番号の項目を開く。絞り込みの入力中は数字も絞り込みに足す
+                    if let KeyCode::Char(c @ '1'..='9') = code
+                        && modifiers.is_empty()
+                        && app.file_tree.filter.is_empty()
+                    {
+                        let idx = c.to_digit(10).unwrap_or(1) as usize;
+                        let target = app.file_tree.scroll_offset + idx - 1;
+                        if target < app.file_tree.entries.len() {
+                            app.file_tree.selected = target;
+                            app.file_tree_enter();
+                        }
+                        continue;
+                    }
+                    match code {
+                        KeyCode::Up => { app.file_tree_move_up(); }
+                        KeyCode::Down => { app.file_tree_move_down(); }
+                        KeyCode::Right if modifiers == KeyModifiers::CONTROL => { app.file_tree.enter(); }
+                        KeyCode::Right => { app.file_tree_right(); }
+                        KeyCode::Left => { app.file_tree_left(); }
+                        KeyCode::Enter => { app.file_tree_enter(); }
+                        KeyCode::Delete => { app.file_tree_delete(); }
+                        KeyCode::F(5) => { app.file_tree_copy_prompt(); }
+                        KeyCode::F(6) => { app.file_tree_move_prompt(); }
+                        KeyCode::Char(' ') if modifiers.is_empty() => { app.file_tree.toggle_mark(); }
+                        KeyCode::F(7) => {
+                            let prompt = Prompt::text("New Directory: Enter name under the FileTree path (a/b creates both)", |app| {
+                                let name = app.popup_input.trim().to_string();
+                                app.file_tree_mkdir(&name);
+                            });
+                            app.open_prompt(prompt, "");
+                        }
+                        KeyCode::Char('s') if modifiers == KeyModifiers::CONTROL => { app.save_file(); }
+                        KeyCode::Char('r') if modifiers == KeyModifiers::CONTROL => { app.file_tree.toggle_recent(); }
+                        KeyCode::Char('t') if modifiers == KeyModifiers::CONTROL => { app.file_tree.cycle_sort(); }
+                        KeyCode::Char('g') if modifiers == KeyModifiers::CONTROL => { app.file_tree.toggle_ignored(); }
+                        KeyCode::Char('d') if modifiers == KeyModifiers::CONTROL => { app.file_tree.details = !app.file_tree.details; }
+                        // それ以外の文字は絞り込みに足す
+                        KeyCode::Char(c) if (modifiers - KeyModifiers::SHIFT).is_empty() => {
+                            let filter = format!("{}{}", app.file_tree.filter, c);
+                            app.file_tree.set_filter(filter);
                         }
-                        match code {
-                            KeyCode::Up => { app.file_tree_move_up(); }
-                            KeyCode::Down => { app.file_tree_move_down(); }
-                            KeyCode::Right => { app.file_tree_enter(); }
-                            KeyCode::Left => { app.file_tree_go_up(); }
-                            KeyCode::Enter => { app.file_tree_enter(); }
-                            KeyCode::Delete => { app.file_tree_delete(); }
-                            KeyCode::Char('s') if modifiers == KeyModifiers::CONTROL => { app.save_file(); }
-                            _ => {}
+                        KeyCode::Backspace if !app.file_tree.filter.is_empty() => {
+                            let mut filter = app.file_tree.filter.clone();
+                            filter.pop();
+                            app.file_tree.set_filter(filter);
                         }
+                        _ => {}
                     }
                 }
             }
         }
     }
 
-    disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
     Ok(())
 }
@@ -0,0 +1,313 @@
+// Markdown のプレビュー (F9 で右側に開閉)
+// 見出し・強調・インラインコード・リンク・リスト・引用・コードブロック・表・区切り線を端末の装飾で描き、
+// 段落は幅で折り返す。エディタの一番上の行から描くので、スクロールすると一緒に動く。
+// 表示する分だけ、その行を含む段落の頭から描き直す（コードブロックの中かどうかは前の ``` を数える）
+use tui::{
+    style::{Color, Modifier, Style},
+    text::{Span, Spans},
+};
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+const CODE_BG: Color = Color::Rgb(50, 56, 64);
+
+// プレビューの1行と、その元になった行
+pub struct Row {
+    pub line: usize,
+    pub spans: Spans<'static>,
+}
+
+type Piece = (String, Style);
+
+fn is_fence(line: &str) -> bool {
+    let t = line.trim_start();
+    t.starts_with("```") || t.starts_with("~~~")
+}
+
+fn is_rule(line: &str) -> bool {
+    let t: String = line.chars().filter(|c| !c.is_whitespace()).collect();
+    t.len() >= 3 && ['-', '*', '_'].iter().any(|&m| t.chars().all(|c| c == m))
+}
+
+fn is_table_separator(line: &str) -> bool {
+    let t = line.trim();
+    t.starts_with('|') && t.contains('-') && t.chars().all(|c| "|-: ".contains(c))
+}
+
+fn table_cells(line: &str) -> Vec<String> {
+    let t = line.trim().trim_start_matches('|');
+    let t = t.strip_suffix('|').unwrap_or(t);
+    t.split('|').map(|c| c.trim().to_string()).collect()
+}
+
+// 1文字の * と _ が斜体の始まり・終わりになるか（`2 * 3` や snake_case はそのまま）
+fn emphasis(chars: &[char], i: usize, closing: bool) -> bool {
+    let prev = i.checked_sub(1).map(|j| chars[j]);
+    let next = chars.get(i + 1);
+    if closing {
+        prev.is_some_and(|c| !c.is_whitespace()) && !(chars[i] == '_' && next.is_some_and(|c| c.is_alphanumeric()))
+    } else {
+        next.is_some_and(|c| !c.is_whitespace()) && !(chars[i] == '_' && prev.is_some_and(|c| c.is_alphanumeric()))
+    }
+}
+
+// 行内の装飾: **太字**、*斜体*・_斜体_、~~取り消し~~、`コード`、[リンク](url)、![画像](src)、\ でエスケープ
+fn inline(text: &str, base: Style) -> Vec<Piece> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out: Vec<Piece> = Vec::new();
+    let mut cur = String::new();
+    let (mut bold, mut italic, mut strike) = (false, false, false);
+    let style = |bold: bool, italic: bool, strike: bool| {
+        let mut s = base;
+        if bold { s = s.add_modifier(Modifier::BOLD); }
+        if italic { s = s.add_modifier(Modifier::ITALIC); }
+        if strike { s = s.add_modifier(Modifier::CROSSED_OUT); }
+        s
+    };
+    let flush = |cur: &mut String, out: &mut Vec<Piece>, s: Style| {
+        if !cur.is_empty() { out.push((std::mem::take(cur), s)); }
+    };
+    let find = |from: usize, pat: &[char]| (from..chars.len()).find(|&j| chars[j..].starts_with(pat));
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        let s = style(bold, italic, strike);
+        let rest = &chars[i..];
+        if c == '\\' && i + 1 < chars.len() && chars[i + 1].is_ascii_punctuation() {
+            cur.push(chars[i + 1]);
+            i += 2;
+        } else if c == '`' && let Some(end) = find(i + 1, &['`']) {
+            flush(&mut cur, &mut out, s);
+            out.push((chars[i + 1..end].iter().collect(), base.fg(Color::LightYellow).bg(CODE_BG)));
+            i = end + 1;
+        } else if rest.starts_with(&['*', '*']) || rest.starts_with(&['_', '_']) {
+            flush(&mut cur, &mut out, s);
+            bold = !bold;
+            i += 2;
+        } else if rest.starts_with(&['~', '~']) {
+            flush(&mut cur, &mut out, s);
+            strike = !strike;
+            i += 2;
+        } else if (c == '*' || c == '_') && emphasis(&chars, i, italic) {
+            flush(&mut cur, &mut out, s);
+            italic = !italic;
+            i += 1;
+        } else if (c == '[' || rest.starts_with(&['!', '[']))
+            && let Some(close) = find(i, &[']', '('])
+            && let Some(end) = find(close + 2, &[')'])
+        {
+            flush(&mut cur, &mut out, s);
+            let image = c == '!';
+            let label: String = chars[i + if image { 2 } else { 1 }..close].iter().collect();
+            let label = if image { format!("[image: {}]", label) } else { label };
+            out.push((label, s.fg(Color::LightBlue).add_modifier(Modifier::UNDERLINED)));
+            i = end + 1;
+        } else {
+            cur.push(c);
+            i += 1;
+        }
+    }
+    flush(&mut cur, &mut out, style(bold, italic, strike));
+    out
+}
+
+// 語の切れ目で幅に収まるように折り返す。2行目からは indent だけ字下げする
+fn wrap(pieces: Vec<Piece>, width: usize, indent: usize) -> Vec<Vec<Piece>> {
+    let mut rows: Vec<Vec<Piece>> = vec![Vec::new()];
+    let mut used = 0;
+    let push = |rows: &mut Vec<Vec<Piece>>, text: &str, style: Style| {
+        let row = rows.last_mut().unwrap();
+        match row.last_mut() {
+            Some((t, s)) if *s == style => t.push_str(text),
+            _ => row.push((text.to_string(), style)),
+        }
+    };
+    for (text, style) in pieces {
+        // 空白の前で区切った語ごとに置く
+        let mut words: Vec<&str> = Vec::new();
+        let mut start = 0;
+        for (i, c) in text.char_indices() {
+            if c == ' ' && i > start {
+                words.push(&text[start..i]);
+                start = i;
+            }
+        }
+        words.push(&text[start..]);
+        for word in words {
+            let w = word.width();
+            if used + w > width && used > indent {
+                rows.push(vec![(" ".repeat(indent), Style::default())]);
+                used = indent;
+                let word = word.trim_start();
+                // 1行に入らない語は文字で切る
+                for c in word.chars() {
+                    let cw = c.width().unwrap_or(0);
+                    if used + cw > width && used > indent {
+                        rows.push(vec![(" ".repeat(indent), Style::default())]);
+                        used = indent;
+                    }
+                    push(&mut rows, &c.to_string(), style);
+                    used += cw;
+                }
+                continue;
+            }
+            push(&mut rows, word, style);
+            used += w;
+        }
+    }
+    rows
+}
+
+fn to_spans(pieces: Vec<Piece>) -> Spans<'static> {
+    Spans::from(pieces.into_iter().map(|(t, s)| Span::styled(t, s)).collect::<Vec<_>>())
+}
+
+// 表: 列の幅をそろえ、見出しの行を太字にして区切り線を引く
+fn table(lines: &[String], first: usize, width: usize, ascii: bool, out: &mut Vec<Row>) {
+    let cells: Vec<Vec<Vec<Piece>>> = lines
+        .iter()
+        .map(|l| if is_table_separator(l) { Vec::new() } else { table_cells(l).iter().map(|c| inline(c, Style::default())).collect() })
+        .collect();
+    let cols = cells.iter().map(Vec::len).max().unwrap_or(0);
+    let cell_width = |c: &Vec<Piece>| c.iter().map(|(t, _)| t.width()).sum::<usize>();
+    let widths: Vec<usize> = (0..cols).map(|i| cells.iter().filter_map(|r| r.get(i)).map(cell_width).max().unwrap_or(0)).collect();
+    // 区切りの行の `:` で右寄せ・中央寄せ
+    let aligns: Vec<(bool, bool)> = lines
+        .iter()
+        .find(|l| is_table_separator(l))
+        .map(|l| table_cells(l).iter().map(|c| (c.starts_with(':'), c.ends_with(':'))).collect())
+        .unwrap_or_default();
+    let (bar, cross, dash) = if ascii { ("|", "+", "-") } else { ("│", "┼", "─") };
+    let border = Style::default().fg(Color::DarkGray);
+    for (k, row) in cells.into_iter().enumerate() {
+        let mut pieces = Vec::new();
+        if row.is_empty() {
+            let rule: Vec<String> = widths.iter().map(|&w| dash.repeat(w + 2)).collect();
+            pieces.push((rule.join(cross), border));
+        } else {
+            for (i, &w) in widths.iter().enumerate() {
+                if i > 0 { pieces.push((bar.to_string(), border)); }
+                let cell = row.get(i).cloned().unwrap_or_default();
+                let pad = w - cell_width(&cell);
+                let before = match aligns.get(i) {
+                    Some((false, true)) => pad,
+                    Some((true, true)) => pad / 2,
+                    _ => 0,
+                };
+                pieces.push((" ".repeat(before + 1), Style::default()));
+                for (t, s) in cell {
+                    pieces.push((t, if k == 0 { s.add_modifier(Modifier::BOLD) } else { s }));
+                }
+                pieces.push((" ".repeat(pad - before + 1), Style::default()));
+            }
+        }
+        // 幅に入らない分は切る
+        let mut used = 0;
+        let clipped: Vec<Piece> = pieces
+            .into_iter()
+            .map(|(t, s)| {
+                let t: String = t.chars().take_while(|c| { used += c.width().unwrap_or(0); used <= width }).collect();
+                (t, s)
+            })
+            .collect();
+        out.push(Row { line: first + k, spans: to_spans(clipped) });
+    }
+}
+
+// リストの記号。(字下げ, 置き換えた記号, 本文)
+fn list_item(line: &str, ascii: bool) -> Option<(usize, String, &str)> {
+    let indent = line.len() - line.trim_start().len();
+    let t = line.trim_start();
+    let (marker, body) = if let Some(body) = t.strip_prefix("- ").or_else(|| t.strip_prefix("* ")).or_else(|| t.strip_prefix("+ ")) {
+        ((if ascii { "* " } else { "• " }).to_string(), body)
+    } else {
+        let digits = t.chars().take_while(char::is_ascii_digit).count();
+        let rest = &t[digits..];
+        if digits == 0 || !(rest.starts_with(". ") || rest.starts_with(") ")) { return None; }
+        (format!("{}. ", &t[..digits]), &rest[2..])
+    };
+    let (marker, body) = match body.get(..4) {
+        Some("[ ] ") => (format!("{}{}", marker, if ascii { "[ ] " } else { "☐ " }), &body[4..]),
+        Some("[x] " | "[X] ") => (format!("{}{}", marker, if ascii { "[x] " } else { "☑ " }), &body[4..]),
+        _ => (marker, body),
+    };
+    Some((indent, marker, body))
+}
+
+// from 行からの表示。rows 行分を返す
+pub fn render(lines: &[String], from: usize, width: usize, rows: usize, ascii: bool) -> Vec<Row> {
+    let width = width.max(8);
+    // from を含む段落の頭から描く
+    let mut start = from.min(lines.len());
+    while start > 0 && !lines[start - 1].trim().is_empty() {
+        start -= 1;
+    }
+    let mut in_code = lines[..start].iter().filter(|l| is_fence(l)).count() % 2 == 1;
+    let mut out: Vec<Row> = Vec::new();
+    let code = Style::default().fg(Color::Gray).bg(CODE_BG);
+    let mut i = start;
+    while i < lines.len() && out.iter().filter(|r| r.line >= from).count() < rows {
+        let line = &lines[i];
+        let trimmed = line.trim();
+        if is_fence(line) {
+            in_code = !in_code;
+            if in_code {
+                let lang = trimmed.trim_start_matches(['`', '~']).trim();
+                let label = if lang.is_empty() { String::new() } else { format!(" {} ", lang) };
+                let text = format!("{:width$}", label, width = width);
+                out.push(Row { line: i, spans: to_spans(vec![(text, code.fg(Color::DarkGray))]) });
+            }
+            i += 1;
+            continue;
+        }
+        if in_code {
+            let text: String = line.replace('\t', "    ").chars().take(width.saturating_sub(1)).collect();
+            let pad = width.saturating_sub(text.width() + 1);
+            out.push(Row { line: i, spans: to_spans(vec![(format!(" {}{}", text, " ".repeat(pad)), code)]) });
+            i += 1;
+            continue;
+        }
+        // 表は区切りの行（|---|）が2行目にあるものだけ
+        if trimmed.starts_with('|') && lines.get(i + 1).is_some_and(|l| is_table_separator(l)) {
+            let end = (i..lines.len()).find(|&j| !lines[j].trim().starts_with('|')).unwrap_or(lines.len());
+            table(&lines[i..end], i, width, ascii, &mut out);
+            i = end;
+            continue;
+        }
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        let rows_of_line = if trimmed.is_empty() {
+            // 空行は続いても1行にする
+            if out.last().is_some_and(|r| r.spans.width() == 0) { Vec::new() } else { vec![Vec::new()] }
+        } else if (1..=6).contains(&level) && trimmed[level..].starts_with(' ') {
+            let title = trimmed[level..].trim().trim_end_matches('#').trim();
+            let style = match level {
+                1 => Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD | Modifier::UNDERLINED),
+                2 => Style::default().fg(Color::LightCyan).add_modifier(Modifier::BOLD),
+                _ => Style::default().fg(Color::LightGreen).add_modifier(Modifier::BOLD),
+            };
+            wrap(inline(title, style), width, 0)
+        } else if is_rule(line) {
+            vec![vec![((if ascii { "-" } else { "─" }).repeat(width), Style::default().fg(Color::DarkGray))]]
+        } else if let Some(body) = trimmed.strip_prefix('>') {
+            let mut pieces = vec![((if ascii { "| " } else { "│ " }).to_string(), Style::default().fg(Color::DarkGray))];
+            pieces.extend(inline(body.trim_start(), Style::default().fg(Color::Gray).add_modifier(Modifier::ITALIC)));
+            wrap(pieces, width, 2)
+        } else if let Some((indent, marker, body)) = list_item(line, ascii) {
+            let lead = " ".repeat(indent) + &marker;
+            let hang = lead.width();
+            let mut pieces = vec![(lead, Style::default().fg(Color::LightBlue))];
+            pieces.extend(inline(body, Style::default()));
+            wrap(pieces, width, hang)
+        } else {
+            let indent = line.len() - line.trim_start().len();
+            let mut pieces = vec![(" ".repeat(indent), Style::default())];
+            pieces.extend(inline(trimmed, Style::default()));
+            wrap(pieces, width, indent)
+        };
+        out.extend(rows_of_line.into_iter().map(|p| Row { line: i, spans: to_spans(p) }));
+        i += 1;
+    }
+    // from より前の行（段落の頭から描いた分）は出さない
+    out.retain(|r| r.line >= from);
+    out.truncate(rows);
+    out
+}
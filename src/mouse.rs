@@ -0,0 +1,66 @@
+// マウス（config: mouse = false で使わない）
+// エディタのスクロールバーをクリックするとその位置へ飛び、つまみをつかんで引きずるとそれに合わせて送る。
+// ホイールは3行ずつ送る。どれもカーソルを表示と同じだけ動かすので、描き直しで表示がカーソルへ戻ることはない
+use crate::{scrollbar_rows, App, Mode};
+use crossterm::event::{MouseButton, MouseEvent, MouseEventKind};
+
+const WHEEL_LINES: usize = 3;
+
+impl App {
+    pub fn handle_mouse(&mut self, event: MouseEvent) {
+        if !matches!(self.mode, Mode::Editor) || self.hex.is_some() { return; }
+        match event.kind {
+            MouseEventKind::Down(MouseButton::Left) => {
+                let Some(row) = self.scrollbar_row(event.column, event.row) else { return };
+                let thumb = scrollbar_rows(self.lines.len(), self.view_height, self.scroll_offset);
+                if thumb.is_empty() { return; }
+                // つまみの外ならつまみの真ん中がそこへ来るように飛んでからつかむ
+                let grab = if thumb.contains(&row) { row - thumb.start } else { thumb.len() / 2 };
+                self.scrollbar_grab = Some(grab);
+                self.record_jump();
+                self.drag_thumb(row, grab);
+            }
+            MouseEventKind::Drag(MouseButton::Left) => {
+                if let Some(grab) = self.scrollbar_grab {
+                    let top = self.scrollbar_area.map_or(0, |area| area.y);
+                    self.drag_thumb(event.row.saturating_sub(top) as usize, grab);
+                }
+            }
+            MouseEventKind::Up(MouseButton::Left) => self.scrollbar_grab = None,
+            MouseEventKind::ScrollDown => self.scroll_view_to(self.scroll_offset + WHEEL_LINES),
+            MouseEventKind::ScrollUp => self.scroll_view_to(self.scroll_offset.saturating_sub(WHEEL_LINES)),
+            _ => {}
+        }
+    }
+
+    // スクロールバー上なら上から何行目か
+    fn scrollbar_row(&self, column: u16, row: u16) -> Option<usize> {
+        let area = self.scrollbar_area?;
+        let inside = column >= area.x && column < area.x + area.width && row >= area.y && row < area.y + area.height;
+        inside.then(|| (row - area.y) as usize)
+    }
+
+    // つまみの grab 行目が row に来るように送る（scrollbar_rows の位置の逆算）
+    fn drag_thumb(&mut self, row: usize, grab: usize) {
+        let (total, visible) = (self.lines.len(), self.view_height);
+        let size = scrollbar_rows(total, visible, 0).len();
+        if size == 0 || visible <= size { return; }
+        let max_scroll = total - visible;
+        let top = row.saturating_sub(grab).min(visible - size);
+        self.scroll_view_to((top * max_scroll + (visible - size) / 2) / (visible - size));
+    }
+
+    // 表示の先頭を offset 行目にし、カーソルも同じだけ動かす
+    fn scroll_view_to(&mut self, offset: usize) {
+        let offset = offset.min(self.lines.len().saturating_sub(self.view_height.max(1)));
+        if offset == self.scroll_offset { return; }
+        let y = if offset > self.scroll_offset {
+            self.cursor_y + (offset - self.scroll_offset)
+        } else {
+            self.cursor_y.saturating_sub(self.scroll_offset - offset)
+        };
+        self.selection_reset();
+        self.move_to_line(y.min(self.lines.len() - 1));
+        self.scroll_offset = offset;
+    }
+}
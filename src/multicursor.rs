@@ -0,0 +1,179 @@
+// 複数の選択 (Ctrl+D)
+// Ctrl+D を重ねて押すと、選んでいる文字列の次の出現をもう1つの選択として足していく。
+// 選択が複数あるあいだは文字の入力・Backspace・Delete をすべての選択（入力の後はそれぞれのカーソル）に同じように行い、
+// 1回の undo で戻せる。どれも1行の中だけの編集で、ほかのキー（Esc も）を押すと足した選択は消えて元の1つに戻る
+use crate::{grapheme, keymap::{self, Action}, App, TextPos};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+// すべての選択に行う編集
+enum Edit {
+    Insert(char),
+    DeleteBack,
+    DeleteForward,
+}
+
+impl App {
+    // 足した選択と今の選択（1行の中のものだけ）を文書の順に。2つ目は今の選択の位置
+    fn all_selections(&self) -> (Vec<(TextPos, TextPos)>, usize) {
+        let cursor = (self.cursor_y, self.cursor_x);
+        let primary = match (self.sel_start, self.sel_end) {
+            (Some(s), Some(e)) if s.0 == e.0 => (s.min(e), s.max(e)),
+            _ => (cursor, cursor),
+        };
+        // 読み直しなどで行が変わっていれば、もう指していない選択は捨てる
+        let valid = |&((y, s), (_, e)): &(TextPos, TextPos)| {
+            self.lines.get(y).is_some_and(|line| s <= e && line.is_char_boundary(s) && line.is_char_boundary(e))
+        };
+        let mut all: Vec<(TextPos, TextPos)> = self.extra_selections.iter().copied().filter(valid).collect();
+        all.push(primary);
+        all.sort();
+        all.dedup();
+        let index = all.iter().position(|&r| r == primary).unwrap_or(0);
+        (all, index)
+    }
+
+    // Ctrl+D で次の出現を足す。見つからなければ false
+    pub fn add_next_occurrence(&mut self, start: TextPos, end: TextPos) -> bool {
+        let needle = self.lines[start.0][start.1..end.1].to_string();
+        let (taken, _) = self.all_selections();
+        let count = self.lines.len();
+        // 今の選択の後ろから探し、末尾まで行けば先頭から。選んであるものは飛ばす
+        let found = (0..=count).find_map(|k| {
+            let y = (start.0 + k) % count;
+            let line = &self.lines[y];
+            let skip = if k == 0 { end.1 } else { 0 };
+            line.get(skip..)?.match_indices(&needle).map(|(i, _)| (y, skip + i)).find(|&pos| !taken.iter().any(|&(s, _)| s == pos))
+        });
+        let Some((y, x)) = found else { return false };
+        self.extra_selections.push((start, end));
+        self.sel_start = Some((y, x));
+        self.sel_end = Some((y, x + needle.len()));
+        self.cursor_y = y;
+        self.cursor_x = x + needle.len();
+        self.shift_selection = true;
+        self.adjust_h_scroll(0);
+        self.message = Some(format!("{} selections of \"{}\"", self.extra_selections.len() + 1, needle));
+        true
+    }
+
+    pub fn clear_extra_selections(&mut self) {
+        self.extra_selections.clear();
+    }
+
+    // 選択が複数あるときのキー。処理したら true、ほかのキーなら足した選択を消して false
+    pub fn handle_multi_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        if self.extra_selections.is_empty() { return false; }
+        if keymap::lookup(&self.bindings, code, modifiers) == Some(Action::SelectNextOccurrence) { return false; }
+        let edit = match code {
+            KeyCode::Char(c) if !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT) => Edit::Insert(c),
+            KeyCode::Backspace if modifiers.is_empty() => Edit::DeleteBack,
+            KeyCode::Delete if modifiers.is_empty() => Edit::DeleteForward,
+            KeyCode::Esc => {
+                self.clear_extra_selections();
+                return true;
+            }
+            _ => {
+                self.clear_extra_selections();
+                return false;
+            }
+        };
+        self.edit_all(edit);
+        true
+    }
+
+    // 前から順に編集し、同じ行の後ろの選択は先の編集で増えた（減った）バイト数だけずらす
+    fn edit_all(&mut self, edit: Edit) {
+        let (ranges, primary) = self.all_selections();
+        self.save_undo();
+        let mut carets = Vec::with_capacity(ranges.len());
+        let mut shift: (usize, isize) = (usize::MAX, 0);
+        for ((y, sx), (_, ex)) in ranges {
+            if shift.0 != y { shift = (y, 0); }
+            let line = &mut self.lines[y];
+            let (sx, ex) = ((sx as isize + shift.1) as usize, (ex as isize + shift.1) as usize);
+            let (from, to) = match edit {
+                _ if sx != ex => (sx, ex),
+                Edit::Insert(_) => (sx, sx),
+                Edit::DeleteBack => (grapheme::prev(line, sx), sx),
+                Edit::DeleteForward => (sx, grapheme::next(line, sx)),
+            };
+            let text = match edit {
+                Edit::Insert(c) => c.to_string(),
+                _ => String::new(),
+            };
+            line.replace_range(from..to, &text);
+            shift.1 += text.len() as isize - (to - from) as isize;
+            carets.push((y, from + text.len()));
+        }
+        let (y, x) = carets.remove(primary);
+        self.extra_selections = carets.into_iter().filter(|&c| c != (y, x)).map(|c| (c, c)).collect();
+        self.extra_selections.dedup();
+        self.selection_reset();
+        self.shift_selection = false;
+        (self.cursor_y, self.cursor_x) = (y, x);
+        self.adjust_h_scroll(0);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(lines: &[&str]) -> App {
+        let mut app = App::new();
+        *app.lines = lines.iter().map(|s| s.to_string()).collect();
+        app
+    }
+
+    fn select(app: &mut App, start: TextPos, end: TextPos) {
+        app.sel_start = Some(start);
+        app.sel_end = Some(end);
+        (app.cursor_y, app.cursor_x) = end;
+    }
+
+    #[test]
+    fn adds_the_next_occurrence() {
+        let mut a = app(&["ab ab", "ab"]);
+        select(&mut a, (0, 0), (0, 2));
+        assert!(a.add_next_occurrence((0, 0), (0, 2)));
+        assert_eq!((a.sel_start, a.sel_end), (Some((0, 3)), Some((0, 5))));
+        assert!(a.add_next_occurrence((0, 3), (0, 5)));
+        assert_eq!((a.sel_start, a.sel_end), (Some((1, 0)), Some((1, 2))));
+        // 全部選んだら先頭に戻っても足すものはない
+        assert!(!a.add_next_occurrence((1, 0), (1, 2)));
+        assert_eq!(a.extra_selections, [((0, 0), (0, 2)), ((0, 3), (0, 5))]);
+    }
+
+    #[test]
+    fn edits_several_carets_on_one_line() {
+        let mut a = app(&["x = ab + ab + ab;"]);
+        a.extra_selections = vec![((0, 4), (0, 6)), ((0, 9), (0, 11))];
+        select(&mut a, (0, 14), (0, 16));
+        // 置き換えで長さが変わっても後ろの選択がずれない
+        a.edit_all(Edit::Insert('é'));
+        assert_eq!(*a.lines, ["x = é + é + é;"]);
+        assert_eq!((a.cursor_y, a.cursor_x), (0, 16));
+        assert_eq!(a.extra_selections, [((0, 6), (0, 6)), ((0, 11), (0, 11))]);
+        a.edit_all(Edit::Insert('!'));
+        assert_eq!(*a.lines, ["x = é! + é! + é!;"]);
+        a.edit_all(Edit::DeleteBack);
+        a.edit_all(Edit::DeleteBack);
+        assert_eq!(*a.lines, ["x =  +  + ;"]);
+        a.edit_all(Edit::DeleteForward);
+        assert_eq!(*a.lines, ["x = + + "]);
+        assert_eq!((a.cursor_x, a.extra_selections.clone()), (8, vec![((0, 4), (0, 4)), ((0, 6), (0, 6))]));
+        // 1回の undo で全部戻る
+        a.undo();
+        assert_eq!(*a.lines, ["x =  +  + ;"]);
+    }
+
+    #[test]
+    fn carets_that_meet_merge() {
+        let mut a = app(&["ab"]);
+        a.extra_selections = vec![((0, 1), (0, 1))];
+        select(&mut a, (0, 2), (0, 2));
+        a.edit_all(Edit::DeleteBack);
+        assert_eq!(*a.lines, [""]);
+        assert!(a.extra_selections.is_empty());
+    }
+}
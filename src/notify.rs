@@ -0,0 +1,102 @@
+// 操作の結果の通知
+// 保存やファイル操作の成功と失敗を画面の右下に数秒出し（失敗は赤で長めに）、履歴に残して Alt+/ で見返せる。
+// ステータスバーのメッセージはこれまで通り、次のキーで消える案内に使う
+use crate::{logging, App, PopupMode};
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+const HISTORY: usize = 100;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+    Ok,
+    Error,
+}
+
+#[derive(Clone)]
+pub struct Notice {
+    pub level: Level,
+    pub text: String,
+    shown: Instant,
+}
+
+impl Notice {
+    fn ttl(&self) -> Duration {
+        match self.level {
+            Level::Ok => Duration::from_secs(3),
+            Level::Error => Duration::from_secs(8),
+        }
+    }
+
+    fn expired(&self) -> bool {
+        self.shown.elapsed() > self.ttl()
+    }
+}
+
+#[derive(Clone, Default)]
+pub struct Notices {
+    pub current: Option<Notice>, // 右下に出している通知
+    history: VecDeque<Notice>,
+}
+
+impl App {
+    pub fn notify_ok(&mut self, text: impl Into<String>) {
+        self.notify(Level::Ok, text.into());
+    }
+
+    pub fn notify_err(&mut self, text: impl Into<String>) {
+        self.notify(Level::Error, text.into());
+    }
+
+    fn notify(&mut self, level: Level, text: String) {
+        match level {
+            Level::Ok => logging::info("notice", &[("text", &text)]),
+            Level::Error => logging::error("notice", &[("text", &text)]),
+        }
+        let notice = Notice { level, text, shown: Instant::now() };
+        if self.notices.history.len() == HISTORY {
+            self.notices.history.pop_front();
+        }
+        self.notices.history.push_back(notice.clone());
+        self.notices.current = Some(notice);
+    }
+
+    // 時間の過ぎた通知を消す。--deterministic では時刻を見ず、次のキーで消す
+    pub fn expire_notice(&mut self, key_pressed: bool) {
+        let expired = match self.notices.current {
+            Some(_) if self.deterministic => key_pressed,
+            Some(ref n) => n.expired(),
+            None => false,
+        };
+        if expired {
+            self.notices.current = None;
+            self.dirty = true;
+        }
+    }
+
+    // 出している通知が消えるまで（メインループが起きる時刻）
+    pub fn notice_left(&self) -> Option<Duration> {
+        let notice = self.notices.current.as_ref().filter(|_| !self.deterministic)?;
+        Some(notice.ttl().saturating_sub(notice.shown.elapsed()) + Duration::from_millis(1))
+    }
+
+    // Alt+/: これまでの通知（新しい順）
+    pub fn show_notices(&mut self) {
+        if self.notices.history.is_empty() {
+            self.message = Some("No messages yet".to_string());
+            return;
+        }
+        let lines = self
+            .notices
+            .history
+            .iter()
+            .rev()
+            .map(|n| format!("{:<6} {}", if n.level == Level::Ok { "ok" } else { "error" }, n.text))
+            .collect();
+        let title = "Messages, newest first (Up/Down/PgUp/PgDn scroll, Esc close)".to_string();
+        self.popup = Some(PopupMode::DiffView { title, lines });
+        self.popup_selected = 0;
+    }
+}
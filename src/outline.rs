@@ -0,0 +1,60 @@
+// アウトライン（見出し）の抽出
+// Markdown は `#` 見出し（コードブロック内は除く）、それ以外は `// --- 名前 ---` や `# === 名前 ===` のような区切りコメント
+#[derive(Clone)]
+pub struct Heading {
+    pub line: usize,
+    pub level: usize,
+    pub title: String,
+}
+
+pub fn headings(lines: &[String], filetype: &str) -> Vec<Heading> {
+    if filetype == "markdown" {
+        markdown_headings(lines)
+    } else {
+        section_comments(lines)
+    }
+}
+
+fn markdown_headings(lines: &[String]) -> Vec<Heading> {
+    let mut result = Vec::new();
+    let mut in_code = false;
+    for (i, line) in lines.iter().enumerate() {
+        if line.trim_start().starts_with("```") || line.trim_start().starts_with("~~~") {
+            in_code = !in_code;
+            continue;
+        }
+        if in_code { continue; }
+        let level = line.chars().take_while(|&c| c == '#').count();
+        if (1..=6).contains(&level) && line[level..].starts_with(' ') {
+            result.push(Heading {
+                line: i,
+                level,
+                title: line[level..].trim().trim_end_matches('#').trim().to_string(),
+            });
+        }
+    }
+    result
+}
+
+fn section_comments(lines: &[String]) -> Vec<Heading> {
+    let mut result = Vec::new();
+    for (i, line) in lines.iter().enumerate() {
+        let trimmed = line.trim_start();
+        let Some(body) = ["//", "#", "--", ";"].iter().find_map(|p| trimmed.strip_prefix(p)) else { continue };
+        let body = body.trim();
+        for fence in ["===", "---"] {
+            if let Some(inner) = body.strip_prefix(fence) {
+                let title = inner.trim_start_matches(fence.chars().next().unwrap()).trim_end_matches(fence.chars().next().unwrap()).trim();
+                if !title.is_empty() {
+                    result.push(Heading {
+                        line: i,
+                        level: if fence == "===" { 1 } else { 2 },
+                        title: title.to_string(),
+                    });
+                }
+                break;
+            }
+        }
+    }
+    result
+}
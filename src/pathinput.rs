@@ -0,0 +1,256 @@
+// パスの入力（Save As と Ctrl+O で開くファイル）
+// ~ を展開し、相対パスは作業ディレクトリから解決して絶対パスを見せる。
+// Tab で入力中の名前を補完する（候補が1つなら確定、複数なら共通の頭まで、それ以上伸びなければ選んでいる候補）。
+// 保存の前に、保存先のディレクトリがあって書き込めるかを確かめる。
+// Ctrl+O で開いたファイルは <状態ディレクトリ>/open_history に新しい順で残し、何も打っていないときに出す
+use crate::{config::Config, prompt::Prompt, App};
+use std::{
+    fs, io,
+    path::{Component, Path, PathBuf},
+};
+
+const MAX_HISTORY: usize = 100;
+
+fn home() -> Option<PathBuf> {
+    std::env::var_os("HOME").filter(|h| !h.is_empty()).map(PathBuf::from)
+}
+
+// . と .. を文字の上だけで畳む（リンクは辿らない）
+fn normalize(path: &Path) -> PathBuf {
+    let mut out = PathBuf::new();
+    for component in path.components() {
+        match component {
+            Component::CurDir => {}
+            Component::ParentDir => {
+                out.pop();
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+// ~ と相対パスを解決した絶対パス
+pub fn expand(input: &str) -> PathBuf {
+    let input = input.trim();
+    let path = match (input, home()) {
+        ("~", Some(home)) => home,
+        (_, Some(home)) if input.starts_with("~/") => home.join(&input[2..]),
+        _ => PathBuf::from(input),
+    };
+    let cwd = std::env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    normalize(&cwd.join(path))
+}
+
+// 入力を最後の / でディレクトリと名前の頭に分ける
+fn split(input: &str) -> (&str, &str) {
+    match input.rfind('/') {
+        Some(i) => (&input[..=i], &input[i + 1..]),
+        None => ("", input),
+    }
+}
+
+// 名前の頭が合う項目（ディレクトリは / 付き）。隠しファイルは . を打ったときだけ
+pub fn candidates(input: &str) -> Vec<String> {
+    let (dir, prefix) = split(input);
+    let Ok(read) = fs::read_dir(expand(if dir.is_empty() { "." } else { dir })) else { return Vec::new() };
+    let mut names: Vec<String> = read
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) { return None; }
+            Some(if e.path().is_dir() { name + "/" } else { name })
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+// Tab で置き換える入力。伸ばせなければ None
+pub fn complete(input: &str, selected: usize) -> Option<String> {
+    let names = candidates(input);
+    let (dir, prefix) = split(input);
+    let first = names.first()?;
+    let common: String = first
+        .chars()
+        .enumerate()
+        .take_while(|&(i, c)| names.iter().all(|n| n.chars().nth(i) == Some(c)))
+        .map(|(_, c)| c)
+        .collect();
+    let name = if common.len() > prefix.len() { common } else { names.get(selected.min(names.len() - 1))?.clone() };
+    let completed = format!("{}{}", dir, name);
+    (completed != input).then_some(completed)
+}
+
+fn history_path() -> Option<PathBuf> {
+    Some(Config::state_dir()?.join("open_history"))
+}
+
+// 1行に1つの絶対パス（新しい順）
+fn load_history() -> Vec<PathBuf> {
+    let Some(content) = history_path().and_then(|p| fs::read_to_string(p).ok()) else { return Vec::new() };
+    content.lines().filter(|l| !l.is_empty()).map(PathBuf::from).collect()
+}
+
+fn save_history(history: &[PathBuf]) {
+    let Some(path) = history_path() else { return };
+    if let Some(dir) = path.parent() {
+        let _ = fs::create_dir_all(dir);
+    }
+    let content: String = history.iter().map(|p| format!("{}\n", p.display())).collect();
+    let _ = fs::write(path, content);
+}
+
+// ホームの下は ~ で見せる
+fn display(path: &Path) -> String {
+    match home().and_then(|h| path.strip_prefix(h).ok().map(Path::to_path_buf)) {
+        Some(rest) if !rest.as_os_str().is_empty() => format!("~/{}", rest.display()),
+        _ => path.display().to_string(),
+    }
+}
+
+// すぐ分かる問題（Enter の前でも出す）
+fn quick_check(path: &Path) -> Result<(), String> {
+    if path.is_dir() {
+        return Err(format!("{} is a directory", path.display()));
+    }
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    if !dir.is_dir() {
+        return Err(format!("directory {} does not exist", dir.display()));
+    }
+    if fs::metadata(path).is_ok_and(|m| m.permissions().readonly()) {
+        return Err(format!("{} is read-only", path.display()));
+    }
+    Ok(())
+}
+
+// 保存してよいか。書き込めるかはディレクトリに試しにファイルを作って確かめる
+pub fn check_save_target(path: &Path) -> Result<(), String> {
+    quick_check(path)?;
+    let dir = path.parent().unwrap_or(Path::new("/"));
+    let probe = dir.join(format!(".rwe-write-test-{}", std::process::id()));
+    match fs::OpenOptions::new().write(true).create_new(true).open(&probe) {
+        Ok(_) => {
+            let _ = fs::remove_file(&probe);
+            Ok(())
+        }
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(()),
+        Err(e) => Err(format!("cannot write to {}: {}", dir.display(), e)),
+    }
+}
+
+impl App {
+    pub fn open_save_as(&mut self, input: &str) {
+        let prompt = Prompt::list("Save As", App::path_items, App::save_as_accept)
+            .live_title(App::save_as_title)
+            .check(App::save_as_check)
+            .complete(App::complete_popup_path);
+        self.open_prompt(prompt, input);
+    }
+
+    // Save As の見出し: 解決した絶対パスと、保存できない理由
+    fn save_as_title(&self) -> String {
+        let path = expand(&self.popup_input);
+        match quick_check(&path) {
+            Ok(()) => format!("Save As: {} (Tab completes, Enter saves)", path.display()),
+            Err(e) => format!("Save As: {}: {} (Tab completes)", path.display(), e),
+        }
+    }
+
+    pub fn path_items(&self) -> Vec<String> {
+        let (dir, _) = split(&self.popup_input);
+        candidates(&self.popup_input).into_iter().map(|name| format!("{}{}", dir, name)).collect()
+    }
+
+    fn complete_popup_path(&mut self) {
+        if let Some(completed) = complete(&self.popup_input, self.popup_selected) {
+            self.popup_input = completed;
+            self.popup_selected = 0;
+        }
+    }
+
+    // Enter の前に確かめる。保存できなければ入力を残して理由を見出しに出す
+    fn save_as_check(&self) -> Result<(), String> {
+        if self.popup_input.trim().is_empty() { return Err("type a file name".to_string()); }
+        let path = expand(&self.popup_input);
+        check_save_target(&path).map_err(|e| format!("cannot save to {}: {}", path.display(), e))
+    }
+
+    fn save_as_accept(&mut self) {
+        let path = expand(&self.popup_input);
+        self.save_as(path);
+    }
+
+    // Ctrl+O: 未保存の変更があれば開かない（保存してから）
+    pub fn open_file_prompt(&mut self) {
+        if self.modified {
+            self.message = Some("Unsaved changes; save before opening another file".to_string());
+            return;
+        }
+        self.open_history = load_history();
+        self.open_file_dialog("");
+    }
+
+    fn open_file_dialog(&mut self, input: &str) {
+        let prompt = Prompt::list("Open File", App::open_file_items, App::open_file_accept)
+            .live_title(App::open_file_title)
+            .check(App::open_file_check)
+            .complete(App::complete_popup_path);
+        self.open_prompt(prompt, input);
+    }
+
+    fn open_file_title(&self) -> String {
+        if self.popup_input.trim().is_empty() {
+            return "Open File: type a path, or pick a recent file (Up/Down, Enter opens)".to_string();
+        }
+        let path = expand(&self.popup_input);
+        match (path.is_file(), path.is_dir()) {
+            (true, _) => format!("Open File: {} (Tab completes, Enter opens)", path.display()),
+            (_, true) => format!("Open File: {} is a directory (Tab completes, Enter enters it)", path.display()),
+            _ => format!("Open File: {} (Tab completes, Enter opens the selected entry)", path.display()),
+        }
+    }
+
+    // 何も打っていなければ履歴、打っていれば補完の候補
+    fn open_file_items(&self) -> Vec<String> {
+        if self.popup_input.trim().is_empty() {
+            return self.open_history.iter().map(|p| display(p)).collect();
+        }
+        self.path_items()
+    }
+
+    // 打ったパスがファイルならそれ、そうでなければ選んでいる項目
+    fn open_file_target(&self) -> String {
+        let items = self.open_file_items();
+        let selected = items.get(self.popup_selected.min(items.len().saturating_sub(1))).cloned();
+        let typed = self.popup_input.trim().to_string();
+        match selected {
+            Some(item) if typed.is_empty() || !expand(&typed).is_file() => item,
+            _ => typed,
+        }
+    }
+
+    fn open_file_check(&self) -> Result<(), String> {
+        let target = self.open_file_target();
+        if target.is_empty() { return Err("type a path".to_string()); }
+        let path = expand(&target);
+        if path.is_dir() || path.is_file() { Ok(()) } else { Err(format!("no such file: {}", path.display())) }
+    }
+
+    // Enter: ファイルを開く。ディレクトリなら中へ進む
+    fn open_file_accept(&mut self) {
+        let target = self.open_file_target();
+        let path = expand(&target);
+        if path.is_dir() {
+            let input = if target.ends_with('/') { target } else { target + "/" };
+            self.open_file_dialog(&input);
+            return;
+        }
+        self.open_history.retain(|p| *p != path);
+        self.open_history.insert(0, path.clone());
+        self.open_history.truncate(MAX_HISTORY);
+        save_history(&self.open_history);
+        self.record_jump();
+        self.open_path(path);
+    }
+}
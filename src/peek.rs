@@ -0,0 +1,62 @@
+// FileTree で選んでいる項目の中身を左側にのぞき見る
+// ファイルは先頭 MAX_BYTES だけ読み、MAX_LINES 行まで出す。NUL を含めばバイナリとして大きさだけ出す。
+// ディレクトリは中の名前を並べる。選択が変わったときだけ読み直す（メインループで sync_peek を呼ぶ）
+use crate::FileTree;
+use std::{
+    io::Read,
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+const MAX_BYTES: u64 = 64 * 1024;
+const MAX_LINES: usize = 200;
+
+#[derive(Clone)]
+pub struct Peek {
+    pub path: PathBuf,
+    pub lines: Rc<Vec<String>>, // 描画のたびに FileTree を複製するので共有する
+    pub note: Option<String>,   // 中身の代わりに出す説明（バイナリ、読めないなど）
+    pub dir: bool,              // lines はディレクトリの中の名前
+}
+
+fn read_file(path: &Path) -> Result<Vec<String>, String> {
+    let file = std::fs::File::open(path).map_err(|e| format!("Cannot read: {}", e))?;
+    let mut bytes = Vec::new();
+    file.take(MAX_BYTES).read_to_end(&mut bytes).map_err(|e| format!("Cannot read: {}", e))?;
+    if bytes.contains(&0) {
+        let size = path.metadata().map(|m| m.len()).unwrap_or(0);
+        return Err(format!("Binary file, {}", crate::format_size(size)));
+    }
+    Ok(String::from_utf8_lossy(&bytes).lines().take(MAX_LINES).map(|l| l.replace('\t', "    ")).collect())
+}
+
+fn read_dir(path: &Path) -> Result<Vec<String>, String> {
+    let read = std::fs::read_dir(path).map_err(|e| format!("Cannot read: {}", e))?;
+    let mut names: Vec<String> = read
+        .filter_map(|e| e.ok())
+        .map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if e.path().is_dir() { name + "/" } else { name }
+        })
+        .collect();
+    names.sort();
+    Ok(names)
+}
+
+impl FileTree {
+    // 選んでいる項目が変わって読み直したら true
+    pub fn sync_peek(&mut self) -> bool {
+        let Some(entry) = self.entries.get(self.selected) else {
+            return self.peek.take().is_some();
+        };
+        if self.peek.as_ref().is_some_and(|p| p.path == entry.path) { return false; }
+        let result = if entry.is_dir { read_dir(&entry.path) } else { read_file(&entry.path) };
+        let (lines, note) = match result {
+            Ok(lines) if lines.is_empty() => (Vec::new(), Some(if entry.is_dir { "Empty directory" } else { "Empty file" }.to_string())),
+            Ok(lines) => (lines, None),
+            Err(note) => (Vec::new(), Some(note)),
+        };
+        self.peek = Some(Peek { path: entry.path.clone(), lines: Rc::new(lines), note, dir: entry.is_dir });
+        true
+    }
+}
@@ -0,0 +1,111 @@
+// キー入力 → 状態更新 → 描画 の所要時間の計測
+// 予算（frame_budget_ms）を超えたフレームは <状態ディレクトリ>/perf.log に追記する
+use crate::config::Config;
+use std::{
+    io::Write,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+
+const MAX_LOG_BYTES: u64 = 1024 * 1024;
+
+#[derive(Clone)]
+pub struct FrameStats {
+    pub trigger: String, // きっかけのキー（と Action）
+    pub update: Duration,
+    pub draw: Duration,
+}
+
+impl FrameStats {
+    pub fn total(&self) -> Duration {
+        self.update + self.draw
+    }
+}
+
+#[derive(Clone)]
+struct Pending {
+    trigger: String,
+    start: Instant,
+    update: Option<Duration>,
+}
+
+#[derive(Clone)]
+pub struct Perf {
+    pub budget: Duration,
+    log: bool,
+    pending: Option<Pending>,
+    pub last: Option<FrameStats>,
+    pub worst: Option<FrameStats>,
+    pub frames: usize,
+    pub slow_frames: usize,
+}
+
+impl Perf {
+    pub fn new(budget: Duration, log: bool) -> Self {
+        Perf { budget, log, pending: None, last: None, worst: None, frames: 0, slow_frames: 0 }
+    }
+
+    // キーを読んだ直後に呼ぶ
+    pub fn begin(&mut self, trigger: String) {
+        self.pending = Some(Pending { trigger, start: Instant::now(), update: None });
+    }
+    // 実行した Action をきっかけの説明に添える
+    pub fn annotate(&mut self, action: &str) {
+        if let Some(p) = self.pending.as_mut() {
+            p.trigger = format!("{} ({})", p.trigger, action);
+        }
+    }
+    // 描画の直前に呼ぶ
+    pub fn update_done(&mut self) {
+        if let Some(p) = self.pending.as_mut()
+            && p.update.is_none()
+        {
+            p.update = Some(p.start.elapsed());
+        }
+    }
+    // 描画の直後に呼ぶ。入力のないフレーム（タイムアウトでの再描画）は数えない
+    pub fn frame_done(&mut self) {
+        let Some(p) = self.pending.take() else { return };
+        let update = p.update.unwrap_or_default();
+        let stats = FrameStats { trigger: p.trigger, update, draw: p.start.elapsed().saturating_sub(update) };
+        self.frames += 1;
+        if stats.total() > self.budget {
+            self.slow_frames += 1;
+            if self.log {
+                self.write_log(&stats);
+            }
+        }
+        if self.worst.as_ref().is_none_or(|w| stats.total() > w.total()) {
+            self.worst = Some(stats.clone());
+        }
+        self.last = Some(stats);
+    }
+    pub fn over_budget(&self) -> bool {
+        self.last.as_ref().is_some_and(|s| s.total() > self.budget)
+    }
+
+    fn write_log(&self, stats: &FrameStats) {
+        let Some(dir) = Config::state_dir() else { return };
+        let _ = std::fs::create_dir_all(&dir);
+        let path = dir.join("perf.log");
+        // 大きくなりすぎたら1世代だけ残して作り直す
+        if std::fs::metadata(&path).is_ok_and(|m| m.len() > MAX_LOG_BYTES) {
+            let _ = std::fs::rename(&path, dir.join("perf.log.old"));
+        }
+        let Ok(mut file) = std::fs::OpenOptions::new().create(true).append(true).open(&path) else { return };
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let _ = writeln!(
+            file,
+            "{}\t{}\tupdate={:.1}ms\tdraw={:.1}ms\ttotal={:.1}ms\tbudget={}ms",
+            now,
+            stats.trigger,
+            ms(stats.update),
+            ms(stats.draw),
+            ms(stats.total()),
+            self.budget.as_millis()
+        );
+    }
+}
+
+pub fn ms(d: Duration) -> f64 {
+    d.as_secs_f64() * 1000.0
+}
@@ -0,0 +1,168 @@
+// 入力のポップアップ（プロンプト）の共通の仕組み
+// 文字を打つ入力欄・打って絞り込む一覧・1キーで答える確認の3つの形を、キー操作と描画の材料ごとここで受け持つ。
+// 機能の側は見出しと Enter で呼ぶ関数（要れば一覧・検証・Tab 補完・Esc の関数）を渡して open_prompt するだけ。
+// 入力と選択は App の popup_input / popup_selected に置き、渡した関数はそこを読む。
+// 検証に通らなければ閉じずに入力を残し、理由を見出しに出す（打ち直すと消える）。
+// 独自のキー（Finder の Ctrl+T など）や状態を持つものは、これまで通り PopupMode に自分の形を作る
+use crate::{App, PopupMode};
+use crossterm::event::KeyCode;
+
+// 入力を確かめる関数。Err は見出しに出す理由
+type Check = fn(&App) -> Result<(), String>;
+
+#[derive(Clone)]
+pub enum Kind {
+    Text,
+    List(fn(&App) -> Vec<String>), // 入力で絞り込んだ項目
+    Keys {
+        question: String,
+        keys: Vec<(char, &'static str)>, // 押すキー（小文字）と説明
+        esc: &'static str,               // Esc の説明
+        answer: fn(&mut App, char),
+    },
+}
+
+#[derive(Clone)]
+pub struct Prompt {
+    pub kind: Kind,
+    title: String,
+    live_title: Option<fn(&App) -> String>, // 入力に合わせて変わる見出し
+    accept: fn(&mut App),                   // Enter（閉じてから呼ぶ。popup_input はまだ残っている）
+    check: Option<Check>,
+    complete: Option<fn(&mut App)>, // Tab
+    cancel: Option<fn(&mut App)>,   // Esc（閉じてから呼ぶ）
+    error: Option<String>,
+}
+
+// 関数は比べられないので見出しで比べる
+impl PartialEq for Prompt {
+    fn eq(&self, other: &Self) -> bool {
+        self.title == other.title
+    }
+}
+
+impl Prompt {
+    fn new(title: &str, kind: Kind, accept: fn(&mut App)) -> Self {
+        Prompt { kind, title: title.to_string(), live_title: None, accept, check: None, complete: None, cancel: None, error: None }
+    }
+
+    pub fn text(title: &str, accept: fn(&mut App)) -> Self {
+        Prompt::new(title, Kind::Text, accept)
+    }
+
+    pub fn list(title: &str, items: fn(&App) -> Vec<String>, accept: fn(&mut App)) -> Self {
+        Prompt::new(title, Kind::List(items), accept)
+    }
+
+    pub fn keys(title: &str, question: String, keys: Vec<(char, &'static str)>, esc: &'static str, answer: fn(&mut App, char)) -> Self {
+        Prompt::new(title, Kind::Keys { question, keys, esc, answer }, |_| {})
+    }
+
+    pub fn live_title(mut self, title: fn(&App) -> String) -> Self {
+        self.live_title = Some(title);
+        self
+    }
+
+    pub fn check(mut self, check: Check) -> Self {
+        self.check = Some(check);
+        self
+    }
+
+    pub fn complete(mut self, complete: fn(&mut App)) -> Self {
+        self.complete = Some(complete);
+        self
+    }
+
+    pub fn on_cancel(mut self, cancel: fn(&mut App)) -> Self {
+        self.cancel = Some(cancel);
+        self
+    }
+
+    // 理由は見出しの名前（: より前）の後に、説明の代わりに出す
+    pub fn title(&self, app: &App) -> String {
+        match (&self.error, self.live_title) {
+            (Some(error), _) => format!("{}: {}", self.title.split(':').next().unwrap_or_default(), error),
+            (None, Some(title)) => title(app),
+            (None, None) => self.title.clone(),
+        }
+    }
+}
+
+impl App {
+    pub fn open_prompt(&mut self, prompt: Prompt, input: &str) {
+        self.popup = Some(PopupMode::Prompt(prompt));
+        self.popup_input = input.to_string();
+        self.popup_selected = 0;
+    }
+
+    fn set_prompt_error(&mut self, error: Option<String>) {
+        if let Some(PopupMode::Prompt(ref mut prompt)) = self.popup {
+            prompt.error = error;
+        }
+    }
+
+    // 呼んだ関数が次のプロンプトを開いていなければ入力を片付ける
+    fn close_prompt_with(&mut self, then: fn(&mut App)) {
+        self.popup = None;
+        then(self);
+        if self.popup.is_none() {
+            self.popup_input.clear();
+            self.popup_selected = 0;
+        }
+    }
+
+    pub fn handle_prompt_key(&mut self, key: KeyCode) {
+        let Some(PopupMode::Prompt(prompt)) = self.popup.clone() else { return };
+        if let Kind::Keys { ref keys, answer, .. } = prompt.kind {
+            match key {
+                KeyCode::Esc => self.close_prompt_with(prompt.cancel.unwrap_or(|_| {})),
+                KeyCode::Char(c) => {
+                    let Some(&(k, _)) = keys.iter().find(|(k, _)| k.eq_ignore_ascii_case(&c)) else { return };
+                    self.popup = None;
+                    answer(self, k);
+                    if self.popup.is_none() {
+                        self.popup_input.clear();
+                        self.popup_selected = 0;
+                    }
+                }
+                _ => {}
+            }
+            return;
+        }
+        match key {
+            KeyCode::Enter => {
+                if let Some(check) = prompt.check
+                    && let Err(e) = check(self)
+                {
+                    self.set_prompt_error(Some(e));
+                    return;
+                }
+                self.close_prompt_with(prompt.accept);
+            }
+            KeyCode::Esc => self.close_prompt_with(prompt.cancel.unwrap_or(|_| {})),
+            KeyCode::Tab => {
+                if let Some(complete) = prompt.complete {
+                    complete(self);
+                    self.set_prompt_error(None);
+                }
+            }
+            KeyCode::Backspace => {
+                self.popup_input.pop();
+                self.popup_selected = 0;
+                self.set_prompt_error(None);
+            }
+            KeyCode::Char(c) => {
+                self.popup_input.push(c);
+                self.popup_selected = 0;
+                self.set_prompt_error(None);
+            }
+            KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+            KeyCode::Down => {
+                if let Kind::List(items) = prompt.kind {
+                    self.popup_selected = (self.popup_selected + 1).min(items(self).len().saturating_sub(1));
+                }
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,180 @@
+// 段落の詰め直し (Ctrl+W)
+// 選択がかかる行（選択がなければカーソルのある段落）の単語を text_width の幅に詰め直す。
+// 行頭の字下げとコメント記号（// # -- > など）はそのまま各行に付け、箇条書きの項目は1つずつ別の段落として詰める。
+// 空行やコメント記号の変わる行で段落を区切る
+use crate::App;
+use unicode_width::UnicodeWidthStr;
+
+// 長いものから調べる（/// を // と読まないように）
+const COMMENT_MARKERS: [&str; 6] = ["///", "//!", "//", "#", "--", ">"];
+
+// 字下げとコメント記号（後ろの空白1つまで）と、その後ろの本文
+fn split_prefix(line: &str) -> (&str, &str) {
+    let indent = line.len() - line.trim_start().len();
+    let rest = &line[indent..];
+    let Some(marker) = COMMENT_MARKERS.iter().find(|m| rest.starts_with(*m)) else { return line.split_at(indent) };
+    let mut end = indent + marker.len();
+    if line[end..].starts_with(' ') { end += 1; }
+    line.split_at(end)
+}
+
+// 箇条書きの印（- * + と 1. 1)）の長さ
+fn list_marker(body: &str) -> Option<usize> {
+    let body = body.trim_start();
+    if ["- ", "* ", "+ "].iter().any(|m| body.starts_with(m)) { return Some(2); }
+    let digits = body.len() - body.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let rest = &body[digits..];
+    (digits > 0 && (rest.starts_with(". ") || rest.starts_with(") "))).then_some(digits + 2)
+}
+
+// 同じ段落に続けてよい行か。コメント記号がなければ字下げが1行目か2行目以降（箇条書きの続き）と同じ行だけ
+fn continues(prefix: &str, rest: &str, line: &str) -> bool {
+    let (p, body) = split_prefix(line);
+    if p.trim_end() != prefix.trim_end() || body.trim().is_empty() || list_marker(body).is_some() { return false; }
+    let indent = line.len() - line.trim_start().len();
+    !prefix.trim().is_empty() || indent == prefix.len() || indent == rest.len()
+}
+
+// 単語を幅に収まるだけ並べる。1語で幅を超えるならその語だけの行にする
+fn fill(words: &[&str], first: &str, rest: &str, width: usize) -> Vec<String> {
+    let mut lines = Vec::new();
+    let mut line = first.to_string();
+    let mut empty = true;
+    for word in words {
+        if !empty && line.width() + 1 + word.width() > width {
+            lines.push(line);
+            line = rest.to_string();
+            empty = true;
+        }
+        if !empty { line.push(' '); }
+        line.push_str(word);
+        empty = false;
+    }
+    lines.push(line);
+    lines
+}
+
+// 行の並びを段落ごとに詰め直す
+pub fn reflow(lines: &[String], width: usize) -> Vec<String> {
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < lines.len() {
+        let (prefix, body) = split_prefix(&lines[i]);
+        if body.trim().is_empty() {
+            out.push(lines[i].clone());
+            i += 1;
+            continue;
+        }
+        // 箇条書きなら2行目からは印の幅だけ下げる
+        let (lead, text) = match list_marker(body) {
+            Some(n) => {
+                let n = n + body.len() - body.trim_start().len();
+                (&body[..n], &body[n..])
+            }
+            None => ("", body),
+        };
+        let first = format!("{}{}", prefix, lead);
+        let rest = format!("{}{}", prefix, " ".repeat(lead.width()));
+        let mut words: Vec<&str> = text.split_whitespace().collect();
+        i += 1;
+        while i < lines.len() && continues(prefix, &rest, &lines[i]) {
+            words.extend(split_prefix(&lines[i]).1.split_whitespace());
+            i += 1;
+        }
+        out.extend(fill(&words, &first, &rest, width));
+    }
+    out
+}
+
+impl App {
+    // ファイルタイプの設定 > コミットメッセージの本文の72桁 > 全体の設定
+    fn text_width(&self) -> usize {
+        match self.config.filetype(self.filetype()).text_width {
+            Some(width) => width,
+            None if self.is_commit_message() => 72,
+            None => self.config.text_width,
+        }
+    }
+
+    // カーソルの行と同じコメント記号で続く、空行までのまとまり
+    fn paragraph_at_cursor(&self) -> Option<(usize, usize)> {
+        let (prefix, body) = split_prefix(&self.lines[self.cursor_y]);
+        if body.trim().is_empty() { return None; }
+        let same = |y: usize| {
+            let (p, b) = split_prefix(&self.lines[y]);
+            p.trim_end() == prefix.trim_end() && !b.trim().is_empty()
+        };
+        let mut first = self.cursor_y;
+        while first > 0 && same(first - 1) { first -= 1; }
+        let mut last = self.cursor_y;
+        while last + 1 < self.lines.len() && same(last + 1) { last += 1; }
+        Some((first, last))
+    }
+
+    pub fn reflow_paragraph(&mut self) {
+        let Some((first, last)) = self.selected_lines().or_else(|| self.paragraph_at_cursor()) else {
+            self.message = Some("No paragraph at the cursor".to_string());
+            return;
+        };
+        let width = self.text_width();
+        let output = reflow(&self.lines[first..=last], width);
+        if output == self.lines[first..=last] {
+            self.message = Some(format!("Already fits in {} columns", width));
+            return;
+        }
+        let end = first + output.len() - 1;
+        self.save_undo();
+        self.lines.splice(first..=last, output);
+        self.selection_reset();
+        self.cursor_y = end;
+        self.cursor_x = self.lines[end].len();
+        self.adjust_h_scroll(0);
+        self.message = Some(format!("Reflowed to {} columns", width));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn run(lines: &[&str], width: usize) -> Vec<String> {
+        let lines: Vec<String> = lines.iter().map(|s| s.to_string()).collect();
+        reflow(&lines, width)
+    }
+
+    #[test]
+    fn fills_words_to_the_width() {
+        assert_eq!(
+            run(&["the quick brown fox jumps over", "the lazy dog"], 15),
+            ["the quick brown", "fox jumps over", "the lazy dog"]
+        );
+    }
+
+    #[test]
+    fn keeps_indent_and_comment_markers() {
+        assert_eq!(run(&["    // aaa bbb", "    // ccc ddd"], 14), ["    // aaa bbb", "    // ccc ddd"]);
+        assert_eq!(run(&["    // aaa bbb ccc ddd"], 14), ["    // aaa bbb", "    // ccc ddd"]);
+        assert_eq!(run(&["/// a", "/// b"], 80), ["/// a b"]);
+        // コメント記号が変われば別の段落
+        assert_eq!(run(&["# a", "// b"], 80), ["# a", "// b"]);
+    }
+
+    #[test]
+    fn fills_list_items_separately() {
+        assert_eq!(
+            run(&["- one two three", "- four", "  five six"], 12),
+            ["- one two", "  three", "- four five", "  six"]
+        );
+        assert_eq!(run(&["1. alpha beta gamma"], 10), ["1. alpha", "   beta", "   gamma"]);
+    }
+
+    #[test]
+    fn blank_lines_separate_paragraphs() {
+        assert_eq!(run(&["a", "b", "", "c"], 80), ["a b", "", "c"]);
+    }
+
+    #[test]
+    fn long_word_gets_its_own_line() {
+        assert_eq!(run(&["short verylongword x"], 5), ["short", "verylongword", "x"]);
+    }
+}
@@ -0,0 +1,87 @@
+// 検索結果パネル（エディタ下部）
+// バッファ内検索・全文検索の結果を残しておき、Alt+Q でいつでも開き直して Up/Down と Enter で移動できる
+use crate::{grapheme, App};
+use crossterm::event::KeyCode;
+use std::path::PathBuf;
+
+#[derive(Clone)]
+pub struct ResultItem {
+    pub path: Option<PathBuf>, // None は名前のないバッファ
+    pub line: usize,
+    pub col: usize, // バイト位置
+    pub text: String,
+}
+
+#[derive(Clone, Default)]
+pub struct ResultsPanel {
+    pub title: String,
+    pub items: Vec<ResultItem>,
+    pub selected: usize,
+    pub scroll: usize,
+    pub visible: bool,
+    pub focused: bool,
+}
+
+impl ResultsPanel {
+    pub fn update_scroll(&mut self, height: usize) {
+        if self.selected < self.scroll {
+            self.scroll = self.selected;
+        } else if height > 0 && self.selected >= self.scroll + height {
+            self.scroll = self.selected + 1 - height;
+        }
+    }
+}
+
+impl App {
+    // 結果を差し替えてパネルを表示する（フォーカスは移さない）
+    pub fn set_results(&mut self, title: String, items: Vec<ResultItem>, selected: usize) {
+        self.results = ResultsPanel { title, items, selected, scroll: 0, visible: true, focused: false };
+    }
+
+    // Alt+Q: パネルを開いてフォーカス、フォーカス中なら閉じる
+    pub fn toggle_results(&mut self) {
+        if self.results.focused {
+            self.results.visible = false;
+            self.results.focused = false;
+        } else if self.results.items.is_empty() {
+            self.message = Some("No search results".to_string());
+        } else {
+            self.results.visible = true;
+            self.results.focused = true;
+            self.task.focused = false;
+        }
+    }
+
+    // フォーカス中のキー処理。Enter で移動してエディタにフォーカスを戻す（パネルは残す）
+    pub fn handle_results_key(&mut self, code: KeyCode) {
+        let len = self.results.items.len();
+        match code {
+            KeyCode::Up => self.results.selected = self.results.selected.saturating_sub(1),
+            KeyCode::Down => self.results.selected = (self.results.selected + 1).min(len.saturating_sub(1)),
+            KeyCode::PageUp => self.results.selected = self.results.selected.saturating_sub(10),
+            KeyCode::PageDown => self.results.selected = (self.results.selected + 10).min(len.saturating_sub(1)),
+            KeyCode::Enter => {
+                self.results.focused = false;
+                self.jump_to_result();
+            }
+            KeyCode::Esc => {
+                self.results.focused = false;
+                self.results.visible = false;
+            }
+            _ => {}
+        }
+    }
+
+    fn jump_to_result(&mut self) {
+        let Some(item) = self.results.items.get(self.results.selected).cloned() else { return };
+        self.record_jump();
+        match item.path {
+            Some(path) => self.goto_location(&path, item.line, item.col),
+            None => {
+                self.cursor_y = item.line.min(self.lines.len() - 1);
+                self.cursor_x = grapheme::floor(&self.lines[self.cursor_y], item.col);
+                self.selection_reset();
+            }
+        }
+    }
+}
@@ -0,0 +1,117 @@
+// 検索の一致判定（バッファ内検索・全文検索・置換で共通）
+// 大文字小文字は 区別する / しない / smart case（クエリに大文字があるときだけ区別）を切り替えられる
+#[derive(Clone, Copy, PartialEq)]
+pub enum CaseMode {
+    Smart,
+    Sensitive,
+    Insensitive,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub struct SearchOptions {
+    pub case: CaseMode,
+    pub whole_word: bool,
+}
+
+impl Default for SearchOptions {
+    fn default() -> Self {
+        SearchOptions { case: CaseMode::Smart, whole_word: false }
+    }
+}
+
+impl SearchOptions {
+    pub fn cycle_case(&mut self) {
+        self.case = match self.case {
+            CaseMode::Smart => CaseMode::Sensitive,
+            CaseMode::Sensitive => CaseMode::Insensitive,
+            CaseMode::Insensitive => CaseMode::Smart,
+        };
+    }
+    // プロンプトに出す現在のモード
+    pub fn label(&self) -> String {
+        let case = match self.case {
+            CaseMode::Smart => "smart-case",
+            CaseMode::Sensitive => "case-sensitive",
+            CaseMode::Insensitive => "ignore-case",
+        };
+        format!("[{}{}]", case, if self.whole_word { ", whole-word" } else { "" })
+    }
+}
+
+pub struct Matcher {
+    query: String,
+    needle: Vec<char>,
+    ignore_case: bool,
+    whole_word: bool,
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+impl Matcher {
+    pub fn new(query: &str, opts: SearchOptions) -> Self {
+        let ignore_case = match opts.case {
+            CaseMode::Smart => !query.chars().any(|c| c.is_uppercase()),
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+        };
+        Matcher { query: query.to_string(), needle: query.chars().collect(), ignore_case, whole_word: opts.whole_word }
+    }
+
+    fn char_eq(&self, a: char, b: char) -> bool {
+        a == b || (self.ignore_case && a.to_lowercase().eq(b.to_lowercase()))
+    }
+
+    // 重ならない一致のバイト範囲
+    pub fn find_all(&self, line: &str) -> Vec<(usize, usize)> {
+        let mut found = Vec::new();
+        if self.needle.is_empty() { return found; }
+        // 大文字小文字を区別するなら標準の部分文字列検索で足りる
+        if !self.ignore_case {
+            for (start, m) in line.match_indices(&self.query) {
+                let end = start + m.len();
+                let before = line[..start].chars().next_back();
+                let after = line[end..].chars().next();
+                if !self.whole_word || (!before.is_some_and(is_word_char) && !after.is_some_and(is_word_char)) {
+                    found.push((start, end));
+                }
+            }
+            return found;
+        }
+        let chars: Vec<(usize, char)> = line.char_indices().collect();
+        let mut i = 0;
+        while i + self.needle.len() <= chars.len() {
+            let hit = self.needle.iter().enumerate().all(|(k, &n)| self.char_eq(chars[i + k].1, n));
+            let end = i + self.needle.len();
+            let bounded = !self.whole_word
+                || ((i == 0 || !is_word_char(chars[i - 1].1)) && (end == chars.len() || !is_word_char(chars[end].1)));
+            if hit && bounded {
+                let end_byte = chars.get(end).map(|c| c.0).unwrap_or(line.len());
+                found.push((chars[i].0, end_byte));
+                i = end;
+            } else {
+                i += 1;
+            }
+        }
+        found
+    }
+
+    pub fn find(&self, line: &str) -> Option<usize> {
+        self.find_all(line).first().map(|m| m.0)
+    }
+
+    // 一致をすべて置き換える。戻り値は (置換後, 置換した数)
+    pub fn replace_all(&self, line: &str, replacement: &str) -> (String, usize) {
+        let matches = self.find_all(line);
+        let mut out = String::new();
+        let mut last = 0;
+        for &(start, end) in &matches {
+            out.push_str(&line[last..start]);
+            out.push_str(replacement);
+            last = end;
+        }
+        out.push_str(&line[last..]);
+        (out, matches.len())
+    }
+}
@@ -0,0 +1,62 @@
+// 一覧の並び順。FileTree やピッカーで共通に使う
+// 並べたい型は Sortable を実装し、sort_items か comparator を使う
+use std::{cmp::Ordering, time::SystemTime};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortMode {
+    Name,     // 名前順（大文字小文字は区別しない）
+    Modified, // 更新日時の新しい順
+    Size,     // サイズの大きい順
+    Frecency, // よく・最近開いた順（同点は更新日時順）
+}
+
+impl SortMode {
+    // 切り替えキーで次のモードへ
+    pub fn next(self) -> Self {
+        match self {
+            SortMode::Name => SortMode::Modified,
+            SortMode::Modified => SortMode::Size,
+            SortMode::Size => SortMode::Frecency,
+            SortMode::Frecency => SortMode::Name,
+        }
+    }
+    pub fn label(self) -> &'static str {
+        match self {
+            SortMode::Name => "name",
+            SortMode::Modified => "mtime",
+            SortMode::Size => "size",
+            SortMode::Frecency => "frecency",
+        }
+    }
+}
+
+// 並べ替えに使う情報。持っていない項目は None のままでよい（後ろに回る）
+pub trait Sortable {
+    fn sort_name(&self) -> String;
+    fn sort_modified(&self) -> Option<SystemTime> { None }
+    fn sort_size(&self) -> Option<u64> { None }
+    fn sort_frecency(&self) -> u64 { 0 }
+}
+
+// 同じ値どうしは名前順にする
+pub fn comparator<T: Sortable>(mode: SortMode) -> impl Fn(&T, &T) -> Ordering {
+    move |a, b| {
+        let primary = match mode {
+            SortMode::Name => Ordering::Equal,
+            SortMode::Modified => b.sort_modified().cmp(&a.sort_modified()),
+            SortMode::Size => b.sort_size().cmp(&a.sort_size()),
+            SortMode::Frecency => b
+                .sort_frecency()
+                .cmp(&a.sort_frecency())
+                .then_with(|| b.sort_modified().cmp(&a.sort_modified())),
+        };
+        primary.then_with(|| {
+            let (na, nb) = (a.sort_name(), b.sort_name());
+            na.to_lowercase().cmp(&nb.to_lowercase()).then(na.cmp(&nb))
+        })
+    }
+}
+
+pub fn sort_items<T: Sortable>(items: &mut [T], mode: SortMode) {
+    items.sort_by(comparator(mode));
+}
@@ -0,0 +1,260 @@
+// スペルチェック（設定 `spell_check = true` で有効）
+// 辞書は `spell.dictionary`（既定 /usr/share/dict/words。1行1語で、hunspell の .dic も読める）。
+// 文章（text / markdown / gitcommit）は全体を、構文木のある言語はコメントだけを調べ、辞書にない語に下線を引く。
+// Alt+m でカーソル位置の語の候補を出して置き換える。一覧の最後の項目でこのセッションの間だけ辞書に加える
+use crate::{syntax::Highlights, App, PopupMode};
+use crossterm::event::KeyCode;
+use std::{collections::HashSet, rc::Rc};
+
+const MAX_SUGGESTIONS: usize = 8;
+
+#[derive(Clone, Default)]
+pub struct Speller {
+    words: Option<Rc<HashSet<String>>>, // 辞書（小文字）。描画のたびに App を複製するので共有する
+    tried: bool,                        // 読み込みを試した（失敗しても繰り返さない）
+    session: HashSet<String>,           // このセッションで辞書に加えた語（小文字）
+}
+
+// 画面に見えている行の綴りの誤り。行ごとに (開始バイト, 終了バイト)
+#[derive(Default)]
+pub struct Misspellings {
+    first: usize,
+    rows: Vec<Vec<(usize, usize)>>,
+}
+
+impl Misspellings {
+    pub fn line(&self, row: usize) -> &[(usize, usize)] {
+        row.checked_sub(self.first).and_then(|i| self.rows.get(i)).map_or(&[], |r| r)
+    }
+}
+
+// 辞書ファイルを読む。hunspell の .dic は先頭行が語数で、語の後ろに `/フラグ` が付く
+fn load_words(path: &str) -> std::io::Result<HashSet<String>> {
+    let content = std::fs::read_to_string(path)?;
+    Ok(content
+        .lines()
+        .map(|l| l.split('/').next().unwrap_or("").trim())
+        .filter(|w| !w.is_empty() && !w.bytes().all(|b| b.is_ascii_digit()))
+        .map(str::to_lowercase)
+        .collect())
+}
+
+// text の中の調べる語の位置。URL・パス・識別子らしい塊（数字や `_` を含むなど）と `code` の中は飛ばす
+fn words(text: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut in_code = false;
+    let mut pos = 0;
+    for token in text.split_inclusive(|c: char| c.is_whitespace()) {
+        let start = pos;
+        pos += token.len();
+        let ticks = token.matches('`').count();
+        let skip = in_code
+            || ticks > 0
+            || token.contains("://")
+            || token.contains(['/', '\\', '@', '_', '=', '<', '>'])
+            || token.contains(|c: char| c.is_ascii_digit());
+        if ticks % 2 == 1 { in_code = !in_code; }
+        if skip { continue; }
+        let mut word_start = None;
+        for (i, c) in token.char_indices().chain(std::iter::once((token.len(), ' '))) {
+            let is_word = c.is_alphabetic() || (c == '\'' && word_start.is_some());
+            match (is_word, word_start) {
+                (true, None) => word_start = Some(i),
+                (false, Some(s)) => {
+                    // 末尾の ' は引用符とみなす
+                    out.push((start + s, start + s + token[s..i].trim_end_matches('\'').len()));
+                    word_start = None;
+                }
+                _ => {}
+            }
+        }
+    }
+    out
+}
+
+// 調べない語: 1文字、英字以外を含む、2文字目以降に大文字（略語や camelCase）
+fn checkable(word: &str) -> bool {
+    word.len() > 1
+        && word.chars().all(|c| c.is_ascii_alphabetic() || c == '\'')
+        && !word.chars().skip(1).any(|c| c.is_ascii_uppercase())
+}
+
+// 隣り合う文字の入れ替えも1回と数える編集距離。max を超えたら None
+fn distance(a: &[u8], b: &[u8], max: usize) -> Option<usize> {
+    if a.len().abs_diff(b.len()) > max { return None; }
+    let mut prev2 = vec![0; b.len() + 1];
+    let mut prev: Vec<usize> = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        let mut cur = vec![i; b.len() + 1];
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                cur[j] = cur[j].min(prev2[j - 2] + 1);
+            }
+        }
+        if cur.iter().min().is_some_and(|&m| m > max) { return None; }
+        prev2 = std::mem::replace(&mut prev, cur);
+    }
+    Some(prev[b.len()]).filter(|&d| d <= max)
+}
+
+// 元の語の大文字の使い方（先頭だけ・全部）を候補に写す
+fn match_case(word: &str, suggestion: &str) -> String {
+    if word.len() > 1 && word.chars().all(|c| !c.is_ascii_lowercase()) {
+        suggestion.to_uppercase()
+    } else if word.starts_with(|c: char| c.is_ascii_uppercase()) {
+        let mut chars = suggestion.chars();
+        chars.next().map(|c| c.to_uppercase().chain(chars).collect()).unwrap_or_default()
+    } else {
+        suggestion.to_string()
+    }
+}
+
+impl Speller {
+    fn known(&self, words: &HashSet<String>, word: &str) -> bool {
+        let lower = word.to_lowercase();
+        let base = lower.strip_suffix("'s").unwrap_or(&lower);
+        [lower.as_str(), base].iter().any(|w| words.contains(*w) || self.session.contains(*w))
+    }
+}
+
+impl App {
+    // spell_check が有効なら最初の1回だけ辞書を読む（メインループで呼ぶ）
+    pub fn load_spelling(&mut self) {
+        if self.config.spell_check && !self.spell.tried {
+            self.load_dictionary();
+            self.dirty = true;
+        }
+    }
+
+    fn load_dictionary(&mut self) -> Option<Rc<HashSet<String>>> {
+        if !self.spell.tried {
+            self.spell.tried = true;
+            match load_words(&self.config.spell_dictionary) {
+                Ok(words) => self.spell.words = Some(Rc::new(words)),
+                Err(e) => self.message = Some(format!("Cannot read dictionary {}: {}", self.config.spell_dictionary, e)),
+            }
+        }
+        self.spell.words.clone()
+    }
+
+    // first..last 行の綴りの誤り。文章は全体、コードはコメントだけ（Markdown のコードブロックは除く）
+    pub fn misspellings(&self, first: usize, last: usize, highlights: &Highlights) -> Misspellings {
+        let mut out = Misspellings { first, rows: Vec::new() };
+        let Some(dict) = self.spell.words.as_deref().filter(|_| self.config.spell_check) else { return out };
+        let filetype = self.filetype();
+        let prose = matches!(filetype, "text" | "markdown" | "gitcommit");
+        let mut fenced = false;
+        if filetype == "markdown" {
+            fenced = self.lines[..first.min(self.lines.len())].iter().filter(|l| is_fence(l)).count() % 2 == 1;
+        }
+        for row in first..last.min(self.lines.len()) {
+            let line = &self.lines[row];
+            let ranges: Vec<(usize, usize)> = if prose {
+                let fence = filetype == "markdown" && is_fence(line);
+                if fence { fenced = !fenced; }
+                if fenced || fence || (filetype == "gitcommit" && line.starts_with('#')) {
+                    Vec::new()
+                } else {
+                    vec![(0, line.len())]
+                }
+            } else {
+                highlights.comments(row).iter().map(|&(s, e)| (s.min(line.len()), e.min(line.len()))).collect()
+            };
+            let mut bad = Vec::new();
+            for (s, e) in ranges {
+                let Some(text) = line.get(s..e) else { continue };
+                for (ws, we) in words(text) {
+                    let word = &text[ws..we];
+                    if checkable(word) && !self.spell.known(dict, word) {
+                        bad.push((s + ws, s + we));
+                    }
+                }
+            }
+            out.rows.push(bad);
+        }
+        out
+    }
+
+    // カーソル位置（またはその直前）の語の範囲
+    fn word_at_cursor(&self) -> Option<(usize, usize)> {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        words(line).into_iter().find(|&(s, e)| s <= x && x <= e)
+    }
+
+    // Alt+m: カーソル位置の語の候補を出す
+    pub fn spelling_suggestions(&mut self) {
+        let Some(dict) = self.load_dictionary() else {
+            if self.message.is_none() {
+                self.message = Some(format!("No dictionary at {} (set spell.dictionary)", self.config.spell_dictionary));
+            }
+            return;
+        };
+        let Some((start, end)) = self.word_at_cursor() else {
+            self.message = Some("No word at cursor".to_string());
+            return;
+        };
+        let word = self.lines[self.cursor_y][start..end].to_string();
+        if self.spell.known(&dict, &word) {
+            self.message = Some(format!("\"{}\" is in the dictionary", word));
+            return;
+        }
+        let lower = word.to_lowercase();
+        let mut found: Vec<(usize, usize, &String)> = dict
+            .iter()
+            .chain(self.spell.session.iter())
+            .filter_map(|w| distance(lower.as_bytes(), w.as_bytes(), 2).map(|d| (d, w.len().abs_diff(lower.len()), w)))
+            .collect();
+        found.sort();
+        let mut suggestions: Vec<String> = Vec::new();
+        for (_, _, w) in found {
+            let s = match_case(&word, w);
+            if !suggestions.contains(&s) { suggestions.push(s); }
+            if suggestions.len() == MAX_SUGGESTIONS { break; }
+        }
+        self.popup = Some(PopupMode::Spelling { line: self.cursor_y, start, end, word, suggestions });
+        self.popup_selected = 0;
+    }
+
+    pub fn spelling_items(&self) -> Vec<String> {
+        let Some(PopupMode::Spelling { ref word, ref suggestions, .. }) = self.popup else { return Vec::new() };
+        let mut items = suggestions.clone();
+        items.push(format!("+ Add \"{}\" to the dictionary (this session)", word));
+        items
+    }
+
+    pub fn handle_spelling_key(&mut self, code: KeyCode) {
+        let Some(PopupMode::Spelling { line, start, end, word, suggestions }) = self.popup.clone() else { return };
+        match code {
+            KeyCode::Up => self.popup_selected = self.popup_selected.saturating_sub(1),
+            KeyCode::Down => self.popup_selected = (self.popup_selected + 1).min(suggestions.len()),
+            KeyCode::Enter => {
+                self.popup = None;
+                match suggestions.get(self.popup_selected) {
+                    // 開いた後に行が変わっていなければ置き換える
+                    Some(s) if self.lines.get(line).and_then(|l| l.get(start..end)) == Some(word.as_str()) => {
+                        self.save_undo();
+                        self.lines[line].replace_range(start..end, s);
+                        self.cursor_y = line;
+                        self.cursor_x = start + s.len();
+                        self.selection_reset();
+                    }
+                    Some(_) => {}
+                    None => {
+                        self.spell.session.insert(word.to_lowercase());
+                        self.message = Some(format!("Added \"{}\" to the dictionary for this session", word));
+                    }
+                }
+            }
+            KeyCode::Esc => self.popup = None,
+            _ => {}
+        }
+    }
+}
+
+fn is_fence(line: &str) -> bool {
+    let t = line.trim_start();
+    t.starts_with("```") || t.starts_with("~~~")
+}
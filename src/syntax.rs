@@ -0,0 +1,286 @@
+// tree-sitter による構文解析（Rust / Python / JavaScript / JSON / C）
+// バッファごとに構文木を持ち、内容が変わったら変わった範囲だけを伝えて差分で解析し直す。
+// 構文木は色付け、Enter の自動インデント、Alt+s / Alt+a の構造選択（ノード単位で選択を広げる・戻す）、
+// スペルチェックするコメントの範囲に使う
+use crate::{buffer::Lines, App, TextPos};
+use std::cmp::Reverse;
+use streaming_iterator::StreamingIterator;
+use tree_sitter::{InputEdit, Language, Parser, Point, Query, QueryCursor, Tree};
+use tui::style::Color;
+
+pub struct Syntax {
+    filetype: &'static str,
+    parser: Parser,
+    tree: Option<Tree>,
+    query: Query,
+    colors: Vec<Option<Color>>,          // キャプチャごとの色（query のキャプチャ番号順）
+    comments: Vec<bool>,                 // キャプチャがコメントか（同じ順）
+    text: String,                        // 最後に解析した内容
+    generation: Option<u64>,             // そのときのバッファの世代
+    selections: Vec<(TextPos, TextPos)>, // 構造選択の履歴（最後が今の選択）
+}
+
+// 画面に見えている行の色付け。行ごとに (開始バイト, 終了バイト, 色) を塗る順に並べる
+#[derive(Default)]
+pub struct Highlights {
+    first: usize,
+    rows: Vec<Vec<(usize, usize, Color)>>,
+    comments: Vec<Vec<(usize, usize)>>, // 行ごとのコメントの範囲
+}
+
+impl Highlights {
+    pub fn line(&self, row: usize) -> &[(usize, usize, Color)] {
+        row.checked_sub(self.first).and_then(|i| self.rows.get(i)).map_or(&[], |r| r)
+    }
+
+    pub fn comments(&self, row: usize) -> &[(usize, usize)] {
+        row.checked_sub(self.first).and_then(|i| self.comments.get(i)).map_or(&[], |r| r)
+    }
+}
+
+fn language(filetype: &str) -> Option<(Language, &'static str)> {
+    Some(match filetype {
+        "rust" => (tree_sitter_rust::LANGUAGE.into(), tree_sitter_rust::HIGHLIGHTS_QUERY),
+        "python" => (tree_sitter_python::LANGUAGE.into(), tree_sitter_python::HIGHLIGHTS_QUERY),
+        "javascript" => (tree_sitter_javascript::LANGUAGE.into(), tree_sitter_javascript::HIGHLIGHT_QUERY),
+        "json" => (tree_sitter_json::LANGUAGE.into(), tree_sitter_json::HIGHLIGHTS_QUERY),
+        "c" => (tree_sitter_c::LANGUAGE.into(), tree_sitter_c::HIGHLIGHT_QUERY),
+        _ => return None,
+    })
+}
+
+// キャプチャ名（`function.method` など）の色。先頭の区分で決める
+fn capture_color(name: &str) -> Option<Color> {
+    if name == "string.special.key" {
+        return Some(Color::Rgb(224, 108, 117));
+    }
+    Some(match name.split('.').next()? {
+        "comment" => Color::Rgb(106, 115, 125),
+        "string" | "character" => Color::Rgb(152, 195, 121),
+        "keyword" => Color::Rgb(198, 120, 221),
+        "function" => Color::Rgb(97, 175, 239),
+        "type" | "constructor" => Color::Rgb(229, 192, 123),
+        "number" | "constant" | "boolean" => Color::Rgb(209, 154, 102),
+        "escape" | "attribute" | "label" => Color::Rgb(86, 182, 194),
+        "property" => Color::Rgb(224, 108, 117),
+        _ => return None,
+    })
+}
+
+fn point_at(text: &[u8], byte: usize) -> Point {
+    let before = &text[..byte];
+    let row = before.iter().filter(|&&c| c == b'\n').count();
+    let column = byte - before.iter().rposition(|&c| c == b'\n').map_or(0, |i| i + 1);
+    Point::new(row, column)
+}
+
+// 古い内容と新しい内容の共通の先頭・末尾を除いた部分を、ひとつの編集として表す
+fn input_edit(old: &str, new: &str) -> InputEdit {
+    let (a, b) = (old.as_bytes(), new.as_bytes());
+    let prefix = a.iter().zip(b).take_while(|(x, y)| x == y).count();
+    let max_suffix = a.len().min(b.len()) - prefix;
+    let suffix = a.iter().rev().zip(b.iter().rev()).take(max_suffix).take_while(|(x, y)| x == y).count();
+    InputEdit {
+        start_byte: prefix,
+        old_end_byte: a.len() - suffix,
+        new_end_byte: b.len() - suffix,
+        start_position: point_at(a, prefix),
+        old_end_position: point_at(a, a.len() - suffix),
+        new_end_position: point_at(b, b.len() - suffix),
+    }
+}
+
+// start..end のうち first..last 行にかかる部分を行ごとの (行, 開始バイト, 終了バイト) にする
+fn row_spans(start: Point, end: Point, first: usize, last: usize) -> impl Iterator<Item = (usize, usize, usize)> {
+    (start.row.max(first)..=end.row.min(last.saturating_sub(1))).map(move |row| {
+        let from = if row == start.row { start.column } else { 0 };
+        let to = if row == end.row { end.column } else { usize::MAX };
+        (row, from, to)
+    })
+}
+
+fn to_pos(p: Point) -> TextPos {
+    (p.row, p.column)
+}
+
+// 文字列やコメント（中の括弧はインデントに数えない）
+fn is_literal(kind: &str) -> bool {
+    kind.contains("string") || kind.contains("comment")
+}
+
+impl Syntax {
+    fn new(filetype: &'static str) -> Option<Self> {
+        let (lang, source) = language(filetype)?;
+        let mut parser = Parser::new();
+        parser.set_language(&lang).ok()?;
+        let query = Query::new(&lang, source).ok()?;
+        let colors = query.capture_names().iter().map(|name| capture_color(name)).collect();
+        let comments = query.capture_names().iter().map(|name| name.starts_with("comment")).collect();
+        Some(Syntax { filetype, parser, tree: None, query, colors, comments, text: String::new(), generation: None, selections: Vec::new() })
+    }
+
+    // lines の内容で構文木を更新する。前回から書き換えていなければ何もしない
+    fn update(&mut self, lines: &Lines) {
+        if self.tree.is_some() && self.generation == Some(lines.generation()) { return; }
+        self.generation = Some(lines.generation());
+        let text = lines.join("\n");
+        if text == self.text && self.tree.is_some() { return; }
+        if let Some(tree) = self.tree.as_mut() {
+            tree.edit(&input_edit(&self.text, &text));
+        }
+        self.tree = self.parser.parse(&text, self.tree.as_ref());
+        self.text = text;
+    }
+
+    // first..last 行の色付け。大きいノードを先に塗り、内側のノードが上に来るようにする
+    fn highlights(&self, first: usize, last: usize) -> Highlights {
+        let rows = last.saturating_sub(first);
+        let mut out = Highlights { first, rows: vec![Vec::new(); rows], comments: vec![Vec::new(); rows] };
+        let Some(tree) = &self.tree else { return out };
+        let mut cursor = QueryCursor::new();
+        cursor.set_point_range(Point::new(first, 0)..Point::new(last, 0));
+        let mut spans = Vec::new();
+        let mut captures = cursor.captures(&self.query, tree.root_node(), self.text.as_bytes());
+        while let Some((m, i)) = captures.next() {
+            let cap = m.captures[*i];
+            let node = cap.node;
+            if let Some(color) = self.colors[cap.index as usize] {
+                spans.push((node.byte_range().len(), m.pattern_index, node.start_position(), node.end_position(), color));
+            }
+            if self.comments[cap.index as usize] {
+                for (row, from, to) in row_spans(node.start_position(), node.end_position(), first, last) {
+                    out.comments[row - first].push((from, to));
+                }
+            }
+        }
+        // 同じ大きさなら前に書かれたパターンを優先する（後から塗る）
+        spans.sort_by_key(|s| (Reverse(s.0), Reverse(s.1)));
+        for (_, _, start, end, color) in spans {
+            for (row, from, to) in row_spans(start, end, first, last) {
+                out.rows[row - first].push((from, to, color));
+            }
+        }
+        out
+    }
+}
+
+impl App {
+    // 構文木を今の内容に合わせる（描画の前と、構文木を使う操作の前に呼ぶ）
+    pub fn sync_syntax(&mut self) {
+        let ft = self.filetype();
+        if self.syntax.as_ref().map(|s| s.filetype) != Some(ft) {
+            self.syntax = Syntax::new(ft);
+        }
+        if let Some(syntax) = self.syntax.as_mut() {
+            syntax.update(&self.lines);
+        }
+    }
+
+    pub fn syntax_highlights(&self, first: usize, last: usize) -> Highlights {
+        self.syntax.as_ref().map(|s| s.highlights(first, last)).unwrap_or_default()
+    }
+
+    // 選択範囲（正規化済み）。なければカーソル位置の空の範囲
+    fn current_range(&self) -> (TextPos, TextPos) {
+        match (self.sel_start, self.sel_end) {
+            (Some(s), Some(e)) => (s.min(e), s.max(e)),
+            _ => ((self.cursor_y, self.cursor_x), (self.cursor_y, self.cursor_x)),
+        }
+    }
+
+    fn set_range(&mut self, (start, end): (TextPos, TextPos)) {
+        if start == end {
+            self.selection_reset();
+        } else {
+            self.sel_start = Some(start);
+            self.sel_end = Some(end);
+            self.shift_selection = true;
+        }
+        (self.cursor_y, self.cursor_x) = end;
+        self.adjust_h_scroll(0);
+    }
+
+    // 今の選択を包む一番小さいノードまで選択を広げる
+    pub fn expand_selection(&mut self) {
+        self.sync_syntax();
+        let current = self.current_range();
+        let Some(syntax) = self.syntax.as_mut() else {
+            self.message = Some("No syntax tree for this file".to_string());
+            return;
+        };
+        let Some(tree) = &syntax.tree else { return };
+        let mut node = tree.root_node().descendant_for_point_range(Point::new(current.0.0, current.0.1), Point::new(current.1.0, current.1.1));
+        let range = loop {
+            let Some(n) = node else {
+                self.message = Some("Selection already covers the whole file".to_string());
+                return;
+            };
+            let range = (to_pos(n.start_position()), to_pos(n.end_position()));
+            if n.is_named() && range != current {
+                break range;
+            }
+            node = n.parent();
+        };
+        if syntax.selections.last() != Some(&current) {
+            syntax.selections = vec![current];
+        }
+        syntax.selections.push(range);
+        self.set_range(range);
+    }
+
+    // expand_selection の前の選択に戻す
+    pub fn shrink_selection(&mut self) {
+        let current = self.current_range();
+        let prev = self.syntax.as_mut().and_then(|syntax| {
+            if syntax.selections.len() < 2 || syntax.selections.last() != Some(&current) {
+                return None;
+            }
+            syntax.selections.pop();
+            syntax.selections.last().copied()
+        });
+        match prev {
+            Some(range) => self.set_range(range),
+            None => self.message = Some("Nothing to shrink".to_string()),
+        }
+    }
+
+    // Enter: 今の行のインデントを引き継ぎ、開き括弧（Python は `:`）で終わっていれば1段深くする。
+    // `{|}` のように閉じ括弧が続くときは、閉じ括弧を元のインデントで次の行へ送る。
+    // 構文木のない言語では false を返す
+    pub fn syntax_enter(&mut self) -> bool {
+        self.sync_syntax();
+        let Some(syntax) = self.syntax.as_ref() else { return false };
+        let Some(tree) = &syntax.tree else { return false };
+        let y = self.cursor_y;
+        let line = &self.lines[y];
+        let x = self.cursor_x.min(line.len());
+        let base: String = line[..x].chars().take_while(|&c| c == ' ' || c == '\t').collect();
+        let opener = line[..x].trim_end().char_indices().next_back().filter(|&(i, c)| {
+            let p = Point::new(y, i);
+            (matches!(c, '{' | '[' | '(') || (c == ':' && syntax.filetype == "python"))
+                && tree.root_node().descendant_for_point_range(p, p).is_some_and(|n| !is_literal(n.kind()))
+        });
+        let mut indent = base.clone();
+        if opener.is_some() {
+            indent.push_str(&if base.contains('\t') { "\t".to_string() } else { self.indent().unit() });
+        }
+        let closer = match opener {
+            Some((_, '{')) => Some('}'),
+            Some((_, '[')) => Some(']'),
+            Some((_, '(')) => Some(')'),
+            _ => None,
+        };
+        let close = closer.is_some_and(|c| line[x..].trim_start().starts_with(c));
+        self.insert_newline();
+        let rest = self.lines[self.cursor_y].trim_start().to_string();
+        if close {
+            self.lines[self.cursor_y] = indent.clone();
+            self.lines.insert(self.cursor_y + 1, format!("{}{}", base, rest));
+        } else {
+            self.lines[self.cursor_y] = format!("{}{}", indent, rest);
+        }
+        self.cursor_x = indent.len();
+        self.adjust_h_scroll(0);
+        true
+    }
+}
@@ -0,0 +1,221 @@
+// タスク実行 (Alt+t)
+// 設定の `task.build = cargo build` のように名前を付けたコマンドを一覧から選んで非同期に実行し、
+// 出力（標準エラーも）をエディタ下部のパネルに流す。Alt+y でパネルを開いてスクロール、Ctrl+C で止める
+use crate::{prompt::Prompt, wake, App};
+use crossterm::event::{KeyCode, KeyModifiers};
+use std::{
+    io::{BufRead, BufReader},
+    os::unix::process::CommandExt,
+    process::{Command, ExitStatus, Stdio},
+    sync::mpsc::{self, Receiver},
+    thread,
+};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum TaskStatus {
+    Running,
+    Success,
+    Failed(Option<i32>), // 終了コード（シグナルで止まったら None）
+}
+
+enum TaskEvent {
+    Line(String),
+    Exit(std::io::Result<ExitStatus>),
+}
+
+pub struct TaskJob {
+    rx: Receiver<TaskEvent>,
+    pid: u32, // プロセスグループの ID
+}
+
+impl TaskJob {
+    // 標準エラーも標準出力にまとめ、出た順に1行ずつ送る
+    fn spawn(command: &str, dir: &std::path::Path) -> std::io::Result<Self> {
+        let mut child = Command::new("sh")
+            .arg("-c")
+            .arg(format!("exec 2>&1\n{}", command))
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::piped())
+            .process_group(0)
+            .spawn()?;
+        let pid = child.id();
+        let stdout = child.stdout.take();
+        let (tx, rx) = mpsc::channel();
+        thread::spawn(move || {
+            if let Some(stdout) = stdout {
+                for line in BufReader::new(stdout).split(b'\n') {
+                    let Ok(line) = line else { break };
+                    let text = String::from_utf8_lossy(&line).trim_end_matches('\r').replace('\t', "    ");
+                    if tx.send(TaskEvent::Line(text)).is_err() { return; }
+                    wake::wake();
+                }
+            }
+            let _ = tx.send(TaskEvent::Exit(child.wait()));
+            wake::wake();
+        });
+        Ok(TaskJob { rx, pid })
+    }
+
+    fn kill(&self) {
+        let _ = Command::new("kill").args(["-KILL", "--", &format!("-{}", self.pid)]).stderr(Stdio::null()).status();
+    }
+}
+
+#[derive(Clone)]
+pub struct TaskPanel {
+    pub name: String,
+    pub lines: Vec<String>,
+    pub status: TaskStatus,
+    pub scroll: usize,
+    pub follow: bool, // 末尾を表示していれば新しい出力に合わせて送る
+    pub visible: bool,
+    pub focused: bool,
+}
+
+impl Default for TaskPanel {
+    fn default() -> Self {
+        TaskPanel {
+            name: String::new(),
+            lines: Vec::new(),
+            status: TaskStatus::Success,
+            scroll: 0,
+            follow: true,
+            visible: false,
+            focused: false,
+        }
+    }
+}
+
+impl TaskPanel {
+    // 描画する高さに合わせてスクロール位置を直す
+    pub fn update_scroll(&mut self, height: usize) {
+        let max = self.lines.len().saturating_sub(height);
+        if self.follow || self.scroll >= max {
+            self.scroll = max;
+            self.follow = true;
+        }
+    }
+
+    fn scroll_by(&mut self, delta: isize) {
+        self.scroll = self.scroll.saturating_add_signed(delta);
+        self.follow = false;
+    }
+}
+
+impl App {
+    // Alt+t: 設定されたタスクの一覧を開く
+    pub fn open_task_list(&mut self) {
+        if self.config.tasks.is_empty() {
+            self.message = Some("No tasks configured (add e.g. `task.build = cargo build` to the config)".to_string());
+            return;
+        }
+        self.open_prompt(Prompt::list("Run Task: type to filter, Enter to run", App::task_items, App::task_accept), "");
+    }
+
+    // 名前に入力した文字を含むタスク
+    pub fn task_matches(&self) -> Vec<(String, String)> {
+        let query = self.popup_input.to_lowercase();
+        self.config.tasks.iter().filter(|(name, _)| name.to_lowercase().contains(&query)).cloned().collect()
+    }
+
+    pub fn task_items(&self) -> Vec<String> {
+        let width = self.config.tasks.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+        self.task_matches().iter().map(|(name, command)| format!("{:width$}  {}", name, command)).collect()
+    }
+
+    pub fn task_accept(&mut self) {
+        let matches = self.task_matches();
+        self.popup = None;
+        self.popup_input.clear();
+        if let Some((name, command)) = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))).cloned() {
+            self.run_task(name, &command);
+        }
+    }
+
+    // 実行中のタスクがあれば止めてから始める
+    fn run_task(&mut self, name: String, command: &str) {
+        if let Some(job) = self.task_job.take() {
+            job.kill();
+        }
+        let (visible, focused) = (true, self.task.focused);
+        match TaskJob::spawn(command, &self.command_dir()) {
+            Ok(job) => {
+                self.task_job = Some(job);
+                self.task = TaskPanel { name, lines: vec![format!("$ {}", command)], status: TaskStatus::Running, visible, focused, ..TaskPanel::default() };
+            }
+            Err(e) => self.message = Some(format!("Cannot run task {}: {}", name, e)),
+        }
+    }
+
+    // 出力と終了を受け取る（メインループで毎回呼ぶ）
+    pub fn poll_task(&mut self) {
+        let Some(job) = self.task_job.as_ref() else { return };
+        let mut exit = None;
+        while let Ok(event) = job.rx.try_recv() {
+            self.dirty = true;
+            match event {
+                TaskEvent::Line(line) => self.task.lines.push(line),
+                TaskEvent::Exit(status) => exit = Some(status),
+            }
+        }
+        let Some(status) = exit else { return };
+        self.task_job = None;
+        let name = &self.task.name;
+        self.task.status = match status {
+            Ok(s) if s.success() => TaskStatus::Success,
+            Ok(s) => TaskStatus::Failed(s.code()),
+            Err(_) => TaskStatus::Failed(None),
+        };
+        self.message = Some(match self.task.status {
+            TaskStatus::Success => format!("Task {} succeeded", name),
+            TaskStatus::Failed(Some(code)) => format!("Task {} failed (exit {})", name, code),
+            _ => format!("Task {} was stopped", name),
+        });
+    }
+
+    fn stop_task(&mut self) {
+        match self.task_job.take() {
+            Some(job) => {
+                job.kill();
+                self.task.status = TaskStatus::Failed(None);
+                self.task.lines.push("[stopped]".to_string());
+                self.message = Some(format!("Task {} was stopped", self.task.name));
+            }
+            None => self.message = Some("No task is running".to_string()),
+        }
+    }
+
+    // Alt+y: パネルを開いてフォーカス、フォーカス中なら閉じる
+    pub fn toggle_task_panel(&mut self) {
+        if self.task.focused {
+            self.task.visible = false;
+            self.task.focused = false;
+        } else if self.task.name.is_empty() {
+            self.message = Some("No task has been run".to_string());
+        } else {
+            self.task.visible = true;
+            self.task.focused = true;
+            self.results.focused = false;
+        }
+    }
+
+    pub fn handle_task_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let panel = &mut self.task;
+        match code {
+            KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => self.stop_task(),
+            KeyCode::Up => panel.scroll_by(-1),
+            KeyCode::Down => panel.scroll_by(1),
+            KeyCode::PageUp => panel.scroll_by(-10),
+            KeyCode::PageDown => panel.scroll_by(10),
+            KeyCode::Home => panel.scroll_by(-(panel.scroll as isize)),
+            KeyCode::End => panel.follow = true,
+            KeyCode::Enter => panel.focused = false,
+            KeyCode::Esc => {
+                panel.focused = false;
+                panel.visible = false;
+            }
+            _ => {}
+        }
+    }
+}
@@ -0,0 +1,49 @@
+// 端末の状態を必ず元に戻す
+// raw モードと代替画面（とマウスの取り込み）は TermGuard を落とすと戻す（エラーで main を抜けたときも）。
+// release は panic = 'abort' で Drop が走らないので、パニックはフックで先に端末を戻してからメッセージを出す
+use crate::logging;
+use crossterm::{
+    cursor,
+    event::{DisableMouseCapture, EnableMouseCapture},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use std::io;
+
+pub struct TermGuard;
+
+impl TermGuard {
+    pub fn enter() -> io::Result<Self> {
+        enable_raw_mode()?;
+        // 代替画面に入れなくても raw モードは戻す
+        let guard = TermGuard;
+        execute!(io::stdout(), EnterAlternateScreen)?;
+        Ok(guard)
+    }
+}
+
+impl Drop for TermGuard {
+    fn drop(&mut self) {
+        restore();
+    }
+}
+
+// マウスのイベントを受ける（端末でのマウスによる文字の選択は Shift を押しながらになる）
+pub fn enable_mouse() -> io::Result<()> {
+    execute!(io::stdout(), EnableMouseCapture)
+}
+
+// 何度呼んでもよい
+pub fn restore() {
+    let _ = disable_raw_mode();
+    let _ = execute!(io::stdout(), DisableMouseCapture, LeaveAlternateScreen, cursor::Show);
+}
+
+pub fn install_panic_hook() {
+    let default = std::panic::take_hook();
+    std::panic::set_hook(Box::new(move |info| {
+        restore();
+        logging::error("panic", &[("message", &info)]);
+        default(info);
+    }));
+}
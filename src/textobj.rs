@@ -0,0 +1,217 @@
+// テキストオブジェクト（括弧の中・引用符の中・単語・段落）
+// Alt+i / Alt+' / Alt+d / Alt+p で選択し、そのままコピー・切り取り・上書き入力に使う。
+// Ctrl+D は単語を選び、もう一度押すと選んでいる文字列の次の出現を選択に足す（multicursor.rs）。
+// Vim キーマップでは d / y / c やビジュアルモードの後の i( i" iw ip など
+use crate::{vim::char_class, App, TextPos};
+
+#[derive(Clone, Copy)]
+pub enum TextObject {
+    Word,
+    Paragraph,
+    Bracket(Option<(char, char)>), // None は一番内側の括弧（種類を問わない）
+    Quote(Option<char>),           // None はカーソルを囲む一番内側の引用符
+}
+
+const BRACKETS: [(char, char); 4] = [('(', ')'), ('[', ']'), ('{', '}'), ('<', '>')];
+const QUOTES: [char; 3] = ['"', '\'', '`'];
+const MAX_LINES: usize = 10_000;
+
+impl TextObject {
+    // Vim の i の後の文字
+    pub fn from_vim(c: char) -> Option<Self> {
+        Some(match c {
+            'w' => TextObject::Word,
+            'p' => TextObject::Paragraph,
+            '(' | ')' | 'b' => TextObject::Bracket(Some(('(', ')'))),
+            '[' | ']' => TextObject::Bracket(Some(('[', ']'))),
+            '{' | '}' | 'B' => TextObject::Bracket(Some(('{', '}'))),
+            '<' | '>' => TextObject::Bracket(Some(('<', '>'))),
+            '"' | '\'' | '`' => TextObject::Quote(Some(c)),
+            _ => return None,
+        })
+    }
+}
+
+impl App {
+    // オブジェクトの範囲 [開始, 終了)。段落は行全体（終了は最終行の末尾）
+    pub fn text_object(&self, obj: TextObject) -> Option<(TextPos, TextPos)> {
+        match obj {
+            TextObject::Word => self.word_object(),
+            TextObject::Paragraph => self.paragraph_object(),
+            TextObject::Bracket(Some(pair)) => self.bracket_object(pair),
+            // 開き括弧が一番後ろにあるもの = 一番内側
+            TextObject::Bracket(None) => BRACKETS
+                .iter()
+                .filter(|&&(open, _)| open != '<')
+                .filter_map(|&pair| self.bracket_object(pair))
+                .max_by_key(|&(start, _)| start),
+            TextObject::Quote(Some(q)) => self.quote_object(q),
+            TextObject::Quote(None) => QUOTES
+                .iter()
+                .filter_map(|&q| self.quote_object(q))
+                .filter(|&(s, e)| s.1 <= self.cursor_x && self.cursor_x <= e.1)
+                .min_by_key(|&(s, e)| e.1 - s.1)
+                .or_else(|| QUOTES.iter().filter_map(|&q| self.quote_object(q)).min_by_key(|&(s, _)| s.1)),
+        }
+    }
+
+    pub fn select_text_object(&mut self, obj: TextObject) {
+        let Some((start, end)) = self.text_object(obj) else {
+            self.message = Some("No text object at cursor".to_string());
+            return;
+        };
+        self.sel_start = Some(start);
+        self.sel_end = Some(end);
+        self.cursor_y = end.0;
+        self.cursor_x = end.1;
+        self.shift_selection = true;
+        self.adjust_h_scroll(0);
+    }
+
+    // Ctrl+D: 1行の中の選択がなければ単語を選ぶ。あれば同じ文字列の次の出現（末尾まで行けば先頭から）を選択に足す
+    pub fn select_next_occurrence(&mut self) {
+        let (start, end) = match (self.sel_start, self.sel_end) {
+            (Some(s), Some(e)) if s != e && s.0 == e.0 => (s.min(e), s.max(e)),
+            _ => {
+                self.clear_extra_selections();
+                return self.select_text_object(TextObject::Word);
+            }
+        };
+        if !self.add_next_occurrence(start, end) {
+            let needle = &self.lines[start.0][start.1..end.1];
+            let none = if self.extra_selections.is_empty() { "No other occurrence" } else { "No more occurrences" };
+            self.message = Some(format!("{} of \"{}\"", none, needle));
+        }
+    }
+
+    // カーソル位置と同じ種類（単語・空白・記号）の文字の並び
+    fn word_object(&self) -> Option<(TextPos, TextPos)> {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        // 行末ではひとつ前の文字を対象にする
+        let (at, cls) = match line[x..].chars().next() {
+            Some(c) => (x, char_class(c)),
+            None => {
+                let (i, c) = line[..x].char_indices().next_back()?;
+                (i, char_class(c))
+            }
+        };
+        let start = line[..at].char_indices().rev().take_while(|&(_, c)| char_class(c) == cls).last().map_or(at, |(i, _)| i);
+        let end = line[at..].char_indices().find(|&(_, c)| char_class(c) != cls).map_or(line.len(), |(i, _)| at + i);
+        Some(((self.cursor_y, start), (self.cursor_y, end)))
+    }
+
+    // 空行で区切られた行のまとまり（空行の上なら空行の並び）
+    fn paragraph_object(&self) -> Option<(TextPos, TextPos)> {
+        let blank = |y: usize| self.lines[y].trim().is_empty();
+        let kind = blank(self.cursor_y);
+        let mut start = self.cursor_y;
+        while start > 0 && blank(start - 1) == kind { start -= 1; }
+        let mut end = self.cursor_y;
+        while end + 1 < self.lines.len() && blank(end + 1) == kind { end += 1; }
+        Some(((start, 0), (end, self.lines[end].len())))
+    }
+
+    // カーソルを囲む open … close の内側（行をまたいでよい）
+    fn bracket_object(&self, (open, close): (char, char)) -> Option<(TextPos, TextPos)> {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        // カーソルが開き括弧の上ならそれを含めて後ろ向きに探す
+        let on_open = line[x..].starts_with(open);
+        let mut depth = 0usize;
+        let mut opener = None;
+        'back: for y in (self.cursor_y.saturating_sub(MAX_LINES)..=self.cursor_y).rev() {
+            let to = if y == self.cursor_y { if on_open { x + open.len_utf8() } else { x } } else { self.lines[y].len() };
+            for (i, c) in self.lines[y][..to].char_indices().rev() {
+                if c == close {
+                    depth += 1;
+                } else if c == open {
+                    if depth == 0 {
+                        opener = Some((y, i));
+                        break 'back;
+                    }
+                    depth -= 1;
+                }
+            }
+        }
+        let (oy, ox) = opener?;
+        let start = (oy, ox + open.len_utf8());
+        let last = (oy + MAX_LINES).min(self.lines.len());
+        for y in oy..last {
+            let from = if y == oy { start.1 } else { 0 };
+            for (i, c) in self.lines[y][from..].char_indices() {
+                if c == open {
+                    depth += 1;
+                } else if c == close {
+                    if depth == 0 { return Some((start, (y, from + i))); }
+                    depth -= 1;
+                }
+            }
+        }
+        None
+    }
+
+    // 行内の引用符の組のうちカーソルを囲むもの、なければカーソルより後ろの最初の組の内側
+    fn quote_object(&self, q: char) -> Option<(TextPos, TextPos)> {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        let mut quotes = Vec::new();
+        let mut escaped = false;
+        for (i, c) in line.char_indices() {
+            if c == q && !escaped { quotes.push(i); }
+            escaped = c == '\\' && !escaped;
+        }
+        let (s, e) = quotes
+            .chunks_exact(2)
+            .map(|p| (p[0], p[1]))
+            .find(|&(_, e)| e >= x)?;
+        Some(((self.cursor_y, s + q.len_utf8()), (self.cursor_y, e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(lines: &[&str], (y, x): TextPos) -> App {
+        let mut app = App::new();
+        *app.lines = lines.iter().map(|s| s.to_string()).collect();
+        (app.cursor_y, app.cursor_x) = (y, x);
+        app
+    }
+
+    #[test]
+    fn selects_the_word_at_the_cursor() {
+        assert_eq!(app(&["let foo_bar = 1;"], (0, 6)).text_object(TextObject::Word), Some(((0, 4), (0, 11))));
+        // 行末ではひとつ前の文字
+        assert_eq!(app(&["let foo_bar = 1;"], (0, 16)).text_object(TextObject::Word), Some(((0, 15), (0, 16))));
+    }
+
+    #[test]
+    fn selects_inside_brackets() {
+        let a = app(&["f(a, [b, c])"], (0, 6));
+        assert_eq!(a.text_object(TextObject::Bracket(None)), Some(((0, 6), (0, 10))));
+        assert_eq!(a.text_object(TextObject::Bracket(Some(('(', ')')))), Some(((0, 2), (0, 11))));
+        assert_eq!(a.text_object(TextObject::Bracket(Some(('{', '}')))), None);
+        // 開き括弧の上ならその括弧、行をまたいでもよい
+        let a = app(&["fn x() {", "    a;", "}"], (0, 7));
+        assert_eq!(a.text_object(TextObject::Bracket(Some(('{', '}')))), Some(((0, 8), (2, 0))));
+    }
+
+    #[test]
+    fn selects_inside_quotes() {
+        let line = r#"a "b \" c" 'd'"#;
+        assert_eq!(app(&[line], (0, 3)).text_object(TextObject::Quote(Some('"'))), Some(((0, 3), (0, 9))));
+        assert_eq!(app(&[line], (0, 12)).text_object(TextObject::Quote(None)), Some(((0, 12), (0, 13))));
+        // 囲む組がなければ後ろの最初の組
+        assert_eq!(app(&[line], (0, 0)).text_object(TextObject::Quote(None)), Some(((0, 3), (0, 9))));
+        assert_eq!(app(&["no quotes"], (0, 0)).text_object(TextObject::Quote(None)), None);
+    }
+
+    #[test]
+    fn selects_the_paragraph() {
+        let lines = ["a", "b", "", "", "c"];
+        assert_eq!(app(&lines, (1, 0)).text_object(TextObject::Paragraph), Some(((0, 0), (1, 1))));
+        assert_eq!(app(&lines, (2, 0)).text_object(TextObject::Paragraph), Some(((2, 0), (3, 0))));
+    }
+}
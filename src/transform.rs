@@ -0,0 +1,213 @@
+// 選択範囲の変換 (Ctrl+T で一覧から選ぶ)
+// base64・URL（パーセント）・16進のエンコードとデコード。選択範囲をその場で置き換えて選択し直し、1回の undo で戻せる。
+// デコードできない入力や、デコードした結果が UTF-8 のテキストでなければ置き換えずに理由を出す
+use crate::{
+    lsp::{self, TextEdit},
+    prompt::Prompt,
+    App,
+};
+
+const BASE64: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+
+type Transform = fn(&str) -> Result<String, String>;
+
+const TRANSFORMS: [(&str, Transform); 6] = [
+    ("Base64 encode", base64_encode),
+    ("Base64 decode", base64_decode),
+    ("URL encode", url_encode),
+    ("URL decode", url_decode),
+    ("Hex encode", hex_encode),
+    ("Hex decode", hex_decode),
+];
+
+fn utf8(bytes: Vec<u8>) -> Result<String, String> {
+    String::from_utf8(bytes).map_err(|e| format!("the decoded bytes are not UTF-8 text (at byte {})", e.utf8_error().valid_up_to()))
+}
+
+fn base64_encode(text: &str) -> Result<String, String> {
+    let mut out = String::new();
+    for chunk in text.as_bytes().chunks(3) {
+        let n = chunk.iter().enumerate().fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            out.push(if i <= chunk.len() { BASE64[(n >> (18 - 6 * i) & 63) as usize] as char } else { '=' });
+        }
+    }
+    Ok(out)
+}
+
+// 空白は読み飛ばす。URL 用の - と _ も読み、末尾の = は省いてもよい
+fn base64_decode(text: &str) -> Result<String, String> {
+    let mut bits = 0u32;
+    let mut count = 0;
+    let mut out = Vec::new();
+    let body = text.trim_end().trim_end_matches('=');
+    for (i, c) in body.char_indices() {
+        if c.is_whitespace() { continue; }
+        let value = match c {
+            '-' => 62,
+            '_' => 63,
+            c => BASE64.iter().position(|&b| b as char == c).ok_or_else(|| format!("'{}' at position {} is not base64", c, i + 1))? as u32,
+        };
+        bits = bits << 6 | value;
+        count += 6;
+        if count >= 8 {
+            count -= 8;
+            out.push((bits >> count) as u8);
+            bits &= (1 << count) - 1;
+        }
+    }
+    if count >= 6 {
+        return Err("the length is not valid for base64".to_string());
+    }
+    utf8(out)
+}
+
+// 予約されていない文字（英数字と -_.~）以外を %XX にする
+fn url_encode(text: &str) -> Result<String, String> {
+    Ok(text
+        .bytes()
+        .map(|b| if b.is_ascii_alphanumeric() || b"-_.~".contains(&b) { (b as char).to_string() } else { format!("%{:02X}", b) })
+        .collect())
+}
+
+fn url_decode(text: &str) -> Result<String, String> {
+    let bytes = text.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = bytes.get(i + 1..i + 3).and_then(|h| std::str::from_utf8(h).ok()).and_then(|h| u8::from_str_radix(h, 16).ok());
+            let Some(b) = hex else { return Err(format!("'%' at position {} is not followed by two hex digits", i + 1)) };
+            out.push(b);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    utf8(out)
+}
+
+fn hex_encode(text: &str) -> Result<String, String> {
+    Ok(text.bytes().map(|b| format!("{:02x}", b)).collect())
+}
+
+// 空白は読み飛ばす
+fn hex_decode(text: &str) -> Result<String, String> {
+    let digits: Vec<(usize, char)> = text.char_indices().filter(|(_, c)| !c.is_whitespace()).collect();
+    if let Some((i, c)) = digits.iter().find(|(_, c)| !c.is_ascii_hexdigit()) {
+        return Err(format!("'{}' at position {} is not a hex digit", c, i + 1));
+    }
+    if !digits.len().is_multiple_of(2) {
+        return Err("odd number of hex digits".to_string());
+    }
+    utf8(digits.chunks(2).map(|p| (p[0].1.to_digit(16).unwrap() * 16 + p[1].1.to_digit(16).unwrap()) as u8).collect())
+}
+
+impl App {
+    pub fn open_transforms(&mut self) {
+        if !matches!((self.sel_start, self.sel_end), (Some(a), Some(b)) if a != b) {
+            self.message = Some("Select the text to transform first".to_string());
+            return;
+        }
+        self.open_prompt(Prompt::list("Transform Selection: type to filter, Enter to apply", App::transform_items, App::transform_accept), "");
+    }
+
+    fn transform_matches(&self) -> Vec<(&'static str, Transform)> {
+        let query = self.popup_input.to_lowercase();
+        TRANSFORMS.into_iter().filter(|(name, _)| name.to_lowercase().contains(&query)).collect()
+    }
+
+    pub fn transform_items(&self) -> Vec<String> {
+        self.transform_matches().iter().map(|(name, _)| name.to_string()).collect()
+    }
+
+    pub fn transform_accept(&mut self) {
+        let matches = self.transform_matches();
+        self.popup = None;
+        self.popup_input.clear();
+        let Some(&(name, transform)) = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))) else { return };
+        let (Some(a), Some(b)) = (self.sel_start, self.sel_end) else { return };
+        let (start, end) = (a.min(b), a.max(b));
+        let text = self.get_selected_text().unwrap_or_default();
+        let output = match transform(&text) {
+            Ok(output) => output,
+            Err(e) => {
+                self.message = Some(format!("{}: {}", name, e));
+                return;
+            }
+        };
+        if output == text {
+            self.message = Some(format!("{}: no changes", name));
+            return;
+        }
+        let new_end = lsp::end_of_insert(start, &output);
+        self.save_undo();
+        lsp::apply_text_edits(&mut self.lines, vec![TextEdit { start, end, text: output }]);
+        self.selection_reset();
+        self.sel_start = Some(start);
+        self.sel_end = Some(new_end);
+        self.shift_selection = true;
+        (self.cursor_y, self.cursor_x) = new_end;
+        self.adjust_h_scroll(0);
+        self.message = Some(name.to_string());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn base64_round_trip() {
+        assert_eq!(base64_encode("").unwrap(), "");
+        assert_eq!(base64_encode("ab").unwrap(), "YWI=");
+        assert_eq!(base64_encode("abc").unwrap(), "YWJj");
+        assert_eq!(base64_encode("hello").unwrap(), "aGVsbG8=");
+        assert_eq!(base64_decode("aGVsbG8=").unwrap(), "hello");
+        assert_eq!(base64_decode(&base64_encode("日本語").unwrap()).unwrap(), "日本語");
+    }
+
+    #[test]
+    fn base64_decode_is_lenient() {
+        // = の省略、空白、URL 用の - と _
+        assert_eq!(base64_decode("aGVsbG8").unwrap(), "hello");
+        assert_eq!(base64_decode("aGVs\nbG8=\n").unwrap(), "hello");
+        assert_eq!(base64_decode("Pz8-").unwrap(), base64_decode("Pz8+").unwrap());
+        assert_eq!(base64_decode("Pz8_").unwrap(), base64_decode("Pz8/").unwrap());
+    }
+
+    #[test]
+    fn base64_decode_rejects_invalid_input() {
+        assert_eq!(base64_decode("aGV*").unwrap_err(), "'*' at position 4 is not base64");
+        assert_eq!(base64_decode("aGVsb").unwrap_err(), "the length is not valid for base64");
+        assert_eq!(base64_decode("/w==").unwrap_err(), "the decoded bytes are not UTF-8 text (at byte 0)");
+    }
+
+    #[test]
+    fn url_round_trip() {
+        assert_eq!(url_encode("a b&c/é-_.~").unwrap(), "a%20b%26c%2F%C3%A9-_.~");
+        assert_eq!(url_decode("a%20b%26c%2f%C3%A9").unwrap(), "a b&c/é");
+        assert_eq!(url_decode("plain+text").unwrap(), "plain+text");
+    }
+
+    #[test]
+    fn url_decode_rejects_invalid_input() {
+        assert_eq!(url_decode("%zz").unwrap_err(), "'%' at position 1 is not followed by two hex digits");
+        assert_eq!(url_decode("abc%4").unwrap_err(), "'%' at position 4 is not followed by two hex digits");
+        assert_eq!(url_decode("%FF").unwrap_err(), "the decoded bytes are not UTF-8 text (at byte 0)");
+    }
+
+    #[test]
+    fn hex_round_trip() {
+        assert_eq!(hex_encode("hi é").unwrap(), "686920c3a9");
+        assert_eq!(hex_decode("68 69 20 C3 A9").unwrap(), "hi é");
+    }
+
+    #[test]
+    fn hex_decode_rejects_invalid_input() {
+        assert_eq!(hex_decode("686").unwrap_err(), "odd number of hex digits");
+        assert_eq!(hex_decode("6g").unwrap_err(), "'g' at position 2 is not a hex digit");
+        assert_eq!(hex_decode("68ff").unwrap_err(), "the decoded bytes are not UTF-8 text (at byte 1)");
+    }
+}
@@ -0,0 +1,202 @@
+// FileTree の削除 (Delete)
+// 消す前に中身の一覧を出して確認し、ゴミ箱（freedesktop.org の仕様: $XDG_DATA_HOME/Trash、
+// 未設定なら ~/.local/share/Trash）へ移す。設定 `delete_permanently = true` ならその場で消す。
+// シンボリックリンクはリンク自身だけを消し、リンクを辿ってツリーの外にあるものを消すときは確認に警告を出す
+use crate::{App, PopupMode};
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MAX_PREVIEW: usize = 500;
+
+pub fn trash_dir() -> Option<PathBuf> {
+    let base = match std::env::var_os("XDG_DATA_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => PathBuf::from(std::env::var_os("HOME")?).join(".local").join("share"),
+    };
+    Some(base.join("Trash"))
+}
+
+// .trashinfo の Path は URL と同じようにエスケープする
+fn escape(path: &Path) -> String {
+    let mut out = String::new();
+    for &b in path.to_string_lossy().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"-._~/".contains(&b) {
+            out.push(b as char);
+        } else {
+            out.push_str(&format!("%{:02X}", b));
+        }
+    }
+    out
+}
+
+// 1970-01-01 からの日数を年月日に（UTC）
+fn civil(days: i64) -> (i64, u32, u32) {
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    (yoe + era * 400 + i64::from(month <= 2), month, day)
+}
+
+fn deletion_date() -> String {
+    let secs = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64;
+    let (y, m, d) = civil(secs.div_euclid(86400));
+    let t = secs.rem_euclid(86400);
+    format!("{:04}-{:02}-{:02}T{:02}:{:02}:{:02}", y, m, d, t / 3600, t / 60 % 60, t % 60)
+}
+
+// path をゴミ箱へ移す。同じ名前があれば `名前.2` のように番号を付ける。
+// 別のファイルシステムへは移せないのでエラーにする（何も消さない）
+pub fn move_to_trash(path: &Path) -> io::Result<()> {
+    let trash = trash_dir().ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no home directory"))?;
+    let name = path.file_name().ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "no file name"))?;
+    // リンク自身を移すので親だけ正規化する
+    let parent = path.parent().filter(|p| !p.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let abs = fs::canonicalize(parent)?.join(name);
+    let (files, info) = (trash.join("files"), trash.join("info"));
+    fs::create_dir_all(&files)?;
+    fs::create_dir_all(&info)?;
+    let content = format!("[Trash Info]\nPath={}\nDeletionDate={}\n", escape(&abs), deletion_date());
+    for n in 1.. {
+        let mut entry = name.to_os_string();
+        if n > 1 { entry.push(format!(".{}", n)); }
+        let info_path = info.join(format!("{}.trashinfo", entry.to_string_lossy()));
+        // .trashinfo を先に作って名前を確保する
+        match fs::OpenOptions::new().write(true).create_new(true).open(&info_path) {
+            Ok(mut file) => {
+                io::Write::write_all(&mut file, content.as_bytes())?;
+                return fs::rename(path, files.join(&entry)).inspect_err(|_| {
+                    let _ = fs::remove_file(&info_path);
+                });
+            }
+            Err(e) if e.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+// 消えるものの一覧（root からの相対パス）と、ファイル・ディレクトリの数と合計サイズ
+fn contents(path: &Path, root: &Path, lines: &mut Vec<String>, counts: &mut (usize, usize, u64)) {
+    let rel = path.strip_prefix(root).unwrap_or(path).to_string_lossy().to_string();
+    let Ok(meta) = path.symlink_metadata() else { return };
+    if meta.is_dir() {
+        counts.1 += 1;
+        if lines.len() < MAX_PREVIEW { lines.push(format!("- {}/", rel)); }
+        let Ok(read) = fs::read_dir(path) else { return };
+        let mut children: Vec<PathBuf> = read.filter_map(|e| e.ok()).map(|e| e.path()).collect();
+        children.sort();
+        for child in children {
+            contents(&child, root, lines, counts);
+        }
+    } else {
+        counts.0 += 1;
+        counts.2 += meta.len();
+        if lines.len() < MAX_PREVIEW { lines.push(format!("- {}", rel)); }
+    }
+}
+
+// 開いたリンクの中の項目は実体がツリーの外にある。外ならその実体のパス
+fn outside_tree(path: &Path, root: &Path) -> Option<PathBuf> {
+    let real = fs::canonicalize(path.parent()?).ok()?.join(path.file_name()?);
+    let root = fs::canonicalize(root).ok()?;
+    (!real.starts_with(root)).then_some(real)
+}
+
+impl App {
+    // Delete: 消すもの（印を付けた項目、なければ選択中の項目）を見せて確認する。リンクはリンク自身だけを消す
+    pub fn file_tree_delete(&mut self) {
+        let paths = self.file_tree.targets();
+        if paths.is_empty() { return; }
+        let mut lines = vec![String::new()];
+        let mut warnings = Vec::new();
+        let mut counts = (0, 0, 0);
+        for path in &paths {
+            if let Some(real) = outside_tree(path, &self.file_tree.current_path) {
+                warnings.push(format!("! Outside the FileTree through a symlink: {}", real.display()));
+            }
+            // FileTree のパスからの相対で出す
+            let current = &self.file_tree.current_path;
+            let root = if path.starts_with(current) { current } else { path.parent().unwrap_or(path) };
+            match fs::read_link(path) {
+                Ok(target) => {
+                    counts.0 += 1;
+                    lines.push(format!("- {} -> {} (only the link is removed)", path.strip_prefix(root).unwrap_or(path).display(), target.display()));
+                }
+                Err(_) => contents(path, root, &mut lines, &mut counts),
+            }
+        }
+        let total = counts.0 + counts.1;
+        if total > lines.len() - 1 {
+            lines.push(format!("  ... and {} more", total - (lines.len() - 1)));
+        }
+        // 先頭の行は数と合計サイズ
+        let (files, dirs, bytes) = counts;
+        lines[0] = format!(
+            "{} file{}, {} director{}, {}",
+            files,
+            if files == 1 { "" } else { "s" },
+            dirs,
+            if dirs == 1 { "y" } else { "ies" },
+            crate::format_size(bytes)
+        );
+        let outside = !warnings.is_empty();
+        lines.splice(0..0, warnings);
+        self.popup = Some(PopupMode::ConfirmDelete { paths, preview: lines, outside });
+        self.popup_selected = 0;
+    }
+
+    pub fn delete_title(&self) -> String {
+        let Some(PopupMode::ConfirmDelete { ref paths, outside, .. }) = self.popup else { return String::new() };
+        let name = match paths.as_slice() {
+            [path] => path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default(),
+            _ => format!("{} entries", paths.len()),
+        };
+        let action = if self.config.delete_permanently { "Delete permanently" } else { "Move to trash" };
+        let warning = if outside { " - outside the tree via a symlink" } else { "" };
+        format!("{} {}? (y)es / (n)o{}", action, name, warning)
+    }
+
+    // 確認した後に消す。開いているファイルが消えたら未保存扱いにする
+    pub fn confirm_delete(&mut self, paths: &[PathBuf]) {
+        let mut done = 0;
+        let mut error = None;
+        for path in paths {
+            let result = if self.config.delete_permanently {
+                if path.is_dir() && !path.is_symlink() { fs::remove_dir_all(path) } else { fs::remove_file(path) }
+            } else {
+                move_to_trash(path)
+            };
+            let name = path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+            match result {
+                Ok(()) => {
+                    done += 1;
+                    if self.current_file.as_deref().is_some_and(|f| f.starts_with(path)) {
+                        self.modified = true;
+                    }
+                    self.file_tree.expanded.retain(|p| !p.starts_with(path));
+                }
+                Err(e) if e.kind() == io::ErrorKind::CrossesDevices => {
+                    error = Some(format!("cannot move {} to the trash on another filesystem (set delete_permanently = true)", name));
+                }
+                Err(e) => error = Some(format!("cannot delete {}: {}", name, e)),
+            }
+        }
+        let verb = if self.config.delete_permanently { "Deleted" } else { "Moved to the trash:" };
+        match (error, paths) {
+            (None, [path]) => self.notify_ok(format!("{} {}", verb, path.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default())),
+            (None, _) => self.notify_ok(format!("{} {} entries", verb, done)),
+            (Some(e), [_]) => self.notify_err(format!("{}{}", e[..1].to_uppercase(), &e[1..])),
+            (Some(e), _) => self.notify_err(format!("{} {} of {} entries; {}", verb, done, paths.len(), e)),
+        }
+        self.file_tree.marked.clear();
+        self.file_tree.reload();
+    }
+}
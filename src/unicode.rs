@@ -0,0 +1,287 @@
+// コードポイントと名前から文字を入れる (Ctrl+U) と、カーソル位置の文字の情報 (Alt+;)
+// 16進のコードポイント（`00E9`・`U+1F600`、空白で区切って複数も可）を打てばその文字を、
+// それ以外は下の表からよく使う記号を名前で絞り込んで選ぶ。Enter でカーソル位置に挿入する。
+// 情報はステータスバーに、カーソルの書記素のコードポイント・UTF-8 のバイト数・表示幅を出す
+use crate::{prompt::Prompt, App};
+use unicode_segmentation::UnicodeSegmentation;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
+
+// よく使う記号と名前（Unicode の名前を小文字で）
+const SYMBOLS: &[(char, &str)] = &[
+    // 矢印
+    ('←', "leftwards arrow"),
+    ('↑', "upwards arrow"),
+    ('→', "rightwards arrow"),
+    ('↓', "downwards arrow"),
+    ('↔', "left right arrow"),
+    ('↕', "up down arrow"),
+    ('⇐', "leftwards double arrow"),
+    ('⇒', "rightwards double arrow"),
+    ('⇔', "left right double arrow"),
+    ('↩', "leftwards arrow with hook (return)"),
+    ('↵', "downwards arrow with corner leftwards (enter)"),
+    ('⟶', "long rightwards arrow"),
+    // 数学
+    ('±', "plus-minus sign"),
+    ('×', "multiplication sign"),
+    ('÷', "division sign"),
+    ('−', "minus sign"),
+    ('≈', "almost equal to"),
+    ('≠', "not equal to"),
+    ('≤', "less-than or equal to"),
+    ('≥', "greater-than or equal to"),
+    ('≡', "identical to"),
+    ('∞', "infinity"),
+    ('√', "square root"),
+    ('∑', "n-ary summation (sum)"),
+    ('∏', "n-ary product"),
+    ('∫', "integral"),
+    ('∂', "partial differential"),
+    ('∆', "increment (delta)"),
+    ('∇', "nabla"),
+    ('∈', "element of"),
+    ('∉', "not an element of"),
+    ('∅', "empty set"),
+    ('∩', "intersection"),
+    ('∪', "union"),
+    ('⊂', "subset of"),
+    ('⊆', "subset of or equal to"),
+    ('∀', "for all"),
+    ('∃', "there exists"),
+    ('¬', "not sign"),
+    ('∧', "logical and"),
+    ('∨', "logical or"),
+    ('⊕', "circled plus (xor)"),
+    ('∘', "ring operator (compose)"),
+    ('·', "middle dot"),
+    ('°', "degree sign"),
+    ('′', "prime (minutes, feet)"),
+    ('″', "double prime (seconds, inches)"),
+    ('‰', "per mille sign"),
+    ('µ', "micro sign"),
+    ('½', "vulgar fraction one half"),
+    ('¼', "vulgar fraction one quarter"),
+    ('¾', "vulgar fraction three quarters"),
+    ('²', "superscript two (squared)"),
+    ('³', "superscript three (cubed)"),
+    // 通貨
+    ('€', "euro sign"),
+    ('£', "pound sign"),
+    ('¥', "yen sign"),
+    ('¢', "cent sign"),
+    ('₹', "indian rupee sign"),
+    ('₩', "won sign"),
+    ('₽', "ruble sign"),
+    ('₿', "bitcoin sign"),
+    // 句読点・引用符
+    ('–', "en dash"),
+    ('—', "em dash"),
+    ('…', "horizontal ellipsis"),
+    ('‘', "left single quotation mark"),
+    ('’', "right single quotation mark (apostrophe)"),
+    ('“', "left double quotation mark"),
+    ('”', "right double quotation mark"),
+    ('«', "left-pointing double angle quotation mark (guillemet)"),
+    ('»', "right-pointing double angle quotation mark (guillemet)"),
+    ('•', "bullet"),
+    ('†', "dagger"),
+    ('‡', "double dagger"),
+    ('§', "section sign"),
+    ('¶', "pilcrow sign (paragraph)"),
+    ('©', "copyright sign"),
+    ('®', "registered sign"),
+    ('™', "trade mark sign"),
+    ('¿', "inverted question mark"),
+    ('¡', "inverted exclamation mark"),
+    ('\u{a0}', "no-break space (nbsp)"),
+    ('\u{200b}', "zero width space"),
+    ('\u{2009}', "thin space"),
+    ('\u{ad}', "soft hyphen"),
+    // 文字
+    ('é', "latin small letter e with acute"),
+    ('è', "latin small letter e with grave"),
+    ('ü', "latin small letter u with diaeresis (umlaut)"),
+    ('ö', "latin small letter o with diaeresis (umlaut)"),
+    ('ä', "latin small letter a with diaeresis (umlaut)"),
+    ('ñ', "latin small letter n with tilde"),
+    ('ç', "latin small letter c with cedilla"),
+    ('ß', "latin small letter sharp s (eszett)"),
+    ('ø', "latin small letter o with stroke"),
+    ('å', "latin small letter a with ring above"),
+    ('æ', "latin small letter ae"),
+    ('α', "greek small letter alpha"),
+    ('β', "greek small letter beta"),
+    ('γ', "greek small letter gamma"),
+    ('δ', "greek small letter delta"),
+    ('ε', "greek small letter epsilon"),
+    ('θ', "greek small letter theta"),
+    ('λ', "greek small letter lambda"),
+    ('μ', "greek small letter mu"),
+    ('π', "greek small letter pi"),
+    ('σ', "greek small letter sigma"),
+    ('τ', "greek small letter tau"),
+    ('φ', "greek small letter phi"),
+    ('ω', "greek small letter omega"),
+    ('Δ', "greek capital letter delta"),
+    ('Σ', "greek capital letter sigma"),
+    ('Ω', "greek capital letter omega"),
+    // 記号
+    ('✓', "check mark"),
+    ('✗', "ballot x"),
+    ('★', "black star"),
+    ('☆', "white star"),
+    ('♥', "black heart suit"),
+    ('●', "black circle"),
+    ('○', "white circle"),
+    ('■', "black square"),
+    ('□', "white square"),
+    ('▲', "black up-pointing triangle"),
+    ('▼', "black down-pointing triangle"),
+    ('☐', "ballot box"),
+    ('☑', "ballot box with check"),
+    ('⌘', "place of interest sign (command key)"),
+    ('⌥', "option key"),
+    ('⇧', "upwards white arrow (shift key)"),
+    ('⌫', "erase to the left (backspace key)"),
+    ('♪', "eighth note"),
+    ('☀', "black sun with rays"),
+    ('☂', "umbrella"),
+    ('☎', "black telephone"),
+    ('⚠', "warning sign"),
+    // 罫線
+    ('─', "box drawings light horizontal"),
+    ('│', "box drawings light vertical"),
+    ('┌', "box drawings light down and right"),
+    ('┐', "box drawings light down and left"),
+    ('└', "box drawings light up and right"),
+    ('┘', "box drawings light up and left"),
+    ('├', "box drawings light vertical and right"),
+    ('┤', "box drawings light vertical and left"),
+    ('┼', "box drawings light vertical and horizontal"),
+    ('═', "box drawings double horizontal"),
+    ('║', "box drawings double vertical"),
+    ('█', "full block"),
+    ('░', "light shade"),
+    // 絵文字
+    ('😀', "grinning face"),
+    ('😂', "face with tears of joy"),
+    ('🙂', "slightly smiling face"),
+    ('😉', "winking face"),
+    ('🤔', "thinking face"),
+    ('👍', "thumbs up sign"),
+    ('👎', "thumbs down sign"),
+    ('🙏', "person with folded hands"),
+    ('👀', "eyes"),
+    ('🎉', "party popper"),
+    ('🔥', "fire"),
+    ('🚀', "rocket"),
+    ('✅', "white heavy check mark"),
+    ('❌', "cross mark"),
+    ('❤', "heavy black heart"),
+    ('💡', "electric light bulb"),
+    ('🐛', "bug"),
+    ('📝', "memo"),
+    ('🔒', "lock"),
+];
+
+// `00E9`・`U+1F600`・`0x263A`。空白で区切れば続けて入れる
+fn parse_codepoints(input: &str) -> Option<Result<String, String>> {
+    let words: Vec<&str> = input.split_whitespace().collect();
+    if words.is_empty() { return None; }
+    let mut text = String::new();
+    for word in words {
+        let digits = ["U+", "u+", "0x", "0X"].iter().find_map(|p| word.strip_prefix(p)).unwrap_or(word);
+        if digits.is_empty() || digits.len() > 6 || !digits.chars().all(|c| c.is_ascii_hexdigit()) { return None; }
+        let cp = u32::from_str_radix(digits, 16).unwrap();
+        match char::from_u32(cp) {
+            Some(c) => text.push(c),
+            None => return Some(Err(format!("U+{:04X} is not a character", cp))),
+        }
+    }
+    Some(Ok(text))
+}
+
+// 見せるときの形。制御文字はエスケープし、幅のない文字（結合文字など）は ◌ に載せる
+fn preview(c: char) -> String {
+    if c.is_control() {
+        c.escape_default().to_string()
+    } else if c.width().unwrap_or(0) == 0 {
+        format!("\u{25cc}{}", c)
+    } else {
+        c.to_string()
+    }
+}
+
+fn codepoints(text: &str) -> String {
+    text.chars().map(|c| format!("U+{:04X}", c as u32)).collect::<Vec<_>>().join(" ")
+}
+
+fn symbol_name(c: char) -> Option<&'static str> {
+    SYMBOLS.iter().find(|(s, _)| *s == c).map(|(_, n)| *n)
+}
+
+impl App {
+    // ステータスバーに出すカーソル位置の書記素の情報
+    pub fn char_info(&self) -> String {
+        let line = &self.lines[self.cursor_y];
+        let Some((_, g)) = line.grapheme_indices(true).find(|(i, g)| self.cursor_x < i + g.len()) else {
+            return "end of line".to_string();
+        };
+        let chars: Vec<char> = g.chars().collect();
+        let name = match chars[..] {
+            [c] => symbol_name(c).map(|n| format!(" {}", n)).unwrap_or_default(),
+            _ => format!(" ({} codepoints)", chars.len()),
+        };
+        let bytes = if g.len() == 1 { "1 byte".to_string() } else { format!("{} bytes", g.len()) };
+        format!("'{}' {}{}  UTF-8 {}  width {}", chars.iter().map(|&c| preview(c)).collect::<String>(), codepoints(g), name, bytes, g.width())
+    }
+
+    pub fn open_unicode_input(&mut self) {
+        self.open_prompt(Prompt::list("Insert Character: hex codepoint or name, Enter to insert", App::unicode_items, App::unicode_accept), "");
+    }
+
+    // (挿入する文字列, 一覧の表示)
+    fn unicode_matches(&self) -> Vec<(String, String)> {
+        let mut out = Vec::new();
+        if let Some(Ok(text)) = parse_codepoints(&self.popup_input) {
+            let name = match text.chars().collect::<Vec<_>>()[..] {
+                [c] => symbol_name(c).unwrap_or("codepoint"),
+                _ => "codepoints",
+            };
+            out.push((text.clone(), format!("{}  {}  {}", text.chars().map(preview).collect::<String>(), codepoints(&text), name)));
+        }
+        // 名前は、打った語がどれも名前のどこかの語の頭に合うものを出す（`arrow right`、`greek pi` など）
+        let query = self.popup_input.to_lowercase();
+        let split = |s: &str| s.split(|c: char| !c.is_alphanumeric()).filter(|w| !w.is_empty()).map(str::to_string).collect::<Vec<_>>();
+        let words = split(&query);
+        for &(c, name) in SYMBOLS {
+            let name_words = split(name);
+            if words.iter().all(|w| name_words.iter().any(|n| n.starts_with(w.as_str()))) && !out.iter().any(|(t, _)| *t == c.to_string()) {
+                out.push((c.to_string(), format!("{}  U+{:04X}  {}", preview(c), c as u32, name)));
+            }
+        }
+        out
+    }
+
+    pub fn unicode_items(&self) -> Vec<String> {
+        self.unicode_matches().into_iter().map(|(_, label)| label).collect()
+    }
+
+    pub fn unicode_accept(&mut self) {
+        let matches = self.unicode_matches();
+        let input = std::mem::take(&mut self.popup_input);
+        self.popup = None;
+        let Some((text, _)) = matches.get(self.popup_selected.min(matches.len().saturating_sub(1))).cloned() else {
+            self.message = Some(match parse_codepoints(&input) {
+                Some(Err(e)) => e,
+                _ => format!("No character named \"{}\"", input.trim()),
+            });
+            return;
+        };
+        if self.sel_start.is_some() && self.sel_end.is_some() && self.sel_start != self.sel_end {
+            self.delete_selection();
+        }
+        self.insert_text(&text);
+        self.message = Some(format!("Inserted {}", codepoints(&text)));
+    }
+}
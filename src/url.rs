@@ -0,0 +1,75 @@
+// バッファの中の http(s) の URL
+// 描画では下線を引き、Alt+x（Vim は gx）でカーソル位置の URL を既定のブラウザで開く
+// （$BROWSER があればそれ、なければ xdg-open、macOS は open）
+use crate::App;
+use std::process::{Command, Stdio};
+
+// 行の中の URL の範囲。文末の句読点と、対になっていない閉じ括弧は含めない
+pub fn find_urls(line: &str) -> Vec<(usize, usize)> {
+    let mut out = Vec::new();
+    let mut from = 0;
+    while let Some(i) = line[from..].find("http") {
+        let start = from + i;
+        let rest = &line[start..];
+        if !(rest.starts_with("http://") || rest.starts_with("https://"))
+            || line[..start].ends_with(|c: char| c.is_alphanumeric())
+        {
+            from = start + 4;
+            continue;
+        }
+        let mut end = start + rest.find(|c: char| c.is_whitespace() || "<>\"'`".contains(c)).unwrap_or(rest.len());
+        loop {
+            let url = &line[start..end];
+            let trim = match url.chars().last() {
+                Some('.' | ',' | ';' | ':' | '!' | '?') => true,
+                Some(')') => url.matches(')').count() > url.matches('(').count(),
+                Some(']') => url.matches(']').count() > url.matches('[').count(),
+                _ => false,
+            };
+            if !trim { break; }
+            end -= 1;
+        }
+        // スキームだけなら URL とみなさない
+        if line[start..end].ends_with("//") {
+            from = end;
+            continue;
+        }
+        out.push((start, end));
+        from = end;
+    }
+    out
+}
+
+fn opener() -> String {
+    match std::env::var("BROWSER") {
+        Ok(browser) if !browser.is_empty() => browser,
+        _ if cfg!(target_os = "macos") => "open".to_string(),
+        _ => "xdg-open".to_string(),
+    }
+}
+
+impl App {
+    // カーソル位置（またはその直前）の URL
+    fn url_at_cursor(&self) -> Option<String> {
+        let line = &self.lines[self.cursor_y];
+        let x = self.cursor_x.min(line.len());
+        find_urls(line).into_iter().find(|&(s, e)| s <= x && x <= e).map(|(s, e)| line[s..e].to_string())
+    }
+
+    pub fn open_url(&mut self) {
+        let Some(url) = self.url_at_cursor() else {
+            self.message = Some("No URL at cursor".to_string());
+            return;
+        };
+        let program = opener();
+        let child = Command::new(&program).arg(&url).stdin(Stdio::null()).stdout(Stdio::null()).stderr(Stdio::null()).spawn();
+        match child {
+            Ok(mut child) => {
+                // 終わるのを待って回収する
+                std::thread::spawn(move || child.wait());
+                self.message = Some(format!("Opening {}", url));
+            }
+            Err(e) => self.notify_err(format!("Cannot run {}: {}", program, e)),
+        }
+    }
+}
@@ -0,0 +1,485 @@
+// Vim風モーダル編集レイヤー（config: keymap = vim）
+// App の編集プリミティブ（カーソル移動・選択・undo）の上に載せている
+use crate::{grapheme, textobj::TextObject, App};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum VimMode {
+    Normal,
+    Insert,
+    Visual,
+}
+
+#[derive(Clone)]
+pub struct VimState {
+    pub mode: VimMode,
+    count: Option<usize>,
+    pending_op: Option<char>, // d / y / c
+    pending_g: bool,
+    pending_i: bool, // オペレータやビジュアルモードの後の i（テキストオブジェクト）
+    // 無名レジスタ（クリップボードが使えない環境用）
+    register: String,
+    linewise: bool,
+}
+
+impl VimState {
+    pub fn new() -> Self {
+        VimState {
+            mode: VimMode::Normal,
+            count: None,
+            pending_op: None,
+            pending_g: false,
+            pending_i: false,
+            register: String::new(),
+            linewise: false,
+        }
+    }
+    fn clear_pending(&mut self) {
+        self.count = None;
+        self.pending_op = None;
+        self.pending_g = false;
+        self.pending_i = false;
+    }
+}
+
+// 0: 空白, 1: 単語文字, 2: 記号
+pub fn char_class(c: char) -> u8 {
+    if c.is_whitespace() {
+        0
+    } else if c.is_alphanumeric() || c == '_' {
+        1
+    } else {
+        2
+    }
+}
+
+impl App {
+    // 処理したら true。false ならそのまま通常のキー処理に流す
+    pub fn handle_vim_key(&mut self, code: KeyCode, modifiers: KeyModifiers) -> bool {
+        match self.vim.mode {
+            VimMode::Insert => {
+                if code == KeyCode::Esc {
+                    self.vim.mode = VimMode::Normal;
+                    if self.cursor_x > 0 { self.vim_left(); }
+                    return true;
+                }
+                false
+            }
+            VimMode::Normal | VimMode::Visual => {
+                // ジャンプリスト（Ctrl+I は端末では Tab として届く）
+                if self.vim.mode == VimMode::Normal {
+                    match (code, modifiers) {
+                        (KeyCode::Char('o'), KeyModifiers::CONTROL) => {
+                            self.jump_back();
+                            return true;
+                        }
+                        (KeyCode::Tab, KeyModifiers::NONE) => {
+                            self.jump_forward();
+                            return true;
+                        }
+                        // 半画面
+                        (KeyCode::Char('d'), KeyModifiers::CONTROL) => {
+                            self.move_half_page(true);
+                            return true;
+                        }
+                        (KeyCode::Char('u'), KeyModifiers::CONTROL) => {
+                            self.move_half_page(false);
+                            return true;
+                        }
+                        _ => {}
+                    }
+                }
+                if modifiers.contains(KeyModifiers::CONTROL) || modifiers.contains(KeyModifiers::ALT) {
+                    return false;
+                }
+                match code {
+                    KeyCode::Esc => {
+                        let pending = self.vim.count.is_some() || self.vim.pending_op.is_some() || self.vim.pending_g;
+                        self.vim.clear_pending();
+                        if self.vim.mode == VimMode::Visual {
+                            self.vim.mode = VimMode::Normal;
+                            self.selection_reset();
+                            return true;
+                        }
+                        pending
+                    }
+                    KeyCode::Char(c) => {
+                        self.vim_char(c);
+                        true
+                    }
+                    KeyCode::Enter => {
+                        self.vim_char('j');
+                        true
+                    }
+                    KeyCode::Backspace => {
+                        self.vim_char('h');
+                        true
+                    }
+                    KeyCode::Delete => {
+                        self.vim_char('x');
+                        true
+                    }
+                    _ => false,
+                }
+            }
+        }
+    }
+
+    fn vim_char(&mut self, c: char) {
+        // カウント
+        if c.is_ascii_digit() && (c != '0' || self.vim.count.is_some()) {
+            let d = c.to_digit(10).unwrap() as usize;
+            self.vim.count = Some(self.vim.count.unwrap_or(0).saturating_mul(10).saturating_add(d));
+            return;
+        }
+        let count = self.vim.count.unwrap_or(1);
+        if self.vim.pending_i {
+            if let Some(obj) = TextObject::from_vim(c) { self.vim_text_object(obj); }
+            self.vim.clear_pending();
+            return;
+        }
+        // g プレフィックス
+        if self.vim.pending_g {
+            self.vim.pending_g = false;
+            if c == 'g' {
+                self.vim_apply_motion('g', count);
+            } else {
+                if self.vim.pending_op.is_none() && self.vim.mode == VimMode::Normal {
+                    match c {
+                        'd' => self.goto_definition(),
+                        'f' => self.goto_file(),
+                        'x' => self.open_url(),
+                        _ => {}
+                    }
+                }
+                self.vim.clear_pending();
+            }
+            return;
+        }
+        if c == 'g' {
+            self.vim.pending_g = true;
+            return;
+        }
+        if self.vim.mode == VimMode::Visual {
+            self.vim_visual_char(c, count);
+            return;
+        }
+        // オペレータ待ち
+        if let Some(op) = self.vim.pending_op {
+            if c == op {
+                // dd / yy / cc は行単位
+                let start = self.cursor_y;
+                let end = (start + count - 1).min(self.lines.len() - 1);
+                self.vim_operate_lines(op, start, end);
+            } else if Self::is_vim_motion(c) {
+                self.vim_apply_motion(c, count);
+                return;
+            } else if c == 'i' {
+                self.vim.pending_i = true;
+                return;
+            }
+            self.vim.clear_pending();
+            return;
+        }
+        match c {
+            'd' | 'y' | 'c' => {
+                self.vim.pending_op = Some(c);
+                return;
+            }
+            c if Self::is_vim_motion(c) => {
+                self.vim_apply_motion(c, count);
+                return;
+            }
+            'i' => self.vim.mode = VimMode::Insert,
+            'a' => {
+                self.vim_right();
+                self.vim.mode = VimMode::Insert;
+            }
+            'I' => {
+                self.cursor_x = 0;
+                self.vim.mode = VimMode::Insert;
+            }
+            'A' => {
+                self.cursor_x = self.lines[self.cursor_y].len();
+                self.vim.mode = VimMode::Insert;
+            }
+            'o' => {
+                self.cursor_x = self.lines[self.cursor_y].len();
+                self.insert_newline();
+                self.vim.mode = VimMode::Insert;
+            }
+            'O' => {
+                self.cursor_x = 0;
+                self.insert_newline();
+                self.cursor_y -= 1;
+                self.vim.mode = VimMode::Insert;
+            }
+            'x' => {
+                let start = (self.cursor_y, self.cursor_x);
+                for _ in 0..count { self.vim_right(); }
+                let end = (self.cursor_y, self.cursor_x);
+                self.vim_operate_range('d', start, end);
+            }
+            'D' | 'C' => {
+                let start = (self.cursor_y, self.cursor_x);
+                let end = (self.cursor_y, self.lines[self.cursor_y].len());
+                self.vim_operate_range(if c == 'D' { 'd' } else { 'c' }, start, end);
+            }
+            'p' | 'P' => {
+                for _ in 0..count { self.vim_put(c == 'p'); }
+            }
+            'u' => {
+                for _ in 0..count { self.undo(); }
+            }
+            '/' => self.open_search(),
+            'n' | 'N' => {
+                for _ in 0..count { self.search_next(c == 'n'); }
+            }
+            'v' => {
+                self.vim.mode = VimMode::Visual;
+                self.sel_start = Some((self.cursor_y, self.cursor_x));
+                self.sel_end = Some((self.cursor_y, self.cursor_x));
+            }
+            _ => {}
+        }
+        self.vim.clear_pending();
+    }
+
+    fn vim_visual_char(&mut self, c: char, count: usize) {
+        if Self::is_vim_motion(c) {
+            self.vim_apply_motion(c, count);
+            self.sel_end = Some((self.cursor_y, self.cursor_x));
+            return;
+        }
+        if let ('d' | 'x' | 'y' | 'c', (Some(s), Some(e))) = (c, (self.sel_start, self.sel_end)) {
+            let (s, mut e) = if s <= e { (s, e) } else { (e, s) };
+            // ビジュアル選択はカーソル位置の文字も含む
+            e.1 = grapheme::next(&self.lines[e.0], e.1);
+            let op = if c == 'x' { 'd' } else { c };
+            self.vim.mode = VimMode::Normal;
+            self.vim_operate_range(op, s, e);
+        } else if c == 'i' {
+            self.vim.pending_i = true;
+            return;
+        } else if c == 'v' {
+            self.vim.mode = VimMode::Normal;
+            self.selection_reset();
+        }
+        self.vim.clear_pending();
+    }
+
+    fn is_vim_motion(c: char) -> bool {
+        matches!(c, 'h' | 'j' | 'k' | 'l' | 'w' | 'b' | 'e' | '0' | '$' | 'G')
+    }
+
+    // モーションを実行し、オペレータ待ちならその範囲に適用する
+    fn vim_apply_motion(&mut self, c: char, count: usize) {
+        let has_count = self.vim.count.is_some();
+        let start = (self.cursor_y, self.cursor_x);
+        match c {
+            'h' => for _ in 0..count { self.vim_left(); },
+            'l' => for _ in 0..count { self.vim_right(); },
+            'j' => for _ in 0..count { self.move_down(); },
+            'k' => for _ in 0..count { self.move_up(); },
+            'w' => for _ in 0..count { self.vim_word_forward(); },
+            'b' => for _ in 0..count { self.vim_word_backward(); },
+            'e' => for _ in 0..count { self.vim_word_end(); },
+            '0' => self.cursor_x = 0,
+            '$' => self.cursor_x = self.lines[self.cursor_y].len(),
+            'G' | 'g' => {
+                if self.vim.pending_op.is_none() { self.record_jump(); }
+                let last = self.lines.len() - 1;
+                self.cursor_y = if has_count { (count - 1).min(last) } else if c == 'G' { last } else { 0 };
+                self.cursor_x = 0;
+            }
+            _ => {}
+        }
+        let end = (self.cursor_y, self.cursor_x);
+        if let Some(op) = self.vim.pending_op {
+            if matches!(c, 'j' | 'k' | 'G' | 'g') {
+                self.vim_operate_lines(op, start.0.min(end.0), start.0.max(end.0));
+            } else {
+                let (s, mut e) = if start <= end { (start, end) } else { (end, start) };
+                // e は終端の文字を含む
+                if c == 'e' {
+                    e.1 = grapheme::next(&self.lines[e.0], e.1);
+                }
+                self.vim_operate_range(op, s, e);
+            }
+        }
+        self.vim.clear_pending();
+        self.adjust_h_scroll(0);
+    }
+
+    // ビジュアルモードなら選択し直し、オペレータ待ちならその範囲に適用する（段落は行単位）
+    fn vim_text_object(&mut self, obj: TextObject) {
+        let Some((s, e)) = self.text_object(obj) else { return };
+        if self.vim.mode == VimMode::Visual {
+            // ビジュアル選択はカーソル位置の文字も含むので終端はひとつ前の文字
+            let e = if e <= s {
+                e
+            } else if e.1 > 0 {
+                (e.0, self.prev_x(e.0, e.1))
+            } else {
+                (e.0 - 1, self.lines[e.0 - 1].len())
+            };
+            self.sel_start = Some(s);
+            self.sel_end = Some(e);
+            self.cursor_y = e.0;
+            self.cursor_x = e.1;
+        } else if let Some(op) = self.vim.pending_op {
+            if matches!(obj, TextObject::Paragraph) {
+                self.vim_operate_lines(op, s.0, e.0);
+            } else {
+                self.vim_operate_range(op, s, e);
+            }
+        }
+    }
+
+    // 文字単位の範囲 [s, e) に d / y / c を適用
+    fn vim_operate_range(&mut self, op: char, s: (usize, usize), e: (usize, usize)) {
+        self.sel_start = Some(s);
+        self.sel_end = Some(e);
+        let text = self.get_selected_text().unwrap_or_default();
+        self.vim_yank(text, false);
+        if op == 'y' || s == e {
+            self.selection_reset();
+            self.cursor_y = s.0;
+            self.cursor_x = s.1;
+        } else {
+            self.delete_selection();
+        }
+        if op == 'c' { self.vim.mode = VimMode::Insert; }
+    }
+
+    // 行単位の範囲 start..=end に d / y / c を適用
+    fn vim_operate_lines(&mut self, op: char, start: usize, end: usize) {
+        let text = self.lines[start..=end].join("\n");
+        self.vim_yank(text, true);
+        match op {
+            'd' => {
+                self.save_undo();
+                self.lines.drain(start..=end);
+                if self.lines.is_empty() { self.lines.push(String::new()); }
+                self.cursor_y = start.min(self.lines.len() - 1);
+            }
+            'c' => {
+                self.save_undo();
+                self.lines.drain(start + 1..=end);
+                self.lines[start].clear();
+                self.cursor_y = start;
+                self.vim.mode = VimMode::Insert;
+            }
+            _ => self.cursor_y = start,
+        }
+        self.cursor_x = 0;
+        self.selection_reset();
+    }
+
+    fn vim_yank(&mut self, text: String, linewise: bool) {
+        if let Some(ctx) = self.clipboard_ctx.as_mut() {
+            let _ = copypasta::ClipboardProvider::set_contents(ctx, text.clone());
+        }
+        self.vim.register = text;
+        self.vim.linewise = linewise;
+    }
+
+    fn vim_put(&mut self, after: bool) {
+        let text = self.vim.register.clone();
+        if self.vim.linewise {
+            self.save_undo();
+            let at = if after { self.cursor_y + 1 } else { self.cursor_y };
+            for (i, line) in text.split('\n').enumerate() {
+                self.lines.insert(at + i, line.to_string());
+            }
+            self.cursor_y = at;
+            self.cursor_x = 0;
+        } else {
+            if after { self.vim_right(); }
+            self.insert_text(&text);
+        }
+    }
+
+    // --- 行内で止まる左右移動 ---
+    fn vim_left(&mut self) {
+        self.cursor_x = grapheme::prev(&self.lines[self.cursor_y], self.cursor_x);
+    }
+    fn vim_right(&mut self) {
+        self.cursor_x = grapheme::next(&self.lines[self.cursor_y], self.cursor_x);
+    }
+
+    // --- 単語モーション (w / b / e) ---
+    fn class_at(&self, y: usize, x: usize) -> Option<u8> {
+        self.lines[y].get(x..).and_then(|t| t.chars().next()).map(char_class)
+    }
+    fn next_x(&self, y: usize, x: usize) -> usize {
+        grapheme::next(&self.lines[y], x)
+    }
+    fn prev_x(&self, y: usize, x: usize) -> usize {
+        grapheme::prev(&self.lines[y], x)
+    }
+    fn vim_word_forward(&mut self) {
+        let (mut y, mut x) = (self.cursor_y, self.cursor_x);
+        if let Some(cls) = self.class_at(y, x).filter(|&c| c != 0) {
+            while self.class_at(y, x) == Some(cls) { x = self.next_x(y, x); }
+        }
+        loop {
+            match self.class_at(y, x) {
+                Some(0) => x = self.next_x(y, x),
+                None => {
+                    if y + 1 >= self.lines.len() { break; }
+                    y += 1;
+                    x = 0;
+                    if self.lines[y].is_empty() { break; }
+                }
+                _ => break,
+            }
+        }
+        self.cursor_y = y;
+        self.cursor_x = x;
+    }
+    fn vim_word_backward(&mut self) {
+        let (mut y, mut x) = (self.cursor_y, self.cursor_x);
+        loop {
+            if x == 0 {
+                if y == 0 { break; }
+                y -= 1;
+                x = self.lines[y].len();
+                if self.lines[y].is_empty() { break; }
+                continue;
+            }
+            let px = self.prev_x(y, x);
+            if self.class_at(y, px) == Some(0) {
+                x = px;
+                continue;
+            }
+            break;
+        }
+        if x > 0 {
+            let cls = self.class_at(y, self.prev_x(y, x));
+            while x > 0 && self.class_at(y, self.prev_x(y, x)) == cls { x = self.prev_x(y, x); }
+        }
+        self.cursor_y = y;
+        self.cursor_x = x;
+    }
+    fn vim_word_end(&mut self) {
+        let (mut y, mut x) = (self.cursor_y, self.cursor_x);
+        if self.class_at(y, x).is_some() { x = self.next_x(y, x); }
+        loop {
+            match self.class_at(y, x) {
+                Some(0) => x = self.next_x(y, x),
+                None => {
+                    if y + 1 >= self.lines.len() { break; }
+                    y += 1;
+                    x = 0;
+                }
+                _ => break,
+            }
+        }
+        if let Some(cls) = self.class_at(y, x) {
+            while self.class_at(y, self.next_x(y, x)) == Some(cls) { x = self.next_x(y, x); }
+        }
+        self.cursor_y = y;
+        self.cursor_x = x;
+    }
+}
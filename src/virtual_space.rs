@@ -0,0 +1,55 @@
+// 仮想空間（config: virtual_space = true、Alt+Insert で切り替え）
+// 折り返さない表示では行末より右へもカーソルを進められ、そこで打った文字の前は空白で埋める。
+// 上下に動いても同じ表示桁を保つので、表や桁をそろえた書き込みに使う。
+// 仮想の桁は矢印キーと文字・Tab の入力のあいだだけ保ち、ほかのキーを押すと行末に戻る
+use crate::{config::Keymap, grapheme, vim::VimMode, App};
+use crossterm::event::{KeyCode, KeyModifiers};
+
+impl App {
+    // Vim のノーマル／ビジュアルモードでは使わない
+    pub fn virtual_space_active(&self) -> bool {
+        self.virtual_space && !self.soft_wrap && !(self.config.keymap == Keymap::Vim && self.vim.mode != VimMode::Insert)
+    }
+
+    // 行末より右にある桁の数（カーソルが行末になければ 0）
+    pub fn virtual_cols(&self) -> usize {
+        if !self.virtual_space_active() || self.cursor_x < self.lines[self.cursor_y].len() { return 0; }
+        self.virtual_x
+    }
+
+    pub fn toggle_virtual_space(&mut self) {
+        self.virtual_space = !self.virtual_space;
+        self.virtual_x = 0;
+        self.message = Some(format!("Virtual space {}", if self.virtual_space { "on" } else { "off" }));
+    }
+
+    // キーを処理する前に呼ぶ
+    pub fn virtual_space_key(&mut self, code: KeyCode, modifiers: KeyModifiers) {
+        let keep = match code {
+            KeyCode::Char(_) => !modifiers.intersects(KeyModifiers::CONTROL | KeyModifiers::ALT),
+            KeyCode::Left | KeyCode::Right | KeyCode::Up | KeyCode::Down | KeyCode::Tab => modifiers.is_empty(),
+            _ => false,
+        };
+        self.virtual_x = if keep { self.virtual_cols() } else { 0 };
+    }
+
+    // 右矢印で次の行へ移らず、行末より右へ進むか（選択中は進まない）
+    pub fn can_enter_virtual_space(&self) -> bool {
+        self.virtual_space_active() && !self.shift_selection && self.cursor_x >= self.lines[self.cursor_y].len()
+    }
+
+    // 上下に動いた先の行が表示桁 col より短ければ、足りない分を仮想の桁にする
+    pub fn virtual_reach(&mut self, col: usize) {
+        let width = grapheme::width(&self.lines[self.cursor_y], self.cursor_x);
+        self.virtual_x = if self.virtual_space_active() && !self.shift_selection { col.saturating_sub(width) } else { 0 };
+    }
+
+    // 打つ前に仮想の桁までを空白で埋める（undo は呼ぶ側でまとめる）
+    pub fn fill_virtual_space(&mut self) {
+        let n = self.virtual_cols();
+        if n == 0 { return; }
+        self.lines[self.cursor_y].push_str(&" ".repeat(n));
+        self.cursor_x = self.lines[self.cursor_y].len();
+        self.virtual_x = 0;
+    }
+}
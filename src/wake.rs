@@ -0,0 +1,90 @@
+// メインループを起こす
+// 端末の入力は専用のスレッドで読んで送り、バックグラウンドの仕事（保存・読み込み・言語サーバー・外部コマンド・タスク）と
+// SIGHUP / SIGTERM は結果を渡したら wake() で知らせる。メインループはどれかが来るか、
+// 次の時刻（通知が消える・FileTree の経過時間の表示・外部コマンドの時間切れ）まで眠るので、何もしていない間は CPU を使わない
+use crate::{App, Mode};
+use crossterm::event::{self, Event};
+use signal_hook::iterator::Signals;
+use std::{
+    io,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, RecvTimeoutError, Sender},
+        OnceLock,
+    },
+    thread,
+    time::Duration,
+};
+
+// 読み込みや保存の進み具合を描き直す間隔
+const PROGRESS_TICK: Duration = Duration::from_millis(100);
+// FileTree の経過時間の表示を描き直す間隔（外での増減はファイル監視が wake() で知らせる）
+const TREE_TICK: Duration = Duration::from_secs(1);
+
+pub enum Wake {
+    Input(io::Result<Event>),
+    Work, // バックグラウンドで何か終わった（中身は各 poll_* で受け取る）
+}
+
+// どのスレッドからでも起こせるように1つだけ持つ
+static WAKER: OnceLock<Sender<Wake>> = OnceLock::new();
+// 知らせが溜まっていれば重ねて送らない（出力の多いタスクでも1回の見回りで受け取る）
+static PENDING: AtomicBool = AtomicBool::new(false);
+
+pub fn wake() {
+    if PENDING.swap(true, Ordering::AcqRel) { return; }
+    if let Some(tx) = WAKER.get() {
+        let _ = tx.send(Wake::Work);
+    }
+}
+
+// 入力を読むスレッドを立てる。シグナルを受けても起こす
+pub fn start(signals: &[i32]) -> io::Result<Receiver<Wake>> {
+    let (tx, rx) = mpsc::channel();
+    let _ = WAKER.set(tx.clone());
+    let mut signals = Signals::new(signals)?;
+    thread::spawn(move || {
+        for _ in signals.forever() {
+            wake();
+        }
+    });
+    thread::spawn(move || {
+        loop {
+            let event = event::read();
+            let failed = event.is_err();
+            if tx.send(Wake::Input(event)).is_err() || failed { break; }
+        }
+    });
+    Ok(rx)
+}
+
+// 次に来るものを待つ。時間切れなら None
+pub fn wait(rx: &Receiver<Wake>, timeout: Option<Duration>) -> Option<Wake> {
+    let wake = match timeout {
+        Some(timeout) => match rx.recv_timeout(timeout) {
+            Ok(wake) => wake,
+            Err(RecvTimeoutError::Timeout) => return None,
+            Err(RecvTimeoutError::Disconnected) => Wake::Input(Err(io::Error::other("input reader stopped"))),
+        },
+        None => rx.recv().unwrap_or_else(|_| Wake::Input(Err(io::Error::other("input reader stopped")))),
+    };
+    if let Wake::Work = wake {
+        PENDING.store(false, Ordering::Release);
+    }
+    Some(wake)
+}
+
+impl App {
+    // 時刻で変わるもののうち一番近いものまでの時間。なければ入力か知らせが来るまで眠る
+    pub fn next_wakeup(&self) -> Option<Duration> {
+        [
+            self.notice_left(),
+            self.external_left(),
+            (self.loading.is_some() || self.io_progress().is_some()).then_some(PROGRESS_TICK),
+            (matches!(self.mode, Mode::FileTree) && self.file_tree.shows_ages() && !self.deterministic).then_some(TREE_TICK),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
+}
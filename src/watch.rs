@@ -0,0 +1,68 @@
+// FileTree の見張り
+// 表示中のディレクトリ（ルートと開いているもの）を OS のファイル監視（Linux なら inotify）で見張り、
+// 中で項目が増減したら印を付けて wake() でメインループを起こす。FileTree は poll_changes で印を見て読み直す。
+// 監視を始められない環境では何も起こさない
+use crate::wake;
+use ::notify::{
+    event::{EventKind, ModifyKind},
+    Event, RecommendedWatcher, RecursiveMode, Watcher,
+};
+use std::{
+    path::PathBuf,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+pub struct DirWatch {
+    watcher: Option<RecommendedWatcher>, // 最初に watch を呼んだときに作る
+    dirs: Vec<PathBuf>,
+    changed: Arc<AtomicBool>,
+}
+
+// 複製した側は見張らず、次に watch を呼んだときに自分で見張り始める
+impl Clone for DirWatch {
+    fn clone(&self) -> Self {
+        DirWatch::new()
+    }
+}
+
+impl DirWatch {
+    pub fn new() -> Self {
+        DirWatch { watcher: None, dirs: Vec::new(), changed: Arc::new(AtomicBool::new(false)) }
+    }
+
+    // 見張るディレクトリを入れ替える。見張り始めたものがあれば true
+    pub fn watch(&mut self, mut dirs: Vec<PathBuf>) -> bool {
+        dirs.sort();
+        if dirs == self.dirs { return false; }
+        if self.watcher.is_none() {
+            let changed = Arc::clone(&self.changed);
+            self.watcher = ::notify::recommended_watcher(move |res: ::notify::Result<Event>| {
+                let Ok(event) = res else { return };
+                // 中身の書き換えは一覧に関係しない。増減と名前の変更だけ
+                if matches!(event.kind, EventKind::Create(_) | EventKind::Remove(_) | EventKind::Modify(ModifyKind::Name(_))) {
+                    changed.store(true, Ordering::Release);
+                    wake::wake();
+                }
+            })
+            .ok();
+        }
+        let Some(watcher) = self.watcher.as_mut() else { return false };
+        for dir in self.dirs.iter().filter(|d| !dirs.contains(d)) {
+            let _ = watcher.unwatch(dir);
+        }
+        let mut started = false;
+        for dir in dirs.iter().filter(|d| !self.dirs.contains(d)) {
+            started |= watcher.watch(dir, RecursiveMode::NonRecursive).is_ok();
+        }
+        self.dirs = dirs;
+        started
+    }
+
+    // 前に見てから項目が増減したか
+    pub fn take_changed(&self) -> bool {
+        self.changed.swap(false, Ordering::AcqRel)
+    }
+}
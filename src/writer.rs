@@ -0,0 +1,134 @@
+// バックグラウンド保存キュー
+// 保存はすべて1本のワーカースレッドで順番に処理するので、連打や同時保存でも書き込みが混ざらない
+// 履歴のバックアップと刈り込みも同じスレッドで行う
+// 書いたバイト数を数えておき、大きなファイルの保存中はステータスバーに進み具合を出す
+use crate::{
+    backup::{self, BackupPolicy},
+    wake,
+};
+use std::{
+    collections::VecDeque,
+    io::{self, Write},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+const CHUNK: usize = 256 * 1024;
+
+pub struct SaveJob {
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+    pub backup: Option<BackupPolicy>, // Some なら上書き前の版を履歴に残す
+}
+
+pub struct SaveResult {
+    pub path: PathBuf,
+    pub bytes: usize,
+    pub elapsed: Duration,
+    pub result: io::Result<()>,
+}
+
+pub struct SaveQueue {
+    tx: Sender<SaveJob>,
+    rx: Receiver<SaveResult>,
+    pending: VecDeque<(PathBuf, u64)>, // 積んだ順の保存先とバイト数（先頭が書いている最中）
+    written: Arc<AtomicU64>,            // 書いている保存のここまでのバイト数
+}
+
+impl SaveQueue {
+    pub fn new() -> Self {
+        let (job_tx, job_rx) = mpsc::channel::<SaveJob>();
+        let (res_tx, res_rx) = mpsc::channel();
+        let written = Arc::new(AtomicU64::new(0));
+        let counter = Arc::clone(&written);
+        thread::spawn(move || {
+            for job in job_rx {
+                let start = Instant::now();
+                let history = job.backup.and_then(|policy| Some((backup::history_dir()?, policy)));
+                if let Some((ref root, _)) = history {
+                    let _ = backup::backup(root, &job.path);
+                }
+                let result = write_counted(&job.path, &job.content, &counter);
+                if let Some((ref root, policy)) = history {
+                    backup::prune(root, policy);
+                }
+                counter.store(0, Ordering::Relaxed);
+                let _ = res_tx.send(SaveResult {
+                    path: job.path,
+                    bytes: job.content.len(),
+                    elapsed: start.elapsed(),
+                    result,
+                });
+                wake::wake();
+            }
+        });
+        SaveQueue { tx: job_tx, rx: res_rx, pending: VecDeque::new(), written }
+    }
+
+    pub fn submit(&mut self, job: SaveJob) {
+        let entry = (job.path.clone(), job.content.len() as u64);
+        if self.tx.send(job).is_ok() {
+            self.pending.push_back(entry);
+        }
+    }
+
+    pub fn try_recv(&mut self) -> Option<SaveResult> {
+        let res = self.rx.try_recv().ok()?;
+        self.pending.pop_front();
+        Some(res)
+    }
+
+    // 書いている最中の保存: 保存先・書いたバイト数・全体
+    pub fn progress(&self) -> Option<(&Path, u64, u64)> {
+        let (path, total) = self.pending.front()?;
+        Some((path, self.written.load(Ordering::Relaxed), *total))
+    }
+
+    // 終了前に残りの保存を待つ
+    pub fn wait_idle(&mut self, timeout: Duration) -> Vec<SaveResult> {
+        let deadline = Instant::now() + timeout;
+        let mut results = Vec::new();
+        while !self.pending.is_empty() {
+            let left = deadline.saturating_duration_since(Instant::now());
+            match self.rx.recv_timeout(left) {
+                Ok(res) => {
+                    self.pending.pop_front();
+                    results.push(res);
+                }
+                Err(_) => break,
+            }
+        }
+        results
+    }
+}
+
+// 同じディレクトリの一時ファイルに書いてから rename する（途中で落ちても元のファイルは壊れない）
+pub fn write_atomic(path: &Path, data: &[u8]) -> io::Result<()> {
+    write_counted(path, data, &AtomicU64::new(0))
+}
+
+// 塊ごとに書いて、書いたバイト数を written に入れる
+fn write_counted(path: &Path, data: &[u8], written: &AtomicU64) -> io::Result<()> {
+    let name = path.file_name().map(|s| s.to_string_lossy().to_string()).unwrap_or_default();
+    let tmp = path.with_file_name(format!(".{}.rwe-tmp", name));
+    {
+        let mut file = std::fs::File::create(&tmp)?;
+        for chunk in data.chunks(CHUNK) {
+            file.write_all(chunk)?;
+            written.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+        }
+        file.sync_all()?;
+    }
+    if let Ok(meta) = std::fs::metadata(path) {
+        let _ = std::fs::set_permissions(&tmp, meta.permissions());
+    }
+    std::fs::rename(&tmp, path).inspect_err(|_| {
+        let _ = std::fs::remove_file(&tmp);
+    })
+}